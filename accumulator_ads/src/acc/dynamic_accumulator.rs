@@ -2,22 +2,45 @@
 //! intersection, union, and disjointness proofs.
 //!
 //! # Trapdoor Note
-//! This implementation requires a secret trapdoor (private key) to perform operations.
-//! The trapdoor should be injected through the constructor for better testability
-//! and separation of concerns.
+//! An accumulator instance holds a secret trapdoor, injected through the
+//! constructor, for its O(1) updates and witnesses (`compute_add`,
+//! `compute_membership_witness`, ...). It is held in a `Zeroizing` wrapper,
+//! and the scalars derived from it are scrubbed once an operation is done
+//! with them.
+//!
+//! Holders of the public parameters alone use the static methods instead:
+//! `calculate_commitment`, `incremental_union` and `incremental_remove` for
+//! accumulator values, and `membership_witnesses` and
+//! `all_membership_witnesses` for witnesses, all computed from the powers
+//! g^(sⁱ) at a cost linear in the set.
 
 use anyhow::{anyhow, ensure, Context, Result};
 use ark_bls12_381::{Fr, G1Affine, G1Projective, G2Affine};
-use ark_ec::{AffineCurve, ProjectiveCurve};
-use ark_ff::{Field, One, PrimeField, Zero};
+use ark_ec::{msm::VariableBaseMSM, AffineCurve, ProjectiveCurve};
+use ark_ff::{batch_inversion, Field, One, PrimeField, Zero};
 use ark_poly::{
     univariate::{DenseOrSparsePolynomial, DensePolynomial},
-    UVPolynomial,
+    EvaluationDomain, Radix2EvaluationDomain, UVPolynomial,
 };
+use ark_std::{cfg_iter, cfg_iter_mut};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::ops::Neg;
+use zeroize::Zeroizing;
 
 use super::proofs::{MembershipProof, NonMembershipProof};
-use crate::acc::utils::{expand_to_poly, poly_to_g1, poly_to_g2};
+use super::setup::parameters_of_degree;
+use crate::acc::utils::{
+    correlate_g1, div_by_linear, expand_to_poly, join, mul_by_linear, poly_to_g1, poly_to_g2,
+    FixedBaseCurvePow, SubproductTree,
+};
+
+/// Largest subtree `all_membership_witnesses` commits to directly, with one
+/// MSM per element, rather than splitting it further.
+#[cfg(test)]
+const WITNESS_LEAF_SIZE: usize = 2;
+#[cfg(not(test))]
+const WITNESS_LEAF_SIZE: usize = 256;
 
 /// Represents the result of a query against the accumulator.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -35,13 +58,17 @@ pub struct DynamicAccumulator {
     pub acc_value: G1Affine,
     /// The secret trapdoor used for O(1) operations.
     /// Injected through constructor for better testability and modularity.
-    trapdoor: Fr,
+    trapdoor: Zeroizing<Fr>,
+    /// Characteristic polynomial P(X) = ∏(X - xᵢ) of the set, if tracked.
+    /// Kept current by `add_element`, `delete_element` and `update_element`.
+    poly: Option<DensePolynomial<Fr>>,
 }
 
+/// Empty accumulator over the insecure test trapdoor `PRI_S`.
+#[cfg(any(test, feature = "insecure-test-params"))]
 impl Default for DynamicAccumulator {
     fn default() -> Self {
-        // Use the default trapdoor from setup for backward compatibility
-        Self::new(super::setup::PRI_S.clone())
+        Self::new(*super::setup::PRI_S)
     }
 }
 
@@ -65,7 +92,8 @@ impl DynamicAccumulator {
             acc_value: G1Projective::from(G1Affine::prime_subgroup_generator())
                 .mul(Fr::one().into_repr())
                 .into_affine(),
-            trapdoor,
+            trapdoor: Zeroizing::new(trapdoor),
+            poly: None,
         }
     }
 
@@ -83,7 +111,19 @@ impl DynamicAccumulator {
 
     /// Static method: Fast calculation of set commitment using MSM.
     /// Used when initializing from a large set from scratch.
+    ///
+    /// # Panics
+    /// Panics if the set is larger than the parameters' degree, or the
+    /// parameters are not initialized; `check_degree` first, or use
+    /// `try_calculate_commitment`. The same holds for the other constructors
+    /// and helpers that commit to a set without returning a `Result`.
     pub fn calculate_commitment(elements: &[Fr]) -> G1Affine {
+        Self::try_calculate_commitment(elements).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// `calculate_commitment`, failing with `DegreeExceeded` instead of
+    /// panicking when the set is larger than the parameters' degree.
+    pub fn try_calculate_commitment(elements: &[Fr]) -> Result<G1Affine> {
         poly_to_g1(expand_to_poly(elements))
     }
 
@@ -95,63 +135,182 @@ impl DynamicAccumulator {
     pub fn from_set(trapdoor: Fr, elements: &[Fr]) -> Self {
         Self {
             acc_value: Self::calculate_commitment(elements),
-            trapdoor,
+            trapdoor: Zeroizing::new(trapdoor),
+            poly: None,
         }
     }
 
-    /// Helper: Compute G2 commitment
+    /// Like `from_set`, but the accumulator also keeps the characteristic
+    /// polynomial of its set. Each `add_element` or `delete_element` then
+    /// updates the coefficients in O(n) field operations, and
+    /// `non_membership_witness` and `polynomial_commitment` start from them
+    /// instead of expanding the set again.
+    ///
+    /// Assigning `acc_value` directly bypasses the polynomial; use the
+    /// element methods (or the `AddProof`/`DeleteProof`/`UpdateProof`
+    /// constructors) on a tracking accumulator.
+    pub fn from_set_tracking(trapdoor: Fr, elements: &[Fr]) -> Self {
+        let poly = expand_to_poly(elements);
+        Self {
+            acc_value: poly_to_g1(poly.clone()).unwrap_or_else(|e| panic!("{}", e)),
+            trapdoor: Zeroizing::new(trapdoor),
+            poly: Some(poly),
+        }
+    }
+
+    /// The cached characteristic polynomial, if this accumulator tracks it.
+    pub fn polynomial(&self) -> Option<&DensePolynomial<Fr>> {
+        self.poly.as_ref()
+    }
+
+    /// Helper: Compute G2 commitment. Panics as `calculate_commitment` does.
     pub fn calculate_commitment_g2(elements: &[Fr]) -> G2Affine {
-        poly_to_g2(expand_to_poly(elements))
+        poly_to_g2(expand_to_poly(elements)).unwrap_or_else(|e| panic!("{}", e))
     }
 
     // ==========================================
     // Backward Compatibility Helpers
     // ==========================================
 
-    /// Creates an accumulator using the default trapdoor from setup.
-    /// This method provides backward compatibility for existing code.
-    ///
-    /// # Note
-    /// For new code, prefer using `new(trapdoor)` with explicit trapdoor injection.
+    /// Creates an accumulator using the insecure test trapdoor `PRI_S`.
+    #[cfg(any(test, feature = "insecure-test-params"))]
+    #[deprecated(note = "inject a trapdoor with `new`, or use `AccumulatorManager`")]
     pub fn with_default_trapdoor() -> Self {
-        Self::new(super::setup::PRI_S.clone())
+        Self::new(*super::setup::PRI_S)
     }
 
-    /// Creates an accumulator from an existing accumulator value using the default trapdoor.
-    /// This is useful when you have an accumulator value but need to perform operations on it.
-    ///
-    /// # Arguments
-    /// * `acc_value` - The existing accumulator value
-    ///
-    /// # Note
-    /// This method assumes the accumulator was created with the default trapdoor.
+    /// Creates an accumulator from an existing accumulator value, assuming it
+    /// was built with the insecure test trapdoor `PRI_S`.
+    #[cfg(any(test, feature = "insecure-test-params"))]
+    #[deprecated(note = "build accumulators with an injected trapdoor, or use `AccumulatorManager`")]
     pub fn from_value(acc_value: G1Affine) -> Self {
         Self {
             acc_value,
-            trapdoor: super::setup::PRI_S.clone(),
+            trapdoor: Zeroizing::new(*super::setup::PRI_S),
+            poly: None,
         }
     }
 
-    /// Static helper: Incrementally adds elements to an accumulator value using the default trapdoor.
-    /// This method provides backward compatibility for existing code.
-    ///
-    /// # Arguments
-    /// * `current_acc` - The current accumulator value
-    /// * `new_elements` - The elements to add
-    ///
-    /// # Note
-    /// For new code, prefer using the instance method `incremental_add_elements`.
+    /// Static helper: `incremental_add_elements` on `current_acc` with the
+    /// insecure test trapdoor `PRI_S`.
+    #[cfg(any(test, feature = "insecure-test-params"))]
+    #[deprecated(note = "use `incremental_add_elements` on an accumulator with an injected \
+                         trapdoor, or `incremental_union` without one")]
     pub fn incremental_add_with_default_trapdoor(
         current_acc: G1Affine,
         new_elements: &[Fr],
     ) -> G1Affine {
         let temp_acc = Self {
             acc_value: current_acc,
-            trapdoor: super::setup::PRI_S.clone(),
+            trapdoor: Zeroizing::new(*super::setup::PRI_S),
+            poly: None,
         };
         temp_acc.incremental_add_elements(new_elements)
     }
 
+    /// Static method: Accumulator of `base ∪ added` computed without the trapdoor.
+    /// Multiplies the base polynomial by the difference-set polynomial
+    /// ∏(x - aᵢ) and commits to the product with the public powers g^(sⁱ), so
+    /// the result is correct for whatever secret the parameters were built
+    /// from.
+    ///
+    /// # Arguments
+    /// * `base` - Elements already accumulated
+    /// * `added` - New elements, disjoint from `base`
+    ///
+    /// # Note
+    /// Needs public parameters of degree at least `base.len() + added.len()`.
+    /// Costs an MSM of that size instead of the O(1) trapdoor update of
+    /// `incremental_add_elements`.
+    pub fn incremental_union(base: &[Fr], added: &[Fr]) -> G1Affine {
+        let union_poly = &expand_to_poly(base) * &expand_to_poly(added);
+        poly_to_g1(union_poly).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Static method: Accumulator of `base ∖ removed` computed without the
+    /// trapdoor, the counterpart of `incremental_union`. Divides the base's
+    /// characteristic polynomial by each (x - rᵢ) and commits to the quotient
+    /// with the public powers g^(sⁱ).
+    ///
+    /// # Arguments
+    /// * `base_poly` - Characteristic polynomial of the current set, e.g. from
+    ///   a tracking accumulator (`polynomial`)
+    /// * `removed` - Elements to remove, all in the set
+    ///
+    /// # Returns
+    /// The new accumulator value and the polynomial of the remaining set.
+    ///
+    /// # Note
+    /// Division costs O(n·k) field operations, so no expansion of the
+    /// remaining set is needed, but the commitment is still an MSM over the
+    /// n - k remaining coefficients: without the trapdoor the cost stays
+    /// linear in the set. Fails if an element is not in the set.
+    pub fn incremental_remove(
+        base_poly: &DensePolynomial<Fr>,
+        removed: &[Fr],
+    ) -> Result<(G1Affine, DensePolynomial<Fr>)> {
+        let mut remaining = base_poly.clone();
+        for &element in removed {
+            let (quotient, remainder) = div_by_linear(&remaining, element);
+            ensure!(remainder.is_zero(), "Element is not in the accumulated set");
+            remaining = quotient;
+        }
+        Ok((poly_to_g1(remaining.clone())?, remaining))
+    }
+
+    /// Static method: Membership witnesses of `targets` in the accumulator of
+    /// `elements`, computed without the trapdoor. Divides the characteristic
+    /// polynomial of `elements` by each (x - tᵢ) and commits to the quotient
+    /// with the public powers g^(sⁱ), so the witnesses verify under whatever
+    /// secret the parameters were built from.
+    ///
+    /// # Note
+    /// Expands the set once, then costs an O(n) division and an MSM of size
+    /// n - 1 per target, where `compute_membership_witnesses` needs a single
+    /// exponentiation. For most of the set, `all_membership_witnesses` is
+    /// cheaper. Fails if a target is not in the set or the parameters are too
+    /// small for it.
+    pub fn membership_witnesses(elements: &[Fr], targets: &[Fr]) -> Result<Vec<G1Affine>> {
+        let poly = expand_to_poly(elements);
+        cfg_iter!(targets)
+            .map(|&target| {
+                let (quotient, remainder) = div_by_linear(&poly, target);
+                ensure!(remainder.is_zero(), "Element is not in the accumulated set");
+                poly_to_g1(quotient)
+            })
+            .collect()
+    }
+
+    /// Static method: Membership witnesses of every element of `elements`,
+    /// in order, computed without the trapdoor. The trapdoorless counterpart
+    /// of `compute_all_witnesses`.
+    ///
+    /// The witness of xⱼ commits to ∏_{i≠j}(X - xᵢ). Going down a subproduct
+    /// tree of the set, each node keeps the commitments g^(sⁱ·C(s)), i below
+    /// its size, where C is the product over the elements outside it; at the
+    /// root they are the public powers. A child's commitments are its
+    /// parent's correlated with the sibling's product, one FFT over G1 per
+    /// node, so all n witnesses cost O(n log² n) group operations instead of
+    /// an MSM of size n each. Subtrees of at most `WITNESS_LEAF_SIZE` elements
+    /// commit to their quotients directly.
+    ///
+    /// # Note
+    /// Needs public parameters of degree at least n - 1.
+    pub fn all_membership_witnesses(elements: &[Fr]) -> Result<Vec<G1Affine>> {
+        if elements.is_empty() {
+            return Ok(Vec::new());
+        }
+        let params = parameters_of_degree(elements.len() - 1)?;
+        let tree = SubproductTree::build(elements, WITNESS_LEAF_SIZE);
+        let bases: Vec<G1Projective> = params.g1_s_vec[..elements.len()]
+            .iter()
+            .map(|p| p.into_projective())
+            .collect();
+        let mut witnesses = vec![G1Projective::zero(); elements.len()];
+        quotient_commitments(&tree, elements, &bases, &mut witnesses);
+        Ok(G1Projective::batch_normalization_into_affine(&witnesses))
+    }
+
     // ==========================================
     // 1. Add & Delete & Update (With Trapdoor s)
     // ==========================================
@@ -159,19 +318,19 @@ impl DynamicAccumulator {
     /// Computes the new accumulator value after adding an element using the trapdoor.
     /// acc' = acc^(s - element)
     pub fn compute_add(&self, element: Fr) -> G1Affine {
-        let s_minus_elem: Fr = self.trapdoor - element;
-        self.acc_value.mul(s_minus_elem).into_affine()
+        let s_minus_elem = Zeroizing::new(*self.trapdoor - element);
+        self.acc_value.mul(*s_minus_elem).into_affine()
     }
 
     /// Computes the new accumulator value after deleting an element using the trapdoor.
     /// acc' = acc^(1 / (s - element))
     pub fn compute_delete(&self, element: Fr) -> Result<G1Affine> {
-        let s_minus_elem: Fr = self.trapdoor - element;
-        let inverse = s_minus_elem.inverse().ok_or_else(|| {
+        let s_minus_elem = Zeroizing::new(*self.trapdoor - element);
+        let inverse = Zeroizing::new(s_minus_elem.inverse().ok_or_else(|| {
             anyhow!("Failed to compute inverse: element might be equal to s (Trapdoor collision)")
-        })?;
+        })?);
 
-        Ok(self.acc_value.mul(inverse).into_affine())
+        Ok(self.acc_value.mul(*inverse).into_affine())
     }
 
     /// Computes the new accumulator value after updating an element using the trapdoor.
@@ -181,8 +340,47 @@ impl DynamicAccumulator {
         let temp_acc = self.compute_delete(old_element)?;
 
         // Step 2: Add new (multiply by (s-new))
-        let s_minus_new: Fr = self.trapdoor - new_element;
-        Ok(temp_acc.mul(s_minus_new).into_affine())
+        let s_minus_new = Zeroizing::new(*self.trapdoor - new_element);
+        Ok(temp_acc.mul(*s_minus_new).into_affine())
+    }
+
+    /// Adds `element`: updates `acc_value` with the trapdoor and multiplies
+    /// the tracked polynomial, if any, by (X - element).
+    pub fn add_element(&mut self, element: Fr) {
+        self.acc_value = self.compute_add(element);
+        if let Some(poly) = &mut self.poly {
+            mul_by_linear(poly, element);
+        }
+    }
+
+    /// Deletes `element`: updates `acc_value` with the trapdoor and divides
+    /// the tracked polynomial, if any, by (X - element). With a tracked
+    /// polynomial, fails without changing anything if `element` is not in
+    /// the set.
+    pub fn delete_element(&mut self, element: Fr) -> Result<()> {
+        let acc_value = self.compute_delete(element)?;
+        if let Some(poly) = &mut self.poly {
+            let (quotient, remainder) = div_by_linear(poly, element);
+            ensure!(remainder.is_zero(), "Element is not in the accumulated set");
+            *poly = quotient;
+        }
+        self.acc_value = acc_value;
+        Ok(())
+    }
+
+    /// Replaces `old_element` with `new_element`, as `delete_element`
+    /// followed by `add_element`.
+    pub fn update_element(&mut self, old_element: Fr, new_element: Fr) -> Result<()> {
+        self.delete_element(old_element)?;
+        self.add_element(new_element);
+        Ok(())
+    }
+
+    /// Commitment g1^P(s) computed from the tracked coefficients with the
+    /// public parameters rather than the trapdoor. `None` if the polynomial
+    /// is not tracked; an error if the parameters are too small for it.
+    pub fn polynomial_commitment(&self) -> Result<Option<G1Affine>> {
+        self.poly.clone().map(poly_to_g1).transpose()
     }
 
     // ==========================================
@@ -205,12 +403,14 @@ impl DynamicAccumulator {
 
         // Step 1: Compute the product of all (s - xᵢ) in the scalar field
         // This is much faster than repeated point multiplications
-        let exponent_product = new_elements
-            .iter()
-            .fold(Fr::one(), |acc, &elem| acc * (self.trapdoor - elem));
+        let exponent_product = Zeroizing::new(
+            new_elements
+                .iter()
+                .fold(Fr::one(), |acc, &elem| acc * (*self.trapdoor - elem)),
+        );
 
         // Step 2: Single point multiplication - only one expensive operation
-        self.acc_value.mul(exponent_product).into_affine()
+        self.acc_value.mul(*exponent_product).into_affine()
     }
 
     // ==========================================
@@ -224,23 +424,58 @@ impl DynamicAccumulator {
         self.compute_delete(element)
     }
 
+    /// Computes the membership witnesses of several elements against the
+    /// current accumulator value, as `compute_membership_witness` would one
+    /// at a time. The batch shares a single field inversion and a fixed-base
+    /// table for the accumulator, and the exponentiations run in parallel.
+    pub fn compute_membership_witnesses(&self, elements: &[Fr]) -> Result<Vec<G1Affine>> {
+        let mut exponents: Zeroizing<Vec<Fr>> =
+            Zeroizing::new(elements.iter().map(|&e| *self.trapdoor - e).collect());
+        ensure!(
+            exponents.iter().all(|e| !e.is_zero()),
+            "Failed to compute inverse: element might be equal to s (Trapdoor collision)"
+        );
+        batch_inversion(&mut exponents);
+        let acc = FixedBaseCurvePow::build(&self.acc_value.into_projective());
+        let points: Vec<G1Projective> = cfg_iter!(exponents).map(|e| acc.apply(e)).collect();
+        Ok(G1Projective::batch_normalization_into_affine(&points))
+    }
+
+    /// Computes the membership witness of every element of a set at once.
+    /// Entry i is g^(∏_{j≠i}(s - xⱼ)), the witness `compute_membership_witness`
+    /// returns for xᵢ against the accumulator of `elements`.
+    ///
+    /// The exponents are built top-down over a halving tree of the set: each
+    /// half inherits its parent's factor times the product over the other
+    /// half, so all n exponents cost O(n log n) field operations instead of an
+    /// inversion and a variable-base exponentiation per element. They are then
+    /// raised on a shared fixed-base table for g in parallel.
+    pub fn compute_all_witnesses(&self, elements: &[Fr]) -> Vec<G1Affine> {
+        let mut exponents = Zeroizing::new(vec![Fr::one(); elements.len()]);
+        complement_products(&self.trapdoor, elements, &Fr::one(), &mut exponents);
+        let g = FixedBaseCurvePow::build(&G1Projective::prime_subgroup_generator());
+        let points: Vec<G1Projective> = cfg_iter!(exponents).map(|e| g.apply(e)).collect();
+        G1Projective::batch_normalization_into_affine(&points)
+    }
+
     /// Computes witnesses for non-membership.
     /// Returns (witness=g2^B(s), g2_a=g2^A(s)) where A(x)P(x) + B(x)(x-element) = 1
     pub fn compute_non_membership_witness(
         element: Fr,
         elements: &[Fr],
     ) -> Result<(G2Affine, G2Affine)> {
-        // 1. Construct P(X)
-        let p_poly = expand_to_poly(elements);
-
-        // 2. Construct (X - element)
-        let elem_poly = DensePolynomial::from_coefficients_vec(vec![element.neg(), Fr::one()]);
-
-        // 3. Solve Bezout identity
-        let (a_poly, b_poly) = crate::acc::utils::solve_bezout_identity(p_poly, elem_poly)
-            .context("GCD is not constant, element might be in set")?;
+        non_membership_from_poly(element, &expand_to_poly(elements))
+    }
 
-        Ok((poly_to_g2(b_poly), poly_to_g2(a_poly)))
+    /// Non-membership witnesses as `compute_non_membership_witness` returns
+    /// them, computed from the tracked polynomial without expanding the set.
+    /// Fails if the polynomial is not tracked (see `from_set_tracking`).
+    pub fn non_membership_witness(&self, element: Fr) -> Result<(G2Affine, G2Affine)> {
+        let poly = self
+            .poly
+            .as_ref()
+            .ok_or_else(|| anyhow!("Accumulator does not track its polynomial"))?;
+        non_membership_from_poly(element, poly)
     }
 
     // ==========================================
@@ -280,13 +515,18 @@ impl DynamicAccumulator {
             "P_intersect does not divide P2",
         )?;
 
-        let witness_a = poly_to_g2(q1_poly.clone());
-        let witness_b = poly_to_g2(q2_poly.clone());
+        let witness_a = poly_to_g2(q1_poly.clone())?;
+        let witness_b = poly_to_g2(q2_poly.clone())?;
 
         let (a_poly, b_poly) = crate::acc::utils::solve_bezout_identity(q1_poly, q2_poly)
             .context("Quotients might not be coprime")?;
 
-        Ok((witness_a, witness_b, poly_to_g1(a_poly), poly_to_g1(b_poly)))
+        Ok((
+            witness_a,
+            witness_b,
+            poly_to_g1(a_poly)?,
+            poly_to_g1(b_poly)?,
+        ))
     }
 
     /// Computes witnesses for disjointness proof.
@@ -300,6 +540,81 @@ impl DynamicAccumulator {
         let (x_poly, y_poly) = crate::acc::utils::solve_bezout_identity(poly1, poly2)
             .context("Sets are not disjoint")?;
 
-        Ok((poly_to_g2(x_poly), poly_to_g2(y_poly)))
+        Ok((poly_to_g2(x_poly)?, poly_to_g2(y_poly)?))
     }
 }
+
+/// Non-membership witnesses (g2^B(s), g2^A(s)) against P(X) = `poly`.
+///
+/// Dividing by (X - element) gives P = Q·(X - element) + P(element), so the
+/// Bezout pair is A = 1/P(element) and B = -Q/P(element): one synthetic
+/// division instead of a polynomial XGCD. P(element) is zero exactly when the
+/// element is in the set.
+fn non_membership_from_poly(
+    element: Fr,
+    poly: &DensePolynomial<Fr>,
+) -> Result<(G2Affine, G2Affine)> {
+    let (quotient, remainder) = div_by_linear(poly, element);
+    let inverse = remainder
+        .inverse()
+        .ok_or_else(|| anyhow!("GCD is not constant, element might be in set"))?;
+    let a_poly = DensePolynomial::from_coefficients_vec(vec![inverse]);
+    let b_poly = DensePolynomial::from_coefficients_vec(
+        quotient.coeffs.iter().map(|c| c.neg() * inverse).collect(),
+    );
+    Ok((poly_to_g2(b_poly)?, poly_to_g2(a_poly)?))
+}
+
+/// Writes `factor · ∏_{j≠i}(s - xⱼ)` over `elements` into `out[i]`.
+fn complement_products(s: &Fr, elements: &[Fr], factor: &Fr, out: &mut [Fr]) {
+    if elements.len() <= 1 {
+        out.fill(*factor);
+        return;
+    }
+    let mid = elements.len() / 2;
+    let (left, right) = elements.split_at(mid);
+    let (left_out, right_out) = out.split_at_mut(mid);
+    let product =
+        |xs: &[Fr]| Zeroizing::new(xs.iter().fold(Fr::one(), |acc, &x| acc * (*s - x)));
+    let left_factor = Zeroizing::new(*factor * *product(right));
+    let right_factor = Zeroizing::new(*factor * *product(left));
+    join(
+        || complement_products(s, left, &left_factor, left_out),
+        || complement_products(s, right, &right_factor, right_out),
+    );
+}
+
+/// Writes into `out[j]` the commitment to ∏_{i≠j}(X - xᵢ) over the elements
+/// of `node`, given `bases[i]` = g^(sⁱ·C(s)) for i < `node.len()`, where C is
+/// the product over the elements outside the node.
+fn quotient_commitments(
+    node: &SubproductTree<Fr>,
+    elements: &[Fr],
+    bases: &[G1Projective],
+    out: &mut [G1Projective],
+) {
+    let Some(children) = &node.children else {
+        let bases = G1Projective::batch_normalization_into_affine(bases);
+        cfg_iter_mut!(out).zip(elements).for_each(|(witness, &x)| {
+            let (quotient, _) = div_by_linear(&node.poly, x);
+            let scalars: Vec<_> = quotient.coeffs.iter().map(|c| c.into_repr()).collect();
+            *witness = VariableBaseMSM::multi_scalar_mul(&bases[..scalars.len()], &scalars);
+        });
+        return;
+    };
+    let (left, right) = children.as_ref();
+    let domain = Radix2EvaluationDomain::<Fr>::new(bases.len()).expect("FFT domain for the set");
+    let mut bases_hat = bases.to_vec();
+    domain.fft_in_place(&mut bases_hat);
+    let (left_bases, right_bases) = join(
+        || correlate_g1(&domain, &bases_hat, &right.poly.coeffs, left.len()),
+        || correlate_g1(&domain, &bases_hat, &left.poly.coeffs, right.len()),
+    );
+
+    let (left_elements, right_elements) = elements.split_at(left.len());
+    let (left_out, right_out) = out.split_at_mut(left.len());
+    join(
+        || quotient_commitments(left, left_elements, &left_bases, left_out),
+        || quotient_commitments(right, right_elements, &right_bases, right_out),
+    );
+}
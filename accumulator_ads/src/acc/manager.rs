@@ -0,0 +1,244 @@
+//! The trusted party that holds the trapdoor.
+//!
+//! Everything else in this crate works from the public parameters alone. An
+//! `AccumulatorManager` is the one role that owns the secret `s`: it applies
+//! O(1) trapdoor updates, hands out membership witnesses and can generate
+//! public parameters. It is available in release builds, for deployments
+//! where a trusted manager serves updates and untrusted servers and clients
+//! only verify.
+//!
+//! The secret is kept in a sealed file: a fixed header, the compressed
+//! scalar and a BLAKE2b checksum over both. On Unix the file is written
+//! owner-only and refused if group or others can access it. Sealing protects
+//! integrity and guards against accidentally readable files; encryption at
+//! rest is left to the platform's secret store.
+//!
+//! In memory the trapdoor is zeroized when the manager is dropped. A setup
+//! ceremony that only needs the parameters calls `destroy` right after
+//! `generate_parameters`.
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use ark_bls12_381::{Fr, G1Affine};
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::Zero;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use std::fs;
+use std::path::Path;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::dynamic_accumulator::DynamicAccumulator;
+use super::setup::PublicParameters;
+
+/// Leading bytes of a sealed trapdoor file.
+const SEAL_MAGIC: &[u8; 4] = b"ACSK";
+/// Format version of a sealed trapdoor file.
+const SEAL_VERSION: u16 = 1;
+const CHECKSUM_LEN: usize = 32;
+
+/// Owner of the accumulator trapdoor.
+pub struct AccumulatorManager {
+    trapdoor: Zeroizing<Fr>,
+}
+
+impl std::fmt::Debug for AccumulatorManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccumulatorManager")
+            .field("trapdoor", &"<redacted>")
+            .finish()
+    }
+}
+
+fn checksum(bytes: &[u8]) -> blake2b_simd::Hash {
+    blake2b_simd::Params::new()
+        .hash_length(CHECKSUM_LEN)
+        .hash(bytes)
+}
+
+impl AccumulatorManager {
+    /// Manager for the trapdoor `s`, which must be non-zero.
+    pub fn new(trapdoor: Fr) -> Result<Self> {
+        ensure!(!trapdoor.is_zero(), "Trapdoor must be non-zero");
+        Ok(Self {
+            trapdoor: Zeroizing::new(trapdoor),
+        })
+    }
+
+    /// Load the trapdoor from a file written by `save_sealed`.
+    pub fn load_sealed<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(path)
+                .with_context(|| format!("Failed to open sealed trapdoor: {:?}", path))?
+                .permissions()
+                .mode();
+            if mode & 0o077 != 0 {
+                bail!(
+                    "Sealed trapdoor {:?} is accessible to group or others (mode {:o})",
+                    path,
+                    mode & 0o777
+                );
+            }
+        }
+        let bytes = Zeroizing::new(
+            fs::read(path)
+                .with_context(|| format!("Failed to read sealed trapdoor: {:?}", path))?,
+        );
+        ensure!(
+            bytes.len() > SEAL_MAGIC.len() + 2 + CHECKSUM_LEN,
+            "Sealed trapdoor is truncated"
+        );
+        let (body, sum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        ensure!(
+            checksum(body).as_bytes() == sum,
+            "Sealed trapdoor checksum mismatch"
+        );
+        ensure!(&body[..4] == SEAL_MAGIC, "Not a sealed trapdoor file");
+        let version = u16::from_be_bytes([body[4], body[5]]);
+        if version != SEAL_VERSION {
+            bail!("Unsupported sealed trapdoor version {}", version);
+        }
+        let mut scalar = &body[6..];
+        let trapdoor = Zeroizing::new(
+            Fr::deserialize(&mut scalar)
+                .map_err(|e| anyhow!("Failed to decode sealed trapdoor: {:?}", e))?,
+        );
+        ensure!(scalar.is_empty(), "Trailing bytes in sealed trapdoor");
+        Self::new(*trapdoor)
+    }
+
+    /// Write the trapdoor to `path` in the sealed format, owner-only on Unix.
+    pub fn save_sealed<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut bytes = Zeroizing::new(SEAL_MAGIC.to_vec());
+        bytes.extend_from_slice(&SEAL_VERSION.to_be_bytes());
+        self.trapdoor
+            .serialize(&mut *bytes)
+            .map_err(|e| anyhow!("Failed to encode trapdoor: {:?}", e))?;
+        let sum = checksum(&bytes);
+        bytes.extend_from_slice(sum.as_bytes());
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("Failed to create sealed trapdoor: {:?}", path))?;
+        std::io::Write::write_all(&mut file, bytes.as_slice())
+            .with_context(|| format!("Failed to write sealed trapdoor: {:?}", path))?;
+        Ok(())
+    }
+
+    /// Accumulator over `elements` that updates with this trapdoor.
+    pub fn accumulator(&self, elements: &[Fr]) -> DynamicAccumulator {
+        DynamicAccumulator::from_set(*self.trapdoor, elements)
+    }
+
+    /// Accumulator value after adding `element` to `acc`.
+    pub fn add(&self, acc: G1Affine, element: Fr) -> G1Affine {
+        self.wrap(acc).compute_add(element)
+    }
+
+    /// Accumulator value after removing `element` from `acc`.
+    pub fn delete(&self, acc: G1Affine, element: Fr) -> Result<G1Affine> {
+        self.wrap(acc).compute_delete(element)
+    }
+
+    /// Membership witness of `element` in `acc`, in O(1).
+    pub fn membership_witness(&self, acc: G1Affine, element: Fr) -> Result<G1Affine> {
+        self.wrap(acc).compute_membership_witness(element)
+    }
+
+    /// Public parameters of `max_degree` for this trapdoor. Publish them
+    /// and keep the sealed file; verifiers need nothing else.
+    pub fn generate_parameters(&self, max_degree: usize) -> PublicParameters {
+        PublicParameters::generate(*self.trapdoor, max_degree)
+    }
+
+    /// Whether `params` are the powers of this trapdoor, so updates made
+    /// with it agree with commitments computed from the parameters.
+    pub fn matches_parameters(&self, params: &PublicParameters) -> bool {
+        let g1_s = params.g1.mul(*self.trapdoor).into_affine();
+        params.g1_s_vec.get(1) == Some(&g1_s)
+    }
+
+    /// Scrub the trapdoor and give up the manager. Dropping it does the
+    /// same; calling this makes the end of the secret's life explicit, e.g.
+    /// right after `generate_parameters` in a setup ceremony.
+    pub fn destroy(mut self) {
+        self.trapdoor.zeroize();
+    }
+
+    fn wrap(&self, acc: G1Affine) -> DynamicAccumulator {
+        let mut wrapped = DynamicAccumulator::new(*self.trapdoor);
+        wrapped.acc_value = acc;
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acc::setup::{self, PRI_S};
+    use crate::acc::utils::digest_set_from_set;
+    use crate::set::Set;
+
+    fn sealed_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("acc-manager-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_manager_round_trips_and_updates() {
+        setup::init_test_parameters();
+        let path = sealed_path("roundtrip");
+        let manager = AccumulatorManager::new(*PRI_S).unwrap();
+        manager.save_sealed(&path).unwrap();
+        let manager = AccumulatorManager::load_sealed(&path).unwrap();
+        assert!(!format!("{:?}", manager).contains(&PRI_S.to_string()));
+
+        let elements = digest_set_from_set(&Set::from_vec(vec![1, 2, 3]));
+        let acc = manager.accumulator(&elements[..2]).acc_value;
+        let acc = manager.add(acc, elements[2]);
+        assert_eq!(acc, DynamicAccumulator::calculate_commitment(&elements));
+        let witness = manager.membership_witness(acc, elements[0]).unwrap();
+        assert_eq!(witness, manager.delete(acc, elements[0]).unwrap());
+        assert_eq!(
+            witness,
+            DynamicAccumulator::calculate_commitment(&elements[1..])
+        );
+        let params = manager.generate_parameters(3);
+        assert_eq!(params.g1_s_vec, setup::get_g1s_vec().unwrap()[..4].to_vec());
+        assert!(manager.matches_parameters(&params));
+        let other = AccumulatorManager::new(Fr::from(7u64)).unwrap();
+        assert!(!other.matches_parameters(&params));
+
+        // A flipped byte breaks the checksum
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[8] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        assert!(AccumulatorManager::load_sealed(&path).is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(AccumulatorManager::new(Fr::zero()).is_err());
+        manager.destroy();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_manager_refuses_readable_seal() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = sealed_path("readable");
+        AccumulatorManager::new(*PRI_S)
+            .unwrap()
+            .save_sealed(&path)
+            .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let err = AccumulatorManager::load_sealed(&path).unwrap_err();
+        assert!(err.to_string().contains("group or others"), "{}", err);
+        fs::remove_file(&path).unwrap();
+    }
+}
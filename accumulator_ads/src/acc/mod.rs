@@ -1,4 +1,5 @@
 pub mod dynamic_accumulator;
+pub mod manager;
 pub mod proofs;
 pub mod serde_impl;
 pub mod setup;
@@ -11,9 +12,11 @@ pub use ark_bls12_381::{
 // Re-export main components
 pub use utils::{digest_set_from_set, expand_to_poly};
 pub use dynamic_accumulator::{DynamicAccumulator, QueryResult};
+pub use manager::AccumulatorManager;
 pub use proofs::*;
 pub use setup::{E_G_G, PublicParameters, init_public_parameters, init_public_parameters_direct, 
-                get_public_parameters, get_g1s, get_g2s, get_g1s_vec, get_g2s_vec};
+                get_public_parameters, get_g1s, get_g2s, get_g1s_vec, get_g2s_vec,
+                public_parameters_max_degree, check_degree, parameters_of_degree, DegreeExceeded};
 
 /// Unit tests for basic accumulator operations
 /// 
@@ -27,12 +30,13 @@ mod tests {
 
     fn init_logger() {
         let _ = env_logger::builder().is_test(true).try_init();
+        crate::acc::setup::init_test_parameters();
     }
 
     #[test]
     fn test_add_delete_flow() {
         init_logger();
-        let mut acc = DynamicAccumulator::default();
+        let mut acc = DynamicAccumulator::new(*setup::PRI_S);
 
         // Create a test element
         let set_elem = Set::from_vec(vec![42]);
@@ -68,6 +72,158 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_witness_updates_follow_accumulator() {
+        init_logger();
+        let elements = digest_set_from_set(&Set::from_vec(vec![1, 2, 3]));
+        let added = digest_set_from_set(&Set::from_vec(vec![4]))[0];
+        let mut acc = DynamicAccumulator::from_set(*setup::PRI_S, &elements);
+        let mut proof = MembershipProof::new(&acc, elements[0]).unwrap();
+
+        let prev_acc = acc.acc_value;
+        acc.acc_value = acc.compute_add(added);
+        proof.update_on_add(prev_acc, added);
+        assert_eq!(proof, MembershipProof::new(&acc, elements[0]).unwrap());
+        assert!(proof.verify(acc.acc_value));
+
+        acc.acc_value = acc.compute_delete(elements[1]).unwrap();
+        proof.update_on_delete(acc.acc_value, elements[1]).unwrap();
+        assert_eq!(proof, MembershipProof::new(&acc, elements[0]).unwrap());
+        assert!(proof.verify(acc.acc_value));
+
+        assert!(proof.update_on_delete(acc.acc_value, elements[0]).is_err());
+    }
+
+    #[test]
+    fn test_incremental_union_matches_trapdoor_update() {
+        init_logger();
+        let base = digest_set_from_set(&Set::from_vec(vec![1, 2, 3]));
+        let added = digest_set_from_set(&Set::from_vec(vec![4, 5]));
+        let all = digest_set_from_set(&Set::from_vec(vec![1, 2, 3, 4, 5]));
+
+        let union = DynamicAccumulator::incremental_union(&base, &added);
+        assert_eq!(union, DynamicAccumulator::calculate_commitment(&all));
+        let base_acc = DynamicAccumulator::from_set(*setup::PRI_S, &base);
+        assert_eq!(union, base_acc.incremental_add_elements(&added));
+        let base_acc = base_acc.acc_value;
+        assert_eq!(DynamicAccumulator::incremental_union(&base, &[]), base_acc);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_default_trapdoor_shims() {
+        init_logger();
+        let base = digest_set_from_set(&Set::from_vec(vec![1, 2, 3]));
+        let added = digest_set_from_set(&Set::from_vec(vec![4, 5]));
+        let base_acc = DynamicAccumulator::from_set(*setup::PRI_S, &base);
+
+        assert_eq!(DynamicAccumulator::default(), DynamicAccumulator::with_default_trapdoor());
+        let restored = DynamicAccumulator::from_value(base_acc.acc_value);
+        assert_eq!(restored, base_acc);
+        assert_eq!(
+            DynamicAccumulator::incremental_add_with_default_trapdoor(base_acc.acc_value, &added),
+            DynamicAccumulator::incremental_union(&base, &added)
+        );
+    }
+
+    #[test]
+    fn test_incremental_remove_matches_trapdoor_delete() {
+        init_logger();
+        let base = digest_set_from_set(&Set::from_vec(vec![1, 2, 3, 4, 5]));
+        let removed = [base[1], base[3]];
+        let remaining: Vec<Fr> = base.iter().filter(|e| !removed.contains(*e)).copied().collect();
+
+        let (acc, poly) =
+            DynamicAccumulator::incremental_remove(&expand_to_poly(&base), &removed).unwrap();
+        assert_eq!(poly, expand_to_poly(&remaining));
+        assert_eq!(acc, DynamicAccumulator::calculate_commitment(&remaining));
+        let mut trapdoor = DynamicAccumulator::from_set(*setup::PRI_S, &base);
+        for element in removed {
+            trapdoor.acc_value = trapdoor.compute_delete(element).unwrap();
+        }
+        assert_eq!(acc, trapdoor.acc_value);
+        assert!(DynamicAccumulator::incremental_remove(&poly, &removed[..1]).is_err());
+    }
+
+    #[test]
+    fn test_oversized_sets_report_degree_exceeded() {
+        init_logger();
+        let available = public_parameters_max_degree().unwrap();
+        let elements = digest_set_from_set(&Set::from_vec((0..available as u64 + 1).collect()));
+        let err = DynamicAccumulator::try_calculate_commitment(&elements).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DegreeExceeded>(),
+            Some(&DegreeExceeded { needed: available + 1, available })
+        );
+        let err = DynamicAccumulator::incremental_remove(&expand_to_poly(&elements), &[])
+            .unwrap_err();
+        assert!(err.downcast_ref::<DegreeExceeded>().is_some(), "{}", err);
+        // The non-membership quotient is one degree lower and still fits
+        assert!(
+            DynamicAccumulator::compute_non_membership_witness(Fr::from(7u64), &elements).is_ok()
+        );
+        assert_eq!(
+            DynamicAccumulator::try_calculate_commitment(&elements[1..]).unwrap(),
+            DynamicAccumulator::calculate_commitment(&elements[1..])
+        );
+    }
+
+    #[test]
+    fn test_compute_all_witnesses_matches_single() {
+        init_logger();
+        let elements = digest_set_from_set(&Set::from_vec((1..=9).collect()));
+        let acc = DynamicAccumulator::from_set(*setup::PRI_S, &elements);
+        let witnesses = acc.compute_all_witnesses(&elements);
+        assert_eq!(witnesses.len(), elements.len());
+        for (elem, witness) in elements.iter().zip(&witnesses) {
+            assert_eq!(*witness, acc.compute_membership_witness(*elem).unwrap());
+        }
+        assert_eq!(acc.compute_membership_witnesses(&elements).unwrap(), witnesses);
+        assert!(acc.compute_all_witnesses(&[]).is_empty());
+        assert_eq!(
+            acc.compute_all_witnesses(&elements[..1]),
+            vec![DynamicAccumulator::empty_commitment()]
+        );
+    }
+
+    #[test]
+    fn test_public_witnesses_match_trapdoor_witnesses() {
+        init_logger();
+        let elements = digest_set_from_set(&Set::from_vec((1..=9).collect()));
+        let acc = DynamicAccumulator::from_set(*setup::PRI_S, &elements);
+        let witnesses = DynamicAccumulator::membership_witnesses(&elements, &elements).unwrap();
+        assert_eq!(witnesses, acc.compute_all_witnesses(&elements));
+        for (elem, witness) in elements.iter().zip(&witnesses) {
+            let proof = MembershipProof {
+                element: *elem,
+                witness: *witness,
+            };
+            assert!(proof.verify(acc.acc_value));
+        }
+        let outsider = digest_set_from_set(&Set::from_vec(vec![10]));
+        assert!(DynamicAccumulator::membership_witnesses(&elements, &outsider).is_err());
+    }
+
+    #[test]
+    fn test_all_public_witnesses_match_single() {
+        init_logger();
+        // Several levels of FFT correlation above the test leaf size, with
+        // uneven halves
+        for n in [1, 2, 5, 13] {
+            let elements = digest_set_from_set(&Set::from_vec((1..=n).collect()));
+            let acc = DynamicAccumulator::from_set(*setup::PRI_S, &elements);
+            let witnesses = DynamicAccumulator::all_membership_witnesses(&elements).unwrap();
+            assert_eq!(witnesses, acc.compute_all_witnesses(&elements), "n = {}", n);
+            assert_eq!(
+                witnesses,
+                DynamicAccumulator::membership_witnesses(&elements, &elements).unwrap()
+            );
+        }
+        assert!(DynamicAccumulator::all_membership_witnesses(&[])
+            .unwrap()
+            .is_empty());
+    }
+
     #[test]
     fn test_disjointness_proof() {
         init_logger();
@@ -108,22 +264,121 @@ mod tests {
         // Intersection
         let (inter_acc, inter_proof) =
             IntersectionProof::new(&s1, &s2, &s_inter).expect("Intersection failed");
-        assert_eq!(
-            inter_acc.acc_value, inter_val,
-            "Intersection acc value mismatch"
-        );
+        assert_eq!(inter_acc, inter_val, "Intersection acc value mismatch");
         assert!(
-            inter_proof.verify(acc1_val, acc2_val, inter_acc.acc_value),
+            inter_proof.verify(acc1_val, acc2_val, inter_acc),
             "Intersection verify failed"
         );
 
         // Union
         let (union_acc, union_proof) =
-            UnionProof::new(&inter_acc, inter_proof, &s_union).expect("Union failed");
-        assert_eq!(union_acc.acc_value, union_val, "Union acc value mismatch");
+            UnionProof::new(inter_acc, inter_proof, &s_union).expect("Union failed");
+        assert_eq!(union_acc, union_val, "Union acc value mismatch");
         assert!(
-            union_proof.verify(acc1_val, acc2_val, union_acc.acc_value),
+            union_proof.verify(acc1_val, acc2_val, union_acc),
             "Union verify failed"
         );
     }
+
+    #[test]
+    fn test_tracked_polynomial_follows_updates() {
+        init_logger();
+        let set = digest_set_from_set(&Set::from_vec((1..=8).collect()));
+        let mut acc = DynamicAccumulator::from_set_tracking(*setup::PRI_S, &set[..4]);
+        acc.add_element(set[4]);
+        AddProof::new(&mut acc, set[5]).unwrap();
+        acc.delete_element(set[1]).unwrap();
+        UpdateProof::new(&mut acc, set[2], set[6]).unwrap();
+
+        let current = [set[0], set[3], set[4], set[5], set[6]];
+        assert_eq!(acc.polynomial(), Some(&expand_to_poly(&current)));
+        assert_eq!(acc.polynomial_commitment().unwrap(), Some(acc.acc_value));
+        assert_eq!(acc.acc_value, DynamicAccumulator::calculate_commitment(&current));
+
+        // The synthetic-division witness is the XGCD one
+        let absent = set[7];
+        let (a, b) = utils::solve_bezout_identity(
+            expand_to_poly(&current),
+            expand_to_poly(&[absent]),
+        )
+        .unwrap();
+        let witness = acc.non_membership_witness(absent).unwrap();
+        assert_eq!(
+            witness,
+            (utils::poly_to_g2(b).unwrap(), utils::poly_to_g2(a).unwrap())
+        );
+        assert_eq!(
+            witness,
+            DynamicAccumulator::compute_non_membership_witness(absent, &current).unwrap()
+        );
+        let proof = NonMembershipProof::from_accumulator(&acc, absent).unwrap();
+        assert!(proof.verify(acc.acc_value));
+
+        // Members have no witness; deleting a non-member changes nothing
+        assert!(acc.non_membership_witness(set[0]).is_err());
+        let before = acc.clone();
+        assert!(acc.delete_element(set[1]).is_err());
+        assert_eq!(acc, before);
+        let untracked = DynamicAccumulator::from_set(*setup::PRI_S, &current);
+        assert!(untracked.polynomial().is_none());
+        assert!(untracked.non_membership_witness(absent).is_err());
+    }
+}
+
+/// Cross-validation of the two ways this crate computes the same values: O(1)
+/// updates with the trapdoor, which the server side uses, and MSMs over the
+/// public parameters, which anyone can run. Both must agree on every shared
+/// operation for sets of any size the test parameters support.
+#[cfg(all(test, feature = "compat-check"))]
+mod compat_tests {
+    use super::*;
+    use crate::acc::utils::digest_set_from_set;
+    use crate::set::Set;
+
+    /// Distinct elements `offset..offset + n`.
+    fn elements(offset: u64, n: u64) -> Vec<Fr> {
+        digest_set_from_set(&Set::from_vec((offset..offset + n).collect()))
+    }
+
+    #[test]
+    fn test_trapdoor_and_public_computation_agree() {
+        setup::init_test_parameters();
+        for n in 0..12u64 {
+            let set = elements(100 * n, n);
+            let extra = elements(100 * n + 50, 3);
+            let public = DynamicAccumulator::calculate_commitment(&set);
+
+            // Commitment: one element at a time, all at once, from the powers
+            let mut acc = DynamicAccumulator::new(*setup::PRI_S);
+            for elem in &set {
+                acc.acc_value = acc.compute_add(*elem);
+            }
+            assert_eq!(acc.acc_value, public, "commitment of {} elements", n);
+            let empty = DynamicAccumulator::new(*setup::PRI_S);
+            assert_eq!(empty.incremental_add_elements(&set), public);
+
+            // Union with new elements
+            let union: Vec<Fr> = set.iter().chain(&extra).copied().collect();
+            let expected = DynamicAccumulator::calculate_commitment(&union);
+            let union_acc = DynamicAccumulator::incremental_union(&set, &extra);
+            assert_eq!(union_acc, expected);
+            assert_eq!(acc.incremental_add_elements(&extra), expected);
+
+            // Deletion and membership witnesses
+            let witnesses = acc.compute_all_witnesses(&set);
+            assert_eq!(acc.compute_membership_witnesses(&set).unwrap(), witnesses);
+            for (i, elem) in set.iter().enumerate() {
+                let rest: Vec<Fr> = set.iter().filter(|e| *e != elem).copied().collect();
+                let without = DynamicAccumulator::calculate_commitment(&rest);
+                assert_eq!(acc.compute_delete(*elem).unwrap(), without);
+                assert_eq!(witnesses[i], without);
+                assert!(MembershipProof::new(&acc, *elem).unwrap().verify(public));
+            }
+
+            // Non-membership is only computed from the set; check it verifies
+            // against the trapdoor-built value
+            let proof = NonMembershipProof::new(extra[0], &set).unwrap();
+            assert!(proof.verify(acc.acc_value));
+        }
+    }
 }
@@ -1,6 +1,7 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ark_bls12_381::{Bls12_381 as Curve, Fr, G1Affine, G2Affine};
 use ark_ec::{AffineCurve, PairingEngine};
+use ark_ff::Field;
 use serde::{Deserialize, Serialize};
 
 use crate::acc::dynamic_accumulator::DynamicAccumulator;
@@ -24,8 +25,8 @@ impl AddProof {
     /// Generates proof and updates accumulator.
     pub fn new(acc: &mut DynamicAccumulator, element: Fr) -> Result<Self> {
         let old_acc = acc.acc_value;
-        let new_acc = acc.compute_add(element);
-        acc.acc_value = new_acc;
+        acc.add_element(element);
+        let new_acc = acc.acc_value;
 
         Ok(Self {
             old_acc_value: old_acc,
@@ -41,7 +42,10 @@ impl AddProof {
     /// SECURITY: Uses ONLY public parameters. No secret knowledge required.
     pub fn verify(&self) -> bool {
         let g2 = G2Affine::prime_subgroup_generator();
-        let g2_s = get_g2s(1_usize); // g2^s from public parameters
+        // g2^s from public parameters; nothing verifies without them
+        let Ok(g2_s) = get_g2s(1_usize) else {
+            return false;
+        };
 
         // Compute g2^(-element) = g2^{-element}
         let g2_neg_elem = g2.mul(self.element.neg()).into_affine();
@@ -72,8 +76,8 @@ impl DeleteProof {
     /// Generates proof and updates accumulator.
     pub fn new(acc: &mut DynamicAccumulator, element: Fr) -> Result<Self> {
         let old_acc = acc.acc_value;
-        let new_acc = acc.compute_delete(element)?;
-        acc.acc_value = new_acc;
+        acc.delete_element(element)?;
+        let new_acc = acc.acc_value;
 
         Ok(Self {
             old_acc_value: old_acc,
@@ -89,7 +93,10 @@ impl DeleteProof {
     /// SECURITY: Uses ONLY public parameters. No secret knowledge required.
     pub fn verify(&self) -> bool {
         let g2 = G2Affine::prime_subgroup_generator();
-        let g2_s = get_g2s(1_usize); // g2^s from public parameters
+        // g2^s from public parameters; nothing verifies without them
+        let Ok(g2_s) = get_g2s(1_usize) else {
+            return false;
+        };
 
         // Compute g2^(-element)
         let g2_neg_elem = g2.mul(self.element.neg()).into_affine();
@@ -122,8 +129,8 @@ impl UpdateProof {
     /// Generates proof and updates accumulator.
     pub fn new(acc: &mut DynamicAccumulator, old_element: Fr, new_element: Fr) -> Result<Self> {
         let old_acc = acc.acc_value;
-        let new_acc = acc.compute_update(old_element, new_element)?;
-        acc.acc_value = new_acc;
+        acc.update_element(old_element, new_element)?;
+        let new_acc = acc.acc_value;
 
         Ok(Self {
             old_acc_value: old_acc,
@@ -139,7 +146,10 @@ impl UpdateProof {
     /// SECURITY: Uses ONLY public parameters. No secret knowledge required.
     pub fn verify(&self) -> bool {
         let g2 = G2Affine::prime_subgroup_generator();
-        let g2_s = get_g2s(1_usize); // g2^s from public parameters
+        // g2^s from public parameters; nothing verifies without them
+        let Ok(g2_s) = get_g2s(1_usize) else {
+            return false;
+        };
 
         // Compute g2^(s-old_element)
         let g2_neg_old = g2.mul(self.old_element.neg()).into_affine();
@@ -179,7 +189,10 @@ impl MembershipProof {
     /// This enables PUBLIC VERIFIABILITY - anyone can verify membership.
     pub fn verify(&self, accumulator: G1Affine) -> bool {
         let g2 = G2Affine::prime_subgroup_generator();
-        let g2_s = get_g2s(1_usize); // g2^s from public parameters
+        // g2^s from public parameters; nothing verifies without them
+        let Ok(g2_s) = get_g2s(1_usize) else {
+            return false;
+        };
 
         // Compute g2^(-element)
         let g2_neg_elem = g2.mul(self.element.neg()).into_affine();
@@ -193,6 +206,28 @@ impl MembershipProof {
 
         lhs == rhs
     }
+
+    /// Refreshes the witness after `added` joined the accumulator, whose value
+    /// before the addition was `prev_acc`. Needs neither the trapdoor nor the set:
+    /// w' = acc'^(1/(s-x)) = acc^((s-y)/(s-x)) = acc · w^(x-y)
+    pub fn update_on_add(&mut self, prev_acc: G1Affine, added: Fr) {
+        let shift = self.element - added;
+        self.witness = (prev_acc.into_projective() + self.witness.mul(shift)).into_affine();
+    }
+
+    /// Refreshes the witness after `removed` left the accumulator, whose value
+    /// after the removal is `new_acc`. From w = acc' · w'^(x-y):
+    /// w' = (w / acc')^(1/(x-y))
+    ///
+    /// Fails if `removed` is the element itself, which has no witness any more.
+    pub fn update_on_delete(&mut self, new_acc: G1Affine, removed: Fr) -> Result<()> {
+        let inverse = (self.element - removed)
+            .inverse()
+            .ok_or_else(|| anyhow!("Cannot update witness: its own element was removed"))?;
+        let quotient = self.witness.into_projective() - new_acc.into_projective();
+        self.witness = quotient.into_affine().mul(inverse).into_affine();
+        Ok(())
+    }
 }
 
 /// A proof of non-membership for an element in the accumulator.
@@ -219,13 +254,27 @@ impl NonMembershipProof {
         })
     }
 
+    /// Proof against the polynomial `acc` tracks (see
+    /// `DynamicAccumulator::from_set_tracking`), without expanding the set.
+    pub fn from_accumulator(acc: &DynamicAccumulator, element: Fr) -> Result<Self> {
+        let (witness, g2_a) = acc.non_membership_witness(element)?;
+        Ok(Self {
+            element,
+            witness,
+            g2_a,
+        })
+    }
+
     /// Verifies non-membership using Bezout's identity: A(s)*P(s) + B(s)*(s-x) = 1
     /// Check: e(Acc, g2^A) * e(g1^(s-x), g2^B) = e(g1, g2)
     ///
     /// SECURITY: Uses ONLY public parameters. No secret knowledge required.
     pub fn verify(&self, acc_value: G1Affine) -> bool {
         let g1 = G1Affine::prime_subgroup_generator();
-        let g1_s = get_g1s(1_usize); // g1^s from public parameters
+        // g1^s from public parameters; nothing verifies without them
+        let Ok(g1_s) = get_g1s(1_usize) else {
+            return false;
+        };
 
         // Compute g1^(-element)
         let g1_neg_elem = g1.mul(self.element.neg()).into_affine();
@@ -264,10 +313,9 @@ impl IntersectionProof {
         set1: &[Fr],
         set2: &[Fr],
         intersection_set: &[Fr],
-    ) -> Result<(DynamicAccumulator, Self)> {
-        // 1. Create the intersection accumulator
-        let trapdoor = super::setup::PRI_S.clone();
-        let intersection_acc = DynamicAccumulator::from_set(trapdoor, intersection_set);
+    ) -> Result<(G1Affine, Self)> {
+        // 1. Commit to the intersection
+        let intersection_acc = DynamicAccumulator::calculate_commitment(intersection_set);
 
         // 2. Compute witnesses using DynamicAccumulator logic
         let (witness_a, witness_b, witness_coprime_a, witness_coprime_b) =
@@ -317,16 +365,15 @@ pub struct UnionProof {
 
 impl UnionProof {
     pub fn new(
-        intersection_acc: &DynamicAccumulator,
+        intersection_acc_value: G1Affine,
         intersection_proof: IntersectionProof,
         union_set: &[Fr],
-    ) -> Result<(DynamicAccumulator, Self)> {
-        // Reconstruct union accumulator
-        let trapdoor = super::setup::PRI_S.clone();
-        let union_acc = DynamicAccumulator::from_set(trapdoor, union_set);
+    ) -> Result<(G1Affine, Self)> {
+        // Commit to the union
+        let union_acc = DynamicAccumulator::calculate_commitment(union_set);
 
         let union_proof = Self {
-            intersection_acc_value: intersection_acc.acc_value,
+            intersection_acc_value,
             intersection_proof,
         };
 
@@ -394,10 +441,11 @@ mod tests {
 
     #[test]
     fn test_update_proof() {
+        crate::acc::setup::init_test_parameters();
         // Create an initial set with some elements
         let initial_set = Set::from_vec(vec![1u64, 2, 3, 4, 5]);
         let digest_set = digest_set_from_set(&initial_set);
-        let trapdoor = crate::acc::setup::PRI_S.clone();
+        let trapdoor = *crate::acc::setup::PRI_S;
         let mut acc = DynamicAccumulator::from_set(trapdoor, &digest_set);
         let initial_acc_value = acc.acc_value;
 
@@ -421,12 +469,13 @@ mod tests {
 
     #[test]
     fn test_update_equals_delete_then_add() {
+        crate::acc::setup::init_test_parameters();
         // Create an initial set
         let initial_set = Set::from_vec(vec![10u64, 20, 30]);
         let digest_set = digest_set_from_set(&initial_set);
-        let trapdoor = crate::acc::setup::PRI_S.clone();
+        let trapdoor = *crate::acc::setup::PRI_S;
 
-        let mut acc1 = DynamicAccumulator::from_set(trapdoor.clone(), &digest_set);
+        let mut acc1 = DynamicAccumulator::from_set(trapdoor, &digest_set);
         let mut acc2 = DynamicAccumulator::from_set(trapdoor, &digest_set);
 
         let old_element = Fr::from(20u64);
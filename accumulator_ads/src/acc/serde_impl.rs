@@ -1,3 +1,7 @@
+use crate::acc::utils::validate_point;
+use ark_bls12_381::Fr;
+use ark_ec::models::SWModelParameters;
+use ark_ec::short_weierstrass_jacobian::GroupAffine;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use core::marker::PhantomData;
 use serde::{
@@ -5,6 +9,30 @@ use serde::{
     ser::Serializer,
 };
 
+/// Checks run on every value after it is deserialized, before it reaches
+/// proof verification. Curve points must lie in the prime-order subgroup.
+pub trait PostDeserialize: Sized {
+    fn check(&self) -> anyhow::Result<()>;
+}
+
+impl<P: SWModelParameters> PostDeserialize for GroupAffine<P> {
+    fn check(&self) -> anyhow::Result<()> {
+        validate_point(self)
+    }
+}
+
+impl PostDeserialize for Fr {
+    fn check(&self) -> anyhow::Result<()> {
+        // Canonical deserialization already rejects non-reduced field elements
+        Ok(())
+    }
+}
+
+fn checked<C: PostDeserialize, E: serde::de::Error>(c: C) -> Result<C, E> {
+    c.check().map_err(E::custom)?;
+    Ok(c)
+}
+
 pub fn serialize<S: Serializer, C: CanonicalSerialize>(c: &C, s: S) -> Result<S::Ok, S::Error> {
     let mut buf = Vec::<u8>::new();
     c.serialize(&mut buf)
@@ -16,7 +44,11 @@ pub fn serialize<S: Serializer, C: CanonicalSerialize>(c: &C, s: S) -> Result<S:
     }
 }
 
-pub fn deserialize<'de, D: Deserializer<'de>, C: CanonicalSerialize + CanonicalDeserialize>(
+pub fn deserialize<
+    'de,
+    D: Deserializer<'de>,
+    C: CanonicalSerialize + CanonicalDeserialize + PostDeserialize,
+>(
     d: D,
 ) -> Result<C, D::Error> {
     use core::fmt;
@@ -24,7 +56,9 @@ pub fn deserialize<'de, D: Deserializer<'de>, C: CanonicalSerialize + CanonicalD
 
     struct HexVisitor<C>(PhantomData<C>);
 
-    impl<'de, C: CanonicalSerialize + CanonicalDeserialize> Visitor<'de> for HexVisitor<C> {
+    impl<'de, C: CanonicalSerialize + CanonicalDeserialize + PostDeserialize> Visitor<'de>
+        for HexVisitor<C>
+    {
         type Value = C;
 
         fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -33,13 +67,15 @@ pub fn deserialize<'de, D: Deserializer<'de>, C: CanonicalSerialize + CanonicalD
 
         fn visit_str<E: DeError>(self, value: &str) -> Result<C, E> {
             let data = hex::decode(value).map_err(E::custom)?;
-            C::deserialize(&data[..]).map_err(E::custom)
+            checked(C::deserialize(&data[..]).map_err(E::custom)?)
         }
     }
 
     struct BytesVisitor<C>(PhantomData<C>);
 
-    impl<'de, C: CanonicalSerialize + CanonicalDeserialize> Visitor<'de> for BytesVisitor<C> {
+    impl<'de, C: CanonicalSerialize + CanonicalDeserialize + PostDeserialize> Visitor<'de>
+        for BytesVisitor<C>
+    {
         type Value = C;
 
         fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -47,7 +83,7 @@ pub fn deserialize<'de, D: Deserializer<'de>, C: CanonicalSerialize + CanonicalD
         }
 
         fn visit_bytes<E: DeError>(self, v: &[u8]) -> Result<C, E> {
-            C::deserialize(v).map_err(E::custom)
+            checked(C::deserialize(v).map_err(E::custom)?)
         }
     }
 
@@ -86,4 +122,19 @@ mod tests {
         assert_eq!(serde_json::from_str::<Foo>(&json).unwrap(), foo);
         assert_eq!(bincode::deserialize::<Foo>(&bin[..]).unwrap(), foo);
     }
+
+    #[test]
+    fn test_serde_rejects_point_outside_subgroup() {
+        use ark_bls12_381::Fq;
+
+        let outside = (1u64..)
+            .find_map(|x| G1Affine::get_point_from_x(Fq::from(x), false))
+            .unwrap();
+        let foo = Foo {
+            f1: outside,
+            f2: G2Affine::prime_subgroup_generator(),
+        };
+        let json = serde_json::to_string(&foo).unwrap();
+        assert!(serde_json::from_str::<Foo>(&json).is_err());
+    }
 }
@@ -1,31 +1,47 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail, ensure};
 use ark_bls12_381::{Bls12_381 as Curve, Fq12, G1Affine, G2Affine};
 use ark_ec::{AffineCurve, PairingEngine};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 use std::path::Path;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 
 use ark_bls12_381::{Fr, G1Projective, G2Projective};
 use ark_ec::ProjectiveCurve;
 
+use crate::acc::utils::validate_points_batch;
+
+pub mod ceremony;
+pub mod ptau;
+
 #[cfg(test)]
-#[allow(dead_code)]
 const GS_VEC_LEN: usize = 20;
 #[cfg(not(test))]
 #[allow(dead_code)]
 const GS_VEC_LEN: usize = 5000;
 
-// Since the project holds the private key, we expose it always
+// Trapdoor of the insecure test parameters. Only tests and builds with the
+// `insecure-test-params` feature have it: everything else works from public
+// parameters, and whoever holds a real
+// secret keeps it in an `AccumulatorManager`, which zeroizes it and can
+// `destroy` it. `accumulator_tree::setup::init_insecure_parameters` builds
+// its parameters from the same value.
+#[cfg(any(test, feature = "insecure-test-params"))]
 lazy_static! {
     pub static ref PRI_S: Fr = Fr::from(259535143263514268207918833918737523409u128);
 }
 
+/// Leading bytes of a parameters file written by `save_to_file`.
+const PARAMS_MAGIC: &[u8; 4] = b"ACPP";
+/// Format version of the parameters file header.
+const PARAMS_VERSION: u16 = 1;
+
 /// Public parameters loaded from trusted setup
-/// These parameters are generated through a trusted setup ceremony
+/// These parameters are generated through a trusted setup ceremony (see
+/// `ceremony` for a multi-party one)
 /// The secret trapdoor s must be destroyed after generating these parameters
 #[derive(Debug, Clone, CanonicalSerialize, CanonicalDeserialize)]
 pub struct PublicParameters {
@@ -40,13 +56,62 @@ pub struct PublicParameters {
 }
 
 impl PublicParameters {
-    /// Load public parameters from a file
+    /// Load public parameters written by `save_to_file`.
+    ///
+    /// The file starts with a header carrying the parameters' `digest`, so a
+    /// tampered or truncated body is refused before use. Every point is then
+    /// checked for subgroup membership and the vectors for being consecutive
+    /// powers of one `s` (`verify_powers`). Files from before the header are
+    /// still read, with the same checks minus the digest.
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = File::open(path.as_ref())
             .with_context(|| format!("Failed to open parameters file: {:?}", path.as_ref()))?;
         let mut reader = BufReader::new(file);
-        let params = Self::deserialize_unchecked(&mut reader)
-            .context("Failed to deserialize public parameters")?;
+        let mut magic = [0u8; 4];
+        reader
+            .read_exact(&mut magic)
+            .context("Parameters file is truncated")?;
+        let embedded = if &magic == PARAMS_MAGIC {
+            let mut version = [0u8; 2];
+            reader.read_exact(&mut version)?;
+            let version = u16::from_be_bytes(version);
+            if version != PARAMS_VERSION {
+                bail!("Unsupported parameters file version {}", version);
+            }
+            let mut digest = [0u8; 32];
+            reader
+                .read_exact(&mut digest)
+                .context("Parameters file is truncated")?;
+            Some(digest)
+        } else {
+            None
+        };
+        // Points are read unchecked for speed and then validated as one batch
+        let params = match embedded {
+            Some(_) => Self::deserialize_unchecked(&mut reader),
+            None => Self::deserialize_unchecked(&mut (&magic[..]).chain(&mut reader)),
+        }
+        .context("Failed to deserialize public parameters")?;
+        ensure!(
+            reader.read(&mut [0u8; 1])? == 0,
+            "Trailing bytes after public parameters"
+        );
+        match embedded {
+            Some(digest) => ensure!(
+                params.digest() == digest,
+                "Public parameters do not match the digest in the file header"
+            ),
+            None => warn!(
+                "Parameters file {:?} has no digest header; re-save it to add one",
+                path.as_ref()
+            ),
+        }
+        params
+            .validate()
+            .context("Public parameters failed point validation")?;
+        params
+            .verify_powers()
+            .context("Public parameters failed the pairing consistency check")?;
 
         info!(
             "Loaded public parameters with {} G1 powers and {} G2 powers",
@@ -57,13 +122,108 @@ impl PublicParameters {
         Ok(params)
     }
 
-    /// Save public parameters to a file
+    /// SHA-256 over the compressed serialization: the identity of the setup,
+    /// embedded by `save_to_file` and reported by verifier bundles. Pin it to
+    /// make sure a deployment loaded the parameters it expects.
+    pub fn digest(&self) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+        let mut bytes = Vec::new();
+        self.serialize(&mut bytes)
+            .expect("serializing into a Vec cannot fail");
+        Sha256::digest(&bytes).into()
+    }
+
+    /// Load the first `max_degree + 1` powers of a BLS12-381 `.ptau`
+    /// transcript (snarkjs, perpetual powers of tau), checking every point
+    /// and the ratios between consecutive powers. See `ptau`.
+    pub fn load_from_ptau<P: AsRef<Path>>(path: P, max_degree: usize) -> Result<Self> {
+        let file = File::open(path.as_ref())
+            .with_context(|| format!("Failed to open ptau file: {:?}", path.as_ref()))?;
+        let params = ptau::read_ptau(&mut BufReader::new(file), max_degree)?;
+        info!(
+            "Imported degree {} public parameters from {:?}",
+            max_degree,
+            path.as_ref()
+        );
+        Ok(params)
+    }
+
+    /// Check that every point is a valid subgroup element and that both power
+    /// vectors start at the standard generators. Subgroup checks run in parallel.
+    pub fn validate(&self) -> Result<()> {
+        if self.g1 != G1Affine::prime_subgroup_generator()
+            || self.g2 != G2Affine::prime_subgroup_generator()
+        {
+            bail!("generators differ from the standard BLS12-381 generators");
+        }
+        if self.g1_s_vec.first() != Some(&self.g1) || self.g2_s_vec.first() != Some(&self.g2) {
+            bail!("power vectors must start with the generators");
+        }
+        validate_points_batch(&self.g1_s_vec).context("invalid G1 power")?;
+        validate_points_batch(&self.g2_s_vec).context("invalid G2 power")?;
+        Ok(())
+    }
+
+    /// Check that both vectors hold the same consecutive powers of one
+    /// non-zero `s`, without knowing it. Compares random linear combinations
+    /// of each vector and the vector shifted by one, so the cost is two MSMs
+    /// and four pairings rather than a pairing per power. Combine with
+    /// `validate` for parameters from an untrusted source.
+    pub fn verify_powers(&self) -> Result<()> {
+        use ark_ec::msm::VariableBaseMSM;
+        use ark_ff::{One, PrimeField, Zero};
+
+        let n = self.g1_s_vec.len().saturating_sub(1);
+        if n == 0 || self.g2_s_vec.len() != n + 1 {
+            bail!("Power vectors must have the same length and degree at least 1");
+        }
+        if self.g1_s_vec[1].is_zero() {
+            bail!("Parameters are powers of s = 0");
+        }
+        // ρ from the parameters themselves, fixed before it is drawn
+        let mut bytes = Vec::new();
+        self.serialize(&mut bytes)
+            .context("Failed to serialize public parameters")?;
+        let hash = blake2b_simd::Params::new().hash_length(64).hash(&bytes);
+        let rho = Fr::from_le_bytes_mod_order(hash.as_bytes());
+        let rho_powers: Vec<_> = std::iter::successors(Some(Fr::one()), |p| Some(*p * rho))
+            .take(n)
+            .map(|p| p.into_repr())
+            .collect();
+
+        let g1_low = VariableBaseMSM::multi_scalar_mul(&self.g1_s_vec[..n], &rho_powers);
+        let g1_high = VariableBaseMSM::multi_scalar_mul(&self.g1_s_vec[1..], &rho_powers);
+        if Curve::pairing(g1_low, self.g2_s_vec[1]) != Curve::pairing(g1_high, self.g2) {
+            bail!("G1 powers are not consecutive powers of s");
+        }
+        let g2_low = VariableBaseMSM::multi_scalar_mul(&self.g2_s_vec[..n], &rho_powers);
+        let g2_high = VariableBaseMSM::multi_scalar_mul(&self.g2_s_vec[1..], &rho_powers);
+        if Curve::pairing(self.g1_s_vec[1], g2_low) != Curve::pairing(self.g1, g2_high) {
+            bail!("G2 powers are not consecutive powers of s");
+        }
+        Ok(())
+    }
+
+    /// Highest polynomial degree these parameters can commit to in both groups.
+    /// A set of `n` elements needs degree `n` (powers `s^0..=s^n`).
+    pub fn max_degree(&self) -> usize {
+        self.g1_s_vec
+            .len()
+            .min(self.g2_s_vec.len())
+            .saturating_sub(1)
+    }
+
+    /// Save public parameters to a file, behind a header with their `digest`
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let file = File::create(path.as_ref())
             .with_context(|| format!("Failed to create parameters file: {:?}", path.as_ref()))?;
         let mut writer = BufWriter::new(file);
+        writer.write_all(PARAMS_MAGIC)?;
+        writer.write_all(&PARAMS_VERSION.to_be_bytes())?;
+        writer.write_all(&self.digest())?;
         self.serialize_unchecked(&mut writer)
             .context("Failed to serialize public parameters")?;
+        writer.flush()?;
 
         info!("Saved public parameters to {:?}", path.as_ref());
         Ok(())
@@ -73,10 +233,17 @@ impl PublicParameters {
     /// In production, this must be done through a trusted setup ceremony
     /// and the secret s MUST be destroyed immediately after generation
     ///
-    /// This function is available in both test and debug builds for development convenience.
-    /// It should NEVER be used in production/release builds.
-    #[cfg(any(test, debug_assertions))]
+    /// This function is available in tests and with the `insecure-test-params`
+    /// feature for development convenience. It should NEVER be used in production.
+    #[cfg(any(test, feature = "insecure-test-params"))]
     pub fn generate_for_testing(secret_s: Fr, max_degree: usize) -> Self {
+        Self::generate(secret_s, max_degree)
+    }
+
+    /// Powers of `secret_s` up to `max_degree` in both groups. Builds without
+    /// test parameters reach this only through
+    /// `AccumulatorManager::generate_parameters`.
+    pub(crate) fn generate(secret_s: Fr, max_degree: usize) -> Self {
         use ark_ff::PrimeField;
 
         let g1 = G1Affine::prime_subgroup_generator();
@@ -85,7 +252,7 @@ impl PublicParameters {
         let mut g1_s_vec = Vec::with_capacity(max_degree + 1);
         let mut g2_s_vec = Vec::with_capacity(max_degree + 1);
 
-        let mut s_power = Fr::from(1u64);
+        let mut s_power = zeroize::Zeroizing::new(Fr::from(1u64));
         for _ in 0..=max_degree {
             g1_s_vec.push(
                 G1Projective::from(g1)
@@ -97,7 +264,7 @@ impl PublicParameters {
                     .mul(s_power.into_repr())
                     .into_affine(),
             );
-            s_power *= secret_s;
+            *s_power *= secret_s;
         }
 
         Self {
@@ -110,9 +277,9 @@ impl PublicParameters {
 }
 
 lazy_static! {
-    /// Global public parameters
+    /// Global public parameters, shared rather than copied by readers
     /// Must be initialized before use via init_public_parameters()
-    static ref PUBLIC_PARAMS: RwLock<Option<PublicParameters>> = RwLock::new(None);
+    static ref PUBLIC_PARAMS: RwLock<Option<Arc<PublicParameters>>> = RwLock::new(None);
 
     // Precomputed Pairing(g1, g2)
     pub static ref E_G_G: Fq12 = Curve::pairing(
@@ -126,7 +293,7 @@ lazy_static! {
 pub fn init_public_parameters<P: AsRef<Path>>(path: P) -> Result<()> {
     let params = PublicParameters::load_from_file(path)?;
     let mut global_params = PUBLIC_PARAMS.write().unwrap();
-    *global_params = Some(params);
+    *global_params = Some(Arc::new(params));
     info!("Public parameters initialized successfully");
     Ok(())
 }
@@ -134,42 +301,171 @@ pub fn init_public_parameters<P: AsRef<Path>>(path: P) -> Result<()> {
 /// Initialize public parameters directly (for testing)
 pub fn init_public_parameters_direct(params: PublicParameters) -> Result<()> {
     let mut global_params = PUBLIC_PARAMS.write().unwrap();
-    *global_params = Some(params);
+    *global_params = Some(Arc::new(params));
     info!("Public parameters initialized directly");
     Ok(())
 }
 
-/// Get reference to public parameters
-/// Panics if parameters are not initialized
-pub fn get_public_parameters() -> PublicParameters {
+/// Get a shared handle to the public parameters, without copying them.
+/// Holders keep the parameters they got even if new ones are installed.
+/// Fails if parameters are not initialized; `parameters_of_degree` also
+/// checks their degree.
+pub fn get_public_parameters() -> Result<Arc<PublicParameters>> {
+    parameters_of_degree(0)
+}
+
+/// Initialize global parameters from the project trapdoor once per test binary.
+/// Tests that verify proofs built with `PRI_S` need `g2^s` to match it.
+#[cfg(test)]
+pub(crate) fn init_test_parameters() {
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let params = PublicParameters::generate_for_testing(*PRI_S, GS_VEC_LEN);
+        init_public_parameters_direct(params).expect("Failed to initialize test parameters");
+    });
+}
+
+/// Degree supported by the initialized parameters, or `None` before initialization.
+pub fn public_parameters_max_degree() -> Option<usize> {
     PUBLIC_PARAMS
         .read()
         .unwrap()
         .as_ref()
-        .expect("Public parameters not initialized. Call init_public_parameters() first.")
+        .map(|params| params.max_degree())
+}
+
+/// A polynomial of degree `needed` does not fit the initialized parameters of
+/// degree `available`. Carried inside the `anyhow::Error` of the fallible
+/// commitment and witness functions; `downcast_ref` recovers it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DegreeExceeded {
+    pub needed: usize,
+    pub available: usize,
+}
+
+impl std::fmt::Display for DegreeExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "public parameters support degree {}, need degree {}",
+            self.available, self.needed
+        )
+    }
+}
+
+impl std::error::Error for DegreeExceeded {}
+
+/// Check that the initialized parameters reach degree `needed`.
+pub fn check_degree(needed: usize) -> Result<()> {
+    parameters_of_degree(needed).map(|_| ())
+}
+
+/// The initialized parameters, if they reach degree `needed`. Fails with
+/// `DegreeExceeded` otherwise.
+pub fn parameters_of_degree(needed: usize) -> Result<Arc<PublicParameters>> {
+    let params = PUBLIC_PARAMS
+        .read()
+        .unwrap()
         .clone()
+        .context("Public parameters not initialized. Call init_public_parameters() first.")?;
+    let available = params.max_degree();
+    if needed > available {
+        return Err(DegreeExceeded { needed, available }.into());
+    }
+    Ok(params)
 }
 
-/// Get a specific G1 power: g1^(s^i)
-pub fn get_g1s(i: usize) -> G1Affine {
-    let params = get_public_parameters();
-    params.g1_s_vec[i]
+/// Get a specific G1 power: g1^(s^i). Fails with `DegreeExceeded` if `i`
+/// exceeds the parameters' degree; to read many powers, take
+/// `parameters_of_degree` once instead.
+pub fn get_g1s(i: usize) -> Result<G1Affine> {
+    Ok(parameters_of_degree(i)?.g1_s_vec[i])
 }
 
-/// Get a specific G2 power: g2^(s^i)
-pub fn get_g2s(i: usize) -> G2Affine {
-    let params = get_public_parameters();
-    params.g2_s_vec[i]
+/// Get a specific G2 power: g2^(s^i). Fails as `get_g1s` does.
+pub fn get_g2s(i: usize) -> Result<G2Affine> {
+    Ok(parameters_of_degree(i)?.g2_s_vec[i])
 }
 
-/// Get all G1 powers as a vector reference
-pub fn get_g1s_vec() -> Vec<G1Affine> {
-    let params = get_public_parameters();
-    params.g1_s_vec.clone()
+/// Get a copy of all G1 powers; `get_public_parameters` shares them instead
+pub fn get_g1s_vec() -> Result<Vec<G1Affine>> {
+    Ok(get_public_parameters()?.g1_s_vec.clone())
 }
 
-/// Get all G2 powers as a vector reference  
-pub fn get_g2s_vec() -> Vec<G2Affine> {
-    let params = get_public_parameters();
-    params.g2_s_vec.clone()
+/// Get a copy of all G2 powers
+pub fn get_g2s_vec() -> Result<Vec<G2Affine>> {
+    Ok(get_public_parameters()?.g2_s_vec.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_rejects_tampered_parameters() {
+        let params = PublicParameters::generate_for_testing(*PRI_S, 4);
+        assert!(params.validate().is_ok());
+
+        let path = std::env::temp_dir().join(format!("acc-params-{}.bin", std::process::id()));
+        params.save_to_file(&path).unwrap();
+        assert!(PublicParameters::load_from_file(&path).is_ok());
+
+        let mut tampered = params.clone();
+        let p = tampered.g1_s_vec[2];
+        tampered.g1_s_vec[2] = G1Affine::new(p.x, p.y + p.y, false);
+        assert!(tampered.validate().is_err());
+
+        tampered.save_to_file(&path).unwrap();
+        assert!(PublicParameters::load_from_file(&path).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_readers_share_the_parameters() {
+        init_test_parameters();
+        let params = get_public_parameters().unwrap();
+        assert!(Arc::ptr_eq(&params, &get_public_parameters().unwrap()));
+        assert_eq!(get_g1s(3).unwrap(), params.g1_s_vec[3]);
+        assert_eq!(get_g2s(3).unwrap(), params.g2_s_vec[3]);
+        let too_far = params.max_degree() + 1;
+        assert!(parameters_of_degree(too_far).is_err());
+        assert!(get_g1s(too_far).is_err() && get_g2s(too_far).is_err());
+    }
+
+    #[test]
+    fn test_parameter_file_digest_and_pairing_checks() {
+        let params = PublicParameters::generate_for_testing(*PRI_S, 4);
+        let path =
+            std::env::temp_dir().join(format!("acc-params-digest-{}.bin", std::process::id()));
+        let load = |bytes: &[u8]| {
+            std::fs::write(&path, bytes).unwrap();
+            PublicParameters::load_from_file(&path)
+        };
+        params.save_to_file(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[6..38], &params.digest());
+
+        // A flipped bit, a cut-off body or an appended byte
+        let mut flipped = bytes.clone();
+        flipped[40] ^= 1;
+        assert!(load(&flipped).is_err());
+        assert!(load(&bytes[..bytes.len() - 10]).is_err());
+        assert!(load(&[&bytes[..], &[0]].concat()).is_err());
+
+        // Valid points in the wrong order pass validation but not the pairings
+        let mut swapped = params.clone();
+        swapped.g1_s_vec.swap(2, 3);
+        assert!(swapped.validate().is_ok());
+        swapped.save_to_file(&path).unwrap();
+        let err = PublicParameters::load_from_file(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("consecutive powers"), "{:#}", err);
+
+        // Files without the header still load
+        let mut legacy = Vec::new();
+        params.serialize_unchecked(&mut legacy).unwrap();
+        assert_eq!(load(&legacy).unwrap().digest(), params.digest());
+        std::fs::remove_file(&path).ok();
+    }
 }
@@ -0,0 +1,278 @@
+//! Multi-party generation of the public parameters.
+//!
+//! `PublicParameters::generate` needs one party to know `s`, and everyone has
+//! to trust that party to forget it. A ceremony instead lets participants
+//! take turns: each picks a secret τ and raises the i-th power of both
+//! vectors to τ^i, so after k contributions the parameters are the powers of
+//! s = τ₁·…·τₖ. Nobody learns `s` unless every participant keeps their τ, so
+//! the result is sound as long as one of them destroys it.
+//!
+//! Every contribution records g^τ in both groups, the new g1^s and a Schnorr
+//! proof of knowledge of τ bound to its place in the chain. `verify` checks
+//! that each g1^s is the previous one raised to that contributor's τ, that
+//! the proofs hold, and that the final vectors are powers of the last g1^s.
+//! The proof of knowledge stops a late participant from choosing a τ that
+//! cancels the earlier ones. A participant checks that their contribution is
+//! still in the transcript by comparing its `tau_g1`.
+//!
+//! The transcript travels between participants as a file: `load_from_file`,
+//! `verify`, `contribute` with fresh randomness, `save_to_file`. The last
+//! one publishes `into_parameters`.
+
+use anyhow::{bail, ensure, Context, Result};
+use ark_bls12_381::{Bls12_381 as Curve, Fr, G1Affine, G2Affine};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{One, PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::{cfg_iter, cfg_iter_mut};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use super::PublicParameters;
+use crate::acc::utils::validate_point;
+
+/// One participant's step in the ceremony.
+#[derive(Debug, Clone, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Contribution {
+    /// g1^s after this contribution
+    pub g1_s: G1Affine,
+    /// g1^τ for the contributor's secret τ
+    pub tau_g1: G1Affine,
+    /// g2^τ, which ties `g1_s` to the previous one
+    pub tau_g2: G2Affine,
+    /// Schnorr proof of knowledge of τ: the commitment g1^r ...
+    pub pok_commitment: G1Affine,
+    /// ... and the response r + c·τ
+    pub pok_response: Fr,
+}
+
+/// Transcript of a ceremony: the current parameters and every contribution
+/// that produced them.
+#[derive(Debug, Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Ceremony {
+    params: PublicParameters,
+    contributions: Vec<Contribution>,
+}
+
+/// Append the encoding of `item` to a hash input.
+fn absorb<T: CanonicalSerialize>(input: &mut Vec<u8>, item: &T) {
+    item.serialize(input)
+        .expect("serializing into a Vec cannot fail");
+}
+
+/// BLAKE2b of `label` and `input`, reduced into the field.
+fn hash_to_fr(label: &[u8], input: &[u8]) -> Fr {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(64)
+        .to_state()
+        .update(label)
+        .update(input)
+        .finalize();
+    Fr::from_le_bytes_mod_order(hash.as_bytes())
+}
+
+/// Fiat-Shamir challenge of the proof of knowledge for contribution `index`.
+fn pok_challenge(index: u64, previous: &G1Affine, contribution: &Contribution) -> Fr {
+    let mut input = Vec::new();
+    absorb(&mut input, &index);
+    absorb(&mut input, previous);
+    absorb(&mut input, &contribution.g1_s);
+    absorb(&mut input, &contribution.tau_g1);
+    absorb(&mut input, &contribution.tau_g2);
+    absorb(&mut input, &contribution.pok_commitment);
+    hash_to_fr(b"acc-ceremony-pok", &input)
+}
+
+impl Ceremony {
+    /// A ceremony for parameters of `max_degree`, before any contribution:
+    /// every power is the generator, i.e. s = 1.
+    pub fn new(max_degree: usize) -> Result<Self> {
+        ensure!(max_degree >= 1, "Ceremony degree must be at least 1");
+        let g1 = G1Affine::prime_subgroup_generator();
+        let g2 = G2Affine::prime_subgroup_generator();
+        Ok(Self {
+            params: PublicParameters {
+                g1,
+                g2,
+                g1_s_vec: vec![g1; max_degree + 1],
+                g2_s_vec: vec![g2; max_degree + 1],
+            },
+            contributions: Vec::new(),
+        })
+    }
+
+    /// Parameters as of the last contribution.
+    pub fn params(&self) -> &PublicParameters {
+        &self.params
+    }
+
+    /// Contributions so far, in order.
+    pub fn contributions(&self) -> &[Contribution] {
+        &self.contributions
+    }
+
+    /// Mix the secret `tau` into the parameters and record the contribution.
+    /// Draw `tau` from a secure random source and scrub the caller's copy
+    /// afterwards; only the one passed in is zeroized here. Rejects 0 and 1, which would erase or keep `s`.
+    pub fn contribute(&mut self, tau: Fr) -> Result<&Contribution> {
+        let tau = Zeroizing::new(tau);
+        ensure!(
+            !tau.is_zero() && !tau.is_one(),
+            "Contribution must not be 0 or 1"
+        );
+        let previous = self.last_g1_s();
+        let index = self.contributions.len() as u64;
+
+        let mut powers = Zeroizing::new(Vec::with_capacity(self.params.g1_s_vec.len()));
+        let mut power = Zeroizing::new(Fr::one());
+        for _ in 0..self.params.g1_s_vec.len() {
+            powers.push(*power);
+            *power *= *tau;
+        }
+        cfg_iter_mut!(self.params.g1_s_vec)
+            .zip(cfg_iter!(powers))
+            .for_each(|(point, p)| *point = point.mul(p.into_repr()).into_affine());
+        cfg_iter_mut!(self.params.g2_s_vec)
+            .zip(cfg_iter!(powers))
+            .for_each(|(point, p)| *point = point.mul(p.into_repr()).into_affine());
+
+        // Deterministic nonce, secret as long as τ is
+        let mut input = Zeroizing::new(Vec::new());
+        absorb(&mut input, &*tau);
+        absorb(&mut input, &index);
+        absorb(&mut input, &previous);
+        let nonce = Zeroizing::new(hash_to_fr(b"acc-ceremony-nonce", &input));
+        let g1 = self.params.g1;
+        let mut contribution = Contribution {
+            g1_s: self.params.g1_s_vec[1],
+            tau_g1: g1.mul(tau.into_repr()).into_affine(),
+            tau_g2: self.params.g2.mul(tau.into_repr()).into_affine(),
+            pok_commitment: g1.mul(nonce.into_repr()).into_affine(),
+            pok_response: Fr::zero(),
+        };
+        let challenge = pok_challenge(index, &previous, &contribution);
+        contribution.pok_response = *nonce + challenge * *tau;
+        self.contributions.push(contribution);
+        Ok(self.contributions.last().unwrap())
+    }
+
+    fn last_g1_s(&self) -> G1Affine {
+        self.contributions.last().map_or(self.params.g1, |c| c.g1_s)
+    }
+
+    /// Check every contribution and that the parameters are the powers of
+    /// the `s` they built. A transcript without contributions fails: its `s`
+    /// is 1.
+    pub fn verify(&self) -> Result<()> {
+        let params = &self.params;
+        ensure!(
+            !self.contributions.is_empty(),
+            "Ceremony has no contributions"
+        );
+        params.validate()?;
+
+        let (g1, g2) = (params.g1, params.g2);
+        let mut previous = g1;
+        for (i, c) in self.contributions.iter().enumerate() {
+            for point in [&c.g1_s, &c.tau_g1, &c.pok_commitment] {
+                validate_point(point).with_context(|| format!("contribution {}", i))?;
+            }
+            validate_point(&c.tau_g2).with_context(|| format!("contribution {}", i))?;
+            if c.tau_g1.is_zero() || c.tau_g1 == g1 {
+                bail!("Contribution {} uses a trivial secret", i);
+            }
+            let challenge = pok_challenge(i as u64, &previous, c);
+            let lhs = g1.mul(c.pok_response.into_repr());
+            let rhs = c.pok_commitment.into_projective() + c.tau_g1.mul(challenge.into_repr());
+            if lhs != rhs {
+                bail!("Contribution {} has an invalid proof of knowledge", i);
+            }
+            if Curve::pairing(c.tau_g1, g2) != Curve::pairing(g1, c.tau_g2) {
+                bail!(
+                    "Contribution {} commits to different secrets in G1 and G2",
+                    i
+                );
+            }
+            if Curve::pairing(c.g1_s, g2) != Curve::pairing(previous, c.tau_g2) {
+                bail!("Contribution {} does not extend the previous one", i);
+            }
+            previous = c.g1_s;
+        }
+        ensure!(
+            params.g1_s_vec[1] == previous,
+            "Parameters do not match the last contribution"
+        );
+
+        params.verify_powers()
+    }
+
+    /// Verify the transcript and hand out its parameters.
+    pub fn into_parameters(self) -> Result<PublicParameters> {
+        self.verify()?;
+        Ok(self.params)
+    }
+
+    /// Load a transcript written by `save_to_file`. Points are checked by
+    /// `verify`, which the caller runs before contributing.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())
+            .with_context(|| format!("Failed to open ceremony file: {:?}", path.as_ref()))?;
+        Self::deserialize_unchecked(&mut BufReader::new(file))
+            .context("Failed to deserialize ceremony transcript")
+    }
+
+    /// Write the transcript to `path` for the next participant.
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path.as_ref())
+            .with_context(|| format!("Failed to create ceremony file: {:?}", path.as_ref()))?;
+        self.serialize_unchecked(&mut BufWriter::new(file))
+            .context("Failed to serialize ceremony transcript")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ceremony_chains_contributions() {
+        let taus = [Fr::from(3u64), Fr::from(11u64), Fr::from(123456789u64)];
+        let mut ceremony = Ceremony::new(4).unwrap();
+        assert!(ceremony.verify().is_err());
+        assert!(ceremony.contribute(Fr::one()).is_err());
+        for tau in taus {
+            ceremony.contribute(tau).unwrap();
+        }
+        ceremony.verify().unwrap();
+
+        let path = std::env::temp_dir().join(format!("acc-ceremony-{}", std::process::id()));
+        ceremony.save_to_file(&path).unwrap();
+        let loaded = Ceremony::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.contributions(), ceremony.contributions());
+        let s = taus.iter().product::<Fr>();
+        let expected = PublicParameters::generate(s, 4);
+        let params = loaded.into_parameters().unwrap();
+        assert_eq!(params.g1_s_vec, expected.g1_s_vec);
+        assert_eq!(params.g2_s_vec, expected.g2_s_vec);
+
+        // A forged proof, a substituted secret or a bent power all fail
+        let mut forged = ceremony.clone();
+        forged.contributions[1].pok_response += Fr::one();
+        assert!(forged.verify().is_err());
+        let mut forged = ceremony.clone();
+        forged.contributions[1].tau_g2 = G2Affine::prime_subgroup_generator()
+            .mul(Fr::from(5u64).into_repr())
+            .into_affine();
+        assert!(forged.verify().is_err());
+        let mut forged = ceremony;
+        forged.params.g1_s_vec[3] = (forged.params.g1_s_vec[3].into_projective()
+            + forged.params.g1.into_projective())
+        .into_affine();
+        assert!(forged.verify().is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Import of powers-of-tau transcripts in the snarkjs `.ptau` format.
+//!
+//! The perpetual powers-of-tau ceremonies publish their BLS12-381 output as
+//! `.ptau` files: a `ptau` magic, a version and a table of sections, each a
+//! type, a byte length and the data. Section 1 holds the field size, the
+//! base field modulus and the power p; section 2 the 2^(p+1) - 1 powers of
+//! tau in G1 and section 3 the 2^p powers in G2. Coordinates are
+//! little-endian Montgomery form, as in arkworks, so they load without
+//! conversion. The other sections (alpha and beta terms, contribution
+//! records, Lagrange bases) are skipped.
+//!
+//! An imported transcript is checked as untrusted input: every point for
+//! subgroup membership (`PublicParameters::validate`) and both vectors for
+//! being consecutive powers of one tau (`PublicParameters::verify_powers`).
+//! The contribution history is not replayed; trust in the ceremony comes
+//! from its published attestations, as for any other consumer of the file.
+
+use anyhow::{Context, Result, bail, ensure};
+use ark_bls12_381::{Fq, Fq2, FqParameters, G1Affine, G2Affine};
+use ark_ec::AffineCurve;
+use ark_ff::{BigInteger384, FpParameters};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+
+use super::PublicParameters;
+
+const PTAU_MAGIC: &[u8; 4] = b"ptau";
+const PTAU_VERSION: u32 = 1;
+const SECTION_HEADER: u32 = 1;
+const SECTION_TAU_G1: u32 = 2;
+const SECTION_TAU_G2: u32 = 3;
+/// Bytes of a BLS12-381 base field element
+const N8: usize = 48;
+const G1_BYTES: usize = 2 * N8;
+const G2_BYTES: usize = 4 * N8;
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Base field element from its little-endian Montgomery form.
+fn fq_from_lem(bytes: &[u8]) -> Result<Fq> {
+    let mut limbs = [0u64; 6];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+        *limb = u64::from_le_bytes(chunk.try_into().unwrap());
+    }
+    let repr = BigInteger384(limbs);
+    ensure!(repr < FqParameters::MODULUS, "Field element is not reduced");
+    Ok(Fq::new(repr))
+}
+
+fn g1_from_lem(bytes: &[u8]) -> Result<G1Affine> {
+    ensure!(
+        bytes.iter().any(|&b| b != 0),
+        "Point at infinity among the powers"
+    );
+    Ok(G1Affine::new(
+        fq_from_lem(&bytes[..N8])?,
+        fq_from_lem(&bytes[N8..])?,
+        false,
+    ))
+}
+
+fn g2_from_lem(bytes: &[u8]) -> Result<G2Affine> {
+    ensure!(
+        bytes.iter().any(|&b| b != 0),
+        "Point at infinity among the powers"
+    );
+    let fq2 =
+        |b: &[u8]| -> Result<Fq2> { Ok(Fq2::new(fq_from_lem(&b[..N8])?, fq_from_lem(&b[N8..])?)) };
+    Ok(G2Affine::new(
+        fq2(&bytes[..2 * N8])?,
+        fq2(&bytes[2 * N8..])?,
+        false,
+    ))
+}
+
+/// `count` fixed-size records from the start of a section.
+fn read_records<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    size: usize,
+    count: usize,
+) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0u8; size * count];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read the first `max_degree + 1` powers of a BLS12-381 `.ptau` transcript
+/// and check them. A transcript of power p serves degrees up to 2^p - 1, the
+/// length of its G2 vector.
+pub fn read_ptau<R: Read + Seek>(reader: &mut R, max_degree: usize) -> Result<PublicParameters> {
+    let mut magic = [0u8; 4];
+    reader
+        .read_exact(&mut magic)
+        .context("Truncated ptau file")?;
+    ensure!(&magic == PTAU_MAGIC, "Not a ptau file");
+    let version = read_u32(reader)?;
+    if version != PTAU_VERSION {
+        bail!("Unsupported ptau version {}", version);
+    }
+
+    let mut sections: HashMap<u32, (u64, u64)> = HashMap::new();
+    for _ in 0..read_u32(reader)? {
+        let kind = read_u32(reader)?;
+        let size = read_u64(reader)?;
+        let offset = reader.stream_position()?;
+        ensure!(
+            sections.insert(kind, (offset, size)).is_none(),
+            "Duplicate ptau section {}",
+            kind
+        );
+        reader.seek(SeekFrom::Current(size as i64))?;
+    }
+    let section = |kind: u32| {
+        sections
+            .get(&kind)
+            .copied()
+            .with_context(|| format!("Missing ptau section {}", kind))
+    };
+
+    let (offset, _) = section(SECTION_HEADER)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    let n8 = read_u32(reader)? as usize;
+    ensure!(
+        n8 == N8,
+        "ptau file is over a {}-byte field, not BLS12-381 (bn128 transcripts are not supported)",
+        n8
+    );
+    let mut modulus = [0u8; N8];
+    reader.read_exact(&mut modulus)?;
+    ensure!(
+        modulus == modulus_bytes(),
+        "ptau file is not over BLS12-381"
+    );
+    let power = read_u32(reader)?;
+    ensure!(power < 32, "Implausible ptau power {}", power);
+    let g2_count = 1usize << power;
+    let g1_count = 2 * g2_count - 1;
+
+    ensure!(max_degree >= 1, "Degree must be at least 1");
+    if max_degree >= g2_count {
+        bail!(
+            "ptau file of power {} supports degree {}, need degree {}",
+            power,
+            g2_count - 1,
+            max_degree
+        );
+    }
+    let (g1_offset, g1_size) = section(SECTION_TAU_G1)?;
+    let (g2_offset, g2_size) = section(SECTION_TAU_G2)?;
+    ensure!(
+        g1_size == (g1_count * G1_BYTES) as u64 && g2_size == (g2_count * G2_BYTES) as u64,
+        "ptau power sections do not match power {}",
+        power
+    );
+
+    let count = max_degree + 1;
+    let g1_bytes = read_records(reader, g1_offset, G1_BYTES, count)?;
+    let g2_bytes = read_records(reader, g2_offset, G2_BYTES, count)?;
+    let params = PublicParameters {
+        g1: G1Affine::prime_subgroup_generator(),
+        g2: G2Affine::prime_subgroup_generator(),
+        g1_s_vec: g1_bytes
+            .chunks_exact(G1_BYTES)
+            .map(g1_from_lem)
+            .collect::<Result<_>>()?,
+        g2_s_vec: g2_bytes
+            .chunks_exact(G2_BYTES)
+            .map(g2_from_lem)
+            .collect::<Result<_>>()?,
+    };
+    params.validate().context("ptau points failed validation")?;
+    params
+        .verify_powers()
+        .context("ptau points are not powers of one tau")?;
+    Ok(params)
+}
+
+/// The base field modulus as the header stores it, little-endian.
+fn modulus_bytes() -> [u8; N8] {
+    let mut bytes = [0u8; N8];
+    for (chunk, limb) in bytes.chunks_exact_mut(8).zip(FqParameters::MODULUS.0) {
+        chunk.copy_from_slice(&limb.to_le_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::Fr;
+    use std::io::Cursor;
+
+    fn lem(x: &Fq) -> Vec<u8> {
+        (x.0).0.iter().flat_map(|limb| limb.to_le_bytes()).collect()
+    }
+
+    fn section(out: &mut Vec<u8>, kind: u32, data: &[u8]) {
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    /// A transcript of `power` as snarkjs lays it out, with an extra section.
+    fn write_ptau(tau: Fr, power: u32) -> Vec<u8> {
+        let g2_count = 1usize << power;
+        let params = PublicParameters::generate(tau, 2 * g2_count - 2);
+        let mut header = (N8 as u32).to_le_bytes().to_vec();
+        header.extend_from_slice(&modulus_bytes());
+        header.extend_from_slice(&power.to_le_bytes());
+        header.extend_from_slice(&power.to_le_bytes());
+        let g1: Vec<u8> = params
+            .g1_s_vec
+            .iter()
+            .flat_map(|p| [lem(&p.x), lem(&p.y)].concat())
+            .collect();
+        let g2: Vec<u8> = params.g2_s_vec[..g2_count]
+            .iter()
+            .flat_map(|p| [lem(&p.x.c0), lem(&p.x.c1), lem(&p.y.c0), lem(&p.y.c1)].concat())
+            .collect();
+
+        let mut out = PTAU_MAGIC.to_vec();
+        out.extend_from_slice(&PTAU_VERSION.to_le_bytes());
+        out.extend_from_slice(&4u32.to_le_bytes());
+        section(&mut out, SECTION_HEADER, &header);
+        section(&mut out, 7, &[1, 2, 3]);
+        section(&mut out, SECTION_TAU_G2, &g2);
+        section(&mut out, SECTION_TAU_G1, &g1);
+        out
+    }
+
+    #[test]
+    fn test_ptau_import_checks_powers() {
+        let tau = Fr::from(987654321u64);
+        let bytes = write_ptau(tau, 2);
+        let params = read_ptau(&mut Cursor::new(&bytes), 3).unwrap();
+        let expected = PublicParameters::generate(tau, 3);
+        assert_eq!(params.g1_s_vec, expected.g1_s_vec);
+        assert_eq!(params.g2_s_vec, expected.g2_s_vec);
+
+        let err = read_ptau(&mut Cursor::new(&bytes), 4).unwrap_err();
+        assert!(err.to_string().contains("supports degree 3"), "{}", err);
+
+        // Swapping two G1 powers keeps every point valid but breaks the ratios
+        let start = bytes.len() - 7 * G1_BYTES;
+        let mut swapped = bytes.clone();
+        let (first, second) = (start + G1_BYTES, start + 2 * G1_BYTES);
+        let power_1 = bytes[first..second].to_vec();
+        swapped.copy_within(second..second + G1_BYTES, first);
+        swapped[second..second + G1_BYTES].copy_from_slice(&power_1);
+        let err = read_ptau(&mut Cursor::new(&swapped), 3).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("not consecutive powers"),
+            "{:#}",
+            err
+        );
+
+        let mut bn128 = bytes;
+        bn128[4 + 4 + 4 + 4 + 8] = 32;
+        assert!(read_ptau(&mut Cursor::new(&bn128), 3).is_err());
+    }
+}
@@ -9,22 +9,48 @@
 //! - Pure math algorithms (e.g., XGCD, FixedBasePow)
 //! - Low-level type conversions (e.g., Digest -> Field)
 //! - Set transformations and polynomial expansions
+//! - Curve point validation
 
 use crate::digest::{Digest, Digestible};
 use crate::set::{Set, SetElement};
-use crate::acc::setup::{get_g1s, get_g2s};
-use ark_bls12_381::{Fr, G1Affine, G2Affine};
+use crate::acc::setup::parameters_of_degree;
+use ark_bls12_381::{Fr, G1Affine, G1Projective, G2Affine};
+use anyhow::{bail, Result};
+use ark_ec::models::SWModelParameters;
+use ark_ec::short_weierstrass_jacobian::GroupAffine;
 use ark_ec::{msm::VariableBaseMSM, ProjectiveCurve};
 use ark_ff::{BigInteger, Field, FpParameters, PrimeField, ToBytes, Zero};
 use ark_poly::{
     univariate::{DenseOrSparsePolynomial, DensePolynomial},
-    Polynomial, UVPolynomial,
+    EvaluationDomain, Polynomial, Radix2EvaluationDomain, UVPolynomial,
 };
+use ark_std::{cfg_into_iter, cfg_iter};
 use log::trace;
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 use std::borrow::Cow;
 use std::iter;
 
+// ==========================================
+// Parallelism
+// ==========================================
+
+/// `rayon::join` with the `parallel` feature; without it, runs `a` then `b`.
+#[cfg(feature = "parallel")]
+pub use rayon::join;
+
+/// `rayon::join` with the `parallel` feature; without it, runs `a` then `b`.
+#[cfg(not(feature = "parallel"))]
+pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    (a(), b())
+}
+
 // ==========================================
 // Set Transformation Functions
 // ==========================================
@@ -33,18 +59,13 @@ use std::iter;
 /// Uses parallel iteration for performance.
 pub fn digest_set_from_set<T: SetElement, F: PrimeField>(input: &Set<T>) -> Vec<F> {
     let elements: Vec<&T> = input.iter().collect();
-    let mut result: Vec<F> = Vec::with_capacity(elements.len());
-    
-    (0..elements.len())
-        .into_par_iter()
+    cfg_into_iter!(0..elements.len())
         .map(|i| {
             let k = elements[i];
             let d = k.to_digest();
             digest_to_prime_field(&d)
         })
-        .collect_into_vec(&mut result);
-    
-    result
+        .collect()
 }
 
 /// Expand a slice of field elements to polynomial ∏(X - xᵢ).
@@ -67,13 +88,102 @@ pub fn expand_to_poly<F: PrimeField>(elements: &[F]) -> DensePolynomial<F> {
             return Cow::Borrowed(&polys[0]);
         }
         let mid = polys.len() / 2;
-        let (left, right) = rayon::join(|| expand(&polys[..mid]), || expand(&polys[mid..]));
+        let (left, right) = join(|| expand(&polys[..mid]), || expand(&polys[mid..]));
         Cow::Owned(left.as_ref() * right.as_ref())
     }
 
     expand(&inputs).into_owned()
 }
 
+/// Multiply `poly` by (X - root) in place, in O(deg) field operations.
+pub fn mul_by_linear<F: PrimeField>(poly: &mut DensePolynomial<F>, root: F) {
+    let coeffs = &mut poly.coeffs;
+    if coeffs.is_empty() {
+        return;
+    }
+    coeffs.push(F::zero());
+    for i in (1..coeffs.len()).rev() {
+        coeffs[i] = coeffs[i - 1] - root * coeffs[i];
+    }
+    coeffs[0] = -(root * coeffs[0]);
+}
+
+/// Divide `poly` by (X - root) by synthetic division.
+/// Returns the quotient and the remainder, which is `poly(root)`.
+pub fn div_by_linear<F: PrimeField>(
+    poly: &DensePolynomial<F>,
+    root: F,
+) -> (DensePolynomial<F>, F) {
+    let mut quotient = vec![F::zero(); poly.coeffs.len().saturating_sub(1)];
+    let mut carry = F::zero();
+    for (i, coeff) in poly.coeffs.iter().enumerate().rev() {
+        carry = *coeff + carry * root;
+        if i > 0 {
+            quotient[i - 1] = carry;
+        }
+    }
+    (DensePolynomial::from_coefficients_vec(quotient), carry)
+}
+
+/// Subproduct tree over a set: every node holds ∏(X - xᵢ) over its slice of
+/// the elements, halved at each level down to slices of at most `leaf_size`.
+pub struct SubproductTree<F: PrimeField> {
+    pub poly: DensePolynomial<F>,
+    pub children: Option<Box<(SubproductTree<F>, SubproductTree<F>)>>,
+}
+
+impl<F: PrimeField> SubproductTree<F> {
+    /// Builds the tree bottom-up, one product per node, in parallel.
+    pub fn build(elements: &[F], leaf_size: usize) -> Self {
+        if elements.len() <= leaf_size.max(1) {
+            return Self {
+                poly: expand_to_poly(elements),
+                children: None,
+            };
+        }
+        let (left, right) = elements.split_at(elements.len() / 2);
+        let (left, right) = join(
+            || Self::build(left, leaf_size),
+            || Self::build(right, leaf_size),
+        );
+        Self {
+            poly: &left.poly * &right.poly,
+            children: Some(Box::new((left, right))),
+        }
+    }
+
+    /// Number of elements under this node.
+    pub fn len(&self) -> usize {
+        self.poly.coeffs.len().saturating_sub(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Sums Σₖ cₖ·bases[i + k] for i < `len`, where `bases_hat` is the FFT of
+/// the bases over `domain` and cₖ are `coeffs`. A correlation of the bases
+/// with the coefficients, so it runs as a convolution with the reversed
+/// coefficients: O(N log N) group operations for a domain of size N, which
+/// must cover `len + coeffs.len() - 1`.
+pub fn correlate_g1(
+    domain: &Radix2EvaluationDomain<Fr>,
+    bases_hat: &[G1Projective],
+    coeffs: &[Fr],
+    len: usize,
+) -> Vec<G1Projective> {
+    let shift = coeffs.len() - 1;
+    let mut reversed: Vec<Fr> = coeffs.iter().rev().copied().collect();
+    domain.fft_in_place(&mut reversed);
+    let mut product: Vec<G1Projective> = cfg_iter!(bases_hat)
+        .zip(cfg_iter!(reversed))
+        .map(|(p, c)| p.mul(c.into_repr()))
+        .collect();
+    domain.ifft_in_place(&mut product);
+    product.drain(shift..shift + len).collect()
+}
+
 // ==========================================
 // Type Conversion Functions
 // ==========================================
@@ -250,57 +360,17 @@ impl<F: PrimeField> FixedBaseScalarPow<F> {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ark_bls12_381::{Fr, G1Projective, G2Projective};
-    use ark_ff::Field;
-    use ark_poly::Polynomial;
-    use core::ops::MulAssign;
-    use rand::Rng;
-
-    #[test]
-    fn test_xgcd() {
-        let poly1 = DensePolynomial::from_coefficients_vec(vec![Fr::from(1u32), Fr::from(1u32)]);
-        let poly2 = DensePolynomial::from_coefficients_vec(vec![Fr::from(2u32), Fr::from(1u32)]);
-        let (g, x, y) = xgcd(&poly1, &poly2).unwrap();
-        assert_eq!(g.degree(), 0);
-        let gcd = &(&poly1 * &x) + &(&poly2 * &y);
-        assert_eq!(gcd, g);
-    }
-
-    #[test]
-    fn test_pow_g1() {
-        let g1p = FixedBaseCurvePow::build(&G1Projective::prime_subgroup_generator());
-        let mut rng = rand::thread_rng();
-        let num: Fr = rng.gen();
-        let mut expect = G1Projective::prime_subgroup_generator();
-        expect.mul_assign(num);
-        assert_eq!(g1p.apply(&num), expect);
-    }
-
-    #[test]
-    fn test_pow_g2() {
-        let g2p = FixedBaseCurvePow::build(&G2Projective::prime_subgroup_generator());
-        let mut rng = rand::thread_rng();
-        let num: Fr = rng.gen();
-        let mut expect = G2Projective::prime_subgroup_generator();
-        expect.mul_assign(num);
-        assert_eq!(g2p.apply(&num), expect);
-    }
-
-    #[test]
-    fn test_pow_fr() {
-        let mut rng = rand::thread_rng();
-        let base: Fr = rng.gen();
-        let num: Fr = rng.gen();
-        let frp = FixedBaseScalarPow::build(&base);
-        let expect = base.pow(num.into_repr());
-        assert_eq!(frp.apply(&num), expect);
-    }
+/// `poly_to_g1`, from when that panicked on small parameters.
+#[deprecated(note = "`poly_to_g1` returns the error itself")]
+pub fn try_poly_to_g1(poly: DensePolynomial<Fr>) -> Result<G1Affine> {
+    poly_to_g1(poly)
 }
 
-pub fn poly_to_g1(poly: DensePolynomial<Fr>) -> G1Affine {
+/// Commitment g1^P(s) to `poly` over the public parameters. Fails with
+/// `DegreeExceeded` if the parameters do not reach the degree of `poly`, or
+/// if they are not initialized.
+pub fn poly_to_g1(poly: DensePolynomial<Fr>) -> Result<G1Affine> {
+    let params = parameters_of_degree(poly.degree())?;
     let mut idxes: Vec<usize> = Vec::with_capacity(poly.degree() + 1);
     for (i, coeff) in poly.coeffs.iter().enumerate() {
         if coeff.is_zero() {
@@ -309,25 +379,28 @@ pub fn poly_to_g1(poly: DensePolynomial<Fr>) -> G1Affine {
         idxes.push(i);
     }
 
-    let mut bases: Vec<G1Affine> = Vec::with_capacity(idxes.len());
-    let mut scalars: Vec<<Fr as PrimeField>::BigInt> = Vec::with_capacity(idxes.len());
-    (0..idxes.len())
-        .into_par_iter()
-        .map(|i| {
-            let idx = idxes[i];
+    let bases: Vec<G1Affine> = cfg_iter!(idxes)
+        .map(|&idx| {
             trace!("access g1 pub key at {}", idx);
-            get_g1s(idx)
+            params.g1_s_vec[idx]
         })
-        .collect_into_vec(&mut bases);
-    (0..idxes.len())
-        .into_par_iter()
-        .map(|i| poly.coeffs[idxes[i]].into_repr())
-        .collect_into_vec(&mut scalars);
+        .collect();
+    let scalars: Vec<<Fr as PrimeField>::BigInt> = cfg_iter!(idxes)
+        .map(|&idx| poly.coeffs[idx].into_repr())
+        .collect();
 
-    VariableBaseMSM::multi_scalar_mul(&bases[..], &scalars[..]).into_affine()
+    Ok(VariableBaseMSM::multi_scalar_mul(&bases[..], &scalars[..]).into_affine())
 }
 
-pub fn poly_to_g2(poly: DensePolynomial<Fr>) -> G2Affine {
+/// `poly_to_g2`, from when that panicked on small parameters.
+#[deprecated(note = "`poly_to_g2` returns the error itself")]
+pub fn try_poly_to_g2(poly: DensePolynomial<Fr>) -> Result<G2Affine> {
+    poly_to_g2(poly)
+}
+
+/// Commitment g2^P(s) to `poly`. Fails as `poly_to_g1` does.
+pub fn poly_to_g2(poly: DensePolynomial<Fr>) -> Result<G2Affine> {
+    let params = parameters_of_degree(poly.degree())?;
     let mut idxes: Vec<usize> = Vec::with_capacity(poly.degree() + 1);
     for (i, coeff) in poly.coeffs.iter().enumerate() {
         if coeff.is_zero() {
@@ -336,22 +409,17 @@ pub fn poly_to_g2(poly: DensePolynomial<Fr>) -> G2Affine {
         idxes.push(i);
     }
 
-    let mut bases: Vec<G2Affine> = Vec::with_capacity(idxes.len());
-    let mut scalars: Vec<<Fr as PrimeField>::BigInt> = Vec::with_capacity(idxes.len());
-    (0..idxes.len())
-        .into_par_iter()
-        .map(|i| {
-            let idx = idxes[i];
+    let bases: Vec<G2Affine> = cfg_iter!(idxes)
+        .map(|&idx| {
             trace!("access g2 pub key at {}", idx);
-            get_g2s(idx)
+            params.g2_s_vec[idx]
         })
-        .collect_into_vec(&mut bases);
-    (0..idxes.len())
-        .into_par_iter()
-        .map(|i| poly.coeffs[idxes[i]].into_repr())
-        .collect_into_vec(&mut scalars);
+        .collect();
+    let scalars: Vec<<Fr as PrimeField>::BigInt> = cfg_iter!(idxes)
+        .map(|&idx| poly.coeffs[idx].into_repr())
+        .collect();
 
-    VariableBaseMSM::multi_scalar_mul(&bases[..], &scalars[..]).into_affine()
+    Ok(VariableBaseMSM::multi_scalar_mul(&bases[..], &scalars[..]).into_affine())
 }
 
 /// Solves A*P1 + B*P2 = 1 (GCD normalized).
@@ -377,3 +445,110 @@ pub fn solve_bezout_identity(
 
     Ok((scale_poly(a), scale_poly(b)))
 }
+
+// ==========================================
+// Point Validation
+// ==========================================
+
+/// Check that a point lies on the curve and in the prime-order subgroup.
+/// Points built from untrusted bytes must pass this before they reach a pairing.
+pub fn validate_point<P: SWModelParameters>(p: &GroupAffine<P>) -> Result<()> {
+    if !p.is_on_curve() {
+        bail!("point is not on the curve");
+    }
+    if !p.is_in_correct_subgroup_assuming_on_curve() {
+        bail!("point is not in the prime-order subgroup");
+    }
+    Ok(())
+}
+
+/// Validate many points at once, spreading the subgroup checks across threads.
+/// Reports the index of the first invalid point.
+pub fn validate_points_batch<P: SWModelParameters>(points: &[GroupAffine<P>]) -> Result<()> {
+    let invalid = |p: &GroupAffine<P>| {
+        !p.is_on_curve() || !p.is_in_correct_subgroup_assuming_on_curve()
+    };
+    #[cfg(feature = "parallel")]
+    let bad = points.par_iter().position_first(invalid);
+    #[cfg(not(feature = "parallel"))]
+    let bad = points.iter().position(invalid);
+    if let Some(i) = bad {
+        bail!("point {} is not a valid subgroup element", i);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::{Fr, G1Projective, G2Projective};
+    use ark_ff::Field;
+    use ark_poly::Polynomial;
+    use core::ops::MulAssign;
+    use rand::Rng;
+
+    #[test]
+    fn test_xgcd() {
+        let poly1 = DensePolynomial::from_coefficients_vec(vec![Fr::from(1u32), Fr::from(1u32)]);
+        let poly2 = DensePolynomial::from_coefficients_vec(vec![Fr::from(2u32), Fr::from(1u32)]);
+        let (g, x, y) = xgcd(&poly1, &poly2).unwrap();
+        assert_eq!(g.degree(), 0);
+        let gcd = &(&poly1 * &x) + &(&poly2 * &y);
+        assert_eq!(gcd, g);
+    }
+
+    #[test]
+    fn test_pow_g1() {
+        let g1p = FixedBaseCurvePow::build(&G1Projective::prime_subgroup_generator());
+        let mut rng = rand::thread_rng();
+        let num: Fr = rng.gen();
+        let mut expect = G1Projective::prime_subgroup_generator();
+        expect.mul_assign(num);
+        assert_eq!(g1p.apply(&num), expect);
+    }
+
+    #[test]
+    fn test_pow_g2() {
+        let g2p = FixedBaseCurvePow::build(&G2Projective::prime_subgroup_generator());
+        let mut rng = rand::thread_rng();
+        let num: Fr = rng.gen();
+        let mut expect = G2Projective::prime_subgroup_generator();
+        expect.mul_assign(num);
+        assert_eq!(g2p.apply(&num), expect);
+    }
+
+    #[test]
+    fn test_pow_fr() {
+        let mut rng = rand::thread_rng();
+        let base: Fr = rng.gen();
+        let num: Fr = rng.gen();
+        let frp = FixedBaseScalarPow::build(&base);
+        let expect = base.pow(num.into_repr());
+        assert_eq!(frp.apply(&num), expect);
+    }
+
+    #[test]
+    fn test_validate_point() {
+        use ark_bls12_381::Fq;
+        use ark_ec::AffineCurve;
+
+        let g1 = G1Affine::prime_subgroup_generator();
+        assert!(validate_point(&g1).is_ok());
+        assert!(validate_point(&G2Affine::prime_subgroup_generator()).is_ok());
+
+        let off_curve = G1Affine::new(g1.x, g1.y + Fq::from(1u64), false);
+        assert!(validate_point(&off_curve).is_err());
+
+        // The G1 cofactor is large, so a point from an arbitrary x is almost surely
+        // on the curve but outside the prime-order subgroup.
+        let outside = (1u64..)
+            .find_map(|x| G1Affine::get_point_from_x(Fq::from(x), false))
+            .unwrap();
+        assert!(outside.is_on_curve());
+        assert!(validate_point(&outside).is_err());
+
+        assert!(validate_points_batch(&[g1, g1]).is_ok());
+        let err = validate_points_batch(&[g1, g1, outside]).unwrap_err();
+        assert!(err.to_string().contains("point 2"));
+    }
+}
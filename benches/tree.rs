@@ -0,0 +1,143 @@
+//! Criterion benchmarks of the tree's proof-carrying operations.
+//!
+//! Each operation runs against trees of several sizes, built once per size
+//! with `build_parallel`. Mutations run on a copy restored from a snapshot
+//! outside the timed section, so every iteration starts from the same tree.
+//! Parameters come from a fixed, publicly known trapdoor through
+//! `AccumulatorManager`, since the insecure test setup is only compiled into
+//! debug builds.
+//!
+//! Run with `cargo bench --bench tree`, or `cargo bench --bench tree -- insert`
+//! for one group.
+
+use accumulator_ads::acc::setup::init_public_parameters_direct;
+use accumulator_ads::{AccumulatorManager, Fr, Set};
+use accumulator_tree::AccumulatorTree;
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::sync::Once;
+
+/// Tree sizes, in keys. Non-membership proofs need parameters of the
+/// largest size.
+const SIZES: [usize; 3] = [64, 256, 1024];
+
+/// Trapdoor of the benchmark parameters. Not a secret.
+const BENCH_TRAPDOOR: u64 = 0x5eed;
+
+static INIT: Once = Once::new();
+
+fn init_params() {
+    INIT.call_once(|| {
+        let manager =
+            AccumulatorManager::new(Fr::from(BENCH_TRAPDOOR)).expect("trapdoor is non-zero");
+        let max = *SIZES.iter().max().unwrap();
+        init_public_parameters_direct(manager.generate_parameters(max))
+            .expect("Failed to initialize benchmark parameters");
+    });
+}
+
+fn key(i: usize) -> String {
+    format!("key{:06}", i)
+}
+
+/// Published tree over `key(0..n)`, each with FID `fid{i}`.
+fn tree_of(n: usize) -> AccumulatorTree {
+    init_params();
+    let entries = (0..n)
+        .map(|i| (key(i), Set::from_vec(vec![format!("fid{}", i)])))
+        .collect();
+    let mut tree = AccumulatorTree::build_parallel(entries, 0).expect("benchmark entries");
+    tree.publish_epoch();
+    tree
+}
+
+fn bench_mutations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mutate");
+    group.sample_size(10);
+    for n in SIZES {
+        let bytes = tree_of(n).to_bytes();
+        let fresh = || AccumulatorTree::from_bytes(&bytes).expect("snapshot round-trips");
+        let target = key(n / 2);
+        let fid = format!("fid{}", n / 2);
+        group.bench_function(BenchmarkId::new("insert", n), |b| {
+            b.iter_batched(
+                fresh,
+                |mut tree| tree.insert(key(n), "new".to_string()),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("insert_with_proof", n), |b| {
+            b.iter_batched(
+                fresh,
+                |mut tree| tree.insert_with_proof(key(n), "new".to_string()).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("update_with_proof", n), |b| {
+            b.iter_batched(
+                fresh,
+                |mut tree| tree.update_with_proof(&target, &fid, "new".to_string()),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("delete", n), |b| {
+            b.iter_batched(
+                fresh,
+                |mut tree| tree.delete(&target, &fid),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("delete_with_proof", n), |b| {
+            b.iter_batched(
+                fresh,
+                |mut tree| tree.delete_with_proof(&target, &fid),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query");
+    group.sample_size(20);
+    for n in SIZES {
+        let tree = tree_of(n);
+        let target = key(n / 2);
+        group.bench_with_input(
+            BenchmarkId::new("select_with_proof", n),
+            &tree,
+            |b, tree| b.iter(|| tree.select_with_proof(&target).unwrap()),
+        );
+        group.bench_with_input(BenchmarkId::new("nonmembership", n), &tree, |b, tree| {
+            b.iter(|| tree.select_nonmembership_proof("absent"))
+        });
+    }
+    group.finish();
+}
+
+fn bench_verification(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify");
+    for n in SIZES {
+        let mut tree = tree_of(n);
+        let target = key(n / 2);
+        let fids = Set::from_vec(vec![format!("fid{}", n / 2)]);
+        let query = tree.select_with_proof(&target).unwrap();
+        let absent = tree
+            .select_nonmembership_proof("absent")
+            .expect("absent keys have a proof");
+        let inserted = tree.insert_with_proof(key(n), "new".to_string()).unwrap();
+        group.bench_function(BenchmarkId::new("select", n), |b| {
+            b.iter(|| assert!(query.verify_full(&target, &fids)))
+        });
+        group.bench_function(BenchmarkId::new("nonmembership", n), |b| {
+            b.iter(|| assert!(absent.verify("absent")))
+        });
+        group.bench_function(BenchmarkId::new("insert", n), |b| {
+            b.iter(|| assert!(inserted.verify_insert()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_mutations, bench_queries, bench_verification);
+criterion_main!(benches);
@@ -1,25 +1,59 @@
+use crate::Hash;
+use crate::canonical::{CanonicalEncode, impl_canonical_eq, normalize_g1, normalize_g2};
+use crate::epoch::EpochCommitment;
 use accumulator_ads::acc::utils::digest_to_prime_field;
 use accumulator_ads::digest::Digestible;
 use accumulator_ads::{G1Affine, digest_set_from_set};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MembershipProof {
+    #[serde(with = "accumulator_ads::acc::serde_impl")]
     pub witness: G1Affine,
 }
 
 impl MembershipProof {
+    /// Return the proof with its witness in normalized form.
+    pub fn canonicalize(&self) -> Self {
+        Self {
+            witness: normalize_g1(self.witness),
+        }
+    }
+
     pub fn verify(&self, acc: &G1Affine, key: &str) -> bool {
-        // Convert key to field element
-        let key_digest = key.to_digest();
-        let key_fr = digest_to_prime_field(&key_digest);
+        // Verify using accumulator_ads with the key as a field element
+        self.with_key(key).verify(*acc)
+    }
 
-        // Create membership proof and verify using accumulator_ads
-        let proof = accumulator_ads::MembershipProof {
-            witness: self.witness,
-            element: key_fr,
-        };
+    /// Refresh the witness of `key` after `added` joined the accumulator,
+    /// whose value before was `prev_acc`, without asking the prover.
+    pub fn update_on_add(&mut self, key: &str, prev_acc: G1Affine, added: &str) {
+        let mut proof = self.with_key(key);
+        proof.update_on_add(prev_acc, digest_to_prime_field(&added.to_digest()));
+        self.witness = proof.witness;
+    }
 
-        proof.verify(*acc)
+    /// Refresh the witness of `key` after `removed` left the accumulator,
+    /// whose value is now `new_acc`. Fails if `removed` is `key`.
+    pub fn update_on_delete(
+        &mut self,
+        key: &str,
+        new_acc: G1Affine,
+        removed: &str,
+    ) -> Result<(), String> {
+        let mut proof = self.with_key(key);
+        proof
+            .update_on_delete(new_acc, digest_to_prime_field(&removed.to_digest()))
+            .map_err(|e| e.to_string())?;
+        self.witness = proof.witness;
+        Ok(())
+    }
+
+    fn with_key(&self, key: &str) -> accumulator_ads::MembershipProof {
+        accumulator_ads::MembershipProof {
+            witness: self.witness,
+            element: digest_to_prime_field(&key.to_digest()),
+        }
     }
 }
 
@@ -30,22 +64,107 @@ pub fn verify_membership(acc: &G1Affine, witness: &G1Affine, key: &str) -> bool
     MembershipProof { witness: *witness }.verify(acc, key)
 }
 
-#[derive(Debug, Clone)]
+/// Check many membership witnesses, each `(acc, witness, key)`, with one
+/// product of two pairings instead of two pairings per witness.
+///
+/// Each check `e(w, g2^(s-x)) = e(acc, g2)` is rewritten as
+/// `e(w, g2^s) = e(acc + x*w, g2)` and the checks are summed with random
+/// 128-bit weights `r_i`, giving
+/// `e(sum r_i*w_i, g2^s) = e(sum r_i*(acc_i + x_i*w_i), g2)`. A batch with
+/// a bad witness passes with probability about 2^-128. The weights are
+/// derived from a hash of the whole batch, so the prover cannot pick
+/// witnesses that cancel out.
+pub fn verify_membership_batch(items: &[(G1Affine, G1Affine, &str)]) -> bool {
+    use accumulator_ads::acc::setup::get_g2s;
+    use accumulator_ads::{Curve, G1Projective, G2Affine};
+    use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+    use ark_ff::{One, PrimeField};
+    #[cfg(feature = "parallel")]
+    use rayon::prelude::*;
+    use sha2::{Digest, Sha256};
+
+    if items.is_empty() {
+        return true;
+    }
+    let mut transcript = Sha256::new();
+    transcript.update(BATCH_TAG);
+    for (acc, witness, key) in items {
+        let mut bytes = Vec::new();
+        acc.encode_canonical(&mut bytes);
+        witness.encode_canonical(&mut bytes);
+        key.encode_canonical(&mut bytes);
+        transcript.update(bytes);
+    }
+    let seed = transcript.finalize();
+
+    let terms = ark_std::cfg_iter!(items)
+        .enumerate()
+        .map(|(i, (acc, witness, key))| {
+            let weight: Hash = Sha256::new()
+                .chain_update(seed)
+                .chain_update((i as u64).to_be_bytes())
+                .finalize()
+                .into();
+            let r = accumulator_ads::Fr::from_le_bytes_mod_order(&weight[..16]);
+            let x: accumulator_ads::Fr = digest_to_prime_field(&key.to_digest());
+            let w = witness.mul(r);
+            let z = acc.mul(r) + witness.mul(r * x);
+            (w, z)
+        });
+    let add = |(w1, z1): (G1Projective, G1Projective), (w2, z2): (G1Projective, G1Projective)| {
+        (w1 + w2, z1 + z2)
+    };
+    #[cfg(feature = "parallel")]
+    let (lhs, rhs) = terms.reduce(Default::default, add);
+    #[cfg(not(feature = "parallel"))]
+    let (lhs, rhs) = terms.fold(Default::default(), add);
+    let Ok(g2_s) = get_g2s(1) else {
+        return false;
+    };
+    let g2 = G2Affine::prime_subgroup_generator();
+    Curve::product_of_pairings(&[
+        (lhs.into_affine().into(), g2_s.into()),
+        ((-rhs).into_affine().into(), g2.into()),
+    ])
+    .is_one()
+}
+
+const BATCH_TAG: &[u8] = b"acc-tree/membership-batch";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AccProof {
     Membership(MembershipProof),
-    NonMembership(NonMembershipProof),
+    NonMembership(Box<NonMembershipProof>),
+}
+
+impl AccProof {
+    /// Return the proof with all curve points in normalized form.
+    pub fn canonicalize(&self) -> Self {
+        match self {
+            AccProof::Membership(mp) => AccProof::Membership(mp.canonicalize()),
+            AccProof::NonMembership(nm) => AccProof::NonMembership(Box::new(nm.canonicalize())),
+        }
+    }
 }
 
 /// Non-membership proof using cryptographic accumulator
 /// This proves that a key is NOT in the accumulated set using Bézout coefficients
-#[derive(Debug, Clone)]
+///
+/// Leaves sit in insertion order unless the tree sorts them, so in general
+/// neighbouring leaves say nothing about absent keys (see `gap` for the
+/// sorted-leaf form). The Bézout pair is checked against the accumulator of
+/// every live key, which leaves the prover no neighbours to choose.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NonMembershipProof {
     /// The key being proved as non-member
     pub key: String,
     /// The accumulator value of the tree (all keys)
+    #[serde(with = "accumulator_ads::acc::serde_impl")]
     pub accumulator: G1Affine,
     /// The underlying cryptographic non-membership proof from accumulator_ads
     pub acc_proof: accumulator_ads::NonMembershipProof,
+    /// Published epoch the accumulator belongs to (None when proved against unpublished state)
+    pub commitment: Option<EpochCommitment>,
 }
 
 impl NonMembershipProof {
@@ -67,11 +186,35 @@ impl NonMembershipProof {
                 key,
                 accumulator,
                 acc_proof,
+                commitment: None,
             }),
             Err(_) => None, // Key is in the set, cannot create non-membership proof
         }
     }
 
+    /// Attach the published epoch whose global accumulator this proof was built against.
+    pub fn with_commitment(mut self, commitment: EpochCommitment) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// Return the proof with all curve points in normalized form.
+    pub fn canonicalize(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            accumulator: normalize_g1(self.accumulator),
+            acc_proof: accumulator_ads::NonMembershipProof {
+                element: self.acc_proof.element,
+                witness: normalize_g2(self.acc_proof.witness),
+                g2_a: normalize_g2(self.acc_proof.g2_a),
+            },
+            commitment: self.commitment.clone().map(|mut c| {
+                c.global_acc = normalize_g1(c.global_acc);
+                c
+            }),
+        }
+    }
+
     /// Verify the non-membership proof
     /// Returns true if the key is proven to NOT be in the accumulated set
     pub fn verify(&self, expected_key: &str) -> bool {
@@ -84,4 +227,56 @@ impl NonMembershipProof {
         // This checks: A(s)*P(s) + B(s)*(s-x) = 1 using pairings
         self.acc_proof.verify(self.accumulator)
     }
+
+    /// Verify the proof and that it was produced against the published epoch
+    /// whose commitment digest the client trusts.
+    /// Checks:
+    /// 1. The embedded commitment hashes to `trusted_digest`
+    /// 2. The proof's accumulator is that epoch's global accumulator
+    /// 3. The Bézout non-membership proof holds for `expected_key`
+    pub fn verify_in_epoch(&self, expected_key: &str, trusted_digest: &Hash) -> bool {
+        let commitment = match &self.commitment {
+            Some(c) => c,
+            None => return false,
+        };
+        if commitment.digest() != *trusted_digest {
+            return false;
+        }
+        if commitment.global_acc != self.accumulator {
+            return false;
+        }
+        self.verify(expected_key)
+    }
+}
+
+impl CanonicalEncode for MembershipProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.witness.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for NonMembershipProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.accumulator.encode_canonical(out);
+        self.acc_proof.encode_canonical(out);
+        self.commitment.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for AccProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            AccProof::Membership(mp) => {
+                out.push(0);
+                mp.encode_canonical(out);
+            }
+            AccProof::NonMembership(nm) => {
+                out.push(1);
+                nm.encode_canonical(out);
+            }
+        }
+    }
 }
+
+impl_canonical_eq!(MembershipProof, NonMembershipProof, AccProof);
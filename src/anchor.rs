@@ -0,0 +1,335 @@
+//! External anchoring of published epochs.
+//!
+//! A pinned epoch digest only proves what the server committed to, not when.
+//! An `Anchor` registered on the tree is handed every commitment produced by
+//! `AccumulatorTree::publish` and returns third-party evidence that the digest
+//! existed at that point: an RFC 3161 timestamp token (`TsaAnchor`) or whatever
+//! an external service such as a chain relayer acknowledges (`WebhookAnchor`).
+//!
+//! Neither anchor ships an HTTP client. Both post through `HttpPost`, so callers
+//! plug in the client (and TLS setup) they already use.
+
+use crate::Hash;
+use crate::epoch::EpochCommitment;
+
+/// Minimal HTTP transport the anchors send through. Shared with the tree, so
+/// it must be usable from any thread.
+pub trait HttpPost: Send + Sync {
+    /// POST `body` to `url` and return the response body of a 2xx reply.
+    fn post(&self, url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// Evidence returned by an anchor for one epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorReceipt {
+    /// `Anchor::name` of the anchor that produced it
+    pub anchor: String,
+    pub epoch: u64,
+    /// `EpochCommitment::digest` that was anchored
+    pub digest: Hash,
+    /// Anchor-specific evidence (DER timestamp token, webhook response body)
+    pub evidence: Vec<u8>,
+}
+
+impl AnchorReceipt {
+    /// Check the receipt was issued for `commitment`.
+    pub fn matches(&self, commitment: &EpochCommitment) -> bool {
+        self.epoch == commitment.epoch && self.digest == commitment.digest()
+    }
+}
+
+/// Hook invoked with each newly published epoch commitment. Anchors live in
+/// the tree, which may be shared across threads.
+pub trait Anchor: Send + Sync {
+    /// Short identifier recorded in receipts.
+    fn name(&self) -> &str;
+    /// Anchor `commitment` externally and return the evidence.
+    fn anchor(&self, commitment: &EpochCommitment) -> Result<AnchorReceipt, String>;
+}
+
+// ---- RFC 3161 ----
+
+/// DER OID 2.16.840.1.101.3.4.2.1 (SHA-256)
+const SHA256_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend_from_slice(&bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn der_uint(v: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = v
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|b| *b == 0)
+        .collect();
+    if bytes.first().is_none_or(|b| b & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    der(0x02, &bytes)
+}
+
+/// One DER element split off a buffer: (tag, content, whole element, rest).
+type DerSplit<'a> = (u8, &'a [u8], &'a [u8], &'a [u8]);
+
+fn read_der(bytes: &[u8]) -> Result<DerSplit<'_>, String> {
+    let truncated = || "truncated DER element".to_string();
+    let (&tag, rest) = bytes.split_first().ok_or_else(truncated)?;
+    let (&first, mut rest) = rest.split_first().ok_or_else(truncated)?;
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return Err("unsupported DER length".to_string());
+        }
+        let len = rest[..n]
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        rest = &rest[n..];
+        len
+    };
+    if rest.len() < len {
+        return Err(truncated());
+    }
+    let header = bytes.len() - rest.len();
+    Ok((tag, &rest[..len], &bytes[..header + len], &rest[len..]))
+}
+
+/// DER `TimeStampReq` for a SHA-256 `digest`, asking for the TSA certificate.
+pub fn timestamp_request(digest: &Hash, nonce: u64) -> Vec<u8> {
+    let algorithm = der(0x30, &[der(0x06, SHA256_OID), vec![0x05, 0x00]].concat());
+    let imprint = der(0x30, &[algorithm, der(0x04, digest)].concat());
+    let body = [
+        der_uint(1),
+        imprint,
+        der_uint(nonce),
+        vec![0x01, 0x01, 0xff],
+    ]
+    .concat();
+    der(0x30, &body)
+}
+
+/// Extract the timestamp token from a DER `TimeStampResp`. Fails unless the
+/// status is granted (0) or grantedWithMods (1) and the token carries `digest`.
+/// The token's CMS signature is not checked here; it is kept as evidence for
+/// verifiers that hold the TSA certificate chain.
+pub fn parse_timestamp_response(bytes: &[u8], digest: &Hash) -> Result<Vec<u8>, String> {
+    let (tag, resp, _, _) = read_der(bytes)?;
+    if tag != 0x30 {
+        return Err("TimeStampResp is not a SEQUENCE".to_string());
+    }
+    let (_, status_info, _, rest) = read_der(resp)?;
+    let (tag, status, _, _) = read_der(status_info)?;
+    if tag != 0x02 || status.len() != 1 {
+        return Err("malformed PKIStatus".to_string());
+    }
+    if status[0] > 1 {
+        return Err(format!("timestamp request rejected (status {})", status[0]));
+    }
+    if rest.is_empty() {
+        return Err("granted response has no timestamp token".to_string());
+    }
+    let (_, _, token, _) = read_der(rest)?;
+    if !token.windows(digest.len()).any(|w| w == digest) {
+        return Err("timestamp token does not cover the epoch digest".to_string());
+    }
+    Ok(token.to_vec())
+}
+
+/// Anchor to an RFC 3161 timestamp authority.
+pub struct TsaAnchor<T: HttpPost> {
+    url: String,
+    transport: T,
+}
+
+impl<T: HttpPost> TsaAnchor<T> {
+    pub fn new(url: impl Into<String>, transport: T) -> Self {
+        Self {
+            url: url.into(),
+            transport,
+        }
+    }
+}
+
+impl<T: HttpPost> Anchor for TsaAnchor<T> {
+    fn name(&self) -> &str {
+        "rfc3161"
+    }
+
+    fn anchor(&self, commitment: &EpochCommitment) -> Result<AnchorReceipt, String> {
+        let digest = commitment.digest();
+        // The digest is unique per commitment, so it doubles as the nonce
+        let nonce = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        let reply = self.transport.post(
+            &self.url,
+            "application/timestamp-query",
+            &timestamp_request(&digest, nonce),
+        )?;
+        Ok(AnchorReceipt {
+            anchor: self.name().to_string(),
+            epoch: commitment.epoch,
+            digest,
+            evidence: parse_timestamp_response(&reply, &digest)?,
+        })
+    }
+}
+
+// ---- Webhook ----
+
+/// JSON body posted by `WebhookAnchor`.
+pub fn webhook_payload(commitment: &EpochCommitment) -> String {
+    format!(
+        "{{\"epoch\":{},\"digest\":\"{}\",\"forest_hash\":\"{}\",\"key_count\":{}}}",
+        commitment.epoch,
+        hex::encode(commitment.digest()),
+        hex::encode(commitment.forest_hash),
+        commitment.key_count
+    )
+}
+
+/// Anchor that posts each commitment as JSON to an external service (a chain
+/// relayer, transparency log, ...) and keeps its response as evidence.
+pub struct WebhookAnchor<T: HttpPost> {
+    url: String,
+    transport: T,
+}
+
+impl<T: HttpPost> WebhookAnchor<T> {
+    pub fn new(url: impl Into<String>, transport: T) -> Self {
+        Self {
+            url: url.into(),
+            transport,
+        }
+    }
+}
+
+impl<T: HttpPost> Anchor for WebhookAnchor<T> {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn anchor(&self, commitment: &EpochCommitment) -> Result<AnchorReceipt, String> {
+        let payload = webhook_payload(commitment);
+        let reply = self
+            .transport
+            .post(&self.url, "application/json", payload.as_bytes())?;
+        Ok(AnchorReceipt {
+            anchor: self.name().to_string(),
+            epoch: commitment.epoch,
+            digest: commitment.digest(),
+            evidence: reply,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use std::sync::{Arc, Mutex};
+
+    /// (url, body) of every request sent
+    type Sent = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+    /// Records requests and answers with a fixed reply (or a TSA-shaped one).
+    #[derive(Clone, Default)]
+    struct Recorder {
+        sent: Sent,
+        tsa: bool,
+    }
+
+    impl HttpPost for Recorder {
+        fn post(&self, url: &str, _content_type: &str, body: &[u8]) -> Result<Vec<u8>, String> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((url.to_string(), body.to_vec()));
+            if !self.tsa {
+                return Ok(b"ok".to_vec());
+            }
+            // Echo the request's messageImprint inside a stand-in token
+            let (_, req, _, _) = read_der(body)?;
+            let (_, _, _, rest) = read_der(req)?;
+            let (_, _, imprint, _) = read_der(rest)?;
+            let token = der(0x30, imprint);
+            Ok(der(0x30, &[der(0x30, &der_uint(0)), token].concat()))
+        }
+    }
+
+    #[test]
+    fn test_publish_invokes_anchors() {
+        crate::setup::init_test_parameters();
+        let webhook = Recorder::default();
+        let tsa = Recorder {
+            tsa: true,
+            ..Default::default()
+        };
+        let mut tree = AccumulatorTree::new();
+        tree.add_anchor(Box::new(WebhookAnchor::new(
+            "https://relay",
+            webhook.clone(),
+        )));
+        tree.add_anchor(Box::new(TsaAnchor::new("https://tsa", tsa.clone())));
+
+        tree.stage(crate::txn::TxnOp::Insert {
+            key: "a".to_string(),
+            fid: "fa".to_string(),
+        });
+        let transition = tree.publish();
+        assert_eq!(transition.receipts.len(), 2);
+        for receipt in &transition.receipts {
+            assert!(receipt.as_ref().unwrap().matches(&transition.commitment));
+        }
+
+        let sent = webhook.sent.lock().unwrap();
+        assert_eq!(sent[0].0, "https://relay");
+        assert_eq!(
+            sent[0].1,
+            webhook_payload(&transition.commitment).into_bytes()
+        );
+        assert_eq!(tsa.sent.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_timestamp_response_checks() {
+        let digest = [7u8; 32];
+        let request = timestamp_request(&digest, 0x80);
+        assert_eq!(request[0], 0x30);
+        assert!(request.windows(32).any(|w| w == digest));
+
+        let rejected = der(0x30, &der(0x30, &der_uint(2)));
+        assert!(parse_timestamp_response(&rejected, &digest).is_err());
+
+        let granted = |token: Vec<u8>| der(0x30, &[der(0x30, &der_uint(0)), token].concat());
+        let token = der(0x30, &der(0x04, &digest));
+        assert_eq!(
+            parse_timestamp_response(&granted(token.clone()), &digest),
+            Ok(token)
+        );
+        let other = der(0x30, &der(0x04, &[8u8; 32]));
+        assert!(parse_timestamp_response(&granted(other), &digest).is_err());
+        assert!(parse_timestamp_response(&der(0x30, &der(0x30, &der_uint(0))), &digest).is_err());
+
+        // Long-form lengths round-trip through the reader
+        let long = der(0x04, &[1u8; 300]);
+        let (_, content, whole, rest) = read_der(&long).unwrap();
+        assert_eq!(
+            (content.len(), whole.len(), rest.len()),
+            (300, long.len(), 0)
+        );
+    }
+}
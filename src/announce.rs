@@ -0,0 +1,160 @@
+//! Signed root announcements (feature `signed-roots`).
+//!
+//! A `RootManifest` authenticates root hashes through the epoch digest, but
+//! root accumulators are not part of the commitment, so a client checking
+//! accumulator witnesses has to learn them some other way. The tree maintainer
+//! signs a `RootAnnouncement` of every root hash and accumulator of an epoch
+//! with its Ed25519 key; a client holding the maintainer's verifying key checks
+//! the signature and registers the roots with its `Verifier`.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::tree::AccumulatorTree;
+use crate::verifier::Verifier;
+use crate::wire::{CanonicalDecode, WireMessage, WireReader};
+use accumulator_ads::G1Affine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier as _, VerifyingKey};
+
+const ANNOUNCEMENT_DOMAIN: &[u8] = b"acc-tree/root-announcement/v1";
+
+/// Roots of one epoch, signed by the tree maintainer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootAnnouncement {
+    pub epoch: u64,
+    /// Hash and accumulator of each root, in forest order
+    pub roots: Vec<(Hash, G1Affine)>,
+    pub signature: Signature,
+}
+
+impl RootAnnouncement {
+    /// Sign `roots` as the roots of `epoch`.
+    pub fn new(epoch: u64, roots: Vec<(Hash, G1Affine)>, key: &SigningKey) -> Self {
+        let mut announcement = Self {
+            epoch,
+            roots,
+            signature: Signature::from_bytes(&[0u8; 64]),
+        };
+        announcement.signature = key.sign(&announcement.signed_bytes());
+        announcement
+    }
+
+    /// Announce the roots of the tree's last published epoch. Fails if the
+    /// forest has changed since publication.
+    pub fn from_tree(tree: &AccumulatorTree, key: &SigningKey) -> Result<Self, String> {
+        let commitment = tree
+            .published_commitment()
+            .ok_or_else(|| "no epoch has been published".to_string())?;
+        if crate::epoch::forest_hash(&tree.root_entries()) != commitment.forest_hash {
+            return Err("forest changed since the commitment was published".to_string());
+        }
+        let roots = tree
+            .roots_info()
+            .into_iter()
+            .map(|r| (r.hash, r.acc))
+            .collect();
+        Ok(Self::new(commitment.epoch, roots, key))
+    }
+
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        self.epoch.encode_canonical(out);
+        self.roots.encode_canonical(out);
+    }
+
+    /// Message the signature covers.
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = ANNOUNCEMENT_DOMAIN.to_vec();
+        self.encode_body(&mut out);
+        out
+    }
+
+    /// Check the announcement is signed by `signer`, the maintainer key the
+    /// client was given out of band.
+    pub fn verify(&self, signer: &VerifyingKey) -> Result<(), String> {
+        signer
+            .verify(&self.signed_bytes(), &self.signature)
+            .map_err(|e| format!("announcement signature: {}", e))
+    }
+
+    /// Check the signature and trust the announced roots, with their
+    /// accumulators, in `verifier`.
+    pub fn register(&self, verifier: &mut Verifier, signer: &VerifyingKey) -> Result<(), String> {
+        self.verify(signer)?;
+        for (root, acc) in &self.roots {
+            verifier.register(*root, *acc);
+        }
+        Ok(())
+    }
+}
+
+impl CanonicalEncode for RootAnnouncement {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.encode_body(out);
+        out.extend_from_slice(&self.signature.to_bytes());
+    }
+}
+
+impl CanonicalDecode for RootAnnouncement {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Self {
+            epoch: u64::decode_canonical(r)?,
+            roots: Vec::decode_canonical(r)?,
+            signature: Signature::from_bytes(&r.take(64)?.try_into().unwrap()),
+        })
+    }
+}
+
+impl WireMessage for RootAnnouncement {
+    const TYPE_TAG: u8 = 22;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{from_wire, to_wire};
+
+    #[test]
+    fn test_signed_roots_register_with_verifier() {
+        crate::setup::init_test_parameters();
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut tree = AccumulatorTree::new();
+        for k in ["a", "b", "c"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        assert!(RootAnnouncement::from_tree(&tree, &key).is_err());
+        let commitment = tree.publish_epoch();
+        let announcement = RootAnnouncement::from_tree(&tree, &key).unwrap();
+        assert_eq!(announcement.epoch, commitment.epoch);
+        assert_eq!(announcement.roots.len(), 2);
+
+        let decoded: RootAnnouncement = from_wire(&to_wire(&announcement)).unwrap();
+        assert_eq!(decoded, announcement);
+        decoded.verify(&key.verifying_key()).unwrap();
+        let other = SigningKey::from_bytes(&[8u8; 32]).verifying_key();
+        assert!(decoded.verify(&other).is_err());
+
+        // Swapping in another accumulator or epoch breaks the signature
+        let mut tampered = decoded.clone();
+        tampered.roots[0].1 = tampered.roots[1].1;
+        assert!(tampered.verify(&key.verifying_key()).is_err());
+        tampered = decoded.clone();
+        tampered.epoch += 1;
+        assert!(tampered.verify(&key.verifying_key()).is_err());
+
+        let mut verifier = Verifier::default();
+        assert!(
+            tampered
+                .register(&mut verifier, &key.verifying_key())
+                .is_err()
+        );
+        decoded
+            .register(&mut verifier, &key.verifying_key())
+            .unwrap();
+        for root in tree.roots_info() {
+            assert!(verifier.is_registered(&root.hash));
+        }
+
+        // The forest must still be the published one
+        tree.insert("d".to_string(), "fd".to_string());
+        assert!(RootAnnouncement::from_tree(&tree, &key).is_err());
+    }
+}
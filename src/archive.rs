@@ -0,0 +1,325 @@
+//! Archive of generated proofs.
+//!
+//! Responses are stored under `(epoch, query hash)` so a server can answer a
+//! repeated query from the archive instead of recomputing witnesses, and are also
+//! indexed by the digest of their canonical encoding so auditors can fetch a
+//! historical proof by content. Entries expire after a TTL and the archive evicts
+//! the oldest entries once its entry or byte budget is exceeded.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::clock::{Clock, elapsed, system_clock};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const POINT_QUERY_DOMAIN: &[u8] = b"acc-tree/query/point";
+
+/// Hash identifying a point lookup of `key`.
+pub fn point_query_hash(key: &str) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(POINT_QUERY_DOMAIN);
+    hasher.update(key.canonical_bytes());
+    hasher.finalize().into()
+}
+
+/// Lookup key of an archived response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArchiveKey {
+    /// Published epoch the response was generated against
+    pub epoch: u64,
+    /// Hash of the query that produced the response
+    pub query_hash: Hash,
+}
+
+impl ArchiveKey {
+    pub fn new(epoch: u64, query_hash: Hash) -> Self {
+        Self { epoch, query_hash }
+    }
+}
+
+/// Retention limits of a `ProofArchive`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveConfig {
+    /// Entries older than this are ignored and purged (None keeps them forever)
+    pub ttl: Option<Duration>,
+    /// Maximum number of stored responses
+    pub max_entries: usize,
+    /// Maximum total size of the canonical encodings of stored responses
+    pub max_bytes: usize,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            ttl: None,
+            max_entries: 10_000,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ArchivedEntry<V> {
+    value: V,
+    digest: Hash,
+    size: usize,
+    stored_at: SystemTime,
+    seq: u64,
+}
+
+/// Bounded store of generated responses with content-addressed lookup.
+#[derive(Debug, Clone)]
+pub struct ProofArchive<V> {
+    config: ArchiveConfig,
+    entries: HashMap<ArchiveKey, ArchivedEntry<V>>,
+    by_digest: HashMap<Hash, ArchiveKey>,
+    /// Insertion order, oldest first, used for eviction
+    order: BTreeMap<u64, ArchiveKey>,
+    next_seq: u64,
+    total_bytes: usize,
+    /// Time source for entry ages
+    clock: Arc<dyn Clock>,
+}
+
+impl<V: CanonicalEncode + Clone> ProofArchive<V> {
+    pub fn new(config: ArchiveConfig) -> Self {
+        Self::with_clock(config, system_clock())
+    }
+
+    /// Archive that ages entries by `clock` instead of the system clock.
+    pub fn with_clock(config: ArchiveConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+            by_digest: HashMap::new(),
+            order: BTreeMap::new(),
+            next_seq: 0,
+            total_bytes: 0,
+            clock,
+        }
+    }
+
+    pub fn config(&self) -> &ArchiveConfig {
+        &self.config
+    }
+
+    /// Number of stored responses (including expired ones not yet purged).
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total size of the canonical encodings currently stored.
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    fn is_expired(&self, entry: &ArchivedEntry<V>, now: SystemTime) -> bool {
+        match self.config.ttl {
+            Some(ttl) => elapsed(entry.stored_at, now) > ttl,
+            None => false,
+        }
+    }
+
+    /// Store `value` under `key`, replacing any previous response for the same
+    /// key, and return the content digest it can be retrieved by. Responses larger
+    /// than the whole byte budget are not stored.
+    pub fn insert(&mut self, key: ArchiveKey, value: V) -> Hash {
+        let bytes = value.canonical_bytes();
+        let digest: Hash = Sha256::digest(&bytes).into();
+        let size = bytes.len();
+
+        self.remove(&key);
+        if size > self.config.max_bytes || self.config.max_entries == 0 {
+            return digest;
+        }
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.order.insert(seq, key);
+        self.by_digest.insert(digest, key);
+        self.total_bytes += size;
+        self.entries.insert(
+            key,
+            ArchivedEntry {
+                value,
+                digest,
+                size,
+                stored_at: self.clock.now(),
+                seq,
+            },
+        );
+        self.evict_over_budget();
+        digest
+    }
+
+    /// Response stored under `key`, unless it has expired.
+    pub fn get(&self, key: &ArchiveKey) -> Option<&V> {
+        let entry = self.entries.get(key)?;
+        if self.is_expired(entry, self.clock.now()) {
+            return None;
+        }
+        Some(&entry.value)
+    }
+
+    /// Response whose canonical encoding hashes to `digest`, unless it has expired.
+    pub fn get_by_digest(&self, digest: &Hash) -> Option<&V> {
+        let key = self.by_digest.get(digest)?;
+        self.get(key)
+    }
+
+    /// Return the archived response for `key`, generating and storing it with
+    /// `generate` on a miss.
+    pub fn get_or_insert_with(&mut self, key: ArchiveKey, generate: impl FnOnce() -> V) -> V {
+        if let Some(v) = self.get(&key) {
+            return v.clone();
+        }
+        let value = generate();
+        self.insert(key, value.clone());
+        value
+    }
+
+    /// All unexpired responses generated against `epoch`, ordered by query hash.
+    pub fn epoch_entries(&self, epoch: u64) -> Vec<(ArchiveKey, &V)> {
+        let now = self.clock.now();
+        let mut out: Vec<(ArchiveKey, &V)> = self
+            .entries
+            .iter()
+            .filter(|(k, e)| k.epoch == epoch && !self.is_expired(e, now))
+            .map(|(k, e)| (*k, &e.value))
+            .collect();
+        out.sort_by_key(|(k, _)| *k);
+        out
+    }
+
+    /// Remove the response stored under `key`.
+    pub fn remove(&mut self, key: &ArchiveKey) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.seq);
+        if self.by_digest.get(&entry.digest) == Some(key) {
+            self.by_digest.remove(&entry.digest);
+        }
+        self.total_bytes -= entry.size;
+        Some(entry.value)
+    }
+
+    /// Drop every expired entry. Returns the number of entries removed.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let expired: Vec<ArchiveKey> = self
+            .entries
+            .iter()
+            .filter(|(_, e)| self.is_expired(e, now))
+            .map(|(k, _)| *k)
+            .collect();
+        for key in &expired {
+            self.remove(key);
+        }
+        expired.len()
+    }
+
+    fn evict_over_budget(&mut self) {
+        while self.entries.len() > self.config.max_entries
+            || self.total_bytes > self.config.max_bytes
+        {
+            let oldest = match self.order.first_key_value() {
+                Some((_, key)) => *key,
+                None => break,
+            };
+            self.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_proof::Proof;
+    use crate::utils::leaf_hash;
+    use accumulator_ads::Set;
+
+    fn proof_for(key: &str) -> Proof {
+        let leaf = leaf_hash(key, &Set::from_vec(vec!["f".to_string()]), 0, false);
+        Proof::new(leaf, leaf, vec![])
+    }
+
+    #[test]
+    fn test_lookup_by_key_and_digest() {
+        let mut archive = ProofArchive::new(ArchiveConfig::default());
+        let key = ArchiveKey::new(1, point_query_hash("a"));
+        let digest = archive.insert(key, proof_for("a"));
+
+        assert_eq!(archive.get(&key), Some(&proof_for("a")));
+        assert_eq!(archive.get_by_digest(&digest), Some(&proof_for("a")));
+        assert_eq!(digest, proof_for("a").canonical_digest());
+        assert!(
+            archive
+                .get(&ArchiveKey::new(2, point_query_hash("a")))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_get_or_insert_with_hits_archive() {
+        let mut archive = ProofArchive::new(ArchiveConfig::default());
+        let key = ArchiveKey::new(1, point_query_hash("a"));
+        let mut calls = 0;
+        for _ in 0..3 {
+            archive.get_or_insert_with(key, || {
+                calls += 1;
+                proof_for("a")
+            });
+        }
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_evicts_oldest_over_entry_limit() {
+        let config = ArchiveConfig {
+            max_entries: 2,
+            ..ArchiveConfig::default()
+        };
+        let mut archive = ProofArchive::new(config);
+        for k in ["a", "b", "c"] {
+            archive.insert(ArchiveKey::new(1, point_query_hash(k)), proof_for(k));
+        }
+        assert_eq!(archive.len(), 2);
+        assert!(
+            archive
+                .get(&ArchiveKey::new(1, point_query_hash("a")))
+                .is_none()
+        );
+        assert_eq!(archive.epoch_entries(1).len(), 2);
+    }
+
+    #[test]
+    fn test_byte_budget_and_ttl() {
+        let size = proof_for("a").canonical_bytes().len();
+        let config = ArchiveConfig {
+            ttl: Some(Duration::from_secs(60)),
+            max_entries: 100,
+            max_bytes: size,
+        };
+        let clock = crate::clock::MockClock::default();
+        let mut archive = ProofArchive::with_clock(config, Arc::new(clock.clone()));
+        archive.insert(ArchiveKey::new(1, point_query_hash("a")), proof_for("a"));
+        archive.insert(ArchiveKey::new(1, point_query_hash("b")), proof_for("b"));
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive.total_bytes(), size);
+
+        clock.advance(Duration::from_secs(60));
+        let key = ArchiveKey::new(1, point_query_hash("b"));
+        assert!(archive.get(&key).is_some());
+        assert_eq!(archive.purge_expired(), 0);
+        clock.advance(Duration::from_secs(1));
+        assert!(archive.get(&key).is_none());
+        assert_eq!(archive.purge_expired(), 1);
+        assert!(archive.is_empty());
+        assert_eq!(archive.total_bytes(), 0);
+    }
+}
@@ -0,0 +1,282 @@
+//! Arena storage for the Merkle structure of a root.
+//!
+//! `Node` holds its children through `Arc`, so every node is a separate heap
+//! allocation and dropping a subtree walks it. `NodeArena` keeps nodes in one
+//! `Vec` and links them with generational indices: a `NodeId` names a slot and
+//! the generation it was handed out in, so an id kept past `remove` is detected
+//! instead of silently reading whatever reused the slot. Only hashes and leaf
+//! state are stored; accumulators stay with `Node`. `src/bin/node_bench.rs`
+//! compares this layout against boxed, `Rc` and `Arc` nodes.
+
+use crate::Hash;
+use crate::node::Node;
+use crate::utils::{leaf_hash, nonleaf_hash};
+use accumulator_ads::Set;
+
+/// Handle to a node in a `NodeArena`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone)]
+pub enum ArenaNode {
+    Leaf {
+        key: String,
+        fids: Set<String>,
+        deleted: bool,
+    },
+    NonLeaf {
+        hash: Hash,
+        level: usize,
+        left: NodeId,
+        right: NodeId,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u32,
+    node: Option<ArenaNode>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NodeArena {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+impl NodeArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of live nodes.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn alloc(&mut self, node: ArenaNode) -> NodeId {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.node = Some(node);
+            return NodeId {
+                index,
+                generation: slot.generation,
+            };
+        }
+        self.slots.push(Slot {
+            generation: 0,
+            node: Some(node),
+        });
+        NodeId {
+            index: self.slots.len() as u32 - 1,
+            generation: 0,
+        }
+    }
+
+    /// Node behind `id`, or None if it was removed.
+    pub fn get(&self, id: NodeId) -> Option<&ArenaNode> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_ref()
+    }
+
+    fn node(&self, id: NodeId) -> Result<&ArenaNode, String> {
+        self.get(id)
+            .ok_or_else(|| format!("stale node id {:?}", id))
+    }
+
+    pub fn insert_leaf(&mut self, key: String, fids: Set<String>) -> NodeId {
+        self.alloc(ArenaNode::Leaf {
+            key,
+            fids,
+            deleted: false,
+        })
+    }
+
+    pub fn level(&self, id: NodeId) -> Result<usize, String> {
+        Ok(match self.node(id)? {
+            ArenaNode::Leaf { .. } => 0,
+            ArenaNode::NonLeaf { level, .. } => *level,
+        })
+    }
+
+    pub fn hash(&self, id: NodeId) -> Result<Hash, String> {
+        Ok(match self.node(id)? {
+            ArenaNode::Leaf {
+                key, fids, deleted, ..
+            } => leaf_hash(key, fids, 0, *deleted),
+            ArenaNode::NonLeaf { hash, .. } => *hash,
+        })
+    }
+
+    /// Join two roots of equal level under a new node.
+    pub fn merge(&mut self, left: NodeId, right: NodeId) -> Result<NodeId, String> {
+        let level = self.level(left)?;
+        if self.level(right)? != level {
+            return Err("merged nodes must have the same level".to_string());
+        }
+        let hash = nonleaf_hash(self.hash(left)?, self.hash(right)?);
+        Ok(self.alloc(ArenaNode::NonLeaf {
+            hash,
+            level: level + 1,
+            left,
+            right,
+        }))
+    }
+
+    /// Copy the Merkle structure of `node` into the arena.
+    pub fn import(&mut self, node: &Node) -> NodeId {
+        match node {
+            Node::Leaf {
+                key, fids, deleted, ..
+            } => self.alloc(ArenaNode::Leaf {
+                key: key.clone(),
+                fids: fids.clone(),
+                deleted: *deleted,
+            }),
+            Node::NonLeaf {
+                hash,
+                level,
+                left,
+                right,
+                ..
+            } => {
+                let left = self.import(left);
+                let right = self.import(right);
+                self.alloc(ArenaNode::NonLeaf {
+                    hash: *hash,
+                    level: *level,
+                    left,
+                    right,
+                })
+            }
+        }
+    }
+
+    /// Free `id` and everything below it. Ids of freed nodes stop resolving.
+    pub fn remove(&mut self, id: NodeId) -> Result<(), String> {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            self.node(id)?;
+            let slot = &mut self.slots[id.index as usize];
+            if let Some(ArenaNode::NonLeaf { left, right, .. }) = slot.node.take() {
+                stack.push(left);
+                stack.push(right);
+            }
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(id.index);
+        }
+        Ok(())
+    }
+
+    /// Ids from `root` down to the leaf at position `pos`.
+    fn path_to(&self, root: NodeId, pos: u64) -> Result<Vec<NodeId>, String> {
+        let mut path = vec![root];
+        let mut cur = root;
+        let mut pos = pos;
+        while let ArenaNode::NonLeaf {
+            level, left, right, ..
+        } = self.node(cur)?
+        {
+            let half = 1u64 << (level - 1);
+            cur = if pos < half {
+                *left
+            } else {
+                pos -= half;
+                *right
+            };
+            path.push(cur);
+        }
+        if pos != 0 {
+            return Err("leaf position out of range".to_string());
+        }
+        Ok(path)
+    }
+
+    /// Leaf at position `pos` under `root`.
+    pub fn leaf_at(&self, root: NodeId, pos: u64) -> Result<&ArenaNode, String> {
+        let path = self.path_to(root, pos)?;
+        self.node(*path.last().unwrap())
+    }
+
+    /// Replace the FIDs of the leaf at `pos` and rehash its ancestors.
+    pub fn set_fids(
+        &mut self,
+        root: NodeId,
+        pos: u64,
+        new_fids: Set<String>,
+    ) -> Result<(), String> {
+        let path = self.path_to(root, pos)?;
+        let leaf = *path.last().unwrap();
+        if let Some(ArenaNode::Leaf { fids, .. }) = self.slots[leaf.index as usize].node.as_mut() {
+            *fids = new_fids;
+        }
+        for id in path.iter().rev().skip(1) {
+            let Some(ArenaNode::NonLeaf { left, right, .. }) = self.get(*id) else {
+                unreachable!("ancestors on a path are internal nodes");
+            };
+            let new_hash = nonleaf_hash(self.hash(*left)?, self.hash(*right)?);
+            if let Some(ArenaNode::NonLeaf { hash, .. }) =
+                self.slots[id.index as usize].node.as_mut()
+            {
+                *hash = new_hash;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_import_and_update_match_tree() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for k in ["a", "b", "c", "d"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        let mut arena = NodeArena::new();
+        let root = arena.import(&tree.roots[0]);
+        assert_eq!(arena.len(), 7);
+        assert_eq!(arena.hash(root), Ok(tree.roots[0].hash()));
+
+        tree.insert("c".to_string(), "fc2".to_string());
+        let fids = tree.select("c").unwrap();
+        let pos = (0..4)
+            .find(|&p| matches!(arena.leaf_at(root, p), Ok(ArenaNode::Leaf { key, .. }) if key == "c"))
+            .unwrap();
+        arena.set_fids(root, pos, fids).unwrap();
+        assert_eq!(arena.hash(root), Ok(tree.roots[0].hash()));
+        assert!(arena.leaf_at(root, 4).is_err());
+    }
+
+    #[test]
+    fn test_removed_ids_do_not_resolve() {
+        let mut arena = NodeArena::new();
+        let a = arena.insert_leaf("a".to_string(), Set::new());
+        let b = arena.insert_leaf("b".to_string(), Set::new());
+        let ab = arena.merge(a, b).unwrap();
+        assert!(arena.merge(ab, a).is_err());
+
+        arena.remove(ab).unwrap();
+        assert!(arena.is_empty());
+        assert!(arena.get(a).is_none() && arena.hash(ab).is_err());
+
+        // A reused slot gets a new generation
+        let c = arena.insert_leaf("c".to_string(), Set::new());
+        assert!(arena.get(c).is_some());
+        assert!(arena.get(a).is_none() && arena.get(b).is_none());
+    }
+}
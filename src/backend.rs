@@ -0,0 +1,359 @@
+//! Accumulator backends.
+//!
+//! `NodeAccumulator` is what the tree needs from an accumulator: commit to a
+//! key set, and prove and check that a key is in it or not. `PairingAccumulator`
+//! is the BLS12-381 accumulator every node and response uses today; it needs
+//! the public parameters of a trusted setup.
+//!
+//! With the `rsa-backend` feature, `RsaAccumulator` offers the same operations
+//! in an RSA group of unknown order, for deployments that cannot run a pairing
+//! setup. Keys are hashed to primes; membership is a root of the accumulator,
+//! non-membership a Bézout pair (Li, Li and Xue). It is experimental: nodes and
+//! responses still carry pairing values, so the RSA backend is usable on its
+//! own but not yet as the accumulator inside the forest.
+
+use crate::acc_proof::MembershipProof;
+use accumulator_ads::acc::utils::digest_to_prime_field;
+use accumulator_ads::digest::Digestible;
+use accumulator_ads::{DynamicAccumulator, G1Affine, Set, digest_set_from_set};
+use std::fmt::Debug;
+
+/// Set accumulator with membership and non-membership witnesses.
+pub trait NodeAccumulator {
+    type Value: Clone + PartialEq + Debug;
+    type Witness: Clone + Debug;
+    type NonMembershipWitness: Clone + Debug;
+
+    /// Accumulator of `keys`.
+    fn commit(&self, keys: &Set<String>) -> Self::Value;
+
+    /// Witness that `key` is in `keys` (None if it is not, or if the witness
+    /// cannot be computed).
+    fn prove_membership(&self, keys: &Set<String>, key: &str) -> Option<Self::Witness>;
+
+    fn verify_membership(&self, acc: &Self::Value, key: &str, witness: &Self::Witness) -> bool;
+
+    /// Witness that `key` is not in `keys` (None if it is).
+    fn prove_non_membership(
+        &self,
+        keys: &Set<String>,
+        key: &str,
+    ) -> Option<Self::NonMembershipWitness>;
+
+    fn verify_non_membership(
+        &self,
+        acc: &Self::Value,
+        key: &str,
+        witness: &Self::NonMembershipWitness,
+    ) -> bool;
+}
+
+/// The BLS12-381 accumulator behind `Node::NonLeaf::acc`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PairingAccumulator;
+
+impl NodeAccumulator for PairingAccumulator {
+    type Value = G1Affine;
+    type Witness = MembershipProof;
+    type NonMembershipWitness = accumulator_ads::NonMembershipProof;
+
+    fn commit(&self, keys: &Set<String>) -> G1Affine {
+        if keys.is_empty() {
+            return crate::utils::empty_acc();
+        }
+        DynamicAccumulator::calculate_commitment(&digest_set_from_set(keys))
+    }
+
+    fn prove_membership(&self, keys: &Set<String>, key: &str) -> Option<MembershipProof> {
+        let witness = crate::witness_cache::compute_witness(keys, key).ok()?;
+        Some(MembershipProof { witness })
+    }
+
+    fn verify_membership(&self, acc: &G1Affine, key: &str, witness: &MembershipProof) -> bool {
+        witness.verify(acc, key)
+    }
+
+    fn prove_non_membership(
+        &self,
+        keys: &Set<String>,
+        key: &str,
+    ) -> Option<accumulator_ads::NonMembershipProof> {
+        let element = digest_to_prime_field(&key.to_digest());
+        accumulator_ads::NonMembershipProof::new(element, &digest_set_from_set(keys)).ok()
+    }
+
+    fn verify_non_membership(
+        &self,
+        acc: &G1Affine,
+        key: &str,
+        witness: &accumulator_ads::NonMembershipProof,
+    ) -> bool {
+        witness.element == digest_to_prime_field(&key.to_digest()) && witness.verify(*acc)
+    }
+}
+
+#[cfg(feature = "rsa-backend")]
+pub use rsa::{RsaAccumulator, RsaNonMembershipWitness, RsaWitness};
+
+#[cfg(feature = "rsa-backend")]
+mod rsa {
+    use super::NodeAccumulator;
+    use accumulator_ads::Set;
+    use num_bigint::{BigInt, BigUint, Sign};
+    use num_integer::Integer;
+    use sha2::{Digest, Sha256};
+
+    /// RSA-2048 challenge modulus; nobody is known to hold its factors.
+    const RSA_2048: &str = "\
+        C7970CEEDCC3B0754490201A7AA613CD73911081C790F5F1A8726F463550BB5B\
+        7FF0DB8E1EA1189EC72F93D1650011BD721AEEACC2ACDE32A04107F0648C2813\
+        A31F5B0B7765FF8B44B4B6FFC93384B646EB09C7CF5E8592D40EA33C80039F35\
+        B4F14A04B51F7BFD781BE4D1673164BA8EB991C2C4D730BBBE35F592BDEF524A\
+        F7E8DAEFD26C66FC02C479AF89D64D373F442709439DE66CEB955F3EA37D5159\
+        F6135809F85334B5CB1813ADDC80CD05609F10AC6A95AD65872C909525BDAD32\
+        BC729592642920F24C61DC5B3C3B7923E56B16A4D9D373D8721F24A3FC0F1B31\
+        31F55615172866BCCC30F95054C824E733A5EB6817F7BC16399D48C6361CC7E5";
+
+    /// Domain separator for hashing keys to primes
+    const PRIME_DOMAIN: &[u8] = b"acc-tree/rsa-prime/v1";
+
+    /// Miller-Rabin bases; hash outputs are not chosen by the prover, so fixed
+    /// bases are enough.
+    const MR_BASES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+    /// Membership witness: the accumulator of every other key.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RsaWitness {
+        pub value: BigUint,
+    }
+
+    /// Non-membership witness `(a, g^b)` with `a * u + b * x = 1`, where `u`
+    /// is the product of the accumulated primes and `x` the key's prime.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RsaNonMembershipWitness {
+        pub a: BigUint,
+        pub g_b: BigUint,
+    }
+
+    /// Accumulator in the group of units modulo an RSA modulus.
+    #[derive(Debug, Clone)]
+    pub struct RsaAccumulator {
+        modulus: BigUint,
+        generator: BigUint,
+    }
+
+    impl Default for RsaAccumulator {
+        fn default() -> Self {
+            Self::rsa_2048()
+        }
+    }
+
+    impl RsaAccumulator {
+        /// Accumulator over `modulus` with generator `generator`. The modulus
+        /// must have unknown factorization for the witnesses to mean anything.
+        pub fn new(modulus: BigUint, generator: BigUint) -> Result<Self, String> {
+            if modulus.bits() < 1024 || modulus.is_even() {
+                return Err("modulus must be an odd number of at least 1024 bits".to_string());
+            }
+            let one = BigUint::from(1u32);
+            if generator <= one || generator >= modulus || generator.gcd(&modulus) != one {
+                return Err("generator must be a unit other than 1".to_string());
+            }
+            Ok(Self { modulus, generator })
+        }
+
+        /// The RSA-2048 challenge modulus with generator 3; needs no setup.
+        pub fn rsa_2048() -> Self {
+            let modulus = BigUint::parse_bytes(RSA_2048.as_bytes(), 16).expect("valid modulus");
+            Self::new(modulus, BigUint::from(3u32)).expect("valid parameters")
+        }
+
+        /// 256-bit prime representative of `key`.
+        pub fn hash_to_prime(key: &str) -> BigUint {
+            (0u64..)
+                .map(|counter| {
+                    let mut hasher = Sha256::new();
+                    hasher.update(PRIME_DOMAIN);
+                    hasher.update((key.len() as u64).to_be_bytes());
+                    hasher.update(key.as_bytes());
+                    hasher.update(counter.to_be_bytes());
+                    let mut bytes: [u8; 32] = hasher.finalize().into();
+                    bytes[0] |= 0x80;
+                    bytes[31] |= 1;
+                    BigUint::from_bytes_be(&bytes)
+                })
+                .find(is_probable_prime)
+                .expect("some counter yields a prime")
+        }
+
+        fn product<'a>(keys: impl Iterator<Item = &'a String>) -> BigUint {
+            keys.fold(BigUint::from(1u32), |acc, k| acc * Self::hash_to_prime(k))
+        }
+
+        fn pow(&self, exponent: &BigUint) -> BigUint {
+            self.generator.modpow(exponent, &self.modulus)
+        }
+
+        /// `g^e` for a possibly negative `e`.
+        fn pow_signed(&self, exponent: &BigInt) -> Option<BigUint> {
+            let magnitude = exponent.magnitude();
+            if exponent.sign() != Sign::Minus {
+                return Some(self.pow(magnitude));
+            }
+            let g = BigInt::from(self.generator.clone());
+            let n = BigInt::from(self.modulus.clone());
+            let egcd = g.extended_gcd(&n);
+            if egcd.gcd != BigInt::from(1u32) {
+                return None;
+            }
+            let inverse = egcd.x.mod_floor(&n).to_biguint()?;
+            Some(inverse.modpow(magnitude, &self.modulus))
+        }
+    }
+
+    fn is_probable_prime(n: &BigUint) -> bool {
+        let one = BigUint::from(1u32);
+        let two = BigUint::from(2u32);
+        if *n < two {
+            return false;
+        }
+        for &p in &MR_BASES {
+            let p = BigUint::from(p);
+            if *n == p {
+                return true;
+            }
+            if (n % &p).bits() == 0 {
+                return false;
+            }
+        }
+        let n_minus_one = n - &one;
+        let s = n_minus_one.trailing_zeros().unwrap_or(0);
+        let d = &n_minus_one >> s;
+        MR_BASES.iter().all(|&a| {
+            let mut x = BigUint::from(a).modpow(&d, n);
+            if x == one || x == n_minus_one {
+                return true;
+            }
+            for _ in 1..s {
+                x = x.modpow(&two, n);
+                if x == n_minus_one {
+                    return true;
+                }
+            }
+            false
+        })
+    }
+
+    impl NodeAccumulator for RsaAccumulator {
+        type Value = BigUint;
+        type Witness = RsaWitness;
+        type NonMembershipWitness = RsaNonMembershipWitness;
+
+        fn commit(&self, keys: &Set<String>) -> BigUint {
+            self.pow(&Self::product(keys.iter()))
+        }
+
+        fn prove_membership(&self, keys: &Set<String>, key: &str) -> Option<RsaWitness> {
+            if !keys.contains(&key.to_string()) {
+                return None;
+            }
+            let others = Self::product(keys.iter().filter(|k| k.as_str() != key));
+            Some(RsaWitness {
+                value: self.pow(&others),
+            })
+        }
+
+        fn verify_membership(&self, acc: &BigUint, key: &str, witness: &RsaWitness) -> bool {
+            witness.value < self.modulus
+                && witness
+                    .value
+                    .modpow(&Self::hash_to_prime(key), &self.modulus)
+                    == *acc
+        }
+
+        fn prove_non_membership(
+            &self,
+            keys: &Set<String>,
+            key: &str,
+        ) -> Option<RsaNonMembershipWitness> {
+            let x = BigInt::from(Self::hash_to_prime(key));
+            let u = BigInt::from(Self::product(keys.iter()));
+            let egcd = u.extended_gcd(&x);
+            if egcd.gcd != BigInt::from(1u32) {
+                return None;
+            }
+            // Shift to 0 <= a < x, moving the sign onto b
+            let a = egcd.x.mod_floor(&x);
+            let b = (BigInt::from(1u32) - &a * &u) / &x;
+            Some(RsaNonMembershipWitness {
+                a: a.to_biguint()?,
+                g_b: self.pow_signed(&b)?,
+            })
+        }
+
+        fn verify_non_membership(
+            &self,
+            acc: &BigUint,
+            key: &str,
+            witness: &RsaNonMembershipWitness,
+        ) -> bool {
+            let x = Self::hash_to_prime(key);
+            if witness.g_b >= self.modulus {
+                return false;
+            }
+            let lhs = acc.modpow(&witness.a, &self.modulus) * witness.g_b.modpow(&x, &self.modulus)
+                % &self.modulus;
+            lhs == self.generator
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(ks: &[&str]) -> Set<String> {
+        Set::from_vec(ks.iter().map(|k| k.to_string()).collect())
+    }
+
+    fn check_non_membership<A: NodeAccumulator>(backend: &A) {
+        let set = keys(&["a", "b", "c"]);
+        let acc = backend.commit(&set);
+        assert_ne!(acc, backend.commit(&keys(&["a", "b"])));
+        assert!(backend.prove_non_membership(&set, "b").is_none());
+        let absent = backend.prove_non_membership(&set, "z").unwrap();
+        assert!(backend.verify_non_membership(&acc, "z", &absent));
+        assert!(!backend.verify_non_membership(&acc, "y", &absent));
+        let grown = backend.commit(&keys(&["a", "b", "c", "z"]));
+        assert!(!backend.verify_non_membership(&grown, "z", &absent));
+    }
+
+    #[test]
+    fn test_pairing_backend_non_membership() {
+        crate::setup::init_test_parameters();
+        check_non_membership(&PairingAccumulator);
+        assert!(
+            PairingAccumulator
+                .prove_membership(&keys(&["a"]), "b")
+                .is_none()
+        );
+    }
+
+    #[cfg(feature = "rsa-backend")]
+    #[test]
+    fn test_rsa_backend() {
+        let rsa = RsaAccumulator::default();
+        check_non_membership(&rsa);
+
+        let set = keys(&["a", "b", "c"]);
+        let acc = rsa.commit(&set);
+        let witness = rsa.prove_membership(&set, "b").unwrap();
+        assert!(rsa.verify_membership(&acc, "b", &witness));
+        assert!(!rsa.verify_membership(&acc, "a", &witness));
+        assert!(rsa.prove_membership(&set, "z").is_none());
+
+        let empty = keys(&[]);
+        let nm = rsa.prove_non_membership(&empty, "a").unwrap();
+        assert!(rsa.verify_non_membership(&rsa.commit(&empty), "a", &nm));
+    }
+}
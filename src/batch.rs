@@ -0,0 +1,36 @@
+//! Report of a batch applied with `AccumulatorTree::apply_ops`.
+//!
+//! Applying mixed inserts, updates and deletes one by one rehashes a leaf's
+//! path on every operation and recomputes accumulators per merge. A batch
+//! instead replays all operations on each key's leaf state first, so several
+//! changes to one leaf collapse into one, then writes the final states root by
+//! root in path order and recomputes every affected ancestor once.
+
+use crate::epoch::RootEntry;
+use crate::txn::TxnOp;
+
+/// How a root that existed before the batch changed in place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootTransition {
+    /// Position of the root in the forest before the batch
+    pub index: usize,
+    pub before: RootEntry,
+    pub after: RootEntry,
+}
+
+/// Outcome of `AccumulatorTree::apply_ops`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchReport {
+    /// Operations that changed some leaf state when replayed in order
+    pub applied: usize,
+    /// Operations that did not apply (missing FID, revive policy, ...)
+    pub rejected: Vec<TxnOp>,
+    /// Keys whose leaf ends up different, in first-mentioned order
+    pub changed_keys: Vec<String>,
+    /// Existing roots rewritten in place, before new keys were merged in
+    pub transitions: Vec<RootTransition>,
+    /// Leaves created for keys that had none
+    pub new_leaves: usize,
+    /// Roots of the forest after the batch
+    pub roots_after: Vec<RootEntry>,
+}
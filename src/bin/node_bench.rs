@@ -0,0 +1,275 @@
+//! Node ownership benchmark.
+//!
+//! Builds a perfect binary tree of `--leaves` leaves, walks to pseudo-random
+//! leaf positions, and rewrites pseudo-random leaves (rehashing their paths).
+//! It does this for four node layouts: boxed children (what `Node` uses),
+//! `Rc` and `Arc` children, and the generational-index `NodeArena`. Only the
+//! Merkle structure is exercised, so no public parameters are needed.
+//!
+//! Build and run with:
+//! `cargo run --release --features bench --bin node_bench -- --leaves 65536 --ops 100000`
+
+use accumulator_ads::Set;
+use accumulator_tree::arena::NodeArena;
+use accumulator_tree::{Hash, leaf_hash, nonleaf_hash};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+struct Config {
+    leaves: u64,
+    ops: u64,
+}
+
+impl Config {
+    fn from_args() -> Result<Self, String> {
+        let mut config = Config {
+            leaves: 1 << 16,
+            ops: 100_000,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(flag) = args.next() {
+            let value: u64 = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", flag))?
+                .parse()
+                .map_err(|e| format!("invalid value for {}: {}", flag, e))?;
+            match flag.as_str() {
+                "--leaves" => config.leaves = value.max(1).next_power_of_two(),
+                "--ops" => config.ops = value,
+                _ => return Err(format!("unknown flag {}", flag)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// xorshift64*: deterministic, dependency-free positions.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+fn leaf_fids(i: u64, version: u64) -> Set<String> {
+    Set::from_vec(vec![format!("fid{}-{}", i, version)])
+}
+
+/// Build, query and mutate operations every pointer-based layout provides.
+trait Layout: Sized {
+    const NAME: &'static str;
+    fn leaf(key: String, fids: Set<String>) -> Self;
+    fn join(left: Self, right: Self) -> Self;
+    fn hash(&self) -> Hash;
+    fn leaf_key(&self, pos: u64, level: u32) -> &str;
+    fn set_fids(&mut self, pos: u64, level: u32, fids: Set<String>);
+}
+
+/// Pointer-based node generic over how children are owned.
+macro_rules! pointer_layout {
+    ($name:ident, $ptr:ident, $label:expr, $get_mut:path) => {
+        enum $name {
+            Leaf {
+                key: String,
+                fids: Set<String>,
+            },
+            NonLeaf {
+                hash: Hash,
+                left: $ptr<$name>,
+                right: $ptr<$name>,
+            },
+        }
+
+        impl Layout for $name {
+            const NAME: &'static str = $label;
+
+            fn leaf(key: String, fids: Set<String>) -> Self {
+                $name::Leaf { key, fids }
+            }
+
+            fn join(left: Self, right: Self) -> Self {
+                $name::NonLeaf {
+                    hash: nonleaf_hash(left.hash(), right.hash()),
+                    left: $ptr::new(left),
+                    right: $ptr::new(right),
+                }
+            }
+
+            fn hash(&self) -> Hash {
+                match self {
+                    $name::Leaf { key, fids } => leaf_hash(key, fids, 0, false),
+                    $name::NonLeaf { hash, .. } => *hash,
+                }
+            }
+
+            fn leaf_key(&self, pos: u64, level: u32) -> &str {
+                match self {
+                    $name::Leaf { key, .. } => key,
+                    $name::NonLeaf { left, right, .. } => {
+                        let half = 1u64 << (level - 1);
+                        if pos < half {
+                            left.leaf_key(pos, level - 1)
+                        } else {
+                            right.leaf_key(pos - half, level - 1)
+                        }
+                    }
+                }
+            }
+
+            fn set_fids(&mut self, pos: u64, level: u32, new_fids: Set<String>) {
+                match self {
+                    $name::Leaf { fids, .. } => *fids = new_fids,
+                    $name::NonLeaf { hash, left, right } => {
+                        let half = 1u64 << (level - 1);
+                        if pos < half {
+                            $get_mut(left).unwrap().set_fids(pos, level - 1, new_fids);
+                        } else {
+                            $get_mut(right)
+                                .unwrap()
+                                .set_fids(pos - half, level - 1, new_fids);
+                        }
+                        *hash = nonleaf_hash(left.hash(), right.hash());
+                    }
+                }
+            }
+        }
+    };
+}
+
+fn box_get_mut<T>(b: &mut Box<T>) -> Option<&mut T> {
+    Some(b)
+}
+
+pointer_layout!(BoxNode, Box, "box", box_get_mut);
+pointer_layout!(RcNode, Rc, "rc", Rc::get_mut);
+pointer_layout!(ArcNode, Arc, "arc", Arc::get_mut);
+
+struct Timings {
+    build: Duration,
+    query: Duration,
+    mutate: Duration,
+    root: Hash,
+}
+
+fn run_pointer<L: Layout>(config: &Config) -> Timings {
+    let level = config.leaves.trailing_zeros();
+
+    let start = Instant::now();
+    let mut nodes: Vec<L> = (0..config.leaves)
+        .map(|i| L::leaf(format!("key{}", i), leaf_fids(i, 0)))
+        .collect();
+    while nodes.len() > 1 {
+        let mut next = Vec::with_capacity(nodes.len() / 2);
+        let mut it = nodes.into_iter();
+        while let (Some(l), Some(r)) = (it.next(), it.next()) {
+            next.push(L::join(l, r));
+        }
+        nodes = next;
+    }
+    let mut root = nodes.pop().unwrap();
+    let build = start.elapsed();
+
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let start = Instant::now();
+    for _ in 0..config.ops {
+        std::hint::black_box(root.leaf_key(rng.below(config.leaves), level));
+    }
+    let query = start.elapsed();
+
+    let start = Instant::now();
+    for v in 1..=config.ops {
+        let pos = rng.below(config.leaves);
+        root.set_fids(pos, level, leaf_fids(pos, v));
+    }
+    let mutate = start.elapsed();
+
+    Timings {
+        build,
+        query,
+        mutate,
+        root: root.hash(),
+    }
+}
+
+fn run_arena(config: &Config) -> Timings {
+    let mut arena = NodeArena::new();
+
+    let start = Instant::now();
+    let mut nodes: Vec<_> = (0..config.leaves)
+        .map(|i| arena.insert_leaf(format!("key{}", i), leaf_fids(i, 0)))
+        .collect();
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks(2)
+            .map(|pair| arena.merge(pair[0], pair[1]).unwrap())
+            .collect();
+    }
+    let root = nodes[0];
+    let build = start.elapsed();
+
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let start = Instant::now();
+    for _ in 0..config.ops {
+        std::hint::black_box(arena.leaf_at(root, rng.below(config.leaves)).unwrap());
+    }
+    let query = start.elapsed();
+
+    let start = Instant::now();
+    for v in 1..=config.ops {
+        let pos = rng.below(config.leaves);
+        arena.set_fids(root, pos, leaf_fids(pos, v)).unwrap();
+    }
+    let mutate = start.elapsed();
+
+    Timings {
+        build,
+        query,
+        mutate,
+        root: arena.hash(root).unwrap(),
+    }
+}
+
+fn main() {
+    let config = match Config::from_args() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("node_bench: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let runs = [
+        (BoxNode::NAME, run_pointer::<BoxNode>(&config)),
+        (RcNode::NAME, run_pointer::<RcNode>(&config)),
+        (ArcNode::NAME, run_pointer::<ArcNode>(&config)),
+        ("arena", run_arena(&config)),
+    ];
+    // Same operations, so every layout must end at the same root
+    let expected = runs[0].1.root;
+    for (name, t) in &runs {
+        if t.root != expected {
+            eprintln!("node_bench: {} produced a different root", name);
+            std::process::exit(1);
+        }
+        println!(
+            "layout={} leaves={} ops={} build={:?} query={:?} mutate={:?} query_per_op={:?} mutate_per_op={:?}",
+            name,
+            config.leaves,
+            config.ops,
+            t.build,
+            t.query,
+            t.mutate,
+            t.query / config.ops.max(1) as u32,
+            t.mutate / config.ops.max(1) as u32,
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Long-running soak test for `AccumulatorTree`.
+//!
+//! Runs a large number of mixed insert/update/delete/select operations over a
+//! bounded key space and periodically reports resident memory, root count,
+//! tombstone ratio and proof latency percentiles. Memory that keeps growing
+//! while the live key count is flat points at `Rc` cycles, unbounded caches or
+//! tombstone accumulation.
+//!
+//! Build and run with:
+//! `cargo run --release --features soak --bin soak -- --params params.bin --ops 1000000 --keys 256`
+//!
+//! Debug builds may omit `--params` and generate throwaway test parameters.
+
+use accumulator_tree::AccumulatorTree;
+use std::time::{Duration, Instant};
+
+struct Config {
+    ops: u64,
+    keys: u64,
+    report_every: u64,
+    proof_every: u64,
+    params: Option<String>,
+}
+
+impl Config {
+    fn from_args() -> Result<Self, String> {
+        let mut config = Config {
+            ops: 1_000_000,
+            keys: 256,
+            report_every: 100_000,
+            proof_every: 100,
+            params: None,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", flag))?;
+            if flag == "--params" {
+                config.params = Some(value);
+                continue;
+            }
+            let value: u64 = value
+                .parse()
+                .map_err(|e| format!("invalid value for {}: {}", flag, e))?;
+            match flag.as_str() {
+                "--ops" => config.ops = value,
+                "--keys" => config.keys = value.max(1),
+                "--report-every" => config.report_every = value.max(1),
+                "--proof-every" => config.proof_every = value.max(1),
+                _ => return Err(format!("unknown flag {}", flag)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// xorshift64*: deterministic, dependency-free operation mix.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// Resident set size in KiB (Linux only; `None` elsewhere).
+fn rss_kib() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4)
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let idx = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[idx]
+}
+
+fn report(op: u64, tree: &AccumulatorTree, latencies: &mut Vec<Duration>, baseline_rss: u64) {
+    let roots = tree.roots_info();
+    let live: u64 = roots.iter().map(|r| r.live_keys).sum();
+    let dead: u64 = roots.iter().map(|r| r.tombstones).sum();
+    let tombstone_ratio = if live + dead == 0 {
+        0.0
+    } else {
+        dead as f64 / (live + dead) as f64
+    };
+    latencies.sort();
+    let rss = rss_kib().unwrap_or(0);
+    println!(
+        "ops={} rss_kib={} rss_growth_kib={} roots={} live={} tombstones={} tombstone_ratio={:.3} \
+         proof_p50={:?} proof_p99={:?} proof_max={:?}",
+        op,
+        rss,
+        rss as i64 - baseline_rss as i64,
+        roots.len(),
+        live,
+        dead,
+        tombstone_ratio,
+        percentile(latencies, 0.50),
+        percentile(latencies, 0.99),
+        latencies.last().copied().unwrap_or_default(),
+    );
+    latencies.clear();
+}
+
+/// Load parameters from `--params`, or generate test parameters in debug builds.
+fn init_params(config: &Config) -> Result<(), String> {
+    match &config.params {
+        Some(path) => accumulator_tree::init(path),
+        None => generate_params(config.keys as usize),
+    }
+}
+
+#[cfg(feature = "insecure-test-params")]
+fn generate_params(max_degree: usize) -> Result<(), String> {
+    use accumulator_ads::Fr;
+    use accumulator_ads::acc::setup::{PublicParameters, init_public_parameters_direct};
+
+    let params = PublicParameters::generate_for_testing(Fr::from(123456789u128), max_degree);
+    init_public_parameters_direct(params).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "insecure-test-params"))]
+fn generate_params(_max_degree: usize) -> Result<(), String> {
+    Err("without the insecure-test-params feature, --params <file> is required".to_string())
+}
+
+fn main() {
+    let config = match Config::from_args() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("soak: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = init_params(&config) {
+        eprintln!("soak: {}", e);
+        std::process::exit(2);
+    }
+
+    let mut tree = AccumulatorTree::new();
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let mut latencies = Vec::new();
+    let baseline_rss = rss_kib().unwrap_or(0);
+
+    for op in 1..=config.ops {
+        let key = format!("key{}", rng.below(config.keys));
+        let fid = format!("fid{}", rng.below(4));
+        match rng.below(10) {
+            0..=3 => {
+                tree.insert(key, fid);
+            }
+            4..=5 => {
+                tree.update(&key, &fid, format!("fid{}", rng.below(4)));
+            }
+            6..=7 => {
+                tree.delete(&key, &fid);
+            }
+            _ => {
+                if op % config.proof_every == 0 {
+                    let start = Instant::now();
+                    let response = match tree.select_with_proof(&key) {
+                        Ok(response) => response,
+                        Err(e) => {
+                            eprintln!("soak: proof of {}: {}", key, e);
+                            std::process::exit(1);
+                        }
+                    };
+                    latencies.push(start.elapsed());
+                    std::hint::black_box(response);
+                } else {
+                    std::hint::black_box(tree.select(&key));
+                }
+            }
+        }
+
+        if op % config.report_every == 0 || op == config.ops {
+            report(op, &tree, &mut latencies, baseline_rss);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Signed provisioning bundle for offline verifiers.
+//!
+//! An air-gapped verifier needs three things before it can check anything: a
+//! commitment to start from, the public parameters proofs are checked against,
+//! and a way to know both came from the operator. `VerifierBundle` packs the
+//! epoch commitment, a digest of the full public parameters, the handful of
+//! points verification actually reads (generators and first powers in both
+//! groups) and the operator's Ed25519 public key into one signed file.
+//! `VerifierBundle` needs the `signed-roots` feature; `VerifierParams` does
+//! not.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+#[cfg(feature = "signed-roots")]
+use crate::epoch::EpochCommitment;
+use crate::wire::{CanonicalDecode, WireReader};
+use accumulator_ads::G1Affine;
+use accumulator_ads::acc::setup::PublicParameters;
+use ark_bls12_381::G2Affine;
+#[cfg(feature = "signed-roots")]
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+/// Leading bytes of an exported bundle.
+#[cfg(feature = "signed-roots")]
+pub const BUNDLE_MAGIC: &[u8; 4] = b"ACVB";
+
+/// Version written by `export`.
+#[cfg(feature = "signed-roots")]
+pub const BUNDLE_VERSION: u16 = 1;
+
+#[cfg(feature = "signed-roots")]
+const BUNDLE_DOMAIN: &[u8] = b"acc-tree/verifier-bundle/v1";
+
+/// SHA-256 over the serialized public parameters, identifying the setup a
+/// bundle was cut from (`PublicParameters::digest`).
+pub fn parameter_digest(params: &PublicParameters) -> Hash {
+    params.digest()
+}
+
+/// The part of the public parameters verification reads: pairing checks only
+/// use the generators and `s^1` in each group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifierParams {
+    pub g1: G1Affine,
+    pub g2: G2Affine,
+    pub g1_s: G1Affine,
+    pub g2_s: G2Affine,
+}
+
+impl VerifierParams {
+    pub fn from_public_parameters(params: &PublicParameters) -> Result<Self, String> {
+        if params.max_degree() < 1 {
+            return Err("public parameters have no s^1 powers".to_string());
+        }
+        Ok(Self {
+            g1: params.g1,
+            g2: params.g2,
+            g1_s: params.g1_s_vec[1],
+            g2_s: params.g2_s_vec[1],
+        })
+    }
+
+    /// Degree-1 parameters a verifier can install with
+    /// `init_public_parameters_direct`. They cannot build accumulators.
+    pub fn to_public_parameters(&self) -> PublicParameters {
+        PublicParameters {
+            g1: self.g1,
+            g2: self.g2,
+            g1_s_vec: vec![self.g1, self.g1_s],
+            g2_s_vec: vec![self.g2, self.g2_s],
+        }
+    }
+}
+
+impl CanonicalEncode for VerifierParams {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.g1.encode_canonical(out);
+        self.g2.encode_canonical(out);
+        self.g1_s.encode_canonical(out);
+        self.g2_s.encode_canonical(out);
+    }
+}
+
+impl CanonicalDecode for VerifierParams {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Self {
+            g1: CanonicalDecode::decode_canonical(r)?,
+            g2: CanonicalDecode::decode_canonical(r)?,
+            g1_s: CanonicalDecode::decode_canonical(r)?,
+            g2_s: CanonicalDecode::decode_canonical(r)?,
+        })
+    }
+}
+
+/// Everything an offline verifier is provisioned with, signed by the operator.
+#[cfg(feature = "signed-roots")]
+#[derive(Debug, Clone)]
+pub struct VerifierBundle {
+    pub commitment: EpochCommitment,
+    pub signer: VerifyingKey,
+    /// `parameter_digest` of the full parameters `params` were taken from
+    pub params_digest: Hash,
+    pub params: VerifierParams,
+    pub signature: Signature,
+}
+
+#[cfg(feature = "signed-roots")]
+impl VerifierBundle {
+    /// Build and sign a bundle for `commitment` under `params`.
+    pub fn new(
+        commitment: EpochCommitment,
+        params: &PublicParameters,
+        key: &SigningKey,
+    ) -> Result<Self, String> {
+        let mut bundle = Self {
+            commitment,
+            signer: key.verifying_key(),
+            params_digest: parameter_digest(params),
+            params: VerifierParams::from_public_parameters(params)?,
+            signature: Signature::from_bytes(&[0u8; 64]),
+        };
+        bundle.signature = key.sign(&bundle.signed_bytes());
+        Ok(bundle)
+    }
+
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        self.commitment.encode_canonical(out);
+        out.extend_from_slice(self.signer.as_bytes());
+        self.params_digest.encode_canonical(out);
+        self.params.encode_canonical(out);
+    }
+
+    /// Message the signature covers.
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = BUNDLE_DOMAIN.to_vec();
+        out.extend_from_slice(&BUNDLE_VERSION.to_be_bytes());
+        self.encode_body(&mut out);
+        out
+    }
+
+    /// Check the signature against the key embedded in the bundle.
+    pub fn verify_signature(&self) -> Result<(), String> {
+        self.signer
+            .verify(&self.signed_bytes(), &self.signature)
+            .map_err(|e| format!("bundle signature: {}", e))
+    }
+
+    /// Serialize as `magic || version || body || signature`.
+    pub fn export(&self) -> Vec<u8> {
+        let mut out = BUNDLE_MAGIC.to_vec();
+        out.extend_from_slice(&BUNDLE_VERSION.to_be_bytes());
+        self.encode_body(&mut out);
+        out.extend_from_slice(&self.signature.to_bytes());
+        out
+    }
+
+    /// Parse an exported bundle and accept it only if it is signed by `signer`,
+    /// the operator key the verifier was given out of band.
+    pub fn import(bytes: &[u8], signer: &VerifyingKey) -> Result<Self, String> {
+        let mut r = WireReader::new(bytes);
+        if r.take(BUNDLE_MAGIC.len())? != BUNDLE_MAGIC {
+            return Err("not a verifier bundle".to_string());
+        }
+        let version = u16::decode_canonical(&mut r)?;
+        if version != BUNDLE_VERSION {
+            return Err(format!("unsupported bundle version {}", version));
+        }
+        let commitment = EpochCommitment::decode_canonical(&mut r)?;
+        let key: [u8; 32] = r.take(32)?.try_into().unwrap();
+        let embedded = VerifyingKey::from_bytes(&key).map_err(|e| format!("signer key: {}", e))?;
+        let params_digest = Hash::decode_canonical(&mut r)?;
+        let params = VerifierParams::decode_canonical(&mut r)?;
+        let signature = Signature::from_bytes(&r.take(64)?.try_into().unwrap());
+        r.finish()?;
+
+        if embedded != *signer {
+            return Err("bundle is signed by an untrusted key".to_string());
+        }
+        let bundle = Self {
+            commitment,
+            signer: embedded,
+            params_digest,
+            params,
+            signature,
+        };
+        bundle.verify_signature()?;
+        Ok(bundle)
+    }
+}
+
+#[cfg(all(test, feature = "signed-roots"))]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for k in ["a", "b", "c"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        let commitment = tree.publish_epoch();
+        let params = accumulator_ads::get_public_parameters().unwrap();
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+
+        let bundle = VerifierBundle::new(commitment.clone(), &params, &key).unwrap();
+        let bytes = bundle.export();
+        let imported = VerifierBundle::import(&bytes, &key.verifying_key()).unwrap();
+        assert_eq!(imported.commitment, commitment);
+        assert_eq!(imported.params_digest, parameter_digest(&params));
+        assert_eq!(imported.params, bundle.params);
+
+        let tiny = imported.params.to_public_parameters();
+        assert!(tiny.validate().is_ok());
+        assert_eq!(tiny.max_degree(), 1);
+        assert_eq!(tiny.g2_s_vec[1], params.g2_s_vec[1]);
+    }
+
+    #[test]
+    fn test_bundle_rejects_tampering() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        let params = accumulator_ads::get_public_parameters().unwrap();
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let bytes = VerifierBundle::new(tree.publish_epoch(), &params, &key)
+            .unwrap()
+            .export();
+
+        // Another operator's key
+        let other = SigningKey::from_bytes(&[8u8; 32]).verifying_key();
+        assert!(VerifierBundle::import(&bytes, &other).is_err());
+
+        // Flipped epoch number
+        let mut tampered = bytes.clone();
+        tampered[BUNDLE_MAGIC.len() + 2 + 7] ^= 1;
+        assert!(VerifierBundle::import(&tampered, &key.verifying_key()).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(VerifierBundle::import(&trailing, &key.verifying_key()).is_err());
+        assert!(VerifierBundle::import(&bytes[..bytes.len() - 1], &key.verifying_key()).is_err());
+    }
+}
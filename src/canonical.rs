@@ -0,0 +1,255 @@
+//! Canonical byte encodings for proofs and responses.
+//!
+//! Two proofs that convince a verifier of the same statement must encode to the
+//! same bytes: FID sets are written in sorted order and curve points use their
+//! compressed form (so every representation of the identity collapses). Equality
+//! and hashing of proof/response types are defined on these bytes, which lets
+//! caches and dedup layers treat equivalent proofs as equal.
+
+use crate::Hash;
+use accumulator_ads::{Fr, G1Affine, G2Affine, Set};
+use ark_ec::models::SWModelParameters;
+use ark_ec::short_weierstrass_jacobian::GroupAffine;
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_serialize::CanonicalSerialize;
+use sha2::{Digest, Sha256};
+
+/// Serde for `Option<G1Affine>` fields, with points in the same compressed
+/// form as `accumulator_ads::acc::serde_impl` (and subgroup-checked on the way
+/// in).
+pub(crate) mod serde_opt_g1 {
+    use accumulator_ads::G1Affine;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Point(#[serde(with = "accumulator_ads::acc::serde_impl")] G1Affine);
+
+    pub(crate) fn serialize<S: Serializer>(p: &Option<G1Affine>, s: S) -> Result<S::Ok, S::Error> {
+        p.map(Point).serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<G1Affine>, D::Error> {
+        Ok(Option::<Point>::deserialize(d)?.map(|p| p.0))
+    }
+}
+
+/// Types with a single, construction-independent byte encoding.
+pub trait CanonicalEncode {
+    /// Append the canonical encoding of `self` to `out`.
+    fn encode_canonical(&self, out: &mut Vec<u8>);
+
+    /// Canonical encoding as an owned buffer.
+    fn canonical_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_canonical(&mut out);
+        out
+    }
+
+    /// SHA-256 of the canonical encoding, suitable as a cache or dedup key.
+    fn canonical_digest(&self) -> Hash {
+        Sha256::digest(self.canonical_bytes()).into()
+    }
+}
+
+/// Normalize a G1 point so every encoding of the same group element compares equal.
+pub(crate) fn normalize_g1(p: G1Affine) -> G1Affine {
+    p.into_projective().into_affine()
+}
+
+/// Normalize a G2 point so every encoding of the same group element compares equal.
+pub(crate) fn normalize_g2(p: G2Affine) -> G2Affine {
+    p.into_projective().into_affine()
+}
+
+impl CanonicalEncode for bool {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+/// Fixed-width big-endian integers.
+macro_rules! impl_canonical_for_int {
+    ($($t:ty),*) => {$(
+        impl CanonicalEncode for $t {
+            fn encode_canonical(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+        }
+    )*};
+}
+
+impl_canonical_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl CanonicalEncode for Hash {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+/// Length-prefixed, like a `Vec<u8>`.
+impl CanonicalEncode for [u8] {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.len() as u32).to_be_bytes());
+        out.extend_from_slice(self);
+    }
+}
+
+impl CanonicalEncode for str {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.len() as u32).to_be_bytes());
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl CanonicalEncode for String {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.as_str().encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for Set<String> {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        let mut items: Vec<&String> = self.iter().collect();
+        items.sort();
+        out.extend_from_slice(&(items.len() as u32).to_be_bytes());
+        for item in items {
+            item.encode_canonical(out);
+        }
+    }
+}
+
+impl CanonicalEncode for Fr {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.serialize(&mut *out)
+            .expect("serializing into a Vec cannot fail");
+    }
+}
+
+/// Covers both `G1Affine` and `G2Affine` (compressed encoding).
+impl<P: SWModelParameters> CanonicalEncode for GroupAffine<P> {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.serialize(&mut *out)
+            .expect("serializing into a Vec cannot fail");
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Option<T> {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            Some(v) => {
+                out.push(1);
+                v.encode_canonical(out);
+            }
+            None => out.push(0),
+        }
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Box<T> {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.as_ref().encode_canonical(out);
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for std::sync::Arc<T> {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.as_ref().encode_canonical(out);
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Vec<T> {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.len() as u32).to_be_bytes());
+        for item in self {
+            item.encode_canonical(out);
+        }
+    }
+}
+
+impl<A: CanonicalEncode, B: CanonicalEncode> CanonicalEncode for (A, B) {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.0.encode_canonical(out);
+        self.1.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for accumulator_ads::NonMembershipProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.element.encode_canonical(out);
+        self.witness.encode_canonical(out);
+        self.g2_a.encode_canonical(out);
+    }
+}
+
+/// Implement `PartialEq`, `Eq` and `Hash` in terms of `CanonicalEncode`.
+macro_rules! impl_canonical_eq {
+    ($($t:ty),*) => {$(
+        impl PartialEq for $t {
+            fn eq(&self, other: &Self) -> bool {
+                use $crate::canonical::CanonicalEncode;
+                self.canonical_bytes() == other.canonical_bytes()
+            }
+        }
+
+        impl Eq for $t {}
+
+        impl std::hash::Hash for $t {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                use $crate::canonical::CanonicalEncode;
+                self.canonical_bytes().hash(state);
+            }
+        }
+    )*};
+}
+
+pub(crate) use impl_canonical_eq;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acc_proof::{AccProof, MembershipProof};
+    use crate::merkle_proof::Proof;
+    use crate::response::QueryResponse;
+    use crate::utils::leaf_hash;
+    use ark_ff::Zero;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_set_encoding_is_order_independent() {
+        let a = Set::from_vec(vec!["x".to_string(), "y".to_string(), "z".to_string()]);
+        let b = Set::from_vec(vec!["z".to_string(), "x".to_string(), "y".to_string()]);
+        assert_eq!(a.canonical_bytes(), b.canonical_bytes());
+    }
+
+    #[test]
+    fn test_identity_points_collapse() {
+        let zero = G1Affine::zero();
+        let odd_zero = G1Affine::new(zero.x, zero.y + zero.y, true);
+        assert_ne!(zero, odd_zero);
+        assert_eq!(zero.canonical_bytes(), odd_zero.canonical_bytes());
+        assert_eq!(normalize_g1(odd_zero), zero);
+    }
+
+    #[test]
+    fn test_equivalent_responses_dedup() {
+        let fids_a = Set::from_vec(vec!["f1".to_string(), "f2".to_string()]);
+        let fids_b = Set::from_vec(vec!["f2".to_string(), "f1".to_string()]);
+        let leaf = leaf_hash("k", &fids_a, 0, false);
+        let witness = G1Affine::prime_subgroup_generator();
+
+        let build = |fids: Set<String>| {
+            QueryResponse::new(
+                Some(fids),
+                Some(Proof::new(leaf, leaf, vec![])),
+                Some(witness),
+                Some(AccProof::Membership(MembershipProof { witness })),
+            )
+        };
+
+        let mut seen = HashSet::new();
+        assert!(seen.insert(build(fids_a)));
+        assert!(!seen.insert(build(fids_b)));
+    }
+}
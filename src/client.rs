@@ -0,0 +1,427 @@
+//! Verifier-side SDK (feature `client`).
+//!
+//! Everything an application needs to check answers from an untrusted
+//! server, and nothing that builds them: `CommitmentTracker` follows the
+//! published epochs from a trusted starting point, verifies point queries and
+//! non-membership proofs against them, keeps membership witnesses for the
+//! global accumulator up to date, and enforces a `FreshnessPolicy`. Nothing
+//! here touches the forest or the parallel batch checks in `validate`, so
+//! `--no-default-features --features client` builds it without rayon.
+
+use crate::acc_proof::{AccProof, MembershipProof, NonMembershipProof, verify_membership};
+use crate::clock::{Clock, elapsed, system_clock};
+use crate::epoch::{
+    EpochCommitment, EpochTransition, RootEntry, check_proof_depth, forest_hash, genesis_commitment,
+};
+use crate::response::QueryResponse;
+use accumulator_ads::{G1Affine, Set};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// How stale an answer the client accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FreshnessPolicy {
+    /// Non-membership proofs may be bound to an epoch up to this many epochs
+    /// behind the current one (0: current epoch only)
+    pub max_epoch_lag: u64,
+    /// Longest time the tracker may go without advancing (None: no limit)
+    pub max_age: Option<Duration>,
+}
+
+/// Witness of `key` after `added` joined an accumulator whose value was
+/// `prev_acc` (see `MembershipProof::update_on_add`).
+pub fn witness_after_add(
+    witness: G1Affine,
+    key: &str,
+    prev_acc: G1Affine,
+    added: &str,
+) -> G1Affine {
+    let mut proof = MembershipProof { witness };
+    proof.update_on_add(key, prev_acc, added);
+    proof.witness
+}
+
+/// Witness of `key` after `removed` left the accumulator, whose value is now
+/// `new_acc` (see `MembershipProof::update_on_delete`). None if `removed` is
+/// `key`.
+pub fn witness_after_remove(
+    witness: G1Affine,
+    key: &str,
+    new_acc: G1Affine,
+    removed: &str,
+) -> Option<G1Affine> {
+    let mut proof = MembershipProof { witness };
+    proof.update_on_delete(key, new_acc, removed).ok()?;
+    Some(proof.witness)
+}
+
+/// Trusted view of the published epochs.
+#[derive(Debug, Clone)]
+pub struct CommitmentTracker {
+    policy: FreshnessPolicy,
+    /// Commitments within the lag window, oldest first; the last is current
+    history: VecDeque<EpochCommitment>,
+    /// Roots of the current epoch
+    roots: Vec<RootEntry>,
+    updated_at: SystemTime,
+    clock: Arc<dyn Clock>,
+    /// Membership witnesses against the current global accumulator
+    witnesses: HashMap<String, G1Affine>,
+}
+
+impl CommitmentTracker {
+    /// Start from a commitment obtained out of band (pinned or signed) and
+    /// the roots it commits to.
+    pub fn new(
+        trusted: EpochCommitment,
+        roots: Vec<RootEntry>,
+        policy: FreshnessPolicy,
+    ) -> Result<Self, String> {
+        if forest_hash(&roots) != trusted.forest_hash {
+            return Err("roots do not match the trusted commitment".to_string());
+        }
+        let clock = system_clock();
+        Ok(Self {
+            policy,
+            history: VecDeque::from([trusted]),
+            roots,
+            updated_at: clock.now(),
+            clock,
+            witnesses: HashMap::new(),
+        })
+    }
+
+    /// Start from the empty forest. Needs no out-of-band commitment, but the
+    /// tracker can then only follow a server from its first publication on.
+    pub fn from_genesis(policy: FreshnessPolicy) -> Self {
+        Self::new(genesis_commitment(), Vec::new(), policy)
+            .expect("genesis commits to the empty forest")
+    }
+
+    /// Measure freshness with `clock` instead of the system clock. Restarts
+    /// the freshness window at the clock's current time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.updated_at = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    pub fn policy(&self) -> FreshnessPolicy {
+        self.policy
+    }
+
+    /// Commitment of the current epoch.
+    pub fn current(&self) -> &EpochCommitment {
+        self.history.back().expect("history is never empty")
+    }
+
+    /// Roots of the current epoch.
+    pub fn roots(&self) -> &[RootEntry] {
+        &self.roots
+    }
+
+    /// Trusted commitment of `epoch`, if it is within the lag window.
+    pub fn commitment(&self, epoch: u64) -> Option<&EpochCommitment> {
+        self.history.iter().find(|c| c.epoch == epoch)
+    }
+
+    /// Check the tracker has advanced recently enough at time `now`.
+    pub fn check_age_at(&self, now: SystemTime) -> Result<(), String> {
+        match self.policy.max_age {
+            Some(max) if elapsed(self.updated_at, now) > max => {
+                Err("no epoch received within the freshness window".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Move to the next epoch. Checks:
+    /// 1. The transition starts at the current epoch and ends at the next one
+    /// 2. Its key count is consistent with the added and removed keys
+    /// 3. The transition proof verifies
+    ///
+    /// Tracked witnesses are refreshed when the epoch changed a single key and
+    /// dropped otherwise. Returns the keys whose witnesses must be fetched again.
+    pub fn advance(&mut self, transition: &EpochTransition) -> Result<Vec<String>, String> {
+        let current = self.current();
+        let next = &transition.commitment;
+        let proof = &transition.proof;
+        if next.epoch != current.epoch + 1 {
+            return Err(format!(
+                "expected epoch {}, got {}",
+                current.epoch + 1,
+                next.epoch
+            ));
+        }
+        if proof.pre_forest_hash() != current.forest_hash
+            || proof.pre_global_acc != current.global_acc
+        {
+            return Err("transition does not start at the current epoch".to_string());
+        }
+        if proof.post_forest_hash() != next.forest_hash || proof.post_global_acc != next.global_acc
+        {
+            return Err("transition does not end at the new commitment".to_string());
+        }
+        let expected_count = (current.key_count + proof.added_keys.len() as u64)
+            .checked_sub(proof.removed_keys.len() as u64);
+        if expected_count != Some(next.key_count) {
+            return Err("key count does not match the transition".to_string());
+        }
+        if !proof.verify() {
+            return Err("invalid transition proof".to_string());
+        }
+
+        let prev_acc = current.global_acc;
+        let stale = self.refresh_witnesses(prev_acc, next.global_acc, proof);
+
+        self.roots = proof.post_roots.clone();
+        self.history.push_back(next.clone());
+        while self.history.len() as u64 > self.policy.max_epoch_lag + 1 {
+            self.history.pop_front();
+        }
+        self.updated_at = self.clock.now();
+        Ok(stale)
+    }
+
+    /// Bring tracked witnesses from `prev_acc` to `next_acc`, returning the
+    /// keys that could not be refreshed.
+    fn refresh_witnesses(
+        &mut self,
+        prev_acc: G1Affine,
+        next_acc: G1Affine,
+        proof: &crate::response::TransactionResponse,
+    ) -> Vec<String> {
+        let added: Vec<&String> = proof.added_keys.iter().collect();
+        let removed: Vec<&String> = proof.removed_keys.iter().collect();
+        let mut stale = Vec::new();
+        self.witnesses.retain(|key, witness| {
+            if proof.removed_keys.contains(key) {
+                return false;
+            }
+            let refreshed = match (added.as_slice(), removed.as_slice()) {
+                ([], []) => Some(*witness),
+                ([y], []) => Some(witness_after_add(*witness, key, prev_acc, y)),
+                ([], [y]) => witness_after_remove(*witness, key, next_acc, y),
+                _ => None,
+            };
+            match refreshed {
+                Some(w) if verify_membership(&next_acc, &w, key) => {
+                    *witness = w;
+                    true
+                }
+                _ => {
+                    stale.push(key.clone());
+                    false
+                }
+            }
+        });
+        stale.sort();
+        stale
+    }
+
+    /// Keep `witness` for `key` up to date across epochs. It must prove
+    /// membership in the current global accumulator.
+    pub fn track_witness(&mut self, key: &str, witness: G1Affine) -> Result<(), String> {
+        if !verify_membership(&self.current().global_acc, &witness, key) {
+            return Err(format!(
+                "witness does not prove '{}' in the current epoch",
+                key
+            ));
+        }
+        self.witnesses.insert(key.to_string(), witness);
+        Ok(())
+    }
+
+    /// Current witness of a tracked key.
+    pub fn witness(&self, key: &str) -> Option<G1Affine> {
+        self.witnesses.get(key).copied()
+    }
+
+    /// Check a non-membership proof is bound to a trusted epoch within the
+    /// lag window and holds for `key`.
+    pub fn verify_nonmembership(
+        &self,
+        key: &str,
+        proof: &NonMembershipProof,
+    ) -> Result<(), String> {
+        let epoch = proof
+            .commitment
+            .as_ref()
+            .ok_or_else(|| "non-membership proof is not bound to an epoch".to_string())?
+            .epoch;
+        let trusted = self
+            .commitment(epoch)
+            .ok_or_else(|| format!("epoch {} is outside the freshness window", epoch))?;
+        if !proof.verify_in_epoch(key, &trusted.digest()) {
+            return Err(format!("invalid non-membership proof for '{}'", key));
+        }
+        Ok(())
+    }
+
+    /// Verify a point query against the current epoch. Returns the FIDs of a
+    /// present key, or None for a key proven absent. Membership answers must
+    /// carry a Merkle path: root accumulators are not part of the commitment.
+    pub fn verify_query(
+        &self,
+        key: &str,
+        response: &QueryResponse,
+    ) -> Result<Option<Set<String>>, String> {
+        self.check_age_at(self.clock.now())?;
+        if let Some(AccProof::NonMembership(nm)) = &response.acc_proof {
+            if response.fids.is_some() {
+                return Err("response claims both presence and absence".to_string());
+            }
+            self.verify_nonmembership(key, nm)?;
+            return Ok(None);
+        }
+
+        let (Some(fids), Some(proof)) = (&response.fids, &response.merkle_proof) else {
+            return Err("membership answer without a Merkle path".to_string());
+        };
+        check_proof_depth(&self.roots, proof)?;
+        if !response.verify_by_mode(key) {
+            return Err(format!("invalid proof for '{}'", key));
+        }
+        if response.sibling_key_counts.is_some() {
+            response.check_key_count_hints(self.current().key_count)?;
+        }
+        Ok(Some(fids.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use crate::policy::{ProofPolicy, VerifierCapabilities};
+
+    fn commit(keys: &[&str]) -> G1Affine {
+        let set = Set::from_vec(keys.iter().map(|k| k.to_string()).collect());
+        accumulator_ads::DynamicAccumulator::calculate_commitment(
+            &accumulator_ads::digest_set_from_set(&set),
+        )
+    }
+
+    fn insert(tree: &mut AccumulatorTree, key: &str) -> EpochTransition {
+        tree.stage(crate::TxnOp::Insert {
+            key: key.to_string(),
+            fid: format!("f{}", key),
+        });
+        tree.publish()
+    }
+
+    #[test]
+    fn test_tracker_follows_epochs_and_verifies_queries() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for k in ["a", "b", "c"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        let first = tree.publish_epoch();
+        let policy = FreshnessPolicy {
+            max_epoch_lag: 1,
+            max_age: Some(Duration::from_secs(3600)),
+        };
+        let clock = crate::clock::MockClock::default();
+        let mut client = CommitmentTracker::new(first.clone(), tree.root_entries(), policy)
+            .unwrap()
+            .with_clock(Arc::new(clock.clone()));
+
+        let merkle = VerifierCapabilities {
+            merkle: true,
+            pairing: false,
+        };
+        let response = tree
+            .select_with_policy("b", &mut ProofPolicy::new(merkle))
+            .unwrap();
+        assert_eq!(
+            client.verify_query("b", &response),
+            Ok(Some(Set::from_vec(vec!["fb".to_string()])))
+        );
+        assert!(client.verify_query("a", &response).is_err());
+        let absent = tree.select_with_proof("zzz").unwrap();
+        assert_eq!(client.verify_query("zzz", &absent), Ok(None));
+
+        // Epochs must be followed in order
+        let second = insert(&mut tree, "d");
+        let third = insert(&mut tree, "e");
+        assert!(client.advance(&third).is_err());
+        client.advance(&second).unwrap();
+        client.advance(&third).unwrap();
+        assert_eq!(client.current().epoch, 3);
+        assert!(client.verify_query("zzz", &absent).is_err());
+        let recent = tree.select_with_proof("zzz").unwrap();
+        assert_eq!(client.verify_query("zzz", &recent), Ok(None));
+
+        // A transition ending elsewhere is rejected
+        let mut forged = insert(&mut tree, "f");
+        forged.commitment.key_count += 1;
+        assert!(client.advance(&forged).is_err());
+        clock.advance(Duration::from_secs(3600));
+        assert!(client.check_age_at(clock.now()).is_ok());
+        clock.advance(Duration::from_secs(1));
+        assert!(client.check_age_at(clock.now()).is_err());
+        assert!(client.verify_query("zzz", &recent).is_err());
+    }
+
+    #[test]
+    fn test_witness_refresh() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+        let first = tree.publish_epoch();
+        let mut client =
+            CommitmentTracker::new(first, tree.root_entries(), FreshnessPolicy::default()).unwrap();
+        client.track_witness("a", commit(&["b"])).unwrap();
+        assert!(client.track_witness("b", commit(&["b"])).is_err());
+
+        // Single-key epochs are followed without the server
+        assert!(client.advance(&insert(&mut tree, "c")).unwrap().is_empty());
+        assert_eq!(client.witness("a"), Some(commit(&["b", "c"])));
+        tree.stage(crate::TxnOp::Delete {
+            key: "b".to_string(),
+            fid: "fb".to_string(),
+        });
+        assert!(client.advance(&tree.publish()).unwrap().is_empty());
+        assert_eq!(client.witness("a"), Some(commit(&["c"])));
+
+        // Larger batches need a fresh witness
+        tree.stage(crate::TxnOp::Insert {
+            key: "d".to_string(),
+            fid: "fd".to_string(),
+        });
+        let stale = client.advance(&insert(&mut tree, "e")).unwrap();
+        assert_eq!(stale, vec!["a".to_string()]);
+        assert_eq!(client.witness("a"), None);
+    }
+
+    #[test]
+    fn test_follow_from_genesis() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        let mut client = CommitmentTracker::from_genesis(FreshnessPolicy::default());
+        assert!(client.current().is_genesis());
+        assert!(client.roots().is_empty());
+
+        client.advance(&insert(&mut tree, "a")).unwrap();
+        client.advance(&insert(&mut tree, "b")).unwrap();
+        assert_eq!(client.current(), tree.published_commitment().unwrap());
+        let merkle = VerifierCapabilities {
+            merkle: true,
+            pairing: false,
+        };
+        let response = tree
+            .select_with_policy("a", &mut ProofPolicy::new(merkle))
+            .unwrap();
+        assert!(client.verify_query("a", &response).unwrap().is_some());
+
+        // Mutations made before the first publication leave no chain to follow
+        let mut direct = AccumulatorTree::new();
+        direct.insert("a".to_string(), "fa".to_string());
+        let first = insert(&mut direct, "b");
+        let mut client = CommitmentTracker::from_genesis(FreshnessPolicy::default());
+        assert!(client.advance(&first).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//! Time sources.
+//!
+//! Everything that reads the time — epoch publication, archive expiry and the
+//! client's freshness checks — asks a `Clock` instead of the OS. `SystemClock`
+//! is the default; embedders with a trusted time service plug in their own,
+//! and tests use `MockClock` to step time forward without sleeping.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of wall-clock time.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The operating system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Shared handle to the system clock, the default everywhere a clock is taken.
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// Clock that only moves when told to. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(UNIX_EPOCH)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Time elapsed from `earlier` to `now`; zero if the clock went backwards.
+pub fn elapsed(earlier: SystemTime, now: SystemTime) -> Duration {
+    now.duration_since(earlier).unwrap_or(Duration::ZERO)
+}
+
+/// Milliseconds since the Unix epoch (0 for earlier times), for encodings.
+pub fn unix_millis(t: SystemTime) -> u64 {
+    elapsed(UNIX_EPOCH, t).as_millis() as u64
+}
+
+pub fn from_unix_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_is_shared_and_stepped() {
+        let clock = MockClock::default();
+        let shared: Arc<dyn Clock> = Arc::new(clock.clone());
+        let start = shared.now();
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(elapsed(start, shared.now()), Duration::from_millis(1500));
+        assert_eq!(elapsed(shared.now(), start), Duration::ZERO);
+        assert_eq!(unix_millis(shared.now()), 1500);
+        clock.set(from_unix_millis(42));
+        assert_eq!(unix_millis(shared.now()), 42);
+    }
+}
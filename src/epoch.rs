@@ -0,0 +1,449 @@
+//! Published epoch commitments.
+//!
+//! Publishing an epoch freezes the forest-wide accumulator (over every live key)
+//! together with the root hashes it was computed from. Clients pin the digest of
+//! that commitment; non-membership proofs carry the commitment so a verifier can
+//! check the Bézout proof was produced against the accumulator it trusts rather
+//! than one picked by the server at answer time.
+//!
+//! Every forest starts from the same `genesis_commitment`: epoch 0, no roots,
+//! the empty accumulator. A tree that only changes through `stage`/`publish`
+//! hands out a chain of transitions starting there, so a new client can follow
+//! it from genesis instead of pinning a first commitment out of band.
+
+use crate::Hash;
+use crate::canonical::{CanonicalEncode, impl_canonical_eq};
+use accumulator_ads::{G1Affine, Set};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const EPOCH_DOMAIN: &[u8] = b"acc-tree/epoch-commitment/v1";
+
+/// Epoch number of the genesis commitment; the first publication is epoch 1.
+pub const GENESIS_EPOCH: u64 = 0;
+
+lazy_static::lazy_static! {
+    static ref GENESIS: EpochCommitment = EpochCommitment::new(
+        GENESIS_EPOCH,
+        forest_hash(&[]),
+        crate::utils::empty_acc(),
+        0,
+    );
+}
+
+/// Commitment to the empty forest, the state every tree starts in.
+pub fn genesis_commitment() -> EpochCommitment {
+    GENESIS.clone()
+}
+
+/// Commitment to the forest state at the moment an epoch was published.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochCommitment {
+    /// Monotonic epoch number, starting at 1 for the first publication
+    pub epoch: u64,
+    /// Hash over the ordered list of root hashes
+    pub forest_hash: Hash,
+    /// Accumulator over the union of all live keys in the forest
+    #[serde(with = "accumulator_ads::acc::serde_impl")]
+    pub global_acc: G1Affine,
+    /// Number of live keys accumulated in `global_acc`
+    pub key_count: u64,
+    /// Id of the tree's `OrderPolicy`, which range proofs are checked under
+    pub key_order: u16,
+}
+
+impl EpochCommitment {
+    pub fn new(epoch: u64, forest_hash: Hash, global_acc: G1Affine, key_count: u64) -> Self {
+        Self {
+            epoch,
+            forest_hash,
+            global_acc,
+            key_count,
+            key_order: 0,
+        }
+    }
+
+    /// The same commitment for a tree ordering keys by `OrderPolicy` `id`.
+    pub fn with_key_order(mut self, id: u16) -> Self {
+        self.key_order = id;
+        self
+    }
+
+    pub fn is_genesis(&self) -> bool {
+        *self == *GENESIS
+    }
+
+    /// Digest clients pin (or receive signed) to trust this epoch.
+    pub fn digest(&self) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update(EPOCH_DOMAIN);
+        hasher.update(self.canonical_bytes());
+        hasher.finalize().into()
+    }
+}
+
+impl CanonicalEncode for EpochCommitment {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.epoch.to_be_bytes());
+        self.forest_hash.encode_canonical(out);
+        self.global_acc.encode_canonical(out);
+        out.extend_from_slice(&self.key_count.to_be_bytes());
+        out.extend_from_slice(&self.key_order.to_be_bytes());
+    }
+}
+
+impl_canonical_eq!(EpochCommitment);
+
+impl CanonicalEncode for RootStamp {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.root.encode_canonical(out);
+        self.changed_in.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for RootManifest {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.commitment.encode_canonical(out);
+        self.roots.encode_canonical(out);
+    }
+}
+
+/// Result of `AccumulatorTree::publish`: the new epoch commitment and a proof
+/// that it differs from the previous published state by exactly the staged
+/// operations.
+#[derive(Debug, Clone)]
+pub struct EpochTransition {
+    pub commitment: EpochCommitment,
+    pub proof: crate::response::TransactionResponse,
+    /// One result per registered anchor, in registration order
+    pub receipts: Vec<Result<crate::anchor::AnchorReceipt, String>>,
+}
+
+/// Prover-side state retained for the last published epoch.
+#[derive(Debug, Clone)]
+pub(crate) struct PublishedEpoch {
+    pub(crate) commitment: EpochCommitment,
+    /// Live keys at publication time, needed to build Bézout witnesses
+    pub(crate) keys: Set<String>,
+    /// Roots at publication time with the epoch each last changed in
+    pub(crate) roots: Vec<RootStamp>,
+    /// Clock time of the publication
+    pub(crate) published_at: std::time::SystemTime,
+}
+
+/// A published root and the epoch in which it last changed. A root keeps its
+/// epoch for as long as its hash stays the same across publications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootStamp {
+    pub root: RootEntry,
+    pub changed_in: u64,
+}
+
+/// Per-root view of a published epoch for consumers that only sync some roots.
+#[derive(Debug, Clone)]
+pub struct RootManifest {
+    pub commitment: EpochCommitment,
+    pub roots: Vec<RootStamp>,
+}
+
+impl RootManifest {
+    /// Check the commitment against the trusted digest, that the listed roots
+    /// hash to its forest hash and that no root claims a future epoch.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// let trusted = tree.publish_epoch().digest();
+    /// let bytes = to_wire(&tree.root_manifest().unwrap());
+    /// let mut manifest: RootManifest = from_wire(&bytes).unwrap();
+    /// assert!(manifest.verify(&trusted));
+    ///
+    /// manifest.roots[0].changed_in += 1;
+    /// assert!(!manifest.verify(&trusted));
+    /// ```
+    pub fn verify(&self, trusted: &Hash) -> bool {
+        let entries: Vec<RootEntry> = self.roots.iter().map(|s| s.root).collect();
+        self.commitment.digest() == *trusted
+            && forest_hash(&entries) == self.commitment.forest_hash
+            && self
+                .roots
+                .iter()
+                .all(|s| s.changed_in >= 1 && s.changed_in <= self.commitment.epoch)
+    }
+
+    /// Whether `root` is one of the listed roots, e.g. the root a point
+    /// proof ends at.
+    pub fn has_root(&self, root: &Hash) -> bool {
+        self.roots.iter().any(|s| s.root.1 == *root)
+    }
+
+    /// Roots (with their position) that changed after `epoch`.
+    pub fn changed_since(&self, epoch: u64) -> Vec<(usize, RootEntry)> {
+        self.roots
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.changed_in > epoch)
+            .map(|(i, s)| (i, s.root))
+            .collect()
+    }
+
+    /// Check the counters against the roots a client synced at `epoch`: every
+    /// root claimed unchanged since then must be one the client already holds.
+    /// Root hashes are bound by `verify`; the counters are not, so this is what
+    /// stops a server from hiding a change behind an old epoch number.
+    pub fn check_against(&self, epoch: u64, synced: &[RootEntry]) -> Result<(), String> {
+        for stamp in &self.roots {
+            if stamp.changed_in <= epoch && !synced.contains(&stamp.root) {
+                return Err(format!(
+                    "root {} claims no change since epoch {} but was not synced",
+                    hex::encode(stamp.root.1),
+                    epoch
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// (level, hash) of a root. Roots are perfect binary trees, so the level is
+/// also the length of every Merkle path under the root.
+pub type RootEntry = (u64, Hash);
+
+/// Hash the ordered list of roots into a single forest hash. Levels are part
+/// of the pre-image, so a commitment also fixes the depth of every root.
+pub fn forest_hash(roots: &[RootEntry]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update((roots.len() as u64).to_be_bytes());
+    for (level, hash) in roots {
+        hasher.update(level.to_be_bytes());
+        hasher.update(hash);
+    }
+    hasher.finalize().into()
+}
+
+/// Level of the root with hash `root_hash`, if it is one of `roots`.
+pub fn root_level(roots: &[RootEntry], root_hash: &Hash) -> Option<u64> {
+    roots.iter().find(|(_, h)| h == root_hash).map(|(l, _)| *l)
+}
+
+/// Check that `proof` ends at one of `roots` and its path is exactly as long
+/// as that root's level, rejecting padded or truncated paths.
+pub fn check_proof_depth(
+    roots: &[RootEntry],
+    proof: &crate::merkle_proof::Proof,
+) -> Result<(), String> {
+    let level = root_level(roots, &proof.root_hash)
+        .ok_or_else(|| "proof root is not a root of the forest".to_string())?;
+    if proof.path.len() as u64 != level {
+        return Err(format!(
+            "path length {} does not match root level {}",
+            proof.path.len(),
+            level
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use crate::acc_proof::AccProof;
+
+    fn sample_tree() -> AccumulatorTree {
+        let mut tree = AccumulatorTree::new();
+        for (k, f) in [("a", "fa"), ("b", "fb"), ("c", "fc")] {
+            tree.insert(k.to_string(), f.to_string());
+        }
+        tree
+    }
+
+    #[test]
+    fn test_genesis_is_the_empty_forest() {
+        crate::setup::init_test_parameters();
+        let genesis = genesis_commitment();
+        assert_eq!(genesis.epoch, GENESIS_EPOCH);
+        assert!(genesis.is_genesis());
+        let mut tree = AccumulatorTree::new();
+        assert_eq!(genesis.forest_hash, forest_hash(&tree.root_entries()));
+        assert_eq!(genesis.global_acc, tree.global_acc());
+
+        // The first publication moves off genesis and starts the chain there
+        let first = tree.publish();
+        assert!(!first.commitment.is_genesis());
+        assert_eq!(first.commitment.epoch, GENESIS_EPOCH + 1);
+        assert_eq!(first.proof.pre_forest_hash(), genesis.forest_hash);
+        assert_eq!(first.proof.pre_global_acc, genesis.global_acc);
+    }
+
+    #[test]
+    fn test_nonmembership_bound_to_published_epoch() {
+        crate::setup::init_test_parameters();
+        let mut tree = sample_tree();
+        let commitment = tree.publish_epoch();
+        assert_eq!(commitment.epoch, 1);
+        let trusted = commitment.digest();
+
+        let nm = tree.select_nonmembership_proof("zzz").expect("absent key");
+        assert!(nm.verify_in_epoch("zzz", &trusted));
+        assert!(!nm.verify_in_epoch("other", &trusted));
+
+        match tree.select_with_proof("zzz").unwrap().acc_proof {
+            Some(AccProof::NonMembership(p)) => assert!(p.verify_in_epoch("zzz", &trusted)),
+            other => panic!("expected non-membership proof, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_proof_rejected_for_other_epoch() {
+        crate::setup::init_test_parameters();
+        let mut tree = sample_tree();
+        let first = tree.publish_epoch().digest();
+
+        tree.insert("d".to_string(), "fd".to_string());
+        let second = tree.publish_epoch();
+        assert_eq!(second.epoch, 2);
+
+        let nm = tree.select_nonmembership_proof("zzz").unwrap();
+        assert!(nm.verify_in_epoch("zzz", &second.digest()));
+        assert!(!nm.verify_in_epoch("zzz", &first));
+    }
+
+    #[test]
+    fn test_substituted_accumulator_rejected() {
+        crate::setup::init_test_parameters();
+        let mut tree = sample_tree();
+        let trusted = tree.publish_epoch().digest();
+
+        // A server that proves against its own accumulator cannot reuse the link.
+        let mut nm = tree.select_nonmembership_proof("zzz").unwrap();
+        let other_keys = Set::from_vec(vec!["x".to_string()]);
+        let forged = crate::acc_proof::NonMembershipProof::new(
+            "zzz".to_string(),
+            accumulator_ads::DynamicAccumulator::calculate_commitment(
+                &accumulator_ads::digest_set_from_set(&other_keys),
+            ),
+            &other_keys,
+        )
+        .unwrap();
+        assert!(forged.verify("zzz"));
+        nm.accumulator = forged.accumulator;
+        nm.acc_proof = forged.acc_proof;
+        assert!(!nm.verify_in_epoch("zzz", &trusted));
+    }
+
+    #[test]
+    fn test_unpublished_tree_has_no_link() {
+        crate::setup::init_test_parameters();
+        let tree = sample_tree();
+        let nm = tree.select_nonmembership_proof("zzz").unwrap();
+        assert!(nm.verify("zzz"));
+        assert!(nm.commitment.is_none());
+        assert!(!nm.verify_in_epoch("zzz", &[0u8; 32]));
+    }
+
+    #[test]
+    fn test_staged_mutations_invisible_until_publish() {
+        use crate::txn::TxnOp;
+
+        crate::setup::init_test_parameters();
+        let mut tree = sample_tree();
+        let first = tree.publish();
+        assert_eq!(first.commitment.epoch, 1);
+        assert!(!tree.has_unpublished_changes());
+
+        tree.stage(TxnOp::Insert {
+            key: "d".to_string(),
+            fid: "fd".to_string(),
+        });
+        tree.stage(TxnOp::Delete {
+            key: "a".to_string(),
+            fid: "fa".to_string(),
+        });
+        tree.stage(TxnOp::Delete {
+            key: "zzz".to_string(),
+            fid: "f".to_string(),
+        });
+
+        // Readers still see epoch 1
+        assert_eq!(tree.select("d"), None);
+        assert!(tree.select("a").is_some());
+        let nm = tree.select_nonmembership_proof("d").unwrap();
+        assert!(nm.verify_in_epoch("d", &first.commitment.digest()));
+        assert!(!tree.has_unpublished_changes());
+
+        let second = tree.publish();
+        assert_eq!(second.commitment.epoch, 2);
+        assert!(tree.staged().is_empty());
+        assert_eq!(second.proof.ops.len(), 2);
+        assert!(second.proof.verify());
+        assert_eq!(second.proof.pre_forest_hash(), first.commitment.forest_hash);
+        assert_eq!(
+            second.proof.post_forest_hash(),
+            second.commitment.forest_hash
+        );
+        assert!(tree.select("d").is_some());
+        assert_eq!(tree.select("a"), None);
+    }
+
+    #[test]
+    fn test_root_manifest_tracks_changed_roots() {
+        crate::setup::init_test_parameters();
+        let mut tree = sample_tree();
+        tree.publish_epoch();
+        let synced = tree.root_entries();
+
+        // "c" sits alone in the level-0 root; the level-1 root is untouched
+        tree.insert("c".to_string(), "fc2".to_string());
+        tree.publish_epoch();
+        let manifest = tree.root_manifest().unwrap();
+        assert!(manifest.verify(&manifest.commitment.digest()));
+        let changed = manifest.changed_since(1);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].1.0, 0);
+        assert!(manifest.changed_since(2).is_empty());
+        assert!(manifest.check_against(1, &synced).is_ok());
+
+        // Hiding the change behind the old counter is caught by the client
+        let mut hidden = manifest.clone();
+        hidden.roots[changed[0].0].changed_in = 1;
+        assert!(hidden.verify(&manifest.commitment.digest()));
+        assert!(hidden.check_against(1, &synced).is_err());
+
+        let mut future = manifest.clone();
+        future.roots[0].changed_in = 3;
+        assert!(!future.verify(&manifest.commitment.digest()));
+    }
+
+    #[test]
+    fn test_levels_bound_into_forest_hash() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for k in ["a", "b", "c"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        let roots = tree.root_entries();
+        let commitment = tree.publish_epoch();
+        assert_eq!(forest_hash(&roots), commitment.forest_hash);
+
+        // The same hashes under other levels give another forest hash
+        let relabeled: Vec<RootEntry> = roots.iter().map(|(l, h)| (l + 1, *h)).collect();
+        assert_ne!(forest_hash(&relabeled), commitment.forest_hash);
+
+        let proof = tree.select_with_proof("a").unwrap().merkle_proof.unwrap();
+        assert!(check_proof_depth(&roots, &proof).is_ok());
+
+        // A path padded with an extra step or cut short is rejected
+        let mut padded = proof.clone();
+        padded.path.push((proof.root_hash, true));
+        padded.root_hash = crate::utils::nonleaf_hash(proof.root_hash, proof.root_hash);
+        let padded_roots: Vec<RootEntry> = vec![(roots[0].0, padded.root_hash)];
+        assert!(check_proof_depth(&padded_roots, &padded).is_err());
+        let mut truncated = proof.clone();
+        truncated.path.pop();
+        assert!(check_proof_depth(&roots, &truncated).is_err());
+    }
+}
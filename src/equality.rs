@@ -0,0 +1,142 @@
+//! Key-set equality between independently built trees.
+//!
+//! Two forests holding the same keys may have completely different shapes (other
+//! insertion orders, rebuilds, other implementations), so their root hashes differ.
+//! Their global accumulators do not: the accumulator is a commitment to the key
+//! set alone. Equal accumulators plus equal committed key counts show that both
+//! published commitments cover exactly the same keys.
+
+use crate::Hash;
+use crate::epoch::EpochCommitment;
+
+/// Evidence that two published commitments accumulate the same key set.
+#[derive(Debug, Clone)]
+pub struct KeySetEqualityProof {
+    pub commitment_a: EpochCommitment,
+    pub commitment_b: EpochCommitment,
+}
+
+impl KeySetEqualityProof {
+    /// Verify against the commitment digests the verifier trusts for each tree.
+    /// Checks:
+    /// 1. Each embedded commitment hashes to its trusted digest
+    /// 2. Both global accumulators are equal
+    /// 3. Both committed key counts are equal
+    pub fn verify(&self, trusted_a: &Hash, trusted_b: &Hash) -> bool {
+        if self.commitment_a.digest() != *trusted_a || self.commitment_b.digest() != *trusted_b {
+            return false;
+        }
+        self.commitment_a.global_acc == self.commitment_b.global_acc
+            && self.commitment_a.key_count == self.commitment_b.key_count
+    }
+}
+
+/// Prove that `tree_a` and `tree_b` commit to the same key set.
+/// Fails if the commitments accumulate different keys or different key counts.
+pub fn prove_equal_keysets(
+    tree_a: &EpochCommitment,
+    tree_b: &EpochCommitment,
+) -> Result<KeySetEqualityProof, String> {
+    if tree_a.key_count != tree_b.key_count {
+        return Err(format!(
+            "key counts differ: {} vs {}",
+            tree_a.key_count, tree_b.key_count
+        ));
+    }
+    if tree_a.global_acc != tree_b.global_acc {
+        return Err("global accumulators differ: key sets are not equal".to_string());
+    }
+    Ok(KeySetEqualityProof {
+        commitment_a: tree_a.clone(),
+        commitment_b: tree_b.clone(),
+    })
+}
+
+/// Rebuild a forest from exported `(key, fid)` pairs and prove it commits to
+/// the same key set as `source`, the last commitment published by the tree the
+/// pairs were exported from. Repeated pairs collapse into one FID, so a dump
+/// from a multiset-style store migrates to its set semantics. The returned
+/// tree has already published its first epoch.
+pub fn migrate_entries<I>(
+    source: &EpochCommitment,
+    entries: I,
+) -> Result<(crate::AccumulatorTree, KeySetEqualityProof), String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut tree = crate::AccumulatorTree::new();
+    for (key, fid) in entries {
+        tree.insert(key, fid);
+    }
+    let target = tree.publish_epoch();
+    let proof = prove_equal_keysets(source, &target)?;
+    Ok((tree, proof))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+
+    fn build(entries: &[(&str, &str)]) -> AccumulatorTree {
+        let mut tree = AccumulatorTree::new();
+        for (k, f) in entries {
+            tree.insert(k.to_string(), f.to_string());
+        }
+        tree
+    }
+
+    #[test]
+    fn test_differently_shaped_trees_are_equal() {
+        crate::setup::init_test_parameters();
+        let mut a = build(&[("a", "1"), ("b", "2"), ("c", "3"), ("d", "4"), ("e", "5")]);
+        let mut b = build(&[("e", "9"), ("d", "8"), ("c", "7"), ("b", "6"), ("a", "5")]);
+
+        let ca = a.publish_epoch();
+        let cb = b.publish_epoch();
+        assert_ne!(ca.forest_hash, cb.forest_hash);
+
+        let proof = prove_equal_keysets(&ca, &cb).expect("same key set");
+        assert!(proof.verify(&ca.digest(), &cb.digest()));
+        assert!(!proof.verify(&cb.digest(), &ca.digest()));
+    }
+
+    #[test]
+    fn test_different_key_sets_rejected() {
+        crate::setup::init_test_parameters();
+        let mut a = build(&[("a", "1"), ("b", "2")]);
+        let mut b = build(&[("a", "1"), ("c", "2")]);
+        let ca = a.publish_epoch();
+        let cb = b.publish_epoch();
+        assert!(prove_equal_keysets(&ca, &cb).is_err());
+
+        // A forged proof pairing the commitments still fails verification
+        let forged = KeySetEqualityProof {
+            commitment_a: ca.clone(),
+            commitment_b: cb.clone(),
+        };
+        assert!(!forged.verify(&ca.digest(), &cb.digest()));
+    }
+
+    #[test]
+    fn test_migrate_entries() {
+        crate::setup::init_test_parameters();
+        let mut source = build(&[("a", "1"), ("b", "2"), ("a", "3"), ("c", "4")]);
+        let commitment = source.publish_epoch();
+        let pairs = |extra: Option<(&str, &str)>| {
+            [("c", "4"), ("a", "1"), ("a", "1"), ("a", "3"), ("b", "2")]
+                .into_iter()
+                .chain(extra)
+                .map(|(k, f)| (k.to_string(), f.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let (tree, proof) = migrate_entries(&commitment, pairs(None)).unwrap();
+        assert_eq!(tree.epoch(), 1);
+        assert_eq!(tree.select("a").unwrap().len(), 2);
+        let target = tree.published_commitment().unwrap().digest();
+        assert!(proof.verify(&commitment.digest(), &target));
+
+        assert!(migrate_entries(&commitment, pairs(Some(("d", "5")))).is_err());
+    }
+}
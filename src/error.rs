@@ -0,0 +1,57 @@
+//! Errors of the tree's mutation and proof APIs.
+//!
+//! `AccTreeError` says why a mutation or proof was refused so callers can
+//! branch on it. Code that only reports errors can keep using strings:
+//! `AccTreeError` converts into `String`, so `?` works in functions that
+//! return `Result<_, String>`.
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AccTreeError {
+    /// The key has no live leaf
+    #[error("key '{0}' not found")]
+    KeyNotFound(String),
+    /// The key is live but its FID set lacks the FID
+    #[error("FID '{fid}' not found under key '{key}'")]
+    FidNotFound { key: String, fid: String },
+    /// A revive targeted a key that is live, not tombstoned
+    #[error("key '{0}' is live, not tombstoned")]
+    KeyLive(String),
+    /// The tree's `RevivePolicy`, or a `ProofPolicy`, refused the operation
+    #[error("{0}")]
+    PolicyRejected(String),
+    /// Malformed arguments, such as reviving with no FIDs
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    /// No public parameters have been loaded
+    #[error("public parameters not initialized; call accumulator_tree::init() first")]
+    ParamsNotInitialized,
+    /// The public parameters are too small for the accumulator involved
+    #[error("public parameters support degree {available}, need degree {needed}")]
+    DegreeExceeded { needed: usize, available: usize },
+    /// The tree reached a state in which a proof could not be built
+    #[error("proof generation failed: {0}")]
+    ProofGenFailed(String),
+    /// The operation proves against the last published epoch, and there is none
+    #[error("no epoch has been published")]
+    NotPublished,
+    /// The forest changed after the last published epoch
+    #[error("forest changed since the commitment was published")]
+    ForestChanged,
+    /// The epoch was never published or has left the retained history
+    #[error("epoch {0} is not retained")]
+    EpochNotRetained(u64),
+    /// Snapshot bytes that do not decode to a valid forest
+    #[error("malformed snapshot: {0}")]
+    Malformed(String),
+    /// A `NodeStore` failed, or returned nodes that do not check out
+    #[error("node store: {0}")]
+    Storage(String),
+}
+
+impl From<AccTreeError> for String {
+    fn from(e: AccTreeError) -> String {
+        e.to_string()
+    }
+}
@@ -0,0 +1,397 @@
+//! Chunked commitments to large FID sets.
+//!
+//! Hashing a leaf normally covers every FID of the key, which is infeasible to
+//! transfer and re-hash for keys with millions of FIDs. Sets larger than
+//! `FID_CHUNK_THRESHOLD` are instead split into content-defined chunks and the
+//! leaf commits to the Merkle root over the chunk hashes. A single FID is then
+//! proven with its chunk and a path to that root, and a missing FID with the
+//! chunks holding its neighbours in sorted order.
+//!
+//! Chunk boundaries depend only on the FIDs themselves (a boundary follows
+//! every FID whose hash falls below a cut-off), so adding or removing one FID
+//! only changes the chunk it lands in.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::merkle_proof::Proof as MerkleProof;
+use crate::utils::nonleaf_hash;
+use accumulator_ads::Set;
+use sha2::{Digest, Sha256};
+
+/// FID sets with more elements than this are committed to in chunks.
+pub const FID_CHUNK_THRESHOLD: usize = 1024;
+
+/// Upper bound on chunk length, regardless of content boundaries.
+pub const MAX_CHUNK_LEN: usize = 256;
+
+/// A boundary follows a FID whose boundary hash starts with a byte below this,
+/// giving chunks of 64 FIDs on average.
+const BOUNDARY_CUTOFF: u8 = 4;
+
+const BOUNDARY_DOMAIN: &[u8] = b"acc-tree/fid-boundary";
+const CHUNK_DOMAIN: &[u8] = b"acc-tree/fid-chunk";
+
+fn is_boundary(fid: &str) -> bool {
+    let mut hasher = Sha256::new();
+    hasher.update(BOUNDARY_DOMAIN);
+    hasher.update(fid.as_bytes());
+    hasher.finalize()[0] < BOUNDARY_CUTOFF
+}
+
+/// Whether `fids` is committed to in chunks rather than element by element.
+pub fn is_chunked(fids: &Set<String>) -> bool {
+    fids.len() > FID_CHUNK_THRESHOLD
+}
+
+/// Split the sorted FIDs into content-defined chunks.
+pub fn chunk_fids(fids: &Set<String>) -> Vec<Vec<String>> {
+    let mut sorted: Vec<&String> = fids.iter().collect();
+    sorted.sort();
+
+    let mut chunks = Vec::new();
+    let mut current = Vec::new();
+    for fid in sorted {
+        current.push(fid.clone());
+        if is_boundary(fid) || current.len() == MAX_CHUNK_LEN {
+            chunks.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Hash of one chunk of sorted FIDs.
+pub fn chunk_hash(chunk: &[String]) -> Hash {
+    let mut out = CHUNK_DOMAIN.to_vec();
+    chunk.to_vec().encode_canonical(&mut out);
+    Sha256::digest(&out).into()
+}
+
+/// Hash levels of the Merkle tree over `leaves`, bottom first. An unpaired
+/// node is carried up unchanged.
+fn levels(leaves: Vec<Hash>) -> Vec<Vec<Hash>> {
+    let mut levels = vec![leaves];
+    while levels.last().unwrap().len() > 1 {
+        let next = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [l, r] => nonleaf_hash(*l, *r),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// Merkle root over the chunk hashes of `fids`.
+pub fn fid_root(fids: &Set<String>) -> Hash {
+    let hashes: Vec<Hash> = chunk_fids(fids).iter().map(|c| chunk_hash(c)).collect();
+    if hashes.is_empty() {
+        return chunk_hash(&[]);
+    }
+    *levels(hashes).last().unwrap().first().unwrap()
+}
+
+/// Proof that a FID belongs to a chunked FID set.
+#[derive(Debug, Clone)]
+pub struct FidProof {
+    /// Chunk containing the FID, sorted
+    pub chunk: Vec<String>,
+    /// Path from the chunk hash to the FID root
+    pub proof: MerkleProof,
+}
+
+impl FidProof {
+    /// Prove that `fid` is in `fids`. Returns `None` if it is not.
+    pub fn new(fids: &Set<String>, fid: &str) -> Option<Self> {
+        let chunks = chunk_fids(fids);
+        let index = chunks.iter().position(|c| c.iter().any(|f| f == fid))?;
+        Some(Self::for_chunk(&chunks, index))
+    }
+
+    /// Proof for the chunk at `index`.
+    fn for_chunk(chunks: &[Vec<String>], index: usize) -> Self {
+        let hashes: Vec<Hash> = chunks.iter().map(|c| chunk_hash(c)).collect();
+        let levels = levels(hashes);
+
+        let mut path = Vec::new();
+        let mut i = index;
+        for level in &levels[..levels.len() - 1] {
+            let sibling = i ^ 1;
+            if sibling < level.len() {
+                path.push((level[sibling], sibling < i));
+            }
+            i /= 2;
+        }
+        let root = levels.last().unwrap()[0];
+        let leaf = levels[0][index];
+        Self {
+            chunk: chunks[index].clone(),
+            proof: MerkleProof::new(root, leaf, path),
+        }
+    }
+
+    /// FID root this proof leads to.
+    pub fn fid_root(&self) -> Hash {
+        self.proof.root_hash
+    }
+
+    /// Check that `fid` is in the chunk and the chunk hashes up to the FID root.
+    pub fn verify(&self, fid: &str) -> bool {
+        self.chunk.iter().any(|f| f == fid) && self.verify_chunk()
+    }
+
+    /// Check that the chunk is sorted, non-empty and hashes up to the FID root.
+    fn verify_chunk(&self) -> bool {
+        !self.chunk.is_empty()
+            && self.chunk.windows(2).all(|w| w[0] < w[1])
+            && chunk_hash(&self.chunk) == self.proof.leaf_hash
+            && self.proof.verify()
+    }
+
+    fn first(&self) -> &str {
+        &self.chunk[0]
+    }
+
+    fn last(&self) -> &str {
+        &self.chunk[self.chunk.len() - 1]
+    }
+
+    /// Whether the chunk is the first of its set: every sibling is on the right.
+    fn is_leftmost(&self) -> bool {
+        self.proof.path.iter().all(|(_, left)| !left)
+    }
+
+    /// Whether the chunk is the last of its set: every sibling is on the left.
+    fn is_rightmost(&self) -> bool {
+        self.proof.path.iter().all(|(_, left)| *left)
+    }
+
+    /// Hashes along the path, from the chunk hash up to just below the root.
+    fn path_hashes(&self) -> Vec<Hash> {
+        let mut current = self.proof.leaf_hash;
+        let mut hashes = vec![current];
+        for (sibling, left) in &self.proof.path {
+            current = if *left {
+                nonleaf_hash(*sibling, current)
+            } else {
+                nonleaf_hash(current, *sibling)
+            };
+            hashes.push(current);
+        }
+        hashes.pop();
+        hashes
+    }
+
+    /// Whether `next` proves the chunk right after this one. Below their
+    /// lowest common ancestor this chunk is the rightmost and `next` the
+    /// leftmost of their subtrees, each is the other's sibling there, and
+    /// above it the paths are the same.
+    fn is_followed_by(&self, next: &FidProof) -> bool {
+        let (left, right) = (&self.proof.path, &next.proof.path);
+        let Some(i) = left.iter().position(|(_, is_left)| !is_left) else {
+            return false;
+        };
+        let Some(j) = right.iter().position(|(_, is_left)| *is_left) else {
+            return false;
+        };
+        self.fid_root() == next.fid_root()
+            && left[i].0 == next.path_hashes()[j]
+            && right[j].0 == self.path_hashes()[i]
+            && left[i + 1..] == right[j + 1..]
+    }
+}
+
+/// Proof that a FID is missing from a chunked FID set, from the chunks that
+/// hold its neighbours in sorted order.
+#[derive(Debug, Clone)]
+pub enum FidAbsenceProof {
+    /// The FID falls between the first and last FID of one chunk
+    Within(FidProof),
+    /// The FID falls between two consecutive chunks
+    Between(FidProof, FidProof),
+    /// The FID sorts before the first chunk
+    Before(FidProof),
+    /// The FID sorts after the last chunk
+    After(FidProof),
+}
+
+impl FidAbsenceProof {
+    /// Prove that `fid` is not in `fids`. Returns `None` if it is, or if
+    /// `fids` is empty.
+    pub fn new(fids: &Set<String>, fid: &str) -> Option<Self> {
+        if fids.contains(&fid.to_string()) {
+            return None;
+        }
+        let chunks = chunk_fids(fids);
+        let last = chunks.len().checked_sub(1)?;
+        let proof = |i| FidProof::for_chunk(&chunks, i);
+        let Some(next) = chunks.iter().position(|c| fid < c[c.len() - 1].as_str()) else {
+            return Some(Self::After(proof(last)));
+        };
+        Some(if fid > chunks[next][0].as_str() {
+            Self::Within(proof(next))
+        } else if next == 0 {
+            Self::Before(proof(0))
+        } else {
+            Self::Between(proof(next - 1), proof(next))
+        })
+    }
+
+    /// FID root this proof leads to.
+    pub fn fid_root(&self) -> Hash {
+        match self {
+            Self::Within(p) | Self::Between(p, _) | Self::Before(p) | Self::After(p) => {
+                p.fid_root()
+            }
+        }
+    }
+
+    /// Check that the chunks hash up to the FID root and leave no room for
+    /// `fid` in the set.
+    pub fn verify(&self, fid: &str) -> bool {
+        match self {
+            Self::Within(p) => {
+                p.verify_chunk()
+                    && p.first() < fid
+                    && fid < p.last()
+                    && !p.chunk.iter().any(|f| f == fid)
+            }
+            Self::Between(lower, upper) => {
+                lower.verify_chunk()
+                    && upper.verify_chunk()
+                    && lower.last() < fid
+                    && fid < upper.first()
+                    && lower.is_followed_by(upper)
+            }
+            Self::Before(p) => p.verify_chunk() && fid < p.first() && p.is_leftmost(),
+            Self::After(p) => p.verify_chunk() && p.last() < fid && p.is_rightmost(),
+        }
+    }
+}
+
+impl CanonicalEncode for FidProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.chunk.encode_canonical(out);
+        self.proof.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for FidAbsenceProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Within(p) => {
+                out.push(0);
+                p.encode_canonical(out);
+            }
+            Self::Between(lower, upper) => {
+                out.push(1);
+                lower.encode_canonical(out);
+                upper.encode_canonical(out);
+            }
+            Self::Before(p) => {
+                out.push(2);
+                p.encode_canonical(out);
+            }
+            Self::After(p) => {
+                out.push(3);
+                p.encode_canonical(out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fid_set(n: usize) -> Set<String> {
+        Set::from_vec((0..n).map(|i| format!("doc{:06}", i)).collect())
+    }
+
+    #[test]
+    fn test_chunks_are_content_defined() {
+        let fids = fid_set(2000);
+        let chunks = chunk_fids(&fids);
+        assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 2000);
+        assert!(chunks.iter().all(|c| c.len() <= MAX_CHUNK_LEN));
+
+        // Adding a FID only rewrites the chunk it lands in
+        let mut more = fids.clone();
+        more.insert("doc000500x".to_string());
+        let changed = chunk_fids(&more)
+            .iter()
+            .filter(|c| !chunks.contains(c))
+            .count();
+        assert_eq!(changed, 1);
+    }
+
+    #[test]
+    fn test_fid_proof() {
+        let fids = fid_set(1500);
+        let root = fid_root(&fids);
+        for fid in ["doc000000", "doc000777", "doc001499"] {
+            let proof = FidProof::new(&fids, fid).unwrap();
+            assert_eq!(proof.fid_root(), root);
+            assert!(proof.verify(fid));
+        }
+        assert!(FidProof::new(&fids, "missing").is_none());
+
+        // A proof only covers the FIDs of its own chunk
+        let mut forged = FidProof::new(&fids, "doc000777").unwrap();
+        assert!(!forged.verify("doc001499"));
+        forged.chunk.push("zzz".to_string());
+        assert!(!forged.verify("zzz"));
+    }
+
+    #[test]
+    fn test_fid_absence_proof() {
+        let fids = fid_set(1500);
+        let root = fid_root(&fids);
+        let chunks = chunk_fids(&fids);
+        let boundary = &chunks[1][0];
+        let within = format!("{}x", chunks[1][1]);
+        let between = format!("{}x", chunks[1].last().unwrap());
+        let cases = [
+            ("a", "Before"),
+            (within.as_str(), "Within"),
+            (between.as_str(), "Between"),
+            ("zzz", "After"),
+        ];
+        for (fid, kind) in cases {
+            let proof = FidAbsenceProof::new(&fids, fid).unwrap();
+            assert!(
+                format!("{:?}", proof).starts_with(kind),
+                "{}: {:?}",
+                fid,
+                proof
+            );
+            assert_eq!(proof.fid_root(), root);
+            assert!(proof.verify(fid));
+        }
+        assert!(FidAbsenceProof::new(&fids, boundary).is_none());
+        assert!(FidAbsenceProof::new(&Set::new(), "a").is_none());
+
+        // A proof does not cover FIDs outside its gap
+        let proof = FidAbsenceProof::new(&fids, &between).unwrap();
+        assert!(!proof.verify(boundary));
+        assert!(!proof.verify("zzz"));
+
+        // Two chunks that are not consecutive leave a gap
+        let skipping = FidAbsenceProof::Between(
+            FidProof::for_chunk(&chunks, 1),
+            FidProof::for_chunk(&chunks, 3),
+        );
+        assert!(!skipping.verify(&between));
+        let not_first = FidAbsenceProof::Before(FidProof::for_chunk(&chunks, 1));
+        assert!(!not_first.verify("a"));
+        let not_last = FidAbsenceProof::After(FidProof::for_chunk(&chunks, 1));
+        assert!(!not_last.verify("zzz"));
+    }
+}
@@ -0,0 +1,106 @@
+//! Read-only trees for proof serving.
+//!
+//! `AccumulatorTree::freeze` consumes a tree and returns a `FrozenTree`. The
+//! type has no mutation methods: it dereferences to a shared, read-only
+//! `AccumulatorTree`, and the only way to change the forest is to build a new
+//! tree. Freezing refreshes stale nodes and computes the membership witness of
+//! every live key against its root up front, one batch per root, so point
+//! proofs are answered without touching the public parameters. Clones share
+//! the forest and the witnesses and can be handed to any number of threads.
+
+use crate::error::AccTreeError;
+use crate::response::QueryResponse;
+use crate::tree::AccumulatorTree;
+use crate::witness_cache::{compute_all_witnesses, compute_witness};
+use accumulator_ads::G1Affine;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Immutable tree with precomputed membership witnesses, built by
+/// `AccumulatorTree::freeze`.
+#[derive(Clone)]
+pub struct FrozenTree {
+    tree: Arc<AccumulatorTree>,
+    /// Witness of each live key in the accumulator of its root
+    witnesses: Arc<HashMap<String, G1Affine>>,
+}
+
+impl FrozenTree {
+    pub(crate) fn new(mut tree: AccumulatorTree) -> Result<Self, AccTreeError> {
+        tree.refresh();
+        let mut witnesses = HashMap::new();
+        for root in &tree.roots {
+            let keys = root.keys();
+            let batch = compute_all_witnesses(&keys)?;
+            witnesses.extend(keys.iter().cloned().zip(batch));
+        }
+        Ok(Self {
+            tree: Arc::new(tree),
+            witnesses: Arc::new(witnesses),
+        })
+    }
+
+    /// `AccumulatorTree::select_with_proof` answered from the precomputed
+    /// witnesses. Fails as the tree's does when the public parameters are
+    /// missing or too small, which freezing has already ruled out for the
+    /// roots.
+    pub fn select_with_proof(&self, key: &str) -> Result<QueryResponse, AccTreeError> {
+        self.tree
+            .select_with_proof_using(key, |root, key| match self.witnesses.get(key) {
+                Some(witness) => Ok(*witness),
+                None => compute_witness(&root.keys(), key),
+            })
+    }
+
+    /// Number of precomputed witnesses, one per live key.
+    pub fn witness_count(&self) -> usize {
+        self.witnesses.len()
+    }
+}
+
+impl std::ops::Deref for FrozenTree {
+    type Target = AccumulatorTree;
+
+    fn deref(&self) -> &AccumulatorTree {
+        &self.tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frozen_tree_serves_same_proofs() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<FrozenTree>();
+
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for i in 0..7 {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        tree.delete("k2", "f2");
+        tree.publish_epoch();
+        let expected: Vec<QueryResponse> = ["k0", "k2", "k6", "zz"]
+            .iter()
+            .map(|k| tree.select_with_proof(k).unwrap())
+            .collect();
+
+        let frozen = tree.freeze().unwrap();
+        assert!(!frozen.has_stale_nodes());
+        assert_eq!(frozen.witness_count(), 6);
+        let handles: Vec<_> = ["k0", "k2", "k6", "zz"]
+            .into_iter()
+            .map(|k| {
+                let frozen = frozen.clone();
+                std::thread::spawn(move || frozen.select_with_proof(k).unwrap())
+            })
+            .collect();
+        let served: Vec<QueryResponse> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(served, expected);
+        for (k, response) in ["k0", "k2", "k6", "zz"].into_iter().zip(&served) {
+            assert!(response.verify_by_mode(k));
+        }
+    }
+}
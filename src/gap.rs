@@ -0,0 +1,293 @@
+//! Non-membership from neighbouring leaves, for trees with sorted leaves.
+//!
+//! A tree built with `AccumulatorTree::with_sorted_leaves` keeps the leaves of
+//! every root sorted by its `OrderPolicy`, tombstones included. A key absent
+//! from such a root falls between two adjacent leaves, before the first leaf
+//! or after the last, unless the root holds the key's tombstone. A `GapProof`
+//! shows one of these for every root of the forest: the leaves, their
+//! positions and the sibling hashes that rebuild the root, as in a
+//! `RootMultiProof`. Adjacency comes from the positions, which the root hash
+//! fixes.
+//!
+//! Root hashes commit to where each leaf sits but not to the leaves being
+//! sorted; a prover that built an unsorted root can show a gap around a key
+//! it holds. The proof is only as good as the sortedness of the roots, which
+//! an auditor holding a snapshot checks with `AccumulatorTree::leaves_sorted`.
+//! The accumulator `NonMembershipProof` carries no such assumption and remains
+//! the proof of choice when the verifier does not trust the prover's build.
+
+use crate::canonical::{CanonicalEncode, impl_canonical_eq};
+use crate::epoch::RootEntry;
+use crate::multiproof::{climb, node_at};
+use crate::node::Node;
+use crate::policy::OrderPolicy;
+use crate::validate::{MAX_PATH_LEN, Validate, check_path_len};
+use crate::{Hash, leaf_hash};
+use accumulator_ads::Set;
+use std::cmp::Ordering;
+
+/// A leaf bounding the gap and its position under the root.
+#[derive(Debug, Clone)]
+pub struct GapLeaf {
+    /// Index of the leaf among the `2^level` leaves of its root, left to right
+    pub index: u64,
+    pub key: String,
+    pub fids: Set<String>,
+    pub deleted: bool,
+}
+
+/// The gap one root leaves for the key.
+#[derive(Debug, Clone)]
+pub struct RootGap {
+    pub root_hash: Hash,
+    pub level: u64,
+    /// Two adjacent leaves around the key, the first or last leaf of the
+    /// root, or the key's own tombstone
+    pub leaves: Vec<GapLeaf>,
+    /// Hashes the leaves do not determine, in the order the verifier uses them
+    pub siblings: Vec<Hash>,
+}
+
+/// Proof that `key` has no live leaf, from `AccumulatorTree::prove_gap`.
+#[derive(Debug, Clone)]
+pub struct GapProof {
+    pub key: String,
+    /// One gap per root, in forest order
+    pub roots: Vec<RootGap>,
+}
+
+/// Index of the last leaf of a root at `level`.
+fn last_index(level: u64) -> u64 {
+    if level == 0 {
+        0
+    } else {
+        u64::MAX >> (64 - level)
+    }
+}
+
+impl GapLeaf {
+    fn at(root: &Node, index: u64) -> Self {
+        let Some(Node::Leaf {
+            key, fids, deleted, ..
+        }) = node_at(root, 0, index)
+        else {
+            unreachable!("roots are perfect trees");
+        };
+        GapLeaf {
+            index,
+            key: key.clone(),
+            fids: fids.clone(),
+            deleted: *deleted,
+        }
+    }
+}
+
+impl RootGap {
+    /// Gap `root`, whose leaves are sorted by `order`, leaves for `key`. `None`
+    /// if the key is live under it.
+    pub(crate) fn new(root: &Node, key: &str, order: &OrderPolicy) -> Option<Self> {
+        // Descend to the last leaf not above the key (or the first leaf)
+        let mut node = root;
+        let mut index = 0u64;
+        while let Node::NonLeaf { left, right, .. } = node {
+            let go_right = order.compare(key, right.edge_key(false)) != Ordering::Less;
+            index = index * 2 + u64::from(go_right);
+            node = if go_right { right } else { left };
+        }
+        let level = root.level() as u64;
+        let found = GapLeaf::at(root, index);
+        let leaves = match order.compare(&found.key, key) {
+            Ordering::Equal if !found.deleted => return None,
+            Ordering::Less if index < last_index(level) => {
+                vec![found, GapLeaf::at(root, index + 1)]
+            }
+            _ => vec![found],
+        };
+
+        let mut siblings = Vec::new();
+        let rebuilt = climb(level, Self::leaf_nodes(&leaves), |height, index| {
+            let hash = node_at(root, height, index)?.hash();
+            siblings.push(hash);
+            Some(hash)
+        });
+        debug_assert_eq!(rebuilt, Some(root.hash()));
+        Some(Self {
+            root_hash: root.hash(),
+            level,
+            leaves,
+            siblings,
+        })
+    }
+
+    fn leaf_nodes(leaves: &[GapLeaf]) -> Vec<(u64, Hash)> {
+        leaves
+            .iter()
+            .map(|leaf| {
+                let hash = leaf_hash(&leaf.key, &leaf.fids, 0, leaf.deleted);
+                (leaf.index, hash)
+            })
+            .collect()
+    }
+
+    /// Whether the leaves leave room for `key` and rebuild `root_hash` with
+    /// every sibling used.
+    pub fn verify(&self, key: &str, order: &OrderPolicy) -> bool {
+        if self.level > MAX_PATH_LEN as u64 {
+            return false;
+        }
+        let last = last_index(self.level);
+        let shows_gap = match self.leaves.as_slice() {
+            [leaf] if leaf.key == key => leaf.deleted,
+            [leaf] if leaf.index == 0 && order.compare(key, &leaf.key) == Ordering::Less => true,
+            [leaf] => leaf.index == last && order.compare(&leaf.key, key) == Ordering::Less,
+            [below, above] => {
+                above.index == below.index + 1
+                    && order.compare(&below.key, key) == Ordering::Less
+                    && order.compare(key, &above.key) == Ordering::Less
+            }
+            _ => false,
+        };
+        if !shows_gap || self.leaves.iter().any(|leaf| leaf.index > last) {
+            return false;
+        }
+        let mut siblings = self.siblings.iter();
+        let root = climb(self.level, Self::leaf_nodes(&self.leaves), |_, _| {
+            siblings.next().copied()
+        });
+        root == Some(self.root_hash) && siblings.next().is_none()
+    }
+}
+
+impl GapProof {
+    /// Check a gap for `key` in each of `roots` (trusted, in forest order,
+    /// e.g. from a `RootManifest`) under the order the tree sorts by.
+    pub fn verify(&self, roots: &[RootEntry], order: &OrderPolicy) -> bool {
+        self.roots.len() == roots.len()
+            && self.roots.iter().zip(roots).all(|(gap, &(level, hash))| {
+                gap.level == level && gap.root_hash == hash && gap.verify(&self.key, order)
+            })
+    }
+}
+
+impl CanonicalEncode for GapLeaf {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.index.encode_canonical(out);
+        self.key.encode_canonical(out);
+        self.fids.encode_canonical(out);
+        self.deleted.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for RootGap {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.root_hash.encode_canonical(out);
+        self.level.encode_canonical(out);
+        self.leaves.encode_canonical(out);
+        self.siblings.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for GapProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.roots.encode_canonical(out);
+    }
+}
+
+impl_canonical_eq!(GapLeaf, RootGap, GapProof);
+
+impl Validate for GapProof {
+    fn validate(&self) -> Result<(), String> {
+        for root in &self.roots {
+            check_path_len(root.level as usize, "gap root level")?;
+            if root.leaves.len() > 2 || root.siblings.len() > 2 * root.level as usize {
+                return Err(format!(
+                    "{} leaves and {} siblings for one gap at level {}",
+                    root.leaves.len(),
+                    root.siblings.len(),
+                    root.level
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::policy::{LeafOrder, OrderPolicy};
+    use crate::wire::{from_wire, to_wire};
+    use crate::{AccTreeError, AccumulatorTree};
+
+    #[test]
+    fn test_sorted_leaves_prove_gaps() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::with_sorted_leaves(OrderPolicy::Natural);
+        // Out of order: roots of levels 2 and 0 whose merges interleave
+        for i in [7, 2, 9, 4, 1] {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        tree.insert_batch([("k12".to_string(), "f12".to_string())]);
+        let mut txn = tree.begin_transaction();
+        txn.insert("k3".to_string(), "f3".to_string());
+        txn.commit();
+        tree.delete("k4", "f4");
+        assert!(tree.leaves_sorted());
+        assert_eq!(tree.leaf_order(), LeafOrder::Sorted);
+        assert_eq!(tree.select("k9").unwrap().len(), 1);
+
+        let roots = tree.root_entries();
+        let order = tree.order();
+        // Between leaves, beyond either end and at a tombstone
+        for key in ["k5", "k0", "k99", "k4", "k10"] {
+            let proof: super::GapProof =
+                from_wire(&to_wire(&tree.prove_gap(key).unwrap())).unwrap();
+            assert!(proof.verify(&roots, &order), "{}", key);
+        }
+        assert_eq!(
+            tree.prove_gap("k7").unwrap_err(),
+            AccTreeError::KeyLive("k7".to_string())
+        );
+
+        let proof = tree.prove_gap("k5").unwrap();
+        let mut forged = proof.clone();
+        forged.key = "k7".to_string();
+        assert!(!forged.verify(&roots, &order));
+        assert!(!proof.verify(&roots[1..], &order));
+        let at = proof
+            .roots
+            .iter()
+            .position(|r| r.leaves.len() == 2)
+            .unwrap();
+        let mut forged = proof.clone();
+        forged.roots[at].leaves[1].index += 1;
+        assert!(!forged.verify(&roots, &order));
+        let mut forged = proof;
+        forged.roots[at].leaves.swap(0, 1);
+        assert!(!forged.verify(&roots, &order));
+
+        // The mode survives a snapshot and compaction keeps roots sorted
+        let mut restored = AccumulatorTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.leaf_order(), LeafOrder::Sorted);
+        assert_eq!(restored.root_hashes(), tree.root_hashes());
+        restored.compact();
+        assert!(restored.leaves_sorted());
+        let mut fresh = AccumulatorTree::with_sorted_leaves(OrderPolicy::Natural);
+        for i in [1, 2, 3, 7, 9, 12] {
+            fresh.insert(format!("k{}", i), format!("f{}", i));
+        }
+        assert_eq!(restored.root_hashes(), fresh.root_hashes());
+        assert!(
+            restored
+                .prove_gap("k4")
+                .unwrap()
+                .verify(&restored.root_entries(), &order)
+        );
+
+        let plain = AccumulatorTree::new();
+        assert!(matches!(
+            plain.prove_gap("k5"),
+            Err(AccTreeError::InvalidInput(_))
+        ));
+    }
+}
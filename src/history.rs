@@ -0,0 +1,241 @@
+//! Retained epochs and per-key change proofs.
+//!
+//! A tree configured with `AccumulatorTree::retain_history` keeps a copy of
+//! the forest of each of its last published epochs together with the staged
+//! operations `publish` applied to reach it. `prove_key_transition` answers
+//! "what happened to this key between epoch a and epoch b" from that: the
+//! key's state in both epochs, each proven against its commitment, and the
+//! published operations on the key in between. A client can audit one key's
+//! evolution without fetching every transition. The operations are not bound
+//! to the commitments on their own; the verifier only accepts them if
+//! replaying them turns the first proven state into the second.
+
+use crate::Hash;
+use crate::acc_proof::NonMembershipProof;
+use crate::canonical::CanonicalEncode;
+use crate::epoch::{EpochCommitment, PublishedEpoch, RootEntry, forest_hash};
+use crate::error::AccTreeError;
+use crate::node::Node;
+use crate::response::LeafWitness;
+use crate::txn::TxnOp;
+use accumulator_ads::Set;
+use std::sync::Arc;
+
+/// A published epoch kept for later proofs.
+#[derive(Debug, Clone)]
+pub(crate) struct EpochRecord {
+    pub(crate) published: Arc<PublishedEpoch>,
+    /// Forest as published, sharing unchanged nodes with the live tree
+    pub(crate) roots: Vec<Arc<Node>>,
+    /// Staged operations `publish` applied to reach this epoch
+    pub(crate) ops: Vec<TxnOp>,
+}
+
+impl EpochRecord {
+    pub(crate) fn epoch(&self) -> u64 {
+        self.published.commitment.epoch
+    }
+
+    /// Proven state of `key` in this epoch.
+    pub(crate) fn key_state(&self, key: &str) -> Result<KeyState, AccTreeError> {
+        if let Some(leaf) = crate::tree::leaf_witness_in(&self.roots, key) {
+            return Ok(KeyState::Leaf(leaf));
+        }
+        let commitment = &self.published.commitment;
+        NonMembershipProof::new(key.to_string(), commitment.global_acc, &self.published.keys)
+            .map(|nm| KeyState::Absent(Box::new(nm.with_commitment(commitment.clone()))))
+            .ok_or_else(|| {
+                AccTreeError::ProofGenFailed(format!(
+                    "cannot prove '{}' absent in epoch {}",
+                    key,
+                    self.epoch()
+                ))
+            })
+    }
+}
+
+/// State of a key in one epoch.
+#[derive(Debug, Clone)]
+pub enum KeyState {
+    /// The key's leaf, live or tombstoned, with its path
+    Leaf(LeafWitness),
+    /// No live leaf: non-membership in the epoch's global accumulator
+    Absent(Box<NonMembershipProof>),
+}
+
+impl KeyState {
+    /// Live FIDs (None if the key is not live).
+    pub fn live_fids(&self) -> Option<&Set<String>> {
+        match self {
+            KeyState::Leaf(leaf) if !leaf.deleted => Some(&leaf.fids),
+            _ => None,
+        }
+    }
+
+    fn check(
+        &self,
+        key: &str,
+        commitment: &EpochCommitment,
+        roots: &[RootEntry],
+    ) -> Result<(), String> {
+        let ok = match self {
+            KeyState::Leaf(leaf) => leaf.verify(key, roots),
+            KeyState::Absent(nm) => nm.verify_in_epoch(key, &commitment.digest()),
+        };
+        if !ok {
+            return Err(format!("invalid state proof in epoch {}", commitment.epoch));
+        }
+        Ok(())
+    }
+}
+
+/// How one key changed between two published epochs.
+#[derive(Debug, Clone)]
+pub struct KeyTransitionProof {
+    pub key: String,
+    pub from: EpochCommitment,
+    pub from_roots: Vec<RootEntry>,
+    pub before: KeyState,
+    pub to: EpochCommitment,
+    pub to_roots: Vec<RootEntry>,
+    pub after: KeyState,
+    /// Published operations on the key after `from` up to `to`, with the
+    /// epoch each was published in
+    pub ops: Vec<(u64, TxnOp)>,
+}
+
+impl KeyTransitionProof {
+    /// Whether the key's live FIDs differ between the two epochs.
+    pub fn changed(&self) -> bool {
+        self.before.live_fids() != self.after.live_fids()
+    }
+
+    pub fn verify(&self, trusted_from: &Hash, trusted_to: &Hash) -> bool {
+        self.check(trusted_from, trusted_to).is_ok()
+    }
+
+    /// Checks:
+    /// 1. Both commitments are the trusted ones, in order, and commit to their roots
+    /// 2. Both states verify for the key in their epoch
+    /// 3. The operations are on the key, published after `from` up to `to`, in
+    ///    order, and replaying them turns the first state into the second
+    pub fn check(&self, trusted_from: &Hash, trusted_to: &Hash) -> Result<(), String> {
+        if self.from.digest() != *trusted_from || self.to.digest() != *trusted_to {
+            return Err("commitments do not match the trusted digests".to_string());
+        }
+        if self.from.epoch > self.to.epoch {
+            return Err("epochs are out of order".to_string());
+        }
+        if forest_hash(&self.from_roots) != self.from.forest_hash
+            || forest_hash(&self.to_roots) != self.to.forest_hash
+        {
+            return Err("roots do not match the commitments".to_string());
+        }
+        self.before.check(&self.key, &self.from, &self.from_roots)?;
+        self.after.check(&self.key, &self.to, &self.to_roots)?;
+
+        let mut epoch = self.from.epoch + 1;
+        let mut state = self.before.live_fids().map(|fids| (fids.clone(), false));
+        for (published_in, op) in &self.ops {
+            if op.key() != self.key || *published_in < epoch || *published_in > self.to.epoch {
+                return Err(format!("operation {:?} does not belong here", op));
+            }
+            epoch = *published_in;
+            state = op.replay(state.clone()).or(state);
+        }
+        let replayed = state.filter(|(_, deleted)| !*deleted).map(|(fids, _)| fids);
+        if replayed.as_ref() != self.after.live_fids() {
+            return Err("operations do not explain the change".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl CanonicalEncode for KeyState {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            KeyState::Leaf(leaf) => {
+                out.push(0);
+                leaf.encode_canonical(out);
+            }
+            KeyState::Absent(nm) => {
+                out.push(1);
+                nm.encode_canonical(out);
+            }
+        }
+    }
+}
+
+impl CanonicalEncode for KeyTransitionProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.from.encode_canonical(out);
+        self.from_roots.encode_canonical(out);
+        self.before.encode_canonical(out);
+        self.to.encode_canonical(out);
+        self.to_roots.encode_canonical(out);
+        self.after.encode_canonical(out);
+        self.ops.encode_canonical(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AccumulatorTree;
+    use crate::txn::TxnOp;
+
+    fn insert(key: &str, fid: &str) -> TxnOp {
+        TxnOp::Insert {
+            key: key.to_string(),
+            fid: fid.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_key_transition_across_epochs() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.retain_history(4);
+        tree.stage(insert("a", "fa"));
+        tree.stage(insert("b", "fb"));
+        let e1 = tree.publish().commitment.digest();
+        tree.stage(insert("c", "fc"));
+        let e2 = tree.publish().commitment.digest();
+        tree.stage(insert("a", "fa2"));
+        tree.stage(TxnOp::Delete {
+            key: "b".to_string(),
+            fid: "fb".to_string(),
+        });
+        let e3 = tree.publish().commitment.digest();
+
+        // "a" was untouched by epoch 2, so nothing explains a change
+        let same = tree.prove_key_transition("a", 1, 2).unwrap();
+        assert!(!same.changed() && same.ops.is_empty());
+        assert!(same.verify(&e1, &e2));
+
+        let grown = tree.prove_key_transition("a", 1, 3).unwrap();
+        assert!(grown.changed());
+        assert_eq!(grown.ops, vec![(3, insert("a", "fa2"))]);
+        assert!(grown.verify(&e1, &e3));
+        assert!(!grown.verify(&e1, &e2));
+
+        // Hiding the operation leaves the change unexplained
+        let mut hidden = grown.clone();
+        hidden.ops.clear();
+        assert!(hidden.check(&e1, &e3).is_err());
+
+        // Keys that appear or disappear are proven absent on the other side
+        let created = tree.prove_key_transition("c", 1, 2).unwrap();
+        assert!(created.before.live_fids().is_none() && created.verify(&e1, &e2));
+        let deleted = tree.prove_key_transition("b", 2, 3).unwrap();
+        assert!(deleted.after.live_fids().is_none() && deleted.verify(&e2, &e3));
+
+        tree.retain_history(1);
+        assert!(tree.prove_key_transition("a", 2, 3).is_err());
+        assert!(
+            tree.prove_key_transition("a", 3, 3)
+                .unwrap()
+                .verify(&e3, &e3)
+        );
+    }
+}
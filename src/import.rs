@@ -0,0 +1,204 @@
+//! Verifiable bulk import from a Certificate-Transparency-style log.
+//!
+//! A source log exports its leaves sorted by `(key, fid)`, each leaf the
+//! canonical encoding of the pair, together with its RFC 6962 Merkle root. The
+//! importer checks the stream against that root, rebuilds an `AccumulatorTree`
+//! from it and returns an `ImportProof` tying the source root to the first
+//! commitment of the new tree. Anyone holding the same stream can rerun the
+//! import and compare; anyone holding just the two roots can check the proof
+//! is about the trees they trust.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::epoch::EpochCommitment;
+use crate::wire::{CanonicalDecode, WireReader};
+use accumulator_ads::Set;
+use sha2::{Digest, Sha256};
+
+const MULTISET_DOMAIN: &[u8] = b"acc-tree/import-keys/v1";
+
+/// RFC 6962 leaf hash: `SHA-256(0x00 || leaf)`.
+pub fn ct_leaf_hash(leaf: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+/// RFC 6962 Merkle tree hash over leaf hashes, splitting at the largest power
+/// of two below the leaf count.
+pub fn ct_root(leaf_hashes: &[Hash]) -> Hash {
+    match leaf_hashes.len() {
+        0 => Sha256::digest([]).into(),
+        1 => leaf_hashes[0],
+        n => {
+            let k = 1 << (n - 1).ilog2();
+            let mut hasher = Sha256::new();
+            hasher.update([0x01]);
+            hasher.update(ct_root(&leaf_hashes[..k]));
+            hasher.update(ct_root(&leaf_hashes[k..]));
+            hasher.finalize().into()
+        }
+    }
+}
+
+/// Leaf bytes for one `(key, fid)` pair.
+pub fn encode_source_leaf(key: &str, fid: &str) -> Vec<u8> {
+    (key.to_string(), fid.to_string()).canonical_bytes()
+}
+
+pub fn decode_source_leaf(leaf: &[u8]) -> Result<(String, String), String> {
+    let mut r = WireReader::new(leaf);
+    let pair = <(String, String)>::decode_canonical(&mut r)?;
+    r.finish()?;
+    Ok(pair)
+}
+
+/// Links a source log to the tree rebuilt from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportProof {
+    /// RFC 6962 root of the imported log
+    pub source_root: Hash,
+    /// Number of leaves in the log, one per `(key, fid)`
+    pub source_size: u64,
+    /// SHA-256 over the keys of every leaf in log order (the key multiset)
+    pub key_multiset: Hash,
+    /// First commitment published by the rebuilt tree
+    pub commitment: EpochCommitment,
+}
+
+impl ImportProof {
+    /// Check the proof against the source root and target commitment digest
+    /// the verifier trusts. Without the leaves this cannot see inside either
+    /// structure, but it rules out proofs for other logs or other trees and
+    /// counts that cannot match (more keys than leaves).
+    pub fn verify(&self, trusted_source_root: &Hash, trusted_target: &Hash) -> bool {
+        self.source_root == *trusted_source_root
+            && self.commitment.digest() == *trusted_target
+            && self.commitment.epoch == 1
+            && self.commitment.key_count <= self.source_size
+    }
+
+    /// Full check for a consumer holding the log: rerun the import and require
+    /// the same root, leaf count, key multiset and tree commitment.
+    pub fn verify_with_leaves(&self, leaves: &[Vec<u8>]) -> Result<(), String> {
+        let (_, rebuilt) = import_sorted_log(leaves.iter().cloned(), &self.source_root)?;
+        if rebuilt != *self {
+            return Err("log does not reproduce the imported tree".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Rebuild a tree from a sorted log whose RFC 6962 root is `source_root`.
+/// Leaves must be strictly increasing in `(key, fid)`, so no pair is dropped
+/// by set semantics. The returned tree has already published its first epoch.
+pub fn import_sorted_log<I>(
+    leaves: I,
+    source_root: &Hash,
+) -> Result<(crate::AccumulatorTree, ImportProof), String>
+where
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    let mut leaf_hashes = Vec::new();
+    let mut multiset = Sha256::new();
+    multiset.update(MULTISET_DOMAIN);
+    let mut entries: Vec<(String, Set<String>)> = Vec::new();
+    let mut last: Option<(String, String)> = None;
+
+    for (i, leaf) in leaves.into_iter().enumerate() {
+        leaf_hashes.push(ct_leaf_hash(&leaf));
+        let (key, fid) = decode_source_leaf(&leaf).map_err(|e| format!("leaf {}: {}", i, e))?;
+        if last
+            .as_ref()
+            .is_some_and(|prev| *prev >= (key.clone(), fid.clone()))
+        {
+            return Err(format!("leaf {} is out of order or repeated", i));
+        }
+        multiset.update(key.canonical_bytes());
+        match entries.last_mut() {
+            Some((k, fids)) if *k == key => {
+                fids.insert(fid.clone());
+            }
+            _ => entries.push((key.clone(), Set::from_vec(vec![fid.clone()]))),
+        }
+        last = Some((key, fid));
+    }
+
+    if ct_root(&leaf_hashes) != *source_root {
+        return Err("leaves do not hash to the source root".to_string());
+    }
+    let mut tree = crate::AccumulatorTree::build_parallel(entries, 0)?;
+    let commitment = tree.publish_epoch();
+    let proof = ImportProof {
+        source_root: *source_root,
+        source_size: leaf_hashes.len() as u64,
+        key_multiset: multiset.finalize().into(),
+        commitment,
+    };
+    Ok((tree, proof))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(pairs: &[(&str, &str)]) -> (Vec<Vec<u8>>, Hash) {
+        let leaves: Vec<Vec<u8>> = pairs
+            .iter()
+            .map(|(k, f)| encode_source_leaf(k, f))
+            .collect();
+        let hashes: Vec<Hash> = leaves.iter().map(|l| ct_leaf_hash(l)).collect();
+        let root = ct_root(&hashes);
+        (leaves, root)
+    }
+
+    #[test]
+    fn test_ct_root_matches_rfc6962_shape() {
+        let h: Vec<Hash> = (0u8..3).map(|i| ct_leaf_hash(&[i])).collect();
+        let node =
+            |l: Hash, r: Hash| -> Hash { Sha256::digest([&[0x01][..], &l, &r].concat()).into() };
+        assert_eq!(ct_root(&h), node(node(h[0], h[1]), h[2]));
+        assert_eq!(
+            hex::encode(ct_root(&[])),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_import_matches_inserts() {
+        crate::setup::init_test_parameters();
+        let pairs = [("a", "1"), ("a", "2"), ("b", "3"), ("c", "4"), ("d", "5")];
+        let (leaves, root) = log(&pairs);
+        let (tree, proof) = import_sorted_log(leaves.clone(), &root).unwrap();
+
+        let mut expected = crate::AccumulatorTree::new();
+        for (k, f) in pairs {
+            expected.insert(k.to_string(), f.to_string());
+        }
+        assert_eq!(proof.commitment, expected.publish_epoch());
+        assert_eq!(tree.select("a").unwrap().len(), 2);
+        assert_eq!(proof.source_size, 5);
+        assert!(proof.verify(&root, &proof.commitment.digest()));
+        assert!(proof.verify_with_leaves(&leaves).is_ok());
+
+        // Dropping a leaf changes the root the importer sees
+        let (fewer, fewer_root) = log(&pairs[..4]);
+        assert!(import_sorted_log(fewer.clone(), &root).is_err());
+        assert!(proof.verify_with_leaves(&fewer).is_err());
+        assert!(!proof.verify(&fewer_root, &proof.commitment.digest()));
+    }
+
+    #[test]
+    fn test_unsorted_or_repeated_leaves_rejected() {
+        crate::setup::init_test_parameters();
+        let (leaves, root) = log(&[("b", "1"), ("a", "1")]);
+        assert!(import_sorted_log(leaves, &root).is_err());
+        let (leaves, root) = log(&[("a", "1"), ("a", "1")]);
+        assert!(import_sorted_log(leaves, &root).is_err());
+        let (mut leaves, _) = log(&[("a", "1")]);
+        leaves[0].push(0);
+        let root = ct_root(&[ct_leaf_hash(&leaves[0])]);
+        assert!(import_sorted_log(leaves, &root).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Composite and user-defined keys.
+//!
+//! The tree indexes `String` keys. A user type becomes a key by giving it a
+//! canonical encoding: `impl_acc_key!` derives that encoding from the listed
+//! fields (each length- or width-prefixed, so `("ab", "c")` and `("a", "bc")`
+//! never collide), a `Digestible` impl, and the `AccKey` string form the tree
+//! stores. The digest of a value equals the digest of its tree key, so a
+//! `Set<MyKey>` accumulates to the same value as the tree does.
+//!
+//! Byte strings (`[u8]`, `Vec<u8>`) and 32-byte hashes are keys as they are,
+//! so UUIDs, content hashes and binary blobs need no string conversion of
+//! their own. FIDs are strings too; an `AccValue` maps a value type to and
+//! from its FID without loss. `AccumulatorTree::insert_typed` and friends take
+//! both.
+
+pub use accumulator_ads::digest::{Digest, Digestible};
+
+use crate::canonical::CanonicalEncode;
+
+/// A value usable as a tree key.
+pub trait AccKey: CanonicalEncode {
+    /// String key stored in the tree: hex of the canonical encoding.
+    fn tree_key(&self) -> String {
+        hex::encode(self.canonical_bytes())
+    }
+}
+
+impl AccKey for [u8] {}
+
+impl AccKey for Vec<u8> {}
+
+/// Fixed width, so the tree key is the plain hex of the 32 bytes.
+impl AccKey for crate::Hash {}
+
+/// A value stored as a FID.
+pub trait AccValue: Sized {
+    /// FID the value is stored as.
+    fn to_fid(&self) -> String;
+
+    /// Value stored as `fid`, or `None` if `fid` was not written by `to_fid`.
+    fn from_fid(fid: &str) -> Option<Self>;
+}
+
+impl AccValue for String {
+    fn to_fid(&self) -> String {
+        self.clone()
+    }
+
+    fn from_fid(fid: &str) -> Option<Self> {
+        Some(fid.to_string())
+    }
+}
+
+/// Hex, so any byte string round-trips.
+impl AccValue for Vec<u8> {
+    fn to_fid(&self) -> String {
+        hex::encode(self)
+    }
+
+    fn from_fid(fid: &str) -> Option<Self> {
+        hex::decode(fid).ok()
+    }
+}
+
+impl AccValue for crate::Hash {
+    fn to_fid(&self) -> String {
+        hex::encode(self)
+    }
+
+    fn from_fid(fid: &str) -> Option<Self> {
+        hex::decode(fid).ok()?.try_into().ok()
+    }
+}
+
+/// Implement `CanonicalEncode`, `Digestible` and `AccKey` for a struct from its
+/// fields, in the given order. Every field must implement `CanonicalEncode`.
+///
+/// ```
+/// use accumulator_tree::{AccKey, impl_acc_key};
+///
+/// struct TenantTerm {
+///     tenant: String,
+///     term: String,
+/// }
+/// impl_acc_key!(TenantTerm { tenant, term });
+///
+/// let a = TenantTerm { tenant: "acme".into(), term: "rust".into() };
+/// let b = TenantTerm { tenant: "acm".into(), term: "erust".into() };
+/// assert_ne!(a.tree_key(), b.tree_key());
+/// ```
+#[macro_export]
+macro_rules! impl_acc_key {
+    ($t:ty { $($field:ident),+ $(,)? }) => {
+        impl $crate::canonical::CanonicalEncode for $t {
+            fn encode_canonical(&self, out: &mut Vec<u8>) {
+                $( $crate::canonical::CanonicalEncode::encode_canonical(&self.$field, out); )+
+            }
+        }
+
+        impl $crate::key::Digestible for $t {
+            fn to_digest(&self) -> $crate::key::Digest {
+                $crate::key::Digestible::to_digest(&$crate::key::AccKey::tree_key(self))
+            }
+        }
+
+        impl $crate::key::AccKey for $t {}
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use accumulator_ads::{DynamicAccumulator, Set, digest_set_from_set};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct TenantTerm {
+        tenant: String,
+        term: String,
+        shard: u32,
+    }
+
+    impl_acc_key!(TenantTerm {
+        tenant,
+        term,
+        shard
+    });
+
+    fn tt(tenant: &str, term: &str, shard: u32) -> TenantTerm {
+        TenantTerm {
+            tenant: tenant.to_string(),
+            term: term.to_string(),
+            shard,
+        }
+    }
+
+    #[test]
+    fn test_field_boundaries_do_not_collide() {
+        assert_ne!(tt("ab", "c", 0).tree_key(), tt("a", "bc", 0).tree_key());
+        assert_ne!(tt("a", "b", 0).tree_key(), tt("a", "b", 1).tree_key());
+        assert_eq!(
+            tt("a", "b", 7).to_digest(),
+            tt("a", "b", 7).tree_key().to_digest()
+        );
+    }
+
+    #[test]
+    fn test_composite_keys_in_tree() {
+        crate::setup::init_test_parameters();
+        let keys = vec![
+            tt("acme", "rust", 0),
+            tt("acme", "go", 0),
+            tt("globex", "rust", 1),
+        ];
+        let mut tree = AccumulatorTree::new();
+        for (i, k) in keys.iter().enumerate() {
+            tree.insert(k.tree_key(), format!("doc{}", i));
+        }
+        let resp = tree
+            .select_with_proof(&tt("acme", "go", 0).tree_key())
+            .unwrap();
+        assert_eq!(resp.fids, Some(Set::from_vec(vec!["doc1".to_string()])));
+        assert!(resp.verify_by_mode(&tt("acme", "go", 0).tree_key()));
+
+        // A set of composite keys accumulates to the tree's global accumulator
+        let set = Set::from_vec(keys);
+        let acc = DynamicAccumulator::calculate_commitment(&digest_set_from_set(&set));
+        assert_eq!(acc, tree.global_acc());
+    }
+
+    #[test]
+    fn test_byte_keys_and_values() {
+        crate::setup::init_test_parameters();
+        let blob = vec![0xffu8, 0x00, 0x80];
+        let digest = [9u8; 32];
+        assert_eq!(blob.tree_key(), blob.as_slice().tree_key());
+        assert_eq!(digest.tree_key(), hex::encode(digest));
+        assert_eq!(Vec::<u8>::from_fid(&blob.to_fid()), Some(blob.clone()));
+        assert_eq!(<[u8; 32]>::from_fid(&blob.to_fid()), None);
+        assert_eq!(Vec::<u8>::from_fid("not hex"), None);
+
+        let mut tree = AccumulatorTree::new();
+        assert!(tree.insert_typed(blob.as_slice(), &digest));
+        assert!(tree.insert_typed(blob.as_slice(), &[1u8; 32]));
+        assert!(tree.insert_typed(&digest, &blob));
+        assert_eq!(
+            tree.select_typed::<_, [u8; 32]>(&blob),
+            Some(vec![[1u8; 32], digest])
+        );
+        assert_eq!(
+            tree.select_typed::<_, Vec<u8>>(&digest),
+            Some(vec![blob.clone()])
+        );
+        // Wrong value type: nothing decodes
+        assert_eq!(tree.select_typed::<_, [u8; 32]>(&digest), Some(vec![]));
+
+        assert!(tree.delete_typed(&digest, &blob));
+        assert_eq!(tree.select_typed::<_, Vec<u8>>(&digest), None);
+        assert!(!tree.delete_typed(&digest, &blob));
+    }
+}
@@ -0,0 +1,97 @@
+//! Bloom filters over the keys of a subtree.
+//!
+//! Upper `NonLeaf` nodes route lookups with a `KeyFilter` instead of a copy of
+//! every key below them. A filter answers "maybe" or "no": a "no" is exact, so
+//! lookups only descend into children that may hold the key, and a false
+//! "maybe" costs one wasted step down. At ten bits per key the false positive
+//! rate is about 1%, against tens of bytes per key for a `Set<String>` at every
+//! level of the tree.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+const BITS_PER_KEY: usize = 10;
+const PROBES: u64 = 7;
+
+/// 64-bit fingerprint of a key. Filters only live in memory, so the
+/// fixed-key std hasher is enough.
+fn fingerprint(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Bit positions probed for `key` in a filter of `words` words (double
+/// hashing over one fingerprint).
+fn probes(key: &str, words: usize) -> impl Iterator<Item = usize> {
+    let h = fingerprint(key);
+    let (h1, h2) = (h & 0xffff_ffff, (h >> 32) | 1);
+    let m = words as u64 * 64;
+    (0..PROBES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % m) as usize)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyFilter {
+    bits: Box<[u64]>,
+}
+
+impl KeyFilter {
+    /// Filter sized for `keys`, with all of them inserted.
+    pub fn from_keys<'a>(keys: impl ExactSizeIterator<Item = &'a String>) -> Self {
+        let words = (keys.len() * BITS_PER_KEY).div_ceil(64).max(1);
+        let mut filter = Self {
+            bits: vec![0u64; words].into_boxed_slice(),
+        };
+        for key in keys {
+            filter.insert(key);
+        }
+        filter
+    }
+
+    pub fn insert(&mut self, key: &str) {
+        for bit in probes(key, self.bits.len()) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// False means `key` was never inserted; true may be a false positive.
+    pub fn may_contain(&self, key: &str) -> bool {
+        probes(key, self.bits.len()).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Raw filter words, for snapshots.
+    pub(crate) fn words(&self) -> &[u64] {
+        &self.bits
+    }
+
+    pub(crate) fn from_words(words: Vec<u64>) -> Result<Self, String> {
+        if words.is_empty() {
+            return Err("key filter has no words".to_string());
+        }
+        Ok(Self {
+            bits: words.into_boxed_slice(),
+        })
+    }
+
+    /// Heap bytes used by the filter.
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives_and_few_false_positives() {
+        let keys: Vec<String> = (0..1000).map(|i| format!("key{}", i)).collect();
+        let filter = KeyFilter::from_keys(keys.iter());
+        assert!(keys.iter().all(|k| filter.may_contain(k)));
+        assert!(filter.size_bytes() <= 1000 * BITS_PER_KEY / 8 + 8);
+
+        let false_positives = (0..10_000)
+            .filter(|i| filter.may_contain(&format!("other{}", i)))
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+}
@@ -0,0 +1,117 @@
+//! Index from keys to the root holding their leaf.
+//!
+//! Finding a key's root used to mean asking every root in turn. The tree
+//! instead keeps a map from a 64-bit fingerprint of every key with a leaf
+//! (live or tombstoned) to the level of its root; in a normalized forest no
+//! two roots share a level, so the level names the root. Keys only move
+//! between roots when roots merge, which relabels the keys of the merged
+//! root, so keeping the map current costs amortized O(1) per insert.
+//!
+//! A key missing from the index has no leaf. Two keys may share a
+//! fingerprint, so a hit is checked against the root and the tree falls back
+//! to asking every root when it does not hold.
+
+use crate::node::Node;
+use std::collections::HashMap;
+use std::hash::{Hash as _, Hasher};
+use std::sync::Arc;
+
+/// Identifies a root of the forest by its level.
+pub type RootId = usize;
+
+/// Fixed-size stand-in for a key in the index.
+pub type KeyFingerprint = u64;
+
+/// Fingerprint of `key`; fast, but not collision resistant.
+pub fn fingerprint(key: &str) -> KeyFingerprint {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct KeyIndex {
+    roots: HashMap<KeyFingerprint, RootId>,
+}
+
+impl KeyIndex {
+    /// Index every leaf of `roots`.
+    pub(crate) fn build(roots: &[Arc<Node>]) -> Self {
+        let mut index = Self::default();
+        for root in roots {
+            index.assign(root);
+        }
+        index
+    }
+
+    /// Point every key with a leaf under `root` at it.
+    pub(crate) fn assign(&mut self, root: &Node) {
+        let level = root.level();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            match node {
+                Node::Leaf { key, .. } => {
+                    self.roots.insert(fingerprint(key), level);
+                }
+                Node::NonLeaf { left, right, .. } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<RootId> {
+        self.roots.get(&fingerprint(key)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AccumulatorTree;
+
+    /// Level of the root holding `key`, found by asking every root.
+    fn scan(tree: &AccumulatorTree, key: &str) -> Option<usize> {
+        tree.roots
+            .iter()
+            .find(|r| r.leaf_state(key).is_some())
+            .map(|r| r.level())
+    }
+
+    fn assert_consistent(tree: &AccumulatorTree, keys: &[String]) {
+        for key in keys {
+            assert_eq!(tree.locate(key), scan(tree, key), "{}", key);
+        }
+        assert_eq!(tree.locate("missing"), None);
+    }
+
+    #[test]
+    fn test_locate_follows_merges() {
+        crate::setup::init_test_parameters();
+        let keys: Vec<String> = (0..16).map(|i| format!("k{:02}", i)).collect();
+        let mut tree = AccumulatorTree::new();
+        for key in &keys[..7] {
+            tree.insert(key.clone(), "f".to_string());
+            assert_consistent(&tree, &keys);
+        }
+
+        tree.delete("k03", "f");
+        assert_eq!(tree.locate("k03"), scan(&tree, "k03"));
+        tree.insert_batch(keys[7..12].iter().map(|k| (k.clone(), "f".to_string())));
+        assert_consistent(&tree, &keys);
+
+        let mut txn = tree.begin_transaction();
+        for key in &keys[12..] {
+            txn.insert(key.clone(), "f".to_string());
+        }
+        txn.commit();
+        assert_consistent(&tree, &keys);
+
+        tree.compact();
+        assert_eq!(tree.locate("k03"), None);
+        assert_consistent(&tree, &keys);
+
+        let restored = AccumulatorTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_consistent(&restored, &keys);
+    }
+}
@@ -1,18 +1,160 @@
+//! Authenticated key/FID forest with Merkle and accumulator proofs.
+//!
+//! # Data model
+//!
+//! There is one tree, `AccumulatorTree`, and every leaf maps a key to a set
+//! of FIDs. `insert`, `update` and `delete` (and their `_with_proof` forms)
+//! each add, replace or remove a single FID of a key's set, through
+//! `Node::insert_fid`, `Node::update_fid` and `Node::delete_fid`; a key whose
+//! set becomes empty is tombstoned. A single-FID index is the special case of
+//! sets with one element, so it needs no separate tree.
+//!
+//! # Errors and panics
+//!
+//! Mutations of `AccumulatorTree` report why they were refused as an
+//! `AccTreeError` (missing key or FID, policy refusal, missing or too small
+//! public parameters, ...): `try_insert`, `try_insert_batch`, `try_update`
+//! and `try_delete`, whose plain forms return whether (or how much) they
+//! applied. So do the proof APIs (`select_with_proof`, `insert_with_proof`,
+//! `update_with_proof`, `delete_with_proof`, `revive_with_proof`, `freeze`,
+//! ...), which also fail when the parameters are missing or too small, and
+//! every other fallible `AccumulatorTree` method: construction
+//! (`build_parallel`, `bulk_load`, `with_expected_keys`), snapshots and node
+//! stores, range, sample and history proofs. Verifiers, wire decoding and
+//! parameter loading return `Result<_, String>`; an `AccTreeError` converts
+//! into a `String`. A panic means an internal invariant was broken.
+//!
+//! Load the parameters with `init` or `init_from_ptau` (or `init_for_testing`
+//! with the `insecure-test-params` feature) before using the tree; `ready` reports whether that has
+//! happened. Until then `try_insert` and `try_delete` refuse to add a key next
+//! to the first leaf or drop one from a merged root, with
+//! `ParamsNotInitialized`. Inserts
+//! that would merge a root with more live keys than the parameters' degree
+//! fail with `DegreeExceeded` before anything changes.
+
 // 模块声明
 pub mod node;
 pub mod tree;
 
 pub mod acc_proof;
+pub mod anchor;
+#[cfg(feature = "signed-roots")]
+pub mod announce;
+pub mod archive;
+pub mod arena;
+pub mod backend;
+pub mod batch;
+pub mod bundle;
+pub mod canonical;
+pub mod clock;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod epoch;
+pub mod equality;
+pub mod error;
+pub mod fid_tree;
+pub mod frozen;
+pub mod gap;
+pub mod history;
+pub mod import;
+pub mod key;
+pub mod key_filter;
+pub mod key_index;
+pub mod lookup;
 pub mod merkle_proof;
+pub mod metrics;
+pub mod misbehavior;
+pub mod multiproof;
+pub mod node_store;
+pub mod peaks;
+pub mod policy;
+pub mod prelude;
+pub mod query;
+pub mod range;
+pub mod range_stream;
+pub mod replay;
 pub mod response;
+pub mod response_builder;
+pub mod sample;
+pub mod setup;
+pub mod snapshot;
+pub mod storage;
+pub mod stream;
+pub mod txn;
 pub mod utils;
+pub mod validate;
+pub mod verifier;
+pub mod watch;
+pub mod wire;
+pub mod witness_cache;
+pub mod witness_delta;
 
 // 对外暴露的公共 API
-pub use utils::{Hash, empty_acc, empty_hash, nonleaf_hash, leaf_hash, print_tree, render_keys};
 pub use node::Node;
-pub use tree::AccumulatorTree;
+pub use tree::{AccumulatorTree, Iter, RootInfo};
+pub use utils::{Hash, empty_acc, empty_hash, leaf_hash, nonleaf_hash, print_tree, render_keys};
 
 pub use acc_proof::NonMembershipProof;
+pub use anchor::{Anchor, AnchorReceipt, HttpPost, TsaAnchor, WebhookAnchor};
+#[cfg(feature = "signed-roots")]
+pub use announce::RootAnnouncement;
+pub use archive::{ArchiveConfig, ArchiveKey, ProofArchive};
+pub use backend::{NodeAccumulator, PairingAccumulator};
+#[cfg(feature = "rsa-backend")]
+pub use backend::{RsaAccumulator, RsaNonMembershipWitness, RsaWitness};
+pub use batch::{BatchReport, RootTransition};
+pub use bundle::VerifierParams;
+#[cfg(feature = "signed-roots")]
+pub use bundle::VerifierBundle;
+pub use canonical::CanonicalEncode;
+pub use clock::{Clock, MockClock, SystemClock};
+#[cfg(feature = "client")]
+pub use client::{CommitmentTracker, FreshnessPolicy};
+pub use epoch::{
+    EpochCommitment, EpochTransition, GENESIS_EPOCH, RootManifest, RootStamp, genesis_commitment,
+};
+pub use equality::{KeySetEqualityProof, migrate_entries, prove_equal_keysets};
+pub use error::AccTreeError;
+pub use frozen::FrozenTree;
+pub use gap::{GapLeaf, GapProof, RootGap};
+pub use history::{KeyState, KeyTransitionProof};
+pub use import::{ImportProof, import_sorted_log};
+pub use key::{AccKey, AccValue};
+pub use lookup::LookupResult;
 pub use merkle_proof::Proof as MerkleProof;
-pub use response::{DeleteResponse, InsertResponse, QueryResponse, UpdateResponse};
-
+pub use metrics::{ProofMetrics, ProofOp, ProofStats};
+pub use misbehavior::{Claim, MisbehaviorEvidence};
+pub use multiproof::{MultiLeaf, MultiProof, RootMultiProof};
+pub use node_store::{NodeStore, StoredNode};
+pub use policy::{
+    LeafOrder, OrderPolicy, ProofMode, ProofPolicy, RevivePolicy, VerificationLevel,
+    VerifierCapabilities,
+};
+pub use peaks::{Peak, PeakProof, bag_peaks};
+pub use query::{BoolOp, Query};
+pub use range::{RangeEntry, RangeResponse};
+pub use range_stream::{RangeHeader, RangeItem, RangeStream, RangeVerifier};
+pub use replay::{Divergence, ReplayLog, ReplayStep};
+pub use response::{
+    DeleteResponse, FidAnswer, FidQueryResponse, InsertResponse, QueryResponse, ReviveResponse,
+    TransactionResponse, UpdateResponse, verify_batch,
+};
+pub use response_builder::{DeleteResponseBuilder, InsertResponseBuilder, UpdateResponseBuilder};
+pub use sample::KeySample;
+#[cfg(feature = "insecure-test-params")]
+pub use setup::init_for_testing;
+pub use setup::{SetupFeatures, init, init_from_ptau, init_pinned, ready, required_setup_degree};
+pub use snapshot::TreeSnapshot;
+pub use storage::{FsStore, MemoryStore, ObjectStore};
+#[cfg(feature = "s3")]
+pub use storage::{S3Config, S3Store};
+#[cfg(feature = "sled-store")]
+pub use storage::SledStore;
+pub use stream::{ProofWriter, StreamFormat, stream_point_proofs};
+pub use txn::{TreeTxn, TxnOp};
+pub use validate::Validate;
+pub use verifier::Verifier;
+pub use watch::{ConsistencyProof, EpochDelta};
+pub use wire::{CanonicalDecode, WireMessage, from_wire, to_wire};
+pub use witness_cache::WitnessCache;
+pub use witness_delta::{AccChange, CompactionDelta};
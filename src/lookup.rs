@@ -0,0 +1,89 @@
+//! Typed result of a key lookup.
+//!
+//! `select` collapses "never inserted" and "deleted" into `None`. Tombstoned
+//! leaves stay in the tree to keep sibling hashes stable, so the tree can tell
+//! the two apart; `LookupResult` surfaces that distinction to callers.
+
+use accumulator_ads::Set;
+
+/// State of a key in the forest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupResult {
+    /// The key is live with this FID set
+    Live(Set<String>),
+    /// The key existed but its leaf is tombstoned.
+    /// `deleted_epoch` is the first epoch whose publication reflects the deletion,
+    /// or `None` if the tombstone was not created through this tree instance
+    /// (e.g. roots restored directly).
+    Tombstoned { deleted_epoch: Option<u64> },
+    /// No leaf for the key has ever existed in the forest
+    Absent,
+}
+
+impl LookupResult {
+    pub fn is_live(&self) -> bool {
+        matches!(self, LookupResult::Live(_))
+    }
+
+    pub fn is_tombstoned(&self) -> bool {
+        matches!(self, LookupResult::Tombstoned { .. })
+    }
+
+    /// FID set of a live key.
+    pub fn fids(&self) -> Option<&Set<String>> {
+        match self {
+            LookupResult::Live(fids) => Some(fids),
+            _ => None,
+        }
+    }
+
+    /// Collapse into the `select`-style `Option`.
+    pub fn into_fids(self) -> Option<Set<String>> {
+        match self {
+            LookupResult::Live(fids) => Some(fids),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_live_tombstoned_absent() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "f1".to_string());
+        tree.insert("b".to_string(), "f2".to_string());
+        tree.publish_epoch();
+        tree.delete("b", "f2");
+
+        assert_eq!(
+            tree.select_status("a"),
+            LookupResult::Live(Set::from_vec(vec!["f1".to_string()]))
+        );
+        assert_eq!(
+            tree.select_status("b"),
+            LookupResult::Tombstoned {
+                deleted_epoch: Some(2)
+            }
+        );
+        assert_eq!(tree.select_status("c"), LookupResult::Absent);
+        assert_eq!(tree.select("b"), None);
+        assert_eq!(tree.select("c"), None);
+    }
+
+    #[test]
+    fn test_partial_delete_stays_live() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "f1".to_string());
+        tree.insert("a".to_string(), "f2".to_string());
+        tree.delete("a", "f1");
+        assert!(tree.select_status("a").is_live());
+        tree.delete("a", "f2");
+        assert!(tree.select_status("a").is_tombstoned());
+    }
+}
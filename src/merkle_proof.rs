@@ -1,7 +1,9 @@
+use crate::canonical::{CanonicalEncode, impl_canonical_eq};
 use crate::{Hash, nonleaf_hash};
 use accumulator_ads::Set;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Proof {
     /// hash of the root of the subtree used for the proof
     pub root_hash: Hash,
@@ -21,6 +23,36 @@ impl Proof {
         }
     }
 
+    /// Build a proof from untrusted byte parts (e.g. fresh off the wire).
+    /// Rejects hashes that are not exactly 32 bytes and paths longer than
+    /// `validate::MAX_PATH_LEN`.
+    pub fn try_new<B: AsRef<[u8]>>(
+        root_hash: &[u8],
+        leaf_hash: &[u8],
+        path: impl IntoIterator<Item = (B, bool)>,
+    ) -> Result<Self, String> {
+        use crate::validate::{Validate, hash_from_slice};
+
+        let root_hash = hash_from_slice(root_hash, "root hash")?;
+        let leaf_hash = hash_from_slice(leaf_hash, "leaf hash")?;
+        let mut siblings = Vec::new();
+        for (i, (sib, is_left)) in path.into_iter().enumerate() {
+            crate::validate::check_path_len(i + 1, "merkle proof")?;
+            let sib = hash_from_slice(sib.as_ref(), &format!("path[{}] sibling hash", i))?;
+            siblings.push((sib, is_left));
+        }
+        let proof = Self::new(root_hash, leaf_hash, siblings);
+        proof.validate()?;
+        Ok(proof)
+    }
+
+    /// Return the canonical form of this proof. A Merkle path has exactly one
+    /// valid shape for a given leaf and root, so this is a structural copy;
+    /// it exists so every proof type can be normalized the same way.
+    pub fn canonicalize(&self) -> Self {
+        self.clone()
+    }
+
     /// Verify the proof by recomputing the root hash from the leaf and path
     pub fn verify(&self) -> bool {
         let mut cur = self.leaf_hash;
@@ -34,6 +66,15 @@ impl Proof {
         cur == self.root_hash
     }
 
+    /// Verify the path up to the root `peak` and that root into the forest
+    /// `digest` (see `peaks`). The path must be as long as the peak's level.
+    pub fn verify_in_digest(&self, peak: &crate::peaks::PeakProof, digest: &Hash) -> bool {
+        self.root_hash == peak.peak.hash
+            && self.path.len() as u64 == peak.peak.level
+            && self.verify()
+            && peak.verify(digest)
+    }
+
     /// Convenience: recompute the leaf hash from `key`/`fids` and verify this proof.
     /// Assumes level=0 and deleted=false (standard existence check).
     /// Returns false if the recomputed leaf hash does not match `self.leaf_hash`.
@@ -51,6 +92,16 @@ impl Proof {
     }
 }
 
+impl CanonicalEncode for Proof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.root_hash.encode_canonical(out);
+        self.leaf_hash.encode_canonical(out);
+        self.path.encode_canonical(out);
+    }
+}
+
+impl_canonical_eq!(Proof);
+
 /// Unit tests for Merkle proof verification
 /// 
 /// These tests verify the correctness of proof construction and verification logic.
@@ -162,4 +213,21 @@ mod tests {
         let proof = Proof::new(wrong_root, leaf_a, vec![(leaf_b, false)]);
         assert!(!proof.verify());
     }
+
+    #[test]
+    fn test_try_new_validates_parts() {
+        let leaf = leaf_hash("A", &Set::from_vec(vec!["fa".to_string()]), 0, false);
+        let sib = [9u8; 32];
+        let root = nonleaf_hash(leaf, sib);
+
+        let proof = Proof::try_new(&root, &leaf, vec![(sib.to_vec(), false)]).unwrap();
+        assert!(proof.verify());
+
+        assert!(Proof::try_new(&root[..31], &leaf, Vec::<(Vec<u8>, bool)>::new()).is_err());
+        let err = Proof::try_new(&root, &leaf, vec![(vec![0u8; 33], false)]).unwrap_err();
+        assert!(err.contains("path[0] sibling hash"));
+
+        let too_deep = vec![(sib, true); crate::validate::MAX_PATH_LEN + 1];
+        assert!(Proof::try_new(&root, &leaf, too_deep).is_err());
+    }
 }
@@ -0,0 +1,109 @@
+//! Proof overhead per operation.
+//!
+//! Every proof-carrying response the tree builds (`select_with_proof`,
+//! `insert_with_proof`, `update_with_proof`, `delete_with_proof`,
+//! `revive_with_proof`) carries `ProofStats` in its `stats` field: how many
+//! canonical bytes its Merkle paths and accumulator witnesses take, and how
+//! long the tree spent building it. Stats describe the prover's work rather
+//! than the answer, so they are not part of the wire or canonical encoding and
+//! a decoded response has none.
+//!
+//! Operators who want the numbers without inspecting responses register a
+//! `ProofMetrics` hook with `AccumulatorTree::set_metrics`; it is handed the
+//! stats of every response as it is built.
+
+use std::time::Duration;
+
+/// Size and cost of one proof-carrying response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProofStats {
+    /// Canonical bytes of the Merkle paths in the response
+    pub merkle_bytes: usize,
+    /// Canonical bytes of the accumulator values and witnesses
+    pub witness_bytes: usize,
+    /// Time the tree spent building the response
+    pub prover_time: Duration,
+}
+
+impl ProofStats {
+    /// Bytes of proof material in the response.
+    pub fn total_bytes(&self) -> usize {
+        self.merkle_bytes + self.witness_bytes
+    }
+}
+
+/// Operation a `ProofStats` was measured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProofOp {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    Revive,
+}
+
+/// Hook handed the stats of every proof-carrying response the tree builds.
+/// Shared with the tree, so it must be usable from any thread.
+pub trait ProofMetrics: Send + Sync {
+    fn record(&self, op: ProofOp, stats: &ProofStats);
+}
+
+/// Responses that carry `ProofStats`.
+pub(crate) trait Instrumented {
+    /// Canonical bytes of the Merkle paths and of the accumulator parts.
+    fn proof_sizes(&self) -> (usize, usize);
+
+    fn set_stats(&mut self, stats: ProofStats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use crate::response::InsertResponse;
+    use crate::wire::{from_wire, to_wire};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(ProofOp, ProofStats)>>);
+
+    impl ProofMetrics for Recorder {
+        fn record(&self, op: ProofOp, stats: &ProofStats) {
+            self.0.lock().unwrap().push((op, *stats));
+        }
+    }
+
+    #[test]
+    fn test_responses_carry_recorded_stats() {
+        crate::setup::init_test_parameters();
+        let recorder = Arc::new(Recorder::default());
+        let mut tree = AccumulatorTree::new();
+        tree.set_metrics(recorder.clone());
+        tree.insert("a".to_string(), "f1".to_string());
+        tree.insert("b".to_string(), "f2".to_string());
+
+        let inserted = tree
+            .insert_with_proof("c".to_string(), "f3".to_string())
+            .unwrap();
+        let selected = tree.select_with_proof("a").unwrap();
+        let deleted = tree.delete_with_proof("b", "f2").unwrap();
+        assert!(selected.verify_by_mode("a"));
+
+        // Mutations report once, not again for the selects they embed
+        let recorded = recorder.0.lock().unwrap().clone();
+        let ops: Vec<ProofOp> = recorded.iter().map(|(op, _)| *op).collect();
+        assert_eq!(ops, [ProofOp::Insert, ProofOp::Select, ProofOp::Delete]);
+        let stats = [inserted.stats, selected.stats, deleted.stats].map(Option::unwrap);
+        for ((_, seen), stats) in recorded.iter().zip(stats) {
+            assert_eq!(*seen, stats);
+            assert!(stats.merkle_bytes > 0 && stats.witness_bytes > 0);
+        }
+        // A delete carries the paths before and after
+        assert!(deleted.stats.unwrap().merkle_bytes > selected.stats.unwrap().merkle_bytes);
+
+        // Stats stay with the prover
+        let decoded: InsertResponse = from_wire(&to_wire(&inserted)).unwrap();
+        assert!(decoded.stats.is_none());
+        assert_eq!(decoded, inserted);
+    }
+}
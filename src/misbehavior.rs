@@ -0,0 +1,252 @@
+//! Evidence of server misbehavior.
+//!
+//! An honest forest holds at most one leaf per key, so within one published
+//! state a key has a single FID set and is either a member or not. Two valid
+//! proofs that contradict each other for the same key and the same forest state
+//! prove the server equivocated. `MisbehaviorEvidence` bundles such a pair so a
+//! third party can re-check it without access to the tree.
+
+use crate::Hash;
+use crate::acc_proof::{AccProof, NonMembershipProof};
+use crate::canonical::CanonicalEncode;
+use crate::epoch::{RootEntry, check_proof_depth, forest_hash};
+use crate::response::QueryResponse;
+
+/// A proof a client received, with what is needed to place it in a forest state.
+#[derive(Debug, Clone)]
+pub enum Claim {
+    /// Membership answer, with the roots of the state it was served from
+    Membership {
+        response: Box<QueryResponse>,
+        epoch_roots: Vec<RootEntry>,
+    },
+    /// Non-membership proof bound to a published epoch
+    NonMembership(Box<NonMembershipProof>),
+}
+
+impl Claim {
+    /// Forest hash of the state the claim was made in.
+    pub fn forest_hash(&self) -> Option<Hash> {
+        match self {
+            Claim::Membership { epoch_roots, .. } => Some(forest_hash(epoch_roots)),
+            Claim::NonMembership(nm) => nm.commitment.as_ref().map(|c| c.forest_hash),
+        }
+    }
+
+    /// Check the claim on its own for `key`.
+    fn verify(&self, key: &str) -> bool {
+        match self {
+            Claim::Membership {
+                response,
+                epoch_roots,
+            } => {
+                let (Some(fids), Some(proof)) = (&response.fids, &response.merkle_proof) else {
+                    return false;
+                };
+                check_proof_depth(epoch_roots, proof).is_ok() && response.verify_full(key, fids)
+            }
+            Claim::NonMembership(nm) => match &nm.commitment {
+                Some(c) => nm.verify_in_epoch(key, &c.digest()),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Two valid, contradictory proofs for one key in one forest state.
+#[derive(Debug, Clone)]
+pub struct MisbehaviorEvidence {
+    pub key: String,
+    pub a: Claim,
+    pub b: Claim,
+}
+
+impl MisbehaviorEvidence {
+    /// Bundle two claims about `key` if they verify and contradict each other.
+    pub fn from_conflict(key: &str, a: Claim, b: Claim) -> Result<Self, String> {
+        let evidence = Self {
+            key: key.to_string(),
+            a,
+            b,
+        };
+        evidence.check()?;
+        Ok(evidence)
+    }
+
+    /// Wrap a `QueryResponse` pair served from the state with `epoch_roots`.
+    /// Non-membership answers are taken from the response's proof.
+    pub fn from_responses(
+        key: &str,
+        a: &QueryResponse,
+        b: &QueryResponse,
+        epoch_roots: &[RootEntry],
+    ) -> Result<Self, String> {
+        let to_claim = |r: &QueryResponse| match &r.acc_proof {
+            Some(AccProof::NonMembership(nm)) => Claim::NonMembership(nm.clone()),
+            _ => Claim::Membership {
+                response: Box::new(r.clone()),
+                epoch_roots: epoch_roots.to_vec(),
+            },
+        };
+        Self::from_conflict(key, to_claim(a), to_claim(b))
+    }
+
+    /// Forest hash of the state the server equivocated on.
+    pub fn forest_hash(&self) -> Option<Hash> {
+        self.a.forest_hash()
+    }
+
+    /// Independently re-check the evidence.
+    pub fn verify(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Checks:
+    /// 1. Both claims verify on their own for `key`
+    /// 2. Both claims refer to the same forest state
+    /// 3. They contradict: member vs non-member, or two different FID sets
+    fn check(&self) -> Result<(), String> {
+        if !self.a.verify(&self.key) || !self.b.verify(&self.key) {
+            return Err(format!("a claim does not verify for key '{}'", self.key));
+        }
+        let state = self.a.forest_hash();
+        if state.is_none() || state != self.b.forest_hash() {
+            return Err("claims refer to different forest states".to_string());
+        }
+        match (&self.a, &self.b) {
+            (Claim::Membership { response: ra, .. }, Claim::Membership { response: rb, .. }) => {
+                if ra.fids == rb.fids {
+                    return Err("membership claims agree".to_string());
+                }
+                Ok(())
+            }
+            (Claim::NonMembership(_), Claim::NonMembership(_)) => {
+                Err("two non-membership claims cannot conflict".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl CanonicalEncode for Claim {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            Claim::Membership {
+                response,
+                epoch_roots,
+            } => {
+                out.push(0);
+                response.encode_canonical(out);
+                epoch_roots.encode_canonical(out);
+            }
+            Claim::NonMembership(nm) => {
+                out.push(1);
+                nm.encode_canonical(out);
+            }
+        }
+    }
+}
+
+impl CanonicalEncode for MisbehaviorEvidence {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.a.encode_canonical(out);
+        self.b.encode_canonical(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use crate::node::Node;
+    use accumulator_ads::Set;
+    use std::sync::Arc;
+
+    fn leaf(key: &str, fid: &str) -> Arc<Node> {
+        Arc::new(Node::leaf(
+            key.to_string(),
+            Set::from_vec(vec![fid.to_string()]),
+            false,
+        ))
+    }
+
+    #[test]
+    fn test_duplicate_leaf_equivocation() {
+        crate::setup::init_test_parameters();
+        // A malicious server builds a root holding two leaves for "k"
+        let mut tree = AccumulatorTree::new();
+        tree.roots
+            .push(Node::merge(leaf("k", "f1"), leaf("k", "f2"), None));
+        tree.index_roots([0]);
+        let roots = tree.root_entries();
+
+        // select finds the left leaf; build the proof for the right one by hand
+        let honest = tree.select_with_proof("k").unwrap();
+        assert!(honest.verify_by_mode("k"));
+        let fids = Set::from_vec(vec!["f2".to_string()]);
+        let left_hash = leaf("k", "f1").hash();
+        let mut forged = honest.clone();
+        forged.fids = Some(fids.clone());
+        forged.merkle_proof = Some(crate::merkle_proof::Proof::new(
+            roots[0].1,
+            crate::utils::leaf_hash("k", &fids, 0, false),
+            vec![(left_hash, true)],
+        ));
+
+        let evidence = MisbehaviorEvidence::from_responses("k", &honest, &forged, &roots).unwrap();
+        assert!(evidence.verify());
+        assert_eq!(evidence.forest_hash(), Some(forest_hash(&roots)));
+
+        // The same answer twice is not evidence
+        assert!(MisbehaviorEvidence::from_responses("k", &honest, &honest, &roots).is_err());
+    }
+
+    #[test]
+    fn test_membership_vs_nonmembership() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+        let commitment = tree.publish_epoch();
+        let roots = tree.root_entries();
+        let member = tree.select_with_proof("a").unwrap();
+        assert!(member.verify_by_mode("a"));
+
+        // A lying server proves "a" absent against an epoch claiming the same roots
+        let fake_keys = Set::from_vec(vec!["b".to_string()]);
+        let fake_acc = accumulator_ads::DynamicAccumulator::calculate_commitment(
+            &accumulator_ads::digest_set_from_set(&fake_keys),
+        );
+        let mut fake_commitment = commitment.clone();
+        fake_commitment.global_acc = fake_acc;
+        let lie = NonMembershipProof::new("a".to_string(), fake_acc, &fake_keys)
+            .unwrap()
+            .with_commitment(fake_commitment);
+
+        let evidence = MisbehaviorEvidence::from_conflict(
+            "a",
+            Claim::Membership {
+                response: Box::new(member.clone()),
+                epoch_roots: roots.clone(),
+            },
+            Claim::NonMembership(Box::new(lie)),
+        )
+        .unwrap();
+        assert!(evidence.verify());
+
+        // An honest non-membership proof for another key does not verify for "a"
+        let other = tree.select_nonmembership_proof("zzz").unwrap();
+        assert!(
+            MisbehaviorEvidence::from_conflict(
+                "a",
+                Claim::Membership {
+                    response: Box::new(member),
+                    epoch_roots: roots,
+                },
+                Claim::NonMembership(Box::new(other)),
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! One Merkle proof for many keys.
+//!
+//! Separate `Proof`s for keys under the same root repeat every sibling hash
+//! their paths have in common, and leave out none of the hashes the verifier
+//! could compute from the other leaves. A `RootMultiProof` instead carries the
+//! proven leaves of one root with their positions, plus only the hashes that
+//! cannot be derived from them. The verifier rebuilds the root level by level,
+//! bottom up and left to right, taking the next sibling hash whenever a node's
+//! sibling is not already known; the prover emits them in that order.
+//!
+//! A `MultiProof` groups the leaves by root. Keys with no live leaf are listed
+//! in `missing` without a proof; ask `select_with_proof` for their
+//! non-membership proofs.
+
+use crate::canonical::{CanonicalEncode, impl_canonical_eq};
+use crate::node::Node;
+use crate::validate::{MAX_PATH_LEN, Validate, check_path_len};
+use crate::{Hash, leaf_hash, nonleaf_hash};
+use accumulator_ads::Set;
+
+/// A proven leaf and its position under the root.
+#[derive(Debug, Clone)]
+pub struct MultiLeaf {
+    /// Index of the leaf among the `2^level` leaves of its root, left to right
+    pub index: u64,
+    pub key: String,
+    pub fids: Set<String>,
+}
+
+/// Leaves of one root and the sibling hashes needed to rebuild it.
+#[derive(Debug, Clone)]
+pub struct RootMultiProof {
+    pub root_hash: Hash,
+    pub level: u64,
+    /// Proven leaves in index order
+    pub leaves: Vec<MultiLeaf>,
+    /// Hashes the leaves do not determine, in the order the verifier uses them
+    pub siblings: Vec<Hash>,
+}
+
+/// Proof for a batch of keys, from `AccumulatorTree::get_many_with_proof`.
+#[derive(Debug, Clone)]
+pub struct MultiProof {
+    pub roots: Vec<RootMultiProof>,
+    /// Requested keys with no live leaf, sorted
+    pub missing: Vec<String>,
+}
+
+/// Hash `nodes` (index, hash), sorted by index, up `level` levels; `sibling`
+/// supplies the hash at (height, index) of every node not derivable from
+/// them. `None` if `sibling` does, or the nodes do not meet in a single root.
+pub(crate) fn climb(
+    level: u64,
+    mut nodes: Vec<(u64, Hash)>,
+    mut sibling: impl FnMut(u64, u64) -> Option<Hash>,
+) -> Option<Hash> {
+    for height in 0..level {
+        let mut next = Vec::with_capacity(nodes.len().div_ceil(2));
+        let mut i = 0;
+        while i < nodes.len() {
+            let (index, hash) = nodes[i];
+            let (left, right) = if index % 2 == 1 {
+                (sibling(height, index - 1)?, hash)
+            } else if let Some(&(_, right)) = nodes.get(i + 1).filter(|n| n.0 == index + 1) {
+                i += 1;
+                (hash, right)
+            } else {
+                (hash, sibling(height, index + 1)?)
+            };
+            next.push((index / 2, nonleaf_hash(left, right)));
+            i += 1;
+        }
+        nodes = next;
+    }
+    match nodes.as_slice() {
+        [(0, root)] => Some(*root),
+        _ => None,
+    }
+}
+
+/// Node at (`height`, `index`) under `root`.
+pub(crate) fn node_at(root: &Node, height: u64, index: u64) -> Option<&Node> {
+    let mut node = root;
+    for h in (height..root.level() as u64).rev() {
+        let Node::NonLeaf { left, right, .. } = node else {
+            return None;
+        };
+        node = if (index >> (h - height)) & 1 == 1 {
+            right
+        } else {
+            left
+        };
+    }
+    Some(node)
+}
+
+impl RootMultiProof {
+    /// Proof for the live `keys` under `root`; other keys are skipped.
+    pub(crate) fn new(root: &Node, keys: &[&str]) -> Self {
+        let mut leaves = Vec::new();
+        for key in keys {
+            let mut path = Vec::new();
+            if let Some(fids) = root.recurse_select_with_proof(key, &mut path) {
+                let index = path
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, sibling_is_left))| *sibling_is_left)
+                    .fold(0u64, |index, (bit, _)| index | (1 << bit));
+                leaves.push(MultiLeaf {
+                    index,
+                    key: key.to_string(),
+                    fids,
+                });
+            }
+        }
+        leaves.sort_by_key(|leaf| leaf.index);
+        leaves.dedup_by_key(|leaf| leaf.index);
+
+        let level = root.level() as u64;
+        let mut siblings = Vec::new();
+        let rebuilt = climb(level, Self::leaf_nodes(&leaves), |height, index| {
+            let hash = node_at(root, height, index)?.hash();
+            siblings.push(hash);
+            Some(hash)
+        });
+        debug_assert_eq!(rebuilt, Some(root.hash()));
+        Self {
+            root_hash: root.hash(),
+            level,
+            leaves,
+            siblings,
+        }
+    }
+
+    fn leaf_nodes(leaves: &[MultiLeaf]) -> Vec<(u64, Hash)> {
+        leaves
+            .iter()
+            .map(|leaf| (leaf.index, leaf_hash(&leaf.key, &leaf.fids, 0, false)))
+            .collect()
+    }
+
+    /// Rebuild the root from the leaves and siblings and compare it with
+    /// `root_hash`. Every sibling must be used.
+    pub fn verify(&self) -> bool {
+        if self.leaves.is_empty()
+            || self.level > MAX_PATH_LEN as u64
+            || self.leaves.windows(2).any(|w| w[0].index >= w[1].index)
+        {
+            return false;
+        }
+        let mut siblings = self.siblings.iter();
+        let root = climb(self.level, Self::leaf_nodes(&self.leaves), |_, _| {
+            siblings.next().copied()
+        });
+        root == Some(self.root_hash) && siblings.next().is_none()
+    }
+}
+
+impl MultiProof {
+    /// Verify every root group. The caller still has to check each
+    /// `root_hash` against roots it trusts, as with a single `Proof`.
+    pub fn verify(&self) -> bool {
+        self.roots.iter().all(RootMultiProof::verify)
+    }
+
+    /// FIDs the proof shows for `key`, if it is among the proven leaves.
+    pub fn fids(&self, key: &str) -> Option<&Set<String>> {
+        self.roots
+            .iter()
+            .flat_map(|r| &r.leaves)
+            .find(|leaf| leaf.key == key)
+            .map(|leaf| &leaf.fids)
+    }
+
+    /// Number of hashes carried besides the leaves.
+    pub fn sibling_count(&self) -> usize {
+        self.roots.iter().map(|r| r.siblings.len()).sum()
+    }
+}
+
+impl CanonicalEncode for MultiLeaf {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.index.encode_canonical(out);
+        self.key.encode_canonical(out);
+        self.fids.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for RootMultiProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.root_hash.encode_canonical(out);
+        self.level.encode_canonical(out);
+        self.leaves.encode_canonical(out);
+        self.siblings.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for MultiProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.roots.encode_canonical(out);
+        self.missing.encode_canonical(out);
+    }
+}
+
+impl_canonical_eq!(MultiLeaf, RootMultiProof, MultiProof);
+
+impl Validate for MultiProof {
+    fn validate(&self) -> Result<(), String> {
+        for root in &self.roots {
+            check_path_len(root.level as usize, "multiproof root level")?;
+            // Each leaf needs at most one sibling per level
+            if root.siblings.len() > root.leaves.len() * root.level as usize {
+                return Err(format!(
+                    "{} siblings for {} leaves at level {}",
+                    root.siblings.len(),
+                    root.leaves.len(),
+                    root.level
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_multiproof_shares_siblings() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        // 13 keys: roots of levels 3, 2 and 0
+        for i in 0..13 {
+            tree.insert(format!("k{:02}", i), format!("f{}", i));
+        }
+        tree.delete("k05", "f5");
+
+        let keys = [
+            "k00", "k01", "k02", "k03", "k09", "k12", "k05", "nope", "k01",
+        ];
+        let proof = tree.get_many_with_proof(&keys);
+        assert!(proof.verify());
+        assert_eq!(proof.missing, vec!["k05".to_string(), "nope".to_string()]);
+        for key in ["k00", "k01", "k02", "k03", "k09", "k12"] {
+            assert_eq!(proof.fids(key), tree.select(key).as_ref());
+            assert!(
+                tree.root_hashes().contains(
+                    &proof
+                        .roots
+                        .iter()
+                        .find(|r| { r.leaves.iter().any(|l| l.key == key) })
+                        .unwrap()
+                        .root_hash
+                )
+            );
+        }
+
+        // Fewer hashes than the separate paths put together
+        let separate: usize = ["k00", "k01", "k02", "k03", "k09", "k12"]
+            .iter()
+            .map(|k| {
+                tree.select_with_proof(k)
+                    .unwrap()
+                    .merkle_proof
+                    .unwrap()
+                    .path
+                    .len()
+            })
+            .sum();
+        assert!(proof.sibling_count() < separate);
+
+        let at = proof.roots.iter().position(|r| r.leaves.len() > 1).unwrap();
+        let mut forged = proof.clone();
+        forged.roots[at].leaves[0].fids = accumulator_ads::Set::from_vec(vec!["x".to_string()]);
+        assert!(!forged.verify());
+        let mut forged = proof.clone();
+        forged.roots[at].leaves[0].index += 1;
+        assert!(!forged.verify());
+        let mut forged = proof.clone();
+        forged.roots[at].siblings.push([0u8; 32]);
+        assert!(!forged.verify());
+        let mut forged = proof;
+        forged.roots[at].leaves.swap(0, 1);
+        assert!(!forged.verify());
+    }
+}
@@ -1,7 +1,11 @@
 use accumulator_ads::{DynamicAccumulator, G1Affine, Set, digest_set_from_set};
-use std::rc::Rc;
+use smallvec::SmallVec;
 
+use crate::key_filter::KeyFilter;
+use crate::policy::OrderPolicy;
 use crate::utils::{Hash, empty_acc, nonleaf_hash};
+use std::cmp::Ordering;
+use std::sync::{Arc, OnceLock};
 
 #[derive(Debug, Clone)]
 pub enum Node {
@@ -10,18 +14,86 @@ pub enum Node {
         fids: Set<String>,
         level: usize,
         deleted: bool,
+        /// Accumulator of the set `{key}`, computed on first use. The key never
+        /// changes, so tombstoning and reviving keep it; `Node::acc` returns
+        /// the empty accumulator for a tombstone instead.
+        acc: OnceLock<G1Affine>,
     },
     NonLeaf {
         hash: Hash,
-        keys: Rc<Set<String>>,
+        keys: NodeKeys,
         acc: G1Affine,
         level: usize,
-        left: Box<Node>,
-        right: Box<Node>,
+        left: Arc<Node>,
+        right: Arc<Node>,
+        /// `keys` and `acc` still include keys tombstoned below this node
+        /// (`hash` is always current). Cleared by `refresh`.
+        stale: bool,
     },
 }
 
+/// New state of one leaf: its route from the root (`true` for right), FIDs
+/// and tombstone flag.
+pub(crate) type LeafChange = (Vec<bool>, Set<String>, bool);
+
+/// Largest key set a `NonLeaf` keeps inline (subtrees up to level 2).
+pub const INLINE_KEYS: usize = 4;
+
+/// Keys under a `NonLeaf`. Nodes near the leaves hold a few keys inline and
+/// sorted, which saves an allocation per node and keeps `has_key` routing
+/// within the node. Larger nodes keep only a count and a `KeyFilter`; their
+/// keys are read from the inline nodes below when needed.
+#[derive(Debug, Clone)]
+pub enum NodeKeys {
+    Inline(SmallVec<[String; INLINE_KEYS]>),
+    Filtered { filter: KeyFilter, len: usize },
+}
+
+impl NodeKeys {
+    pub fn from_set(keys: Set<String>) -> Self {
+        if keys.len() > INLINE_KEYS {
+            return NodeKeys::Filtered {
+                filter: KeyFilter::from_keys(keys.iter()),
+                len: keys.len(),
+            };
+        }
+        let mut inline: SmallVec<[String; INLINE_KEYS]> = keys.iter().cloned().collect();
+        inline.sort_unstable();
+        NodeKeys::Inline(inline)
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            NodeKeys::Inline(keys) => keys.len(),
+            NodeKeys::Filtered { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `key` may be below; exact for inline keys.
+    pub fn may_contain(&self, key: &str) -> bool {
+        match self {
+            NodeKeys::Inline(keys) => keys.binary_search_by(|k| k.as_str().cmp(key)).is_ok(),
+            NodeKeys::Filtered { filter, .. } => filter.may_contain(key),
+        }
+    }
+}
+
 impl Node {
+    /// Leaf of `key` with an empty accumulator cache.
+    pub fn leaf(key: String, fids: Set<String>, deleted: bool) -> Self {
+        Node::Leaf {
+            key,
+            fids,
+            level: 0,
+            deleted,
+            acc: OnceLock::new(),
+        }
+    }
+
     pub fn level(&self) -> usize {
         match self {
             Node::Leaf { level, .. } => *level,
@@ -32,29 +104,54 @@ impl Node {
     pub fn hash(&self) -> Hash {
         match self {
             Node::Leaf {
-                key, fids, level, deleted,
-            } => {
-                crate::utils::leaf_hash(key, fids, *level, *deleted)
-            }
+                key,
+                fids,
+                level,
+                deleted,
+                ..
+            } => crate::utils::leaf_hash(key, fids, *level, *deleted),
             Node::NonLeaf { hash, .. } => *hash,
         }
     }
 
     pub fn acc(&self) -> G1Affine {
         match self {
-            Node::Leaf { key, deleted, .. } => {
+            Node::Leaf {
+                key, deleted, acc, ..
+            } => {
                 if *deleted {
                     // empty set accumulator
                     empty_acc()
                 } else {
-                    let digest_set = digest_set_from_set(&Set::from_vec(vec![key.clone()]));
-                    DynamicAccumulator::calculate_commitment(&digest_set)
+                    *acc.get_or_init(|| leaf_acc(key))
                 }
             }
             Node::NonLeaf { acc, .. } => *acc,
         }
     }
 
+    /// Number of live keys under this node, read from the leaves where the
+    /// cached key set is stale.
+    pub fn live_key_count(&self) -> u64 {
+        match self {
+            Node::Leaf { deleted, .. } => !*deleted as u64,
+            Node::NonLeaf {
+                keys, left, right, stale, ..
+            } => {
+                if *stale {
+                    left.live_key_count() + right.live_key_count()
+                } else {
+                    keys.len() as u64
+                }
+            }
+        }
+    }
+
+    /// Whether this node's key set and accumulator await recomputation.
+    pub fn is_stale(&self) -> bool {
+        matches!(self, Node::NonLeaf { stale: true, .. })
+    }
+
     pub fn keys(&self) -> Set<String> {
         match self {
             Node::Leaf { key, deleted, .. } => {
@@ -64,42 +161,67 @@ impl Node {
                     Set::from_vec(vec![key.clone()])
                 }
             }
-            Node::NonLeaf { keys, .. } => keys.as_ref().clone(),
+            Node::NonLeaf { .. } => {
+                let mut out = Vec::new();
+                self.collect_keys(&mut out);
+                Set::from_vec(out)
+            }
+        }
+    }
+
+    /// Append the keys cached below this node, live or (while stale) not.
+    fn collect_keys(&self, out: &mut Vec<String>) {
+        match self {
+            Node::Leaf { key, deleted, .. } => {
+                if !*deleted {
+                    out.push(key.clone());
+                }
+            }
+            Node::NonLeaf {
+                keys: NodeKeys::Inline(keys),
+                ..
+            } => out.extend(keys.iter().cloned()),
+            Node::NonLeaf { left, right, .. } => {
+                left.collect_keys(out);
+                right.collect_keys(out);
+            }
         }
     }
 
+    /// Whether `target_key` is live below this node. Inline key lists answer
+    /// directly unless stale, when they may still hold tombstoned keys and
+    /// the leaves decide.
     pub fn has_key(&self, target_key: &str) -> bool {
         match self {
             Node::Leaf { key, deleted, .. } => !*deleted && key == target_key,
-            Node::NonLeaf { keys, .. } => keys.contains(&target_key.to_string()),
+            Node::NonLeaf {
+                keys: keys @ NodeKeys::Inline(_),
+                stale: false,
+                ..
+            } => keys.may_contain(target_key),
+            Node::NonLeaf {
+                keys, left, right, ..
+            } => {
+                keys.may_contain(target_key)
+                    && (left.has_key(target_key) || right.has_key(target_key))
+            }
         }
     }
 
-    pub fn collect_leaves(
+    pub(crate) fn collect_leaves(
         &self,
         exclude_key: Option<&str>,
     ) -> std::vec::IntoIter<(String, Set<String>)> {
-        let mut v: Vec<(String, Set<String>)> = Vec::new();
-        match self {
-            Node::Leaf {
-                key, fids, deleted, ..
-            } => {
-                if *deleted {
-                    return v.into_iter();
-                }
-                if let Some(ex) = exclude_key
-                    && ex == key.as_str()
-                {
-                    return v.into_iter();
-                }
-                v.push((key.clone(), fids.clone()));
-            }
-            Node::NonLeaf { left, right, .. } => {
-                v.extend(left.collect_leaves(exclude_key));
-                v.extend(right.collect_leaves(exclude_key));
-            }
-        }
-        v.into_iter()
+        self.iter_leaves()
+            .filter(|(key, _)| Some(*key) != exclude_key)
+            .map(|(key, fids)| (key.to_string(), fids.clone()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Live leaves under this node, left to right, without copying them.
+    pub fn iter_leaves(&self) -> Leaves<'_> {
+        Leaves { stack: vec![self] }
     }
 
     // ==========================================
@@ -130,7 +252,7 @@ impl Node {
 
     /// Build a path-proof for `target_key` within this node (internal recursive implementation).
     /// `path` is populated with sibling hashes on unwind; each entry is (sibling_hash, sibling_is_left).
-    pub fn recurse_select_with_proof(
+    pub(crate) fn recurse_select_with_proof(
         &self,
         target_key: &str,
         path: &mut Vec<(Hash, bool)>,
@@ -165,8 +287,27 @@ impl Node {
         }
     }
 
+    /// Find the leaf for `target_key`, tombstoned or not.
+    /// Returns its FID set and whether it is tombstoned.
+    pub(crate) fn leaf_state(&self, target_key: &str) -> Option<(Set<String>, bool)> {
+        match self {
+            Node::Leaf {
+                key, fids, deleted, ..
+            } => {
+                if key == target_key {
+                    Some((fids.clone(), *deleted))
+                } else {
+                    None
+                }
+            }
+            Node::NonLeaf { left, right, .. } => left
+                .leaf_state(target_key)
+                .or_else(|| right.leaf_state(target_key)),
+        }
+    }
+
     /// Build a proof for `target_key` including leaves that may be tombstoned (internal recursive implementation).
-    pub fn recurse_select_proof_including_deleted(
+    pub(crate) fn recurse_select_proof_including_deleted(
         &self,
         target_key: &str,
         path: &mut Vec<(Hash, bool)>,
@@ -215,9 +356,9 @@ impl Node {
                 hash, left, right, ..
             } => {
                 let changed = if left.has_key(target_key) {
-                    left.insert_fid(target_key, fid)
+                    Arc::make_mut(left).insert_fid(target_key, fid)
                 } else {
-                    right.insert_fid(target_key, fid)
+                    Arc::make_mut(right).insert_fid(target_key, fid)
                 };
                 if changed {
                     *hash = nonleaf_hash(left.hash(), right.hash());
@@ -246,13 +387,15 @@ impl Node {
                 }
             }
             Node::NonLeaf {
-                hash, left, right, ..
+                hash, left, right, stale, ..
             } => {
-                let changed = if left.has_key(target_key) {
-                    left.delete_fid(target_key, fid)
-                } else {
-                    right.delete_fid(target_key, fid)
-                };
+                let child = if left.has_key(target_key) { &mut *left } else { &mut *right };
+                let changed = Arc::make_mut(child).delete_fid(target_key, fid);
+                // A tombstoned key leaves the key set; defer the accumulator
+                // update so many deletes under this node share one recomputation.
+                if child.is_stale() || matches!(**child, Node::Leaf { deleted: true, .. }) {
+                    *stale = true;
+                }
                 if changed {
                     *hash = nonleaf_hash(left.hash(), right.hash());
                 }
@@ -285,9 +428,9 @@ impl Node {
                 hash, left, right, ..
             } => {
                 let changed = if left.has_key(target_key) {
-                    left.update_fid(target_key, old_fid, new_fid)
+                    Arc::make_mut(left).update_fid(target_key, old_fid, new_fid)
                 } else {
-                    right.update_fid(target_key, old_fid, new_fid)
+                    Arc::make_mut(right).update_fid(target_key, old_fid, new_fid)
                 };
                 if changed {
                     *hash = nonleaf_hash(left.hash(), right.hash());
@@ -299,60 +442,325 @@ impl Node {
 
     /// Revive a tombstoned leaf with target_key. Returns new node.
     /// Replaces fids with a new set containing the single fid.
-    pub fn revive(self: Box<Self>, target_key: &str, new_fid: &str) -> Box<Node> {
-        match *self {
+    pub fn revive(self, target_key: &str, new_fid: &str) -> Arc<Node> {
+        self.revive_with(target_key, &Set::from_vec(vec![new_fid.to_string()]))
+    }
+
+    /// Revive a tombstoned leaf with target_key, replacing its fids with `new_fids`.
+    /// Only the nodes on the leaf's path are recomputed; finding the path
+    /// reads the leaves, since tombstoned keys are not in the key filters.
+    pub fn revive_with(mut self, target_key: &str, new_fids: &Set<String>) -> Arc<Node> {
+        let mut located = Vec::new();
+        let wanted = std::collections::HashSet::from([target_key]);
+        self.locate_leaves(&wanted, &mut Vec::new(), &mut located);
+        if let Some((_, (route, _, true))) = located.pop() {
+            self.apply_leaf_states(&[(route, new_fids.clone(), false)], 0);
+        }
+        Arc::new(self)
+    }
+
+    /// Key set and accumulator of a node with children `left` and `right`.
+    fn combine(left: &Node, right: &Node) -> (NodeKeys, G1Affine) {
+        let (keys, acc) = combine_keys(&left.keys(), &right.keys());
+        (NodeKeys::from_set(keys), acc)
+    }
+
+    /// Recompute the key sets and accumulators of stale nodes, bottom up.
+    /// Returns the number of nodes recomputed.
+    pub fn refresh(&mut self) -> usize {
+        match self {
+            Node::NonLeaf {
+                keys, acc, left, right, stale: stale @ true, ..
+            } => {
+                let (l, r) = crate::utils::join(|| refresh_shared(left), || refresh_shared(right));
+                let below = l + r;
+                (*keys, *acc) = Self::combine(left, right);
+                *stale = false;
+                below + 1
+            }
+            _ => 0,
+        }
+    }
+
+    /// Record the route (from this node, `true` for right) and state of every
+    /// leaf whose key is in `wanted`, tombstoned or not.
+    pub(crate) fn locate_leaves(
+        &self,
+        wanted: &std::collections::HashSet<&str>,
+        route: &mut Vec<bool>,
+        out: &mut Vec<(String, LeafChange)>,
+    ) {
+        match self {
             Node::Leaf {
-                key,
-                fids,
-                level,
-                deleted,
+                key, fids, deleted, ..
             } => {
-                if key == target_key && deleted {
-                    Box::new(Node::Leaf {
-                        key,
-                        fids: Set::from_vec(vec![new_fid.to_string()]),
-                        level,
-                        deleted: false,
-                    })
-                } else {
-                    Box::new(Node::Leaf {
-                        key,
-                        fids,
-                        level,
-                        deleted,
-                    })
+                if wanted.contains(key.as_str()) {
+                    out.push((key.clone(), (route.clone(), fids.clone(), *deleted)));
                 }
             }
+            Node::NonLeaf { left, right, .. } => {
+                route.push(false);
+                left.locate_leaves(wanted, route, out);
+                *route.last_mut().unwrap() = true;
+                right.locate_leaves(wanted, route, out);
+                route.pop();
+            }
+        }
+    }
+
+    /// Set the leaves at the routes in `changes` (sorted by route, `depth`
+    /// bits already consumed) to new `(fids, deleted)` states, recomputing each
+    /// affected ancestor once on the way up. Returns whether the set of live
+    /// keys below changed.
+    pub(crate) fn apply_leaf_states(&mut self, changes: &[LeafChange], depth: usize) -> bool {
+        match self {
+            Node::Leaf { fids, deleted, .. } => {
+                let (_, new_fids, new_deleted) = &changes[0];
+                let changed = *deleted != *new_deleted;
+                *fids = new_fids.clone();
+                *deleted = *new_deleted;
+                changed
+            }
             Node::NonLeaf {
-                left, right, level, ..
+                hash, keys, acc, left, right, stale, ..
             } => {
-                let l = left.revive(target_key, new_fid);
-                let r = right.revive(target_key, new_fid);
-                Node::merge(l, r, Some(level))
+                let split = changes.partition_point(|(route, _, _)| !route[depth]);
+                let (l, r) = changes.split_at(split);
+                let mut changed = false;
+                if !l.is_empty() {
+                    changed |= Arc::make_mut(left).apply_leaf_states(l, depth + 1);
+                }
+                if !r.is_empty() {
+                    changed |= Arc::make_mut(right).apply_leaf_states(r, depth + 1);
+                }
+                *hash = nonleaf_hash(left.hash(), right.hash());
+                if changed {
+                    refresh_shared(left);
+                    refresh_shared(right);
+                    (*keys, *acc) = Self::combine(left, right);
+                    *stale = false;
+                }
+                changed
             }
         }
     }
 
     /// Merge two nodes into a new NonLeaf node
     /// If level is provided, use it; otherwise compute as right.level() + 1
-    pub fn merge(left: Box<Node>, right: Box<Node>, level: Option<usize>) -> Box<Node> {
-        let new_keys = Rc::new(left.keys().union(&right.keys()));
-
-        let left_acc = left.acc();
+    pub fn merge(mut left: Arc<Node>, mut right: Arc<Node>, level: Option<usize>) -> Arc<Node> {
+        crate::utils::join(|| refresh_shared(&mut left), || refresh_shared(&mut right));
+        let (keys, acc) = Self::combine(&left, &right);
 
-        // Optimize: Only convert the difference (right - left) to Vec<Fr>
-        // Using HashSet.difference() is O(n), much faster than converting both full sets
-        let diff_elements = right.keys().difference(&left.keys());
-        let diff_fr = digest_set_from_set(&diff_elements);
-        let new_acc = DynamicAccumulator::incremental_add_with_default_trapdoor(left_acc, &diff_fr);
-
-        Box::new(Node::NonLeaf {
+        Arc::new(Node::NonLeaf {
             hash: nonleaf_hash(left.hash(), right.hash()),
-            keys: new_keys,
-            acc: new_acc,
+            keys,
+            acc,
             level: level.unwrap_or_else(|| right.level() + 1),
             left,
             right,
+            stale: false,
+        })
+    }
+
+    /// Key of the leftmost leaf, or of the rightmost if `rightmost`.
+    pub(crate) fn edge_key(&self, rightmost: bool) -> &str {
+        match self {
+            Node::Leaf { key, .. } => key,
+            Node::NonLeaf { left, right, .. } => {
+                if rightmost {
+                    right.edge_key(true)
+                } else {
+                    left.edge_key(false)
+                }
+            }
+        }
+    }
+
+    /// Merge two roots of one level, each sorted by `order`, into a sorted
+    /// root. Unless one root's keys all sort before the other's, the leaves
+    /// are interleaved and every internal node is rebuilt.
+    pub(crate) fn merge_sorted(
+        left: Arc<Node>,
+        right: Arc<Node>,
+        order: &OrderPolicy,
+    ) -> Arc<Node> {
+        if order.compare(left.edge_key(true), right.edge_key(false)) == Ordering::Less {
+            return Node::merge(left, right, None);
+        }
+        if order.compare(right.edge_key(true), left.edge_key(false)) == Ordering::Less {
+            return Node::merge(right, left, None);
+        }
+        let mut leaves = Vec::new();
+        push_leaves(&left, &mut leaves);
+        push_leaves(&right, &mut leaves);
+        leaves.sort_by(|a, b| order.compare(a.edge_key(false), b.edge_key(false)));
+        while leaves.len() > 1 {
+            leaves = leaves
+                .chunks(2)
+                .map(|pair| Node::merge(pair[0].clone(), pair[1].clone(), None))
+                .collect();
+        }
+        leaves.pop().expect("roots have leaves")
+    }
+}
+
+/// Live `(key, fids)` pairs under a node, left to right, from
+/// `Node::iter_leaves`. Holds one pending node per level.
+pub struct Leaves<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = (&'a str, &'a Set<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::Leaf {
+                    key, fids, deleted, ..
+                } => {
+                    if !*deleted {
+                        return Some((key, fids));
+                    }
+                }
+                Node::NonLeaf { left, right, .. } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Append the leaves under `node`, tombstones included, left to right.
+pub(crate) fn push_leaves(node: &Arc<Node>, out: &mut Vec<Arc<Node>>) {
+    match node.as_ref() {
+        Node::Leaf { .. } => out.push(node.clone()),
+        Node::NonLeaf { left, right, .. } => {
+            push_leaves(left, out);
+            push_leaves(right, out);
+        }
+    }
+}
+
+/// `Node::refresh` through a shared pointer, copying the node only if it is
+/// stale and still shared with a snapshot.
+pub(crate) fn refresh_shared(node: &mut Arc<Node>) -> usize {
+    if !node.is_stale() {
+        return 0;
+    }
+    Arc::make_mut(node).refresh()
+}
+
+/// Accumulator of a live leaf.
+pub(crate) fn leaf_acc(key: &str) -> G1Affine {
+    let digest_set = digest_set_from_set(&Set::from_vec(vec![key.to_string()]));
+    DynamicAccumulator::calculate_commitment(&digest_set)
+}
+
+/// `Node::combine` on bare key sets, so subtrees can be built where `Rc` is
+/// unavailable.
+pub(crate) fn combine_keys(
+    left_keys: &Set<String>,
+    right_keys: &Set<String>,
+) -> (Set<String>, G1Affine) {
+    let new_keys = left_keys.union(right_keys);
+
+    // Optimize: Only convert the difference (right - left) to Vec<Fr>
+    // Using HashSet.difference() is O(n), much faster than converting both full sets
+    let diff_elements = right_keys.difference(left_keys);
+    let diff_fr = digest_set_from_set(&diff_elements);
+    (new_keys, union_acc(left_keys, &diff_fr))
+}
+
+/// Accumulator of `left_keys` plus `added`, by the trapdoor-free union, which
+/// holds for any parameters of sufficient degree.
+fn union_acc(left_keys: &Set<String>, added: &[accumulator_ads::Fr]) -> G1Affine {
+    let base = digest_set_from_set(left_keys);
+    DynamicAccumulator::incremental_union(&base, added)
+}
+
+/// A freshly built subtree that keeps the full key set of every node, which
+/// combining the next level up needs. `into_node` reduces them to `NodeKeys`.
+pub(crate) enum Subtree {
+    Leaf {
+        key: String,
+        fids: Set<String>,
+    },
+    NonLeaf {
+        hash: Hash,
+        keys: Set<String>,
+        acc: G1Affine,
+        level: usize,
+        left: Box<Subtree>,
+        right: Box<Subtree>,
+    },
+}
+
+impl Subtree {
+    /// Perfect subtree over `leaves` (a power of two in length), in order.
+    /// Halves larger than `grain` leaves are built concurrently.
+    pub(crate) fn build(leaves: &[(String, Set<String>)], grain: usize) -> Subtree {
+        if leaves.len() == 1 {
+            let (key, fids) = &leaves[0];
+            return Subtree::Leaf {
+                key: key.clone(),
+                fids: fids.clone(),
+            };
+        }
+        let (l, r) = leaves.split_at(leaves.len() / 2);
+        let (left, right) = if leaves.len() > grain {
+            crate::utils::join(|| Self::build(l, grain), || Self::build(r, grain))
+        } else {
+            (Self::build(l, grain), Self::build(r, grain))
+        };
+        let (keys, acc) = combine_keys(&left.keys(), &right.keys());
+        Subtree::NonLeaf {
+            hash: nonleaf_hash(left.hash(), right.hash()),
+            keys,
+            acc,
+            level: left.level() + 1,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    fn level(&self) -> usize {
+        match self {
+            Subtree::Leaf { .. } => 0,
+            Subtree::NonLeaf { level, .. } => *level,
+        }
+    }
+
+    fn hash(&self) -> Hash {
+        match self {
+            Subtree::Leaf { key, fids } => crate::utils::leaf_hash(key, fids, 0, false),
+            Subtree::NonLeaf { hash, .. } => *hash,
+        }
+    }
+
+    fn keys(&self) -> Set<String> {
+        match self {
+            Subtree::Leaf { key, .. } => Set::from_vec(vec![key.clone()]),
+            Subtree::NonLeaf { keys, .. } => keys.clone(),
+        }
+    }
+
+    pub(crate) fn into_node(self) -> Arc<Node> {
+        Arc::new(match self {
+            Subtree::Leaf { key, fids } => Node::leaf(key, fids, false),
+            Subtree::NonLeaf {
+                hash, keys, acc, level, left, right,
+            } => Node::NonLeaf {
+                hash,
+                keys: NodeKeys::from_set(keys),
+                acc,
+                level,
+                left: left.into_node(),
+                right: right.into_node(),
+                stale: false,
+            },
         })
     }
 }
@@ -364,31 +772,12 @@ impl Node {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Once;
-
-    static INIT: Once = Once::new();
-
-    fn init_test_params() {
-        INIT.call_once(|| {
-            use accumulator_ads::acc::setup::{PublicParameters, init_public_parameters_direct};
-            use ark_bls12_381::Fr;
-
-            let secret_s = Fr::from(123456789u128);
-            let params = PublicParameters::generate_for_testing(secret_s, 10);
-            init_public_parameters_direct(params).expect("Failed to initialize test parameters");
-        });
-    }
 
     /// Unit test: Verify basic node properties and methods
     #[test]
     fn test_node_basic_properties() {
-        init_test_params();
-        let leaf = Node::Leaf {
-            key: "test".into(),
-            fids: Set::from_vec(vec!["fid1".into()]),
-            level: 0,
-            deleted: false,
-        };
+        crate::setup::init_test_parameters();
+        let leaf = Node::leaf("test".into(), Set::from_vec(vec!["fid1".into()]), false);
 
         assert_eq!(leaf.level(), 0);
         assert!(leaf.has_key("test"));
@@ -399,13 +788,8 @@ mod tests {
     /// Unit test: Verify tombstone behavior
     #[test]
     fn test_node_deleted_behavior() {
-        init_test_params();
-        let deleted_leaf = Node::Leaf {
-            key: "deleted".into(),
-            fids: Set::from_vec(vec!["fid1".into()]),
-            level: 0,
-            deleted: true,
-        };
+        crate::setup::init_test_parameters();
+        let deleted_leaf = Node::leaf("deleted".into(), Set::from_vec(vec!["fid1".into()]), true);
 
         assert!(!deleted_leaf.has_key("deleted"));
         assert_eq!(deleted_leaf.keys().len(), 0);
@@ -418,22 +802,77 @@ mod tests {
         assert_eq!(deleted_leaf.acc(), empty_acc());
     }
 
+    /// Unit test: A leaf computes its accumulator once and keeps it across a
+    /// tombstone and revive
+    #[test]
+    fn test_leaf_acc_is_cached() {
+        crate::setup::init_test_parameters();
+        let cached = |node: &Node| matches!(node, Node::Leaf { acc, .. } if acc.get().is_some());
+        let mut leaf = Node::leaf("k".into(), Set::from_vec(vec!["f".into()]), false);
+        assert!(!cached(&leaf));
+        assert_eq!(leaf.acc(), leaf_acc("k"));
+        assert!(cached(&leaf));
+
+        assert!(leaf.delete_fid("k", "f"));
+        assert_eq!(leaf.acc(), empty_acc());
+        let revived = leaf.revive("k", "g");
+        assert!(cached(&revived));
+        assert_eq!(revived.acc(), leaf_acc("k"));
+    }
+
+    /// Unit test: Merged accumulators are the commitment to the merged keys
+    #[test]
+    fn test_merge_acc_matches_commitment() {
+        crate::setup::init_test_parameters();
+        let keys = |keys: &[&str]| Set::from_vec(keys.iter().map(|k| k.to_string()).collect());
+        let from_set = |set: &Set<String>| {
+            DynamicAccumulator::from_set(accumulator_ads::Fr::from(1u64), &digest_set_from_set(set))
+                .acc_value
+        };
+        let leaf = |key: &str| Arc::new(Node::leaf(key.into(), keys(&["f"]), false));
+        let left = Node::merge(leaf("a"), leaf("b"), None);
+        let right = Node::merge(leaf("c"), leaf("d"), None);
+        assert_eq!(left.acc(), from_set(&keys(&["a", "b"])));
+        let root = Node::merge(left, right, None);
+        assert_eq!(root.acc(), from_set(&keys(&["a", "b", "c", "d"])));
+
+        // Keys on both sides are accumulated once
+        let (merged, acc) = combine_keys(&keys(&["a", "b"]), &keys(&["b", "e"]));
+        assert_eq!(merged, keys(&["a", "b", "e"]));
+        assert_eq!(acc, from_set(&merged));
+    }
+
+    /// Unit test: The tree can be shared behind a lock across threads
+    #[test]
+    fn test_tree_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Node>();
+        assert_send_sync::<crate::AccumulatorTree>();
+
+        crate::setup::init_test_parameters();
+        let tree = std::sync::Arc::new(std::sync::RwLock::new(crate::AccumulatorTree::new()));
+        tree.write().unwrap().insert("k".to_string(), "f".to_string());
+        let reader = std::sync::Arc::clone(&tree);
+        let fids = std::thread::spawn(move || reader.read().unwrap().select("k"))
+            .join()
+            .unwrap();
+        assert_eq!(fids, Some(Set::from_vec(vec!["f".to_string()])));
+    }
+
     /// Unit test: Verify collect_leaves functionality
     #[test]
     fn test_collect_leaves() {
-        init_test_params();
-        let leaf1 = Box::new(Node::Leaf {
-            key: "a".into(),
-            fids: Set::from_vec(vec!["fa".into()]),
-            level: 0,
-            deleted: false,
-        });
-        let leaf2 = Box::new(Node::Leaf {
-            key: "b".into(),
-            fids: Set::from_vec(vec!["fb".into()]),
-            level: 0,
-            deleted: false,
-        });
+        crate::setup::init_test_parameters();
+        let leaf1 = Arc::new(Node::leaf(
+            "a".into(),
+            Set::from_vec(vec!["fa".into()]),
+            false,
+        ));
+        let leaf2 = Arc::new(Node::leaf(
+            "b".into(),
+            Set::from_vec(vec!["fb".into()]),
+            false,
+        ));
 
         let merged = Node::merge(leaf1, leaf2, None);
 
@@ -456,4 +895,253 @@ mod tests {
         assert_eq!(excluded[0].0, "b");
         assert!(excluded[0].1.contains(&"fb".to_string()));
     }
+
+    /// Unit test: Leaves and tree entries stream without tombstones, sorted
+    /// across roots when the tree sorts its leaves
+    #[test]
+    fn test_iter_yields_live_entries() {
+        crate::setup::init_test_parameters();
+        let keys = [7, 2, 9, 4, 1, 12, 3];
+        let mut plain = crate::AccumulatorTree::new();
+        let mut sorted = crate::AccumulatorTree::with_sorted_leaves(OrderPolicy::Natural);
+        for tree in [&mut plain, &mut sorted] {
+            for i in keys {
+                tree.insert(format!("k{:02}", i), format!("f{}", i));
+            }
+            tree.delete("k04", "f4");
+        }
+
+        // Forest order: every root's leaves in turn
+        let collected: Vec<(String, Set<String>)> = plain
+            .roots
+            .iter()
+            .flat_map(|root| root.collect_leaves(None))
+            .collect();
+        let streamed: Vec<(String, Set<String>)> = plain
+            .iter()
+            .map(|(key, fids)| (key.to_string(), fids.clone()))
+            .collect();
+        assert_eq!(streamed, collected);
+        assert_eq!(streamed.len(), keys.len() - 1);
+
+        assert!(sorted.roots.len() > 1);
+        let streamed: Vec<&str> = sorted.iter().map(|(key, _)| key).collect();
+        assert_eq!(streamed, ["k01", "k02", "k03", "k07", "k09", "k12"]);
+        assert_eq!(
+            sorted.iter().nth(5).unwrap().1,
+            &Set::from_vec(vec!["f12".to_string()])
+        );
+    }
+
+    /// Unit test: Tombstoning marks ancestors stale; refresh matches a fresh build
+    #[test]
+    fn test_lazy_ancestor_refresh() {
+        crate::setup::init_test_parameters();
+        let leaf = |key: &str, deleted: bool| {
+            let fids = if deleted {
+                Set::new()
+            } else {
+                Set::from_vec(vec![format!("f{}", key)])
+            };
+            Arc::new(Node::leaf(key.into(), fids, deleted))
+        };
+        let build = |deleted: [bool; 4]| {
+            let keys = ["a", "b", "c", "d"];
+            let l = Node::merge(leaf(keys[0], deleted[0]), leaf(keys[1], deleted[1]), None);
+            let r = Node::merge(leaf(keys[2], deleted[2]), leaf(keys[3], deleted[3]), None);
+            Node::merge(l, r, None)
+        };
+
+        let mut root = Arc::unwrap_or_clone(build([false; 4]));
+        assert!(root.delete_fid("a", "fa"));
+        assert!(root.delete_fid("b", "fb"));
+        assert!(root.is_stale());
+        // Hashes are current, key sets lag until refresh; lookups read the leaves
+        let expected = build([true, true, false, false]);
+        assert_eq!(root.hash(), expected.hash());
+        assert!(!root.has_key("a") && root.has_key("c"));
+
+        assert_eq!(root.refresh(), 2);
+        assert!(!root.is_stale() && !root.has_key("a"));
+        assert_eq!(root.keys(), expected.keys());
+        assert_eq!(root.acc(), expected.acc());
+        assert_eq!(root.refresh(), 0);
+
+        // Reviving touches only the path to the leaf
+        let Node::NonLeaf { right, .. } = &root else {
+            unreachable!()
+        };
+        let untouched = right.clone();
+        let revived = root.revive("a", "fa");
+        let expected = build([false, true, false, false]);
+        assert_eq!(revived.hash(), expected.hash());
+        assert_eq!(revived.keys(), expected.keys());
+        assert_eq!(revived.acc(), expected.acc());
+        let Node::NonLeaf { right, .. } = revived.as_ref() else {
+            unreachable!()
+        };
+        assert!(Arc::ptr_eq(right, &untouched));
+    }
+
+    /// Unit test: A tombstoned key can be revived before its ancestors are refreshed
+    #[test]
+    fn test_revive_before_refresh() {
+        crate::setup::init_test_parameters();
+        let mut tree = crate::AccumulatorTree::new();
+        for k in ["a", "b", "c", "d"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        tree.delete("a", "fa");
+        assert!(tree.has_stale_nodes());
+        assert!(tree.select_nonmembership_proof("a").is_some());
+
+        tree.insert("a".to_string(), "fa2".to_string());
+        assert_eq!(tree.select("a"), Some(Set::from_vec(vec!["fa2".to_string()])));
+        tree.publish_epoch();
+        assert!(!tree.has_stale_nodes());
+    }
+
+    /// Unit test: A parallel build matches inserting the same entries in order
+    #[test]
+    fn test_build_parallel_matches_inserts() {
+        crate::setup::init_test_parameters();
+        let mut entries: Vec<(String, Set<String>)> = (0..7)
+            .map(|i| (format!("k{}", i), Set::from_vec(vec![format!("f{}", i)])))
+            .collect();
+        entries.push(("k3".to_string(), Set::from_vec(vec!["g3".to_string()])));
+
+        let mut expected = crate::AccumulatorTree::new();
+        for (key, fids) in &entries {
+            for fid in fids.iter() {
+                expected.insert(key.clone(), fid.clone());
+            }
+        }
+        let commitment = expected.publish_epoch();
+        for threads in [1, 3] {
+            let mut built =
+                crate::AccumulatorTree::build_parallel(entries.clone(), threads).unwrap();
+            assert_eq!(built.root_hashes(), expected.root_hashes());
+            let accs: Vec<G1Affine> = built.roots.iter().map(|r| r.acc()).collect();
+            let want: Vec<G1Affine> = expected.roots.iter().map(|r| r.acc()).collect();
+            assert_eq!(accs, want);
+            assert_eq!(built.select("k3"), expected.select("k3"));
+            assert_eq!(built.publish_epoch(), commitment);
+        }
+
+        let empty = vec![("k".to_string(), Set::new())];
+        assert!(crate::AccumulatorTree::build_parallel(empty, 1).is_err());
+    }
+
+    /// Unit test: Bulk loading sorted pairs gives the forest of sorted inserts
+    #[test]
+    fn test_bulk_load_matches_sorted_inserts() {
+        crate::setup::init_test_parameters();
+        let mut pairs: Vec<(String, String)> = (0..7)
+            .map(|i| (format!("k{}", i), format!("f{}", i)))
+            .collect();
+        pairs.insert(4, ("k3".to_string(), "g3".to_string()));
+
+        let mut expected = crate::AccumulatorTree::with_sorted_leaves(OrderPolicy::Natural);
+        for (key, fid) in &pairs {
+            expected.insert(key.clone(), fid.clone());
+        }
+        let commitment = expected.publish_epoch();
+        let mut built =
+            crate::AccumulatorTree::bulk_load(OrderPolicy::Natural, pairs.clone()).unwrap();
+        assert_eq!(built.root_hashes(), expected.root_hashes());
+        let accs: Vec<G1Affine> = built.roots.iter().map(|r| r.acc()).collect();
+        let want: Vec<G1Affine> = expected.roots.iter().map(|r| r.acc()).collect();
+        assert_eq!(accs, want);
+        assert_eq!(built.select("k3").unwrap().len(), 2);
+        assert_eq!(built.locate("k5"), expected.locate("k5"));
+        assert_eq!(built.publish_epoch(), commitment);
+
+        pairs.swap(1, 2);
+        let Err(err) = crate::AccumulatorTree::bulk_load(OrderPolicy::Natural, pairs) else {
+            panic!("out-of-order pairs were loaded");
+        };
+        assert!(
+            matches!(&err, crate::AccTreeError::InvalidInput(m) if m.contains("sorts before")),
+            "{}",
+            err
+        );
+        // 128 keys make a root beyond the test parameters
+        let many = (0..128).map(|i| (format!("k{:03}", i), "f".to_string()));
+        assert!(crate::AccumulatorTree::bulk_load(OrderPolicy::Natural, many).is_err());
+    }
+
+    /// Unit test: A batch lands where one-by-one inserts would put it
+    #[test]
+    fn test_insert_batch_matches_inserts() {
+        crate::setup::init_test_parameters();
+        let mut expected = crate::AccumulatorTree::new();
+        for k in ["a", "b", "c"] {
+            expected.insert(k.to_string(), format!("f{}", k));
+        }
+        expected.delete("b", "fb");
+        let mut batched = crate::AccumulatorTree::new();
+        for k in ["a", "b", "c"] {
+            batched.insert(k.to_string(), format!("f{}", k));
+        }
+        batched.delete("b", "fb");
+
+        // New keys, a repeated new key, an existing key and a revived one
+        let items: Vec<(String, String)> = [
+            ("d", "fd"),
+            ("a", "fa2"),
+            ("e", "fe"),
+            ("d", "fd2"),
+            ("b", "fb2"),
+            ("f", "ff"),
+            ("g", "fg"),
+            ("h", "fh"),
+        ]
+        .iter()
+        .map(|(k, f)| (k.to_string(), f.to_string()))
+        .collect();
+        for (k, f) in items.clone() {
+            expected.insert(k, f);
+        }
+        assert_eq!(batched.insert_batch(items), 8);
+        assert_eq!(batched.root_hashes(), expected.root_hashes());
+        let accs: Vec<G1Affine> = batched.roots.iter().map(|r| r.acc()).collect();
+        let want: Vec<G1Affine> = expected.roots.iter().map(|r| r.acc()).collect();
+        assert_eq!(accs, want);
+        assert_eq!(batched.select("d"), expected.select("d"));
+
+        // Rejections follow the revive policy as single inserts would
+        batched.set_revive_policy(crate::policy::RevivePolicy::ErrorOnDuplicate);
+        let items = [("a", "x"), ("i", "fi"), ("i", "fi2")]
+            .iter()
+            .map(|(k, f)| (k.to_string(), f.to_string()));
+        assert_eq!(batched.insert_batch(items), 1);
+        assert_eq!(batched.select("i").unwrap().len(), 1);
+    }
+
+    /// Unit test: Small subtrees keep their keys inline, larger ones share them
+    #[test]
+    fn test_small_key_sets_inline() {
+        crate::setup::init_test_parameters();
+        let mut tree = crate::AccumulatorTree::new();
+        for k in ["h", "g", "f", "e", "d", "c", "b", "a"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        let root = &tree.roots[0];
+        let Node::NonLeaf { keys, left, .. } = root.as_ref() else {
+            panic!("expected a NonLeaf root");
+        };
+        assert!(matches!(keys, NodeKeys::Filtered { len: 8, .. }));
+        assert!(matches!(left.as_ref(), Node::NonLeaf { keys: NodeKeys::Inline(_), .. }));
+        for k in ["a", "d", "h"] {
+            assert!(root.has_key(k));
+        }
+        assert!(!root.has_key("i") && !left.has_key("") && !left.has_key("zz"));
+        assert_eq!(root.keys().len(), 8);
+
+        // Recomputed sets pick their representation again
+        tree.delete("a", "fa");
+        tree.publish_epoch();
+        assert!(!tree.roots[0].has_key("a"));
+        assert_eq!(tree.roots[0].live_key_count(), 7);
+    }
 }
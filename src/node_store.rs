@@ -0,0 +1,334 @@
+//! Content-addressed node storage.
+//!
+//! A `NodeStore` keeps forest nodes by hash, each internal node naming its
+//! children by hash, together with the current root list. Because nodes are
+//! addressed by content, `AccumulatorTree::save_nodes` only writes what the
+//! store lacks: after a batch of inserts, the new leaves and the paths above
+//! them. `lookup` answers a point query by fetching the nodes on the way to
+//! one leaf, so a forest too large to hold in memory can still be served from
+//! the store; `AccumulatorTree::load_nodes` reads a forest back whole.
+//!
+//! Every `ObjectStore` is a `NodeStore`, with nodes under `nodes/<hash hex>`
+//! and the root list under `roots`. The `sled-store` feature adds `SledStore`,
+//! an embedded on-disk `ObjectStore`.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::node::{Node, NodeKeys};
+use crate::storage::ObjectStore;
+use crate::utils::{leaf_hash, nonleaf_hash};
+use crate::wire::{CanonicalDecode, WireReader};
+use accumulator_ads::{G1Affine, Set};
+use std::sync::Arc;
+
+/// Object key of the root list in an `ObjectStore`.
+const ROOTS_KEY: &str = "roots";
+
+/// A node as stored: internal nodes refer to their children by hash.
+/// Only built on the way to and from a store, so the variants are not boxed.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum StoredNode {
+    Leaf {
+        key: String,
+        fids: Set<String>,
+        deleted: bool,
+    },
+    NonLeaf {
+        level: usize,
+        keys: NodeKeys,
+        acc: G1Affine,
+        stale: bool,
+        left: Hash,
+        right: Hash,
+    },
+}
+
+impl StoredNode {
+    /// Shallow copy of `node`.
+    pub fn of(node: &Node) -> Self {
+        match node {
+            Node::Leaf {
+                key, fids, deleted, ..
+            } => StoredNode::Leaf {
+                key: key.clone(),
+                fids: fids.clone(),
+                deleted: *deleted,
+            },
+            Node::NonLeaf {
+                keys,
+                acc,
+                level,
+                left,
+                right,
+                stale,
+                ..
+            } => StoredNode::NonLeaf {
+                level: *level,
+                keys: keys.clone(),
+                acc: *acc,
+                stale: *stale,
+                left: left.hash(),
+                right: right.hash(),
+            },
+        }
+    }
+
+    /// Hash of the node, the key it is stored under.
+    pub fn hash(&self) -> Hash {
+        match self {
+            StoredNode::Leaf { key, fids, deleted } => leaf_hash(key, fids, 0, *deleted),
+            StoredNode::NonLeaf { left, right, .. } => nonleaf_hash(*left, *right),
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        matches!(self, StoredNode::NonLeaf { stale: true, .. })
+    }
+}
+
+/// Nodes by hash plus the root list.
+pub trait NodeStore {
+    /// The node stored under `hash`, or `None` if there is none.
+    fn get_node(&self, hash: &Hash) -> Result<Option<StoredNode>, String>;
+    /// Store `node` under its hash.
+    fn put_node(&self, node: &StoredNode) -> Result<(), String>;
+    /// Root hashes in forest order, or `None` if none were saved.
+    fn get_roots(&self) -> Result<Option<Vec<Hash>>, String>;
+    fn put_roots(&self, roots: &[Hash]) -> Result<(), String>;
+}
+
+fn node_key(hash: &Hash) -> String {
+    format!("nodes/{}", hex::encode(hash))
+}
+
+impl<S: ObjectStore + ?Sized> NodeStore for S {
+    fn get_node(&self, hash: &Hash) -> Result<Option<StoredNode>, String> {
+        let Some(bytes) = self.get(&node_key(hash))? else {
+            return Ok(None);
+        };
+        let mut r = WireReader::new(&bytes);
+        let node = StoredNode::decode_canonical(&mut r)?;
+        r.finish()?;
+        if node.hash() != *hash {
+            return Err(format!(
+                "node {} does not match its hash",
+                hex::encode(hash)
+            ));
+        }
+        Ok(Some(node))
+    }
+
+    fn put_node(&self, node: &StoredNode) -> Result<(), String> {
+        self.put(&node_key(&node.hash()), &node.canonical_bytes())
+    }
+
+    fn get_roots(&self) -> Result<Option<Vec<Hash>>, String> {
+        let Some(bytes) = self.get(ROOTS_KEY)? else {
+            return Ok(None);
+        };
+        let mut r = WireReader::new(&bytes);
+        let roots = Vec::<Hash>::decode_canonical(&mut r)?;
+        r.finish()?;
+        Ok(Some(roots))
+    }
+
+    fn put_roots(&self, roots: &[Hash]) -> Result<(), String> {
+        self.put(ROOTS_KEY, &roots.to_vec().canonical_bytes())
+    }
+}
+
+fn fetch(store: &dyn NodeStore, hash: &Hash) -> Result<StoredNode, String> {
+    store
+        .get_node(hash)?
+        .ok_or_else(|| format!("missing node {}", hex::encode(hash)))
+}
+
+/// Write `node` and the part of its subtree `store` lacks, children first so
+/// a stored node always has its subtree stored. Returns the nodes written.
+pub(crate) fn save_subtree(store: &dyn NodeStore, node: &Node) -> Result<usize, String> {
+    if let Some(stored) = store.get_node(&node.hash())? {
+        // Same hash, same subtree; only a refreshed key set can be newer
+        if !stored.is_stale() || node.is_stale() {
+            return Ok(0);
+        }
+    }
+    let mut written = 0;
+    if let Node::NonLeaf { left, right, .. } = node {
+        written += save_subtree(store, left)?;
+        written += save_subtree(store, right)?;
+    }
+    store.put_node(&StoredNode::of(node))?;
+    Ok(written + 1)
+}
+
+/// Read the subtree under `hash` back into memory.
+pub(crate) fn load_subtree(store: &dyn NodeStore, hash: &Hash) -> Result<Arc<Node>, String> {
+    let node = match fetch(store, hash)? {
+        StoredNode::Leaf { key, fids, deleted } => Node::leaf(key, fids, deleted),
+        StoredNode::NonLeaf {
+            level,
+            keys,
+            acc,
+            stale,
+            left,
+            right,
+        } => {
+            let left = load_subtree(store, &left)?;
+            let right = load_subtree(store, &right)?;
+            if level == 0 || left.level() + 1 != level || right.level() + 1 != level {
+                return Err(format!("children of a level {} node are unbalanced", level));
+            }
+            Node::NonLeaf {
+                hash: *hash,
+                keys,
+                acc,
+                level,
+                left,
+                right,
+                stale,
+            }
+        }
+    };
+    Ok(Arc::new(node))
+}
+
+/// Live FIDs of `key` in the forest saved to `store`, fetching only the nodes
+/// whose key sets may hold it.
+pub fn lookup(store: &dyn NodeStore, key: &str) -> Result<Option<Set<String>>, String> {
+    for root in store.get_roots()?.unwrap_or_default() {
+        if let Some(fids) = lookup_in(store, &root, key)? {
+            return Ok(Some(fids));
+        }
+    }
+    Ok(None)
+}
+
+fn lookup_in(store: &dyn NodeStore, hash: &Hash, key: &str) -> Result<Option<Set<String>>, String> {
+    match fetch(store, hash)? {
+        StoredNode::Leaf {
+            key: leaf_key,
+            fids,
+            deleted,
+        } => Ok((leaf_key == key && !deleted).then_some(fids)),
+        StoredNode::NonLeaf {
+            keys, left, right, ..
+        } => {
+            if !keys.may_contain(key) {
+                return Ok(None);
+            }
+            match lookup_in(store, &left, key)? {
+                Some(fids) => Ok(Some(fids)),
+                None => lookup_in(store, &right, key),
+            }
+        }
+    }
+}
+
+impl CanonicalEncode for StoredNode {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            StoredNode::Leaf { key, fids, deleted } => {
+                out.push(0);
+                key.encode_canonical(out);
+                fids.encode_canonical(out);
+                deleted.encode_canonical(out);
+            }
+            StoredNode::NonLeaf {
+                level,
+                keys,
+                acc,
+                stale,
+                left,
+                right,
+            } => {
+                out.push(1);
+                (*level as u64).encode_canonical(out);
+                keys.encode_canonical(out);
+                acc.encode_canonical(out);
+                stale.encode_canonical(out);
+                left.encode_canonical(out);
+                right.encode_canonical(out);
+            }
+        }
+    }
+}
+
+impl CanonicalDecode for StoredNode {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        match u8::decode_canonical(r)? {
+            0 => Ok(StoredNode::Leaf {
+                key: String::decode_canonical(r)?,
+                fids: CanonicalDecode::decode_canonical(r)?,
+                deleted: bool::decode_canonical(r)?,
+            }),
+            1 => Ok(StoredNode::NonLeaf {
+                level: u64::decode_canonical(r)? as usize,
+                keys: NodeKeys::decode_canonical(r)?,
+                acc: CanonicalDecode::decode_canonical(r)?,
+                stale: bool::decode_canonical(r)?,
+                left: Hash::decode_canonical(r)?,
+                right: Hash::decode_canonical(r)?,
+            }),
+            tag => Err(format!("unknown node tag {}", tag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use crate::storage::MemoryStore;
+
+    #[test]
+    fn test_save_load_and_lookup() {
+        crate::setup::init_test_parameters();
+        let store = MemoryStore::new();
+        assert!(AccumulatorTree::load_nodes(&store).is_err());
+
+        let mut tree = AccumulatorTree::new();
+        for i in 0..6 {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        let first = tree.save_nodes(&store).unwrap();
+        assert_eq!(tree.save_nodes(&store).unwrap(), 0);
+
+        // Two more leaves merge into the existing level-2 root, so only the
+        // new leaves and the path above them are written
+        tree.insert("k6".to_string(), "f6".to_string());
+        tree.insert("k7".to_string(), "f7".to_string());
+        let second = tree.save_nodes(&store).unwrap();
+        assert!(second > 0 && second < first);
+
+        let loaded = AccumulatorTree::load_nodes(&store).unwrap();
+        assert_eq!(loaded.root_hashes(), tree.root_hashes());
+        assert_eq!(loaded.global_acc(), tree.global_acc());
+        assert_eq!(lookup(&store, "k3").unwrap(), tree.select("k3"));
+        assert_eq!(lookup(&store, "missing").unwrap(), None);
+
+        // Tombstones and refreshed key sets are written again
+        tree.delete("k3", "f3");
+        tree.save_nodes(&store).unwrap();
+        assert_eq!(lookup(&store, "k3").unwrap(), None);
+        tree.refresh();
+        assert!(tree.save_nodes(&store).unwrap() > 0);
+        assert!(
+            !AccumulatorTree::load_nodes(&store)
+                .unwrap()
+                .has_stale_nodes()
+        );
+
+        // A node that does not hash to its key is rejected
+        let root = tree.root_hashes()[0];
+        let bogus = StoredNode::Leaf {
+            key: "x".to_string(),
+            fids: Set::new(),
+            deleted: false,
+        };
+        store
+            .put(&node_key(&root), &bogus.canonical_bytes())
+            .unwrap();
+        assert!(AccumulatorTree::load_nodes(&store).is_err());
+    }
+}
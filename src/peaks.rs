@@ -0,0 +1,192 @@
+//! A single commitment to the whole forest ("bagging the peaks").
+//!
+//! The forest is a list of perfect trees whose count changes with every
+//! insert, so a verifier holding the roots themselves has to track a
+//! variable-length list. Instead, each root is committed to as a `Peak`
+//! (level, hash and accumulator) and the peaks are folded right to left into
+//! one digest, as in a Merkle mountain range:
+//!
+//! ```text
+//! digest = bag(p0, bag(p1, ... bag(p(n-2), p(n-1))))
+//! ```
+//!
+//! A `PeakProof` carries what is needed to get from one root to that digest:
+//! the commitments of the peaks to its left and the bag of the peaks to its
+//! right. Together with a leaf's Merkle path (`Proof::verify_in_digest`) it
+//! proves the leaf against the digest alone, and it authenticates the root's
+//! accumulator, so a membership witness can be checked against `peak.acc`.
+//!
+//! Peak commitments and bags are domain-separated from each other and from
+//! node hashes, so neither can be passed off as the other.
+
+use crate::Hash;
+use crate::canonical::{CanonicalEncode, impl_canonical_eq};
+use crate::validate::{MAX_PATH_LEN, Validate, check_point};
+use accumulator_ads::G1Affine;
+use sha2::{Digest, Sha256};
+
+const PEAK_TAG: &[u8] = b"acc-tree/peak";
+const BAG_TAG: &[u8] = b"acc-tree/bag";
+const EMPTY_TAG: &[u8] = b"acc-tree/empty-forest";
+
+/// One root of the forest as committed to by the digest.
+#[derive(Debug, Clone)]
+pub struct Peak {
+    /// Height of the root; every Merkle path under it has this length
+    pub level: u64,
+    pub hash: Hash,
+    /// Accumulator over the root's key set
+    pub acc: G1Affine,
+}
+
+impl Peak {
+    /// Hash of the peak as it enters the bag.
+    pub fn commitment(&self) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update(PEAK_TAG);
+        hasher.update(self.canonical_bytes());
+        hasher.finalize().into()
+    }
+}
+
+fn bag(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(BAG_TAG);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Fold `hashes` right to left; `None` for an empty list.
+fn bag_hashes(hashes: &[Hash]) -> Option<Hash> {
+    let (last, rest) = hashes.split_last()?;
+    Some(rest.iter().rev().fold(*last, |acc, h| bag(h, &acc)))
+}
+
+/// Digest of the peaks in forest order.
+pub fn bag_peaks(peaks: &[Peak]) -> Hash {
+    let commitments: Vec<Hash> = peaks.iter().map(Peak::commitment).collect();
+    bag_hashes(&commitments).unwrap_or_else(|| Sha256::digest(EMPTY_TAG).into())
+}
+
+/// Proof that a peak is part of a forest digest.
+#[derive(Debug, Clone)]
+pub struct PeakProof {
+    pub peak: Peak,
+    /// Commitments of the peaks before this one, in forest order
+    pub left: Vec<Hash>,
+    /// Bag of the peaks after this one, if there are any
+    pub right: Option<Hash>,
+}
+
+impl PeakProof {
+    /// Proof for `peaks[index]`, or `None` if it is out of range.
+    pub fn new(peaks: &[Peak], index: usize) -> Option<Self> {
+        let peak = peaks.get(index)?.clone();
+        let commitments: Vec<Hash> = peaks.iter().map(Peak::commitment).collect();
+        Some(Self {
+            peak,
+            left: commitments[..index].to_vec(),
+            right: bag_hashes(&commitments[index + 1..]),
+        })
+    }
+
+    /// Digest of the forest this proof places the peak in.
+    pub fn digest(&self) -> Hash {
+        let own = self.peak.commitment();
+        let start = match &self.right {
+            Some(right) => bag(&own, right),
+            None => own,
+        };
+        self.left.iter().rev().fold(start, |acc, h| bag(h, &acc))
+    }
+
+    pub fn verify(&self, digest: &Hash) -> bool {
+        self.digest() == *digest
+    }
+}
+
+impl CanonicalEncode for Peak {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.level.encode_canonical(out);
+        self.hash.encode_canonical(out);
+        self.acc.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for PeakProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.peak.encode_canonical(out);
+        self.left.encode_canonical(out);
+        self.right.encode_canonical(out);
+    }
+}
+
+impl_canonical_eq!(Peak, PeakProof);
+
+impl Validate for PeakProof {
+    fn validate(&self) -> Result<(), String> {
+        check_point(&self.peak.acc, "peak accumulator")?;
+        if self.peak.level > MAX_PATH_LEN as u64 {
+            return Err(format!("peak level {} exceeds maximum", self.peak.level));
+        }
+        // One root per level at most
+        if self.left.len() > MAX_PATH_LEN {
+            return Err(format!("{} peaks to the left is too many", self.left.len()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PeakProof, bag_peaks};
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_leaf_proof_verifies_against_digest() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        let empty = tree.root_digest();
+        assert_eq!(empty, bag_peaks(&[]));
+
+        // 7 keys: roots of levels 0, 1 and 2
+        for i in 0..7 {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        let digest = tree.root_digest();
+        assert_ne!(digest, empty);
+        let peaks = tree.peaks();
+        assert_eq!(peaks.len(), 3);
+        for index in 0..peaks.len() {
+            assert!(PeakProof::new(&peaks, index).unwrap().verify(&digest));
+        }
+        assert!(PeakProof::new(&peaks, 3).is_none());
+
+        for i in 0..7 {
+            let key = format!("k{}", i);
+            let response = tree.select_with_proof(&key).unwrap();
+            assert!(response.verify_by_mode(&key));
+            let proof = response.merkle_proof.unwrap();
+            let peak = tree.peak_proof(&proof.root_hash).unwrap();
+            assert_eq!(Some(peak.peak.acc), response.accumulator);
+            assert!(proof.verify_in_digest(&peak, &digest));
+
+            // The same path does not verify under another peak
+            let at = peaks
+                .iter()
+                .position(|p| p.hash == proof.root_hash)
+                .unwrap();
+            let other = PeakProof::new(&peaks, (at + 1) % peaks.len()).unwrap();
+            assert!(!proof.verify_in_digest(&other, &digest));
+        }
+
+        // Swapping two peaks changes the digest
+        let mut swapped = peaks.clone();
+        swapped.swap(0, 2);
+        assert_ne!(bag_peaks(&swapped), digest);
+
+        tree.insert("k7".to_string(), "f7".to_string());
+        assert_ne!(tree.root_digest(), digest);
+    }
+}
@@ -0,0 +1,500 @@
+//! Per-query choice of proof mode.
+//!
+//! A point query can be answered with a Merkle path (proves the key's FIDs
+//! against a root hash), an accumulator witness (proves the key is in a root's
+//! key set, needs pairings to verify) or both. `ProofPolicy` picks the mode
+//! from what the verifier can check and from the measured cost of producing
+//! each part, and the chosen mode is recorded in the `QueryResponse`.
+
+use std::cmp::Ordering;
+use std::time::Duration;
+
+/// Which parts a `QueryResponse` carries and a verifier must check.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize,
+)]
+pub enum ProofMode {
+    /// FIDs and Merkle path only
+    MerkleOnly,
+    /// Accumulator and (non-)membership witness only
+    AccumulatorOnly,
+    /// Both parts
+    #[default]
+    Combined,
+}
+
+impl ProofMode {
+    pub fn includes_merkle(self) -> bool {
+        self != ProofMode::AccumulatorOnly
+    }
+
+    pub fn includes_accumulator(self) -> bool {
+        self != ProofMode::MerkleOnly
+    }
+
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            ProofMode::MerkleOnly => 0,
+            ProofMode::AccumulatorOnly => 1,
+            ProofMode::Combined => 2,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(ProofMode::MerkleOnly),
+            1 => Some(ProofMode::AccumulatorOnly),
+            2 => Some(ProofMode::Combined),
+            _ => None,
+        }
+    }
+}
+
+/// How much of a `QueryResponse` a verifier checks, independent of how much
+/// the response carries: a constrained client can check only the hashes of a
+/// combined proof while a gateway checks every part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VerificationLevel {
+    /// Merkle path against the returned FIDs; no pairings
+    HashOnly,
+    /// Accumulator (non-)membership witness only
+    AccumulatorOnly,
+    /// Both parts
+    #[default]
+    Full,
+}
+
+impl VerificationLevel {
+    pub fn checks_merkle(self) -> bool {
+        self != VerificationLevel::AccumulatorOnly
+    }
+
+    pub fn checks_accumulator(self) -> bool {
+        self != VerificationLevel::HashOnly
+    }
+}
+
+/// Checks the verifier is able to perform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifierCapabilities {
+    /// Can recompute leaf and node hashes
+    pub merkle: bool,
+    /// Can evaluate pairings for accumulator witnesses
+    pub pairing: bool,
+}
+
+impl VerifierCapabilities {
+    /// Strongest level the verifier can check, or None if it can check
+    /// neither proof kind.
+    pub fn level(self) -> Option<VerificationLevel> {
+        match (self.merkle, self.pairing) {
+            (true, true) => Some(VerificationLevel::Full),
+            (true, false) => Some(VerificationLevel::HashOnly),
+            (false, true) => Some(VerificationLevel::AccumulatorOnly),
+            (false, false) => None,
+        }
+    }
+}
+
+impl Default for VerifierCapabilities {
+    fn default() -> Self {
+        Self {
+            merkle: true,
+            pairing: true,
+        }
+    }
+}
+
+/// What `AccumulatorTree::insert` does with a key that already has a leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RevivePolicy {
+    /// Inserting under a tombstoned key revives it with the new FID
+    #[default]
+    ReviveAllowed,
+    /// Inserting under a tombstoned key is refused; it can only come back
+    /// through `revive_with_proof`
+    RequireExplicitRevive,
+    /// Keys are single-use: inserting under any existing key (live or
+    /// tombstoned) is refused and tombstones are never revived
+    ErrorOnDuplicate,
+}
+
+impl RevivePolicy {
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            RevivePolicy::ReviveAllowed => 0,
+            RevivePolicy::RequireExplicitRevive => 1,
+            RevivePolicy::ErrorOnDuplicate => 2,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(RevivePolicy::ReviveAllowed),
+            1 => Some(RevivePolicy::RequireExplicitRevive),
+            2 => Some(RevivePolicy::ErrorOnDuplicate),
+            _ => None,
+        }
+    }
+
+    /// Check an insert of `key` given its current leaf (`Some(tombstoned)`,
+    /// None if the key has no leaf).
+    pub fn check_insert(self, key: &str, leaf: Option<bool>) -> Result<(), String> {
+        match (self, leaf) {
+            (RevivePolicy::RequireExplicitRevive, Some(true)) => {
+                Err(format!("key '{}' is tombstoned; revive it explicitly", key))
+            }
+            (RevivePolicy::ErrorOnDuplicate, Some(_)) => {
+                Err(format!("key '{}' already exists", key))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check an explicit revive of `key`.
+    pub fn check_revive(self, key: &str) -> Result<(), String> {
+        if self == RevivePolicy::ErrorOnDuplicate {
+            return Err(format!(
+                "key '{}' cannot be revived under ErrorOnDuplicate",
+                key
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Smallest id a custom `OrderPolicy` may use; lower ids are built in.
+pub const CUSTOM_ORDER_MIN: u16 = 256;
+
+/// Order of keys in range queries. Fixed when the tree is created and
+/// recorded in every epoch commitment, so verifiers apply the same order.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum OrderPolicy {
+    /// Byte order: "key10" sorts before "key2"
+    #[default]
+    Lexicographic,
+    /// Runs of digits compare as numbers: "key2" sorts before "key10"
+    Natural,
+    /// Caller-supplied total order, recorded by its id
+    Custom {
+        id: u16,
+        cmp: fn(&str, &str) -> Ordering,
+    },
+}
+
+impl PartialEq for OrderPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for OrderPolicy {}
+
+impl OrderPolicy {
+    /// Custom order recorded as `id`, which must be at least `CUSTOM_ORDER_MIN`.
+    /// `cmp` must be a total order that treats only equal keys as equal.
+    pub fn custom(id: u16, cmp: fn(&str, &str) -> Ordering) -> Result<Self, String> {
+        if id < CUSTOM_ORDER_MIN {
+            return Err(format!("custom order ids start at {}", CUSTOM_ORDER_MIN));
+        }
+        Ok(OrderPolicy::Custom { id, cmp })
+    }
+
+    /// Id recorded in commitments and snapshots.
+    pub fn id(&self) -> u16 {
+        match self {
+            OrderPolicy::Lexicographic => 0,
+            OrderPolicy::Natural => 1,
+            OrderPolicy::Custom { id, .. } => *id,
+        }
+    }
+
+    /// The built-in order with this id (custom orders cannot be recovered).
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            0 => Some(OrderPolicy::Lexicographic),
+            1 => Some(OrderPolicy::Natural),
+            _ => None,
+        }
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            OrderPolicy::Lexicographic => a.cmp(b),
+            OrderPolicy::Natural => natural_cmp(a, b),
+            OrderPolicy::Custom { cmp, .. } => cmp(a, b),
+        }
+    }
+
+    /// Whether `key` lies in `[start, end)`.
+    pub fn in_range(&self, key: &str, start: &str, end: &str) -> bool {
+        self.compare(start, key) != Ordering::Greater && self.compare(key, end) == Ordering::Less
+    }
+}
+
+/// Where new leaves land within a root. Fixed when the tree is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LeafOrder {
+    /// Leaves keep the order they were inserted in; merging two roots only
+    /// hashes the new parent
+    #[default]
+    Insertion,
+    /// Every root keeps its leaves (tombstones included) sorted by the tree's
+    /// `OrderPolicy`, so neighbouring leaves bound the keys between them and a
+    /// `GapProof` can show a key is absent. A merge whose halves overlap
+    /// rebuilds the merged root.
+    Sorted,
+}
+
+impl LeafOrder {
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            LeafOrder::Insertion => 0,
+            LeafOrder::Sorted => 1,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(LeafOrder::Insertion),
+            1 => Some(LeafOrder::Sorted),
+            _ => None,
+        }
+    }
+}
+
+/// Split off the leading run of ASCII digits.
+fn digit_run(s: &[u8]) -> (&[u8], &[u8]) {
+    let n = s.iter().take_while(|b| b.is_ascii_digit()).count();
+    s.split_at(n)
+}
+
+/// Compare digit runs by value and everything else bytewise; keys that only
+/// differ in leading zeros fall back to byte order so distinct keys never
+/// compare equal.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (x.first(), y.first()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let (nx, rest_x) = digit_run(x);
+                let (ny, rest_y) = digit_run(y);
+                let nx = &nx[nx.iter().take_while(|b| **b == b'0').count()..];
+                let ny = &ny[ny.iter().take_while(|b| **b == b'0').count()..];
+                let ord = nx.len().cmp(&ny.len()).then_with(|| nx.cmp(ny));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                (x, y) = (rest_x, rest_y);
+            }
+            (Some(c), Some(d)) => {
+                if c != d {
+                    return c.cmp(d);
+                }
+                (x, y) = (&x[1..], &y[1..]);
+            }
+        }
+    }
+}
+
+/// Weight of a new sample in the moving cost averages (1/8).
+const COST_SMOOTHING: u32 = 8;
+
+fn smooth(avg: Option<Duration>, sample: Duration) -> Duration {
+    match avg {
+        None => sample,
+        Some(avg) => (avg * (COST_SMOOTHING - 1) + sample) / COST_SMOOTHING,
+    }
+}
+
+/// Chooses a `ProofMode` per query.
+#[derive(Debug, Clone, Default)]
+pub struct ProofPolicy {
+    pub capabilities: VerifierCapabilities,
+    /// Generation time above which a combined proof is reduced to its cheaper
+    /// part (None always serves combined proofs when the verifier can check them)
+    pub budget: Option<Duration>,
+    merkle_cost: Option<Duration>,
+    accumulator_cost: Option<Duration>,
+}
+
+impl ProofPolicy {
+    pub fn new(capabilities: VerifierCapabilities) -> Self {
+        Self {
+            capabilities,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Moving averages of the time spent producing (Merkle part, accumulator part).
+    pub fn costs(&self) -> (Option<Duration>, Option<Duration>) {
+        (self.merkle_cost, self.accumulator_cost)
+    }
+
+    /// Record the time spent producing each part of a proof.
+    pub fn record(&mut self, merkle: Option<Duration>, accumulator: Option<Duration>) {
+        if let Some(d) = merkle {
+            self.merkle_cost = Some(smooth(self.merkle_cost, d));
+        }
+        if let Some(d) = accumulator {
+            self.accumulator_cost = Some(smooth(self.accumulator_cost, d));
+        }
+    }
+
+    /// Mode for the next query.
+    pub fn choose(&self) -> Result<ProofMode, String> {
+        let caps = self.capabilities;
+        match (caps.merkle, caps.pairing) {
+            (false, false) => Err("verifier can check neither proof kind".to_string()),
+            (true, false) => Ok(ProofMode::MerkleOnly),
+            (false, true) => Ok(ProofMode::AccumulatorOnly),
+            (true, true) => {
+                if let (Some(budget), Some(m), Some(a)) =
+                    (self.budget, self.merkle_cost, self.accumulator_cost)
+                    && m + a > budget
+                {
+                    return Ok(if m <= a {
+                        ProofMode::MerkleOnly
+                    } else {
+                        ProofMode::AccumulatorOnly
+                    });
+                }
+                Ok(ProofMode::Combined)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_choose_by_capabilities_and_budget() {
+        let merkle_only = VerifierCapabilities {
+            merkle: true,
+            pairing: false,
+        };
+        assert_eq!(
+            ProofPolicy::new(merkle_only).choose(),
+            Ok(ProofMode::MerkleOnly)
+        );
+        let none = VerifierCapabilities {
+            merkle: false,
+            pairing: false,
+        };
+        assert!(ProofPolicy::new(none).choose().is_err());
+
+        let mut policy = ProofPolicy::default().with_budget(Duration::from_millis(5));
+        assert_eq!(policy.choose(), Ok(ProofMode::Combined));
+        policy.record(
+            Some(Duration::from_millis(1)),
+            Some(Duration::from_millis(2)),
+        );
+        assert_eq!(policy.choose(), Ok(ProofMode::Combined));
+        for _ in 0..32 {
+            policy.record(
+                Some(Duration::from_millis(1)),
+                Some(Duration::from_millis(20)),
+            );
+        }
+        assert_eq!(policy.choose(), Ok(ProofMode::MerkleOnly));
+    }
+
+    #[test]
+    fn test_select_with_policy_records_mode() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+
+        let mut policy = ProofPolicy::new(VerifierCapabilities {
+            merkle: true,
+            pairing: false,
+        });
+        let response = tree.select_with_policy("a", &mut policy).unwrap();
+        assert_eq!(response.mode, ProofMode::MerkleOnly);
+        assert!(response.acc_proof.is_none() && response.accumulator.is_none());
+        assert!(response.verify_by_mode("a"));
+        assert!(!response.verify_by_mode("b"));
+        assert!(policy.costs().0.is_some() && policy.costs().1.is_none());
+        // Absence can only be shown with the accumulator
+        assert!(tree.select_with_policy("zzz", &mut policy).is_err());
+
+        let mut policy = ProofPolicy::new(VerifierCapabilities {
+            merkle: false,
+            pairing: true,
+        });
+        let response = tree.select_with_policy("zzz", &mut policy).unwrap();
+        assert_eq!(response.mode, ProofMode::AccumulatorOnly);
+        assert!(response.merkle_proof.is_none());
+        assert!(response.verify_by_mode("zzz"));
+    }
+
+    #[test]
+    fn test_verification_levels() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+        tree.publish_epoch();
+        let light = VerifierCapabilities {
+            merkle: true,
+            pairing: false,
+        };
+        assert_eq!(light.level(), Some(VerificationLevel::HashOnly));
+        assert_eq!(
+            VerifierCapabilities::default().level(),
+            Some(VerificationLevel::Full)
+        );
+
+        // A combined proof can be checked at any level it carries
+        let combined = tree.select_with_proof("a").unwrap();
+        assert!(combined.carries(VerificationLevel::Full));
+        assert!(combined.verify_at("a", VerificationLevel::Full));
+        assert!(combined.verify_at("a", VerificationLevel::AccumulatorOnly));
+        assert!(combined.verify_at("a", VerificationLevel::HashOnly));
+        assert!(!combined.verify_at("b", VerificationLevel::HashOnly));
+
+        // A merkle-only proof cannot satisfy a level that checks the accumulator
+        let merkle_only = tree
+            .select_with_policy("a", &mut ProofPolicy::new(light))
+            .unwrap();
+        assert!(merkle_only.carries(VerificationLevel::HashOnly));
+        assert!(!merkle_only.carries(VerificationLevel::Full));
+        assert!(merkle_only.verify_at("a", VerificationLevel::HashOnly));
+        assert!(!merkle_only.verify_at("a", VerificationLevel::Full));
+        assert!(!merkle_only.verify_at("a", VerificationLevel::AccumulatorOnly));
+
+        // Absence is only provable with the accumulator
+        let absent = tree.select_with_proof("zzz").unwrap();
+        assert!(!absent.carries(VerificationLevel::HashOnly));
+        assert!(!absent.verify_at("zzz", VerificationLevel::HashOnly));
+        assert!(absent.verify_at("zzz", VerificationLevel::AccumulatorOnly));
+        assert!(absent.verify_at("zzz", VerificationLevel::Full));
+    }
+
+    #[test]
+    fn test_natural_order() {
+        let order = OrderPolicy::Natural;
+        let mut keys = vec!["key10", "key2", "key02", "a9b", "a10a", "key", "k"];
+        keys.sort_by(|a, b| order.compare(a, b));
+        assert_eq!(
+            keys,
+            vec!["a9b", "a10a", "k", "key", "key02", "key2", "key10"]
+        );
+        assert!(order.in_range("key9", "key2", "key10"));
+        assert!(!OrderPolicy::Lexicographic.in_range("key9", "key2", "key10"));
+        assert_eq!(OrderPolicy::from_id(order.id()), Some(order));
+        assert_eq!(OrderPolicy::from_id(2), None);
+    }
+}
@@ -0,0 +1,44 @@
+//! Supported public API in one import.
+//!
+//! `use accumulator_tree::prelude::*;` brings in the forest, its proofs and
+//! responses, its errors, epoch commitments, the wire format and the
+//! verification traits. Items outside the prelude are reachable through their
+//! modules but may change between releases.
+
+pub use crate::Hash;
+pub use crate::acc_proof::{AccProof, MembershipProof, NonMembershipProof};
+pub use crate::anchor::{Anchor, AnchorReceipt, HttpPost};
+pub use crate::archive::{ArchiveConfig, ProofArchive};
+#[cfg(feature = "signed-roots")]
+pub use crate::bundle::VerifierBundle;
+pub use crate::canonical::CanonicalEncode;
+pub use crate::epoch::{EpochCommitment, EpochTransition, RootEntry, RootManifest, RootStamp};
+pub use crate::error::AccTreeError;
+pub use crate::key::{AccKey, AccValue};
+pub use crate::lookup::LookupResult;
+pub use crate::merkle_proof::Proof as MerkleProof;
+pub use crate::node::Node;
+pub use crate::policy::{
+    ProofMode, ProofPolicy, RevivePolicy, VerificationLevel, VerifierCapabilities,
+};
+pub use crate::query::{BoolOp, Query};
+pub use crate::response::{
+    DeleteResponse, FidAnswer, FidQueryResponse, InsertResponse, QueryResponse, ReviveResponse,
+    TransactionResponse, UpdateResponse,
+};
+pub use crate::response_builder::{
+    DeleteResponseBuilder, InsertResponseBuilder, UpdateResponseBuilder,
+};
+pub use crate::setup::SetupFeatures;
+pub use crate::storage::ObjectStore;
+pub use crate::tree::AccumulatorTree;
+pub use crate::txn::{TreeTxn, TxnOp};
+pub use crate::validate::Validate;
+pub use crate::verifier::Verifier;
+pub use crate::wire::{CanonicalDecode, WireMessage, from_wire, to_wire};
+
+#[cfg(feature = "client")]
+pub use crate::client::{CommitmentTracker, FreshnessPolicy};
+
+#[cfg(feature = "signed-roots")]
+pub use crate::announce::RootAnnouncement;
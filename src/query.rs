@@ -0,0 +1,163 @@
+//! Typed queries with a reproducible hash.
+//!
+//! A response that carries the hash of the question it answers cannot be
+//! replayed as the answer to another question, and the hash is a stable key
+//! for archives and caches. `Query::query_hash` is computed over the canonical
+//! encoding; point queries keep the `point_query_hash` of the archive so
+//! existing archive keys stay valid.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use sha2::{Digest, Sha256};
+
+const QUERY_DOMAIN: &[u8] = b"acc-tree/query/v1";
+
+/// How the operands of a `Query::Boolean` are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BoolOp {
+    And,
+    Or,
+    /// First operand minus the union of the others
+    Not,
+}
+
+impl BoolOp {
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            BoolOp::And => 0,
+            BoolOp::Or => 1,
+            BoolOp::Not => 2,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(BoolOp::And),
+            1 => Some(BoolOp::Or),
+            2 => Some(BoolOp::Not),
+            _ => None,
+        }
+    }
+}
+
+/// A question asked of the forest.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Query {
+    /// FIDs of one key
+    Point(String),
+    /// Keys in `[start, end)`
+    Range { start: String, end: String },
+    /// Keys starting with the prefix
+    Prefix(String),
+    /// Combination of sub-queries
+    Boolean { op: BoolOp, operands: Vec<Query> },
+    /// `n` leaves selected by `seed` (see `AccumulatorTree::sample_keys`)
+    Sample { seed: Vec<u8>, n: u64 },
+}
+
+impl Query {
+    pub(crate) fn tag(&self) -> u8 {
+        match self {
+            Query::Point(_) => 0,
+            Query::Range { .. } => 1,
+            Query::Prefix(_) => 2,
+            Query::Boolean { .. } => 3,
+            Query::Sample { .. } => 4,
+        }
+    }
+
+    /// Hash binding a response to this exact query.
+    pub fn query_hash(&self) -> Hash {
+        if let Query::Point(key) = self {
+            return crate::archive::point_query_hash(key);
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(QUERY_DOMAIN);
+        hasher.update(self.canonical_bytes());
+        hasher.finalize().into()
+    }
+}
+
+impl CanonicalEncode for Query {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.push(self.tag());
+        match self {
+            Query::Point(key) | Query::Prefix(key) => key.encode_canonical(out),
+            Query::Range { start, end } => {
+                start.encode_canonical(out);
+                end.encode_canonical(out);
+            }
+            Query::Boolean { op, operands } => {
+                out.push(op.tag());
+                operands.encode_canonical(out);
+            }
+            Query::Sample { seed, n } => {
+                seed.encode_canonical(out);
+                n.encode_canonical(out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{from_wire, to_wire};
+
+    fn point(k: &str) -> Query {
+        Query::Point(k.to_string())
+    }
+
+    #[test]
+    fn test_query_hash_is_structural() {
+        assert_eq!(
+            point("a").query_hash(),
+            crate::archive::point_query_hash("a")
+        );
+        assert_ne!(
+            point("a").query_hash(),
+            Query::Prefix("a".to_string()).query_hash()
+        );
+        let and = |ops: Vec<Query>| Query::Boolean {
+            op: BoolOp::And,
+            operands: ops,
+        };
+        assert_ne!(
+            and(vec![point("a"), point("b")]).query_hash(),
+            and(vec![point("b"), point("a")]).query_hash()
+        );
+        // Length prefixes keep field boundaries apart
+        let range = |s: &str, e: &str| Query::Range {
+            start: s.to_string(),
+            end: e.to_string(),
+        };
+        assert_ne!(range("ab", "c").query_hash(), range("a", "bc").query_hash());
+
+        let query = Query::Boolean {
+            op: BoolOp::Not,
+            operands: vec![
+                Query::Prefix("user/".to_string()),
+                range("user/m", "user/n"),
+                Query::Sample {
+                    seed: b"seed".to_vec(),
+                    n: 3,
+                },
+            ],
+        };
+        let decoded: Query = from_wire(&to_wire(&query)).unwrap();
+        assert_eq!(decoded, query);
+        assert_eq!(decoded.query_hash(), query.query_hash());
+    }
+
+    #[test]
+    fn test_deeply_nested_query_rejected() {
+        let mut query = point("a");
+        for _ in 0..100 {
+            query = Query::Boolean {
+                op: BoolOp::Or,
+                operands: vec![query],
+            };
+        }
+        assert!(from_wire::<Query>(&to_wire(&query)).is_err());
+    }
+}
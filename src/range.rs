@@ -0,0 +1,327 @@
+//! Authenticated range queries.
+//!
+//! Leaves sit in the forest in insertion order, not key order (unless the
+//! tree sorts them, see `gap`), so a range has no boundary neighbours whose
+//! paths could show nothing lies between them.
+//! Completeness comes from the published epoch instead: its global
+//! accumulator commits to exactly the live key set. A `RangeResponse` returns
+//! the FIDs and Merkle paths of the keys inside the range and lists every
+//! other live key; the verifier checks the outside keys really are outside
+//! and that both lists together accumulate to the committed value. Leaving out
+//! an in-range key changes that accumulator. The price is a response that
+//! names every live key and a verifier holding parameters of that degree.
+//! Bounds and listing order follow the tree's `OrderPolicy`, whose id the
+//! commitment carries.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::epoch::{EpochCommitment, RootEntry, forest_hash};
+use crate::policy::OrderPolicy;
+use crate::query::Query;
+use crate::response::LeafWitness;
+use accumulator_ads::{DynamicAccumulator, Fr, Set, digest_set_from_set};
+use std::cmp::Ordering;
+
+/// A key inside the range with its leaf.
+#[derive(Debug, Clone)]
+pub struct RangeEntry {
+    pub key: String,
+    pub leaf: LeafWitness,
+}
+
+/// Every live key in `[start, end)` of a published epoch, with a proof that
+/// none was left out.
+#[derive(Debug, Clone)]
+pub struct RangeResponse {
+    pub start: String,
+    pub end: String,
+    pub commitment: EpochCommitment,
+    /// (level, hash) of the roots of the committed forest
+    pub roots: Vec<RootEntry>,
+    /// Live keys inside the range, in key order
+    pub entries: Vec<RangeEntry>,
+    /// Live keys outside the range, in key order
+    pub other_keys: Vec<String>,
+}
+
+/// Strictly increasing under `order`, so no key is listed twice.
+fn strictly_sorted<'a>(order: &OrderPolicy, mut keys: impl Iterator<Item = &'a str>) -> bool {
+    let Some(mut prev) = keys.next() else {
+        return true;
+    };
+    keys.all(|k| {
+        let ok = order.compare(prev, k) == Ordering::Less;
+        prev = k;
+        ok
+    })
+}
+
+impl RangeResponse {
+    /// The query this response answers.
+    pub fn query(&self) -> Query {
+        Query::Range {
+            start: self.start.clone(),
+            end: self.end.clone(),
+        }
+    }
+
+    /// `(key, fid)` pairs in the range, ordered by key then FID.
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        for entry in &self.entries {
+            let mut fids: Vec<&String> = entry.leaf.fids.iter().collect();
+            fids.sort();
+            pairs.extend(fids.into_iter().map(|f| (entry.key.clone(), f.clone())));
+        }
+        pairs
+    }
+
+    pub fn verify(&self, trusted_digest: &Hash) -> bool {
+        self.check(trusted_digest).is_ok()
+    }
+
+    /// `check_with_order` under the built-in order the commitment names.
+    /// Trees with a custom order must be checked with `check_with_order`.
+    pub fn check(&self, trusted_digest: &Hash) -> Result<(), String> {
+        let order = OrderPolicy::from_id(self.commitment.key_order).ok_or_else(|| {
+            format!(
+                "commitment uses custom key order {}",
+                self.commitment.key_order
+            )
+        })?;
+        self.check_with_order(trusted_digest, &order)
+    }
+
+    /// Checks:
+    /// 1. The commitment is the trusted one and commits to `roots`
+    /// 2. Every entry is in the range and its live leaf verifies against a root
+    /// 3. Every other key is outside the range
+    /// 4. Entries and other keys are exactly the committed live key set: the
+    ///    count matches and they accumulate to the global accumulator
+    ///
+    /// Key order and range bounds follow `order`, which must be the one the
+    /// commitment names.
+    pub fn check_with_order(
+        &self,
+        trusted_digest: &Hash,
+        order: &OrderPolicy,
+    ) -> Result<(), String> {
+        check_frame(
+            trusted_digest,
+            order,
+            &self.commitment,
+            &self.roots,
+            (&self.start, &self.end),
+            &self.other_keys,
+        )?;
+
+        if !strictly_sorted(order, self.entries.iter().map(|e| e.key.as_str())) {
+            return Err("entries are not in strict key order".to_string());
+        }
+        for entry in &self.entries {
+            if !order.in_range(&entry.key, &self.start, &self.end) {
+                return Err(format!("key '{}' is outside the range", entry.key));
+            }
+            if entry.leaf.deleted || !entry.leaf.verify(&entry.key, &self.roots) {
+                return Err(format!("invalid leaf for '{}'", entry.key));
+            }
+        }
+
+        // In-range and outside keys are disjoint, so the count is exact
+        if (self.entries.len() + self.other_keys.len()) as u64 != self.commitment.key_count {
+            return Err("key count does not match the commitment".to_string());
+        }
+        let keys: Vec<String> = self
+            .entries
+            .iter()
+            .map(|e| e.key.clone())
+            .chain(self.other_keys.iter().cloned())
+            .collect();
+        check_key_set(&self.commitment, &digest_set_from_set(&Set::from_vec(keys)))
+    }
+}
+
+/// Checks shared by batch and streamed range responses: the commitment is the
+/// trusted one under `order` and commits to `roots`, and `other_keys` are
+/// sorted and outside `[start, end)`.
+pub(crate) fn check_frame(
+    trusted_digest: &Hash,
+    order: &OrderPolicy,
+    commitment: &EpochCommitment,
+    roots: &[RootEntry],
+    (start, end): (&str, &str),
+    other_keys: &[String],
+) -> Result<(), String> {
+    if commitment.digest() != *trusted_digest {
+        return Err("commitment does not match the trusted digest".to_string());
+    }
+    if order.id() != commitment.key_order {
+        return Err(format!(
+            "commitment uses key order {}, not {}",
+            commitment.key_order,
+            order.id()
+        ));
+    }
+    if forest_hash(roots) != commitment.forest_hash {
+        return Err("roots do not match the commitment".to_string());
+    }
+    if !strictly_sorted(order, other_keys.iter().map(String::as_str)) {
+        return Err("other keys are not in strict key order".to_string());
+    }
+    if let Some(key) = other_keys.iter().find(|k| order.in_range(k, start, end)) {
+        return Err(format!("key '{}' is inside the range", key));
+    }
+    Ok(())
+}
+
+/// Check that the key elements accumulate to the committed global
+/// accumulator.
+pub(crate) fn check_key_set(commitment: &EpochCommitment, elements: &[Fr]) -> Result<(), String> {
+    let acc = if elements.is_empty() {
+        crate::utils::empty_acc()
+    } else {
+        DynamicAccumulator::calculate_commitment(elements)
+    };
+    if acc != commitment.global_acc {
+        return Err("keys do not accumulate to the committed key set".to_string());
+    }
+    Ok(())
+}
+
+impl CanonicalEncode for RangeEntry {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.leaf.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for RangeResponse {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.start.encode_canonical(out);
+        self.end.encode_canonical(out);
+        self.commitment.encode_canonical(out);
+        self.roots.encode_canonical(out);
+        self.entries.encode_canonical(out);
+        self.other_keys.encode_canonical(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AccumulatorTree;
+
+    fn pair(k: &str, f: &str) -> (String, String) {
+        (k.to_string(), f.to_string())
+    }
+
+    #[test]
+    fn test_range_with_proof() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for k in ["user/d", "admin", "user/a", "user/c", "zeta", "user/b"] {
+            tree.insert(k.to_string(), format!("f-{}", k));
+        }
+        tree.insert("user/a".to_string(), "g".to_string());
+        tree.delete("user/c", "f-user/c");
+        assert!(tree.range_with_proof("user/", "user0").is_err());
+        let trusted = tree.publish_epoch().digest();
+
+        let response = tree.range_with_proof("user/", "user0").unwrap();
+        assert!(response.verify(&trusted));
+        assert_eq!(
+            response.pairs(),
+            vec![
+                pair("user/a", "f-user/a"),
+                pair("user/a", "g"),
+                pair("user/b", "f-user/b"),
+                pair("user/d", "f-user/d"),
+            ]
+        );
+        assert_eq!(response.other_keys, vec!["admin", "zeta"]);
+
+        let empty = tree.range_with_proof("b", "c").unwrap();
+        assert!(empty.verify(&trusted) && empty.entries.is_empty());
+
+        // Dropping an in-range key, or passing it off as outside, is caught
+        let mut dropped = response.clone();
+        dropped.entries.remove(1);
+        assert!(dropped.check(&trusted).is_err());
+        let mut moved = dropped.clone();
+        moved.other_keys.insert(1, "user/b".to_string());
+        assert!(moved.check(&trusted).is_err());
+
+        let mut forged = response.clone();
+        forged.entries[0].leaf.fids = accumulator_ads::Set::from_vec(vec!["g".to_string()]);
+        assert!(forged.check(&trusted).is_err());
+        let mut swapped = response.clone();
+        swapped.other_keys[0] = "other".to_string();
+        assert!(swapped.check(&trusted).is_err());
+        assert!(!response.verify(&[0u8; 32]));
+    }
+
+    #[test]
+    fn test_range_follows_key_order() {
+        use crate::policy::OrderPolicy;
+        crate::setup::init_test_parameters();
+        let keys = ["key10", "key2", "key9", "key100"];
+        let mut natural = AccumulatorTree::with_order(OrderPolicy::Natural);
+        let mut lexicographic = AccumulatorTree::new();
+        for k in keys {
+            natural.insert(k.to_string(), "f".to_string());
+            lexicographic.insert(k.to_string(), "f".to_string());
+        }
+        let trusted = natural.publish_epoch();
+        assert_eq!(trusted.key_order, OrderPolicy::Natural.id());
+        let trusted = trusted.digest();
+
+        // "key2" <= "key9" < "key10" only when digit runs compare as numbers
+        let response = natural.range_with_proof("key2", "key10").unwrap();
+        assert!(response.verify(&trusted));
+        let in_range: Vec<&str> = response.entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(in_range, vec!["key2", "key9"]);
+        assert_eq!(response.other_keys, vec!["key10", "key100"]);
+        assert!(
+            response
+                .check_with_order(&trusted, &OrderPolicy::Lexicographic)
+                .is_err()
+        );
+
+        let lex_trusted = lexicographic.publish_epoch().digest();
+        assert!(
+            lexicographic
+                .range_with_proof("key2", "key10")
+                .unwrap()
+                .entries
+                .is_empty()
+        );
+        assert!(
+            lexicographic
+                .range_with_proof("key10", "key2")
+                .unwrap()
+                .verify(&lex_trusted)
+        );
+
+        // A custom order is named by its id and supplied by the verifier
+        fn reversed(a: &str, b: &str) -> std::cmp::Ordering {
+            b.cmp(a)
+        }
+        let order = OrderPolicy::custom(300, reversed).unwrap();
+        assert!(OrderPolicy::custom(7, reversed).is_err());
+        let mut custom = AccumulatorTree::with_order(order);
+        for k in keys {
+            custom.insert(k.to_string(), "f".to_string());
+        }
+        let custom_trusted = custom.publish_epoch().digest();
+        let response = custom.range_with_proof("key9", "key10").unwrap();
+        let in_range: Vec<&str> = response.entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(in_range, vec!["key9", "key2", "key100"]);
+        assert!(response.check(&custom_trusted).is_err());
+        assert!(response.check_with_order(&custom_trusted, &order).is_ok());
+
+        let restored = AccumulatorTree::from_bytes(&natural.to_bytes()).unwrap();
+        assert_eq!(restored.order(), OrderPolicy::Natural);
+        assert!(AccumulatorTree::from_bytes(&custom.to_bytes()).is_err());
+        let restored = AccumulatorTree::from_bytes_with_order(&custom.to_bytes(), order).unwrap();
+        assert_eq!(restored.order(), order);
+    }
+}
@@ -0,0 +1,283 @@
+//! Streamed range queries.
+//!
+//! `AccumulatorTree::select_range_stream` answers the same question as
+//! `range_with_proof` (see `range`) one key at a time. A `RangeHeader` comes
+//! first with the commitment, the forest roots and the keys outside the range;
+//! then a `RangeItem` per key inside the range, produced only when the
+//! consumer asks for it. Consecutive paths often end in the same siblings, so
+//! an item carries only the steps that differ from the previous item's path
+//! and how many of that path's top steps to keep.
+//!
+//! `RangeVerifier` checks items as they arrive and only holds the previous key
+//! and path plus one field element per key for the completeness check in
+//! `finish`. Neither side ever holds the FIDs of more than one key.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::epoch::{EpochCommitment, RootEntry, root_level};
+use crate::error::AccTreeError;
+use crate::node::Node;
+use crate::policy::OrderPolicy;
+use crate::range::{check_frame, check_key_set};
+use crate::witness_cache::key_element;
+use accumulator_ads::{Fr, Set};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// Everything about a streamed range except the keys inside it.
+#[derive(Debug, Clone)]
+pub struct RangeHeader {
+    pub start: String,
+    pub end: String,
+    pub commitment: EpochCommitment,
+    /// (level, hash) of the roots of the committed forest
+    pub roots: Vec<RootEntry>,
+    /// Live keys outside the range, in key order
+    pub other_keys: Vec<String>,
+}
+
+/// A key inside the range with its FIDs and the change to the previous
+/// item's Merkle path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeItem {
+    pub key: String,
+    pub fids: Set<String>,
+    /// Number of top steps of the previous path that this path ends with
+    pub kept: u64,
+    /// Steps below those, from the leaf up
+    pub fresh: Vec<(Hash, bool)>,
+}
+
+/// Lazily proven keys of a range, in key order. Built by
+/// `AccumulatorTree::select_range_stream`. An item is an error if its key
+/// has no leaf, which only a forest that no longer matches the commitment
+/// can cause.
+pub struct RangeStream<'a> {
+    header: RangeHeader,
+    roots: &'a [Arc<Node>],
+    keys: std::vec::IntoIter<&'a String>,
+    path: Vec<(Hash, bool)>,
+}
+
+impl<'a> RangeStream<'a> {
+    pub(crate) fn new(header: RangeHeader, roots: &'a [Arc<Node>], keys: Vec<&'a String>) -> Self {
+        Self {
+            header,
+            roots,
+            keys: keys.into_iter(),
+            path: Vec::new(),
+        }
+    }
+
+    /// Header to send before the items.
+    pub fn header(&self) -> &RangeHeader {
+        &self.header
+    }
+}
+
+impl Iterator for RangeStream<'_> {
+    type Item = Result<RangeItem, AccTreeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.keys.next()?;
+        let Some(leaf) = crate::tree::leaf_witness_in(self.roots, key) else {
+            return Some(Err(AccTreeError::ProofGenFailed(format!(
+                "published key '{}' has no leaf",
+                key
+            ))));
+        };
+        let path = leaf.merkle_proof.path;
+        let kept = path
+            .iter()
+            .rev()
+            .zip(self.path.iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let fresh = path[..path.len() - kept].to_vec();
+        self.path = path;
+        Some(Ok(RangeItem {
+            key: key.clone(),
+            fids: leaf.fids,
+            kept: kept as u64,
+            fresh,
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+/// Checks a streamed range item by item.
+pub struct RangeVerifier {
+    header: RangeHeader,
+    order: OrderPolicy,
+    prev_key: Option<String>,
+    path: Vec<(Hash, bool)>,
+    /// Elements of the keys seen so far, in range or not
+    elements: Vec<Fr>,
+}
+
+impl RangeVerifier {
+    /// `with_order` under the built-in order the commitment names.
+    pub fn new(header: RangeHeader, trusted_digest: &Hash) -> Result<Self, String> {
+        let order = OrderPolicy::from_id(header.commitment.key_order).ok_or_else(|| {
+            format!(
+                "commitment uses custom key order {}",
+                header.commitment.key_order
+            )
+        })?;
+        Self::with_order(header, trusted_digest, order)
+    }
+
+    /// Check the header against the trusted commitment; `order` must be the
+    /// one it names.
+    pub fn with_order(
+        header: RangeHeader,
+        trusted_digest: &Hash,
+        order: OrderPolicy,
+    ) -> Result<Self, String> {
+        check_frame(
+            trusted_digest,
+            &order,
+            &header.commitment,
+            &header.roots,
+            (&header.start, &header.end),
+            &header.other_keys,
+        )?;
+        let elements = header.other_keys.iter().map(|k| key_element(k)).collect();
+        Ok(Self {
+            header,
+            order,
+            prev_key: None,
+            path: Vec::new(),
+            elements,
+        })
+    }
+
+    /// Check the next item: it follows the previous key, lies in the range
+    /// and its live leaf hashes up to a root at the right depth.
+    pub fn push(&mut self, item: &RangeItem) -> Result<(), String> {
+        if let Some(prev) = &self.prev_key
+            && self.order.compare(prev, &item.key) != Ordering::Less
+        {
+            return Err("entries are not in strict key order".to_string());
+        }
+        if !self
+            .order
+            .in_range(&item.key, &self.header.start, &self.header.end)
+        {
+            return Err(format!("key '{}' is outside the range", item.key));
+        }
+        let kept = usize::try_from(item.kept)
+            .ok()
+            .filter(|k| *k <= self.path.len())
+            .ok_or_else(|| format!("'{}' keeps more steps than the last path", item.key))?;
+        let mut path = item.fresh.clone();
+        path.extend_from_slice(&self.path[self.path.len() - kept..]);
+        crate::validate::check_path_len(path.len(), "range item")?;
+
+        let mut hash = crate::utils::leaf_hash(&item.key, &item.fids, 0, false);
+        for (sibling, left) in &path {
+            hash = if *left {
+                crate::utils::nonleaf_hash(*sibling, hash)
+            } else {
+                crate::utils::nonleaf_hash(hash, *sibling)
+            };
+        }
+        if root_level(&self.header.roots, &hash) != Some(path.len() as u64) {
+            return Err(format!("invalid leaf for '{}'", item.key));
+        }
+
+        self.elements.push(key_element(&item.key));
+        self.prev_key = Some(item.key.clone());
+        self.path = path;
+        Ok(())
+    }
+
+    /// Check that the keys seen are exactly the committed live key set.
+    pub fn finish(self) -> Result<(), String> {
+        if self.elements.len() as u64 != self.header.commitment.key_count {
+            return Err("key count does not match the commitment".to_string());
+        }
+        check_key_set(&self.header.commitment, &self.elements)
+    }
+}
+
+impl CanonicalEncode for RangeHeader {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.start.encode_canonical(out);
+        self.end.encode_canonical(out);
+        self.commitment.encode_canonical(out);
+        self.roots.encode_canonical(out);
+        self.other_keys.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for RangeItem {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.fids.encode_canonical(out);
+        self.kept.encode_canonical(out);
+        self.fresh.encode_canonical(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+
+    /// Verify a whole stream.
+    fn check(header: &RangeHeader, items: &[RangeItem], trusted: &Hash) -> Result<(), String> {
+        let mut verifier = RangeVerifier::new(header.clone(), trusted)?;
+        for item in items {
+            verifier.push(item)?;
+        }
+        verifier.finish()
+    }
+
+    #[test]
+    fn test_range_stream_matches_batch_response() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for i in 0..8 {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        tree.insert("admin".to_string(), "fa".to_string());
+        tree.delete("k5", "f5");
+        let trusted = tree.publish_epoch().digest();
+
+        let batch = tree.range_with_proof("k1", "k7").unwrap();
+        let stream = tree.select_range_stream("k1", "k7").unwrap();
+        let header = stream.header().clone();
+        assert_eq!(stream.size_hint(), (5, Some(5)));
+        let items: Vec<RangeItem> = stream.collect::<Result<_, _>>().unwrap();
+        assert_eq!(header.other_keys, batch.other_keys);
+        assert_eq!(items.len(), batch.entries.len());
+        for (item, entry) in items.iter().zip(&batch.entries) {
+            assert_eq!((&item.key, &item.fids), (&entry.key, &entry.leaf.fids));
+        }
+        // Neighbouring leaves of one root share the top of their paths
+        assert!(items.iter().any(|item| item.kept > 0));
+        assert!(check(&header, &items, &trusted).is_ok());
+
+        // Dropped, reordered or altered items are caught
+        let mut dropped = items.clone();
+        dropped.remove(2);
+        assert!(check(&header, &dropped, &trusted).is_err());
+        let mut swapped = items.clone();
+        swapped.swap(0, 1);
+        assert!(check(&header, &swapped, &trusted).is_err());
+        let mut forged = items.clone();
+        forged[1].fids = Set::from_vec(vec!["other".to_string()]);
+        assert!(check(&header, &forged, &trusted).is_err());
+        let mut overkept = items.clone();
+        overkept[0].kept = 1;
+        assert!(check(&header, &overkept, &trusted).is_err());
+        let mut hidden = header.clone();
+        hidden.other_keys.retain(|k| k != "admin");
+        assert!(check(&hidden, &items, &trusted).is_err());
+        assert!(check(&header, &items, &[0u8; 32]).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Recorded workloads for regression hunting.
+//!
+//! A `ReplayLog` lists mutations in the order they were applied to a tree,
+//! each with when it ran and the forest digest (`epoch::forest_hash` of the
+//! roots) right after it, plus the seed of the workload that produced them and
+//! the crate version that recorded them. The log is encoded compactly and
+//! versioned like snapshots, so one written by an older build can be loaded by
+//! a newer one. `first_divergence` replays it on a fresh tree and stops at the
+//! first step whose digest differs; since every step carries its digest, the
+//! single pass pinpoints the operation where a hashing or normalization change
+//! starts to show.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::epoch::forest_hash;
+use crate::tree::AccumulatorTree;
+use crate::txn::TxnOp;
+use crate::wire::{CanonicalDecode, WireReader};
+
+/// Leading bytes of an encoded log.
+pub const REPLAY_MAGIC: &[u8; 4] = b"ACTR";
+
+/// Version written by `ReplayLog::to_bytes`.
+pub const REPLAY_VERSION: u16 = 1;
+
+/// One recorded mutation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayStep {
+    /// Milliseconds since the Unix epoch, by the tree's clock
+    pub at_millis: u64,
+    pub op: TxnOp,
+    /// Forest digest after the operation
+    pub digest: Hash,
+}
+
+/// First step whose replayed digest differs from the recorded one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index into `ReplayLog::steps`
+    pub step: usize,
+    pub op: TxnOp,
+    pub expected: Hash,
+    pub actual: Hash,
+}
+
+/// Mutations applied to a tree, with the digest after each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayLog {
+    /// Seed of the generated workload (0 for recorded traffic)
+    pub seed: u64,
+    /// `CARGO_PKG_VERSION` of the build that recorded the log
+    pub recorded_by: String,
+    pub steps: Vec<ReplayStep>,
+}
+
+/// xorshift64*: deterministic, dependency-free operation mix.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// Apply `op` through the tree's direct mutation path.
+fn apply(tree: &mut AccumulatorTree, op: &TxnOp) {
+    match op {
+        TxnOp::Insert { key, fid } => {
+            tree.insert(key.clone(), fid.clone());
+        }
+        TxnOp::Update {
+            key,
+            old_fid,
+            new_fid,
+        } => {
+            tree.update(key, old_fid, new_fid.clone());
+        }
+        TxnOp::Delete { key, fid } => {
+            tree.delete(key, fid);
+        }
+    }
+}
+
+impl ReplayLog {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            recorded_by: env!("CARGO_PKG_VERSION").to_string(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Apply `op` to `tree` and record it with the resulting digest.
+    pub fn record(&mut self, tree: &mut AccumulatorTree, op: TxnOp) {
+        apply(tree, &op);
+        self.steps.push(ReplayStep {
+            at_millis: crate::clock::unix_millis(tree.now()),
+            op,
+            digest: forest_hash(&tree.root_entries()),
+        });
+    }
+
+    /// Record `ops` mixed inserts, updates and deletes over `keys` keys, drawn
+    /// from `seed`, against a fresh tree.
+    pub fn generate(seed: u64, ops: usize, keys: u64) -> Self {
+        let mut log = Self::new(seed);
+        let mut tree = AccumulatorTree::new();
+        // xorshift has a fixed point at 0
+        let mut rng = Rng((seed ^ 0x9E37_79B9_7F4A_7C15).max(1));
+        for _ in 0..ops {
+            let key = format!("key{}", rng.below(keys.max(1)));
+            let fid = format!("fid{}", rng.below(4));
+            let op = match rng.below(10) {
+                0..=5 => TxnOp::Insert { key, fid },
+                6..=7 => TxnOp::Update {
+                    key,
+                    old_fid: fid,
+                    new_fid: format!("fid{}", rng.below(4)),
+                },
+                _ => TxnOp::Delete { key, fid },
+            };
+            log.record(&mut tree, op);
+        }
+        log
+    }
+
+    /// Replay the log on a fresh tree and return the first step whose digest
+    /// differs from the recorded one (None if every step matches).
+    pub fn first_divergence(&self) -> Option<Divergence> {
+        let mut tree = AccumulatorTree::new();
+        self.steps.iter().enumerate().find_map(|(i, step)| {
+            apply(&mut tree, &step.op);
+            let actual = forest_hash(&tree.root_entries());
+            (actual != step.digest).then(|| Divergence {
+                step: i,
+                op: step.op.clone(),
+                expected: step.digest,
+                actual,
+            })
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = REPLAY_MAGIC.to_vec();
+        REPLAY_VERSION.encode_canonical(&mut out);
+        self.seed.encode_canonical(&mut out);
+        self.recorded_by.encode_canonical(&mut out);
+        self.steps.encode_canonical(&mut out);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let body = bytes.strip_prefix(REPLAY_MAGIC).ok_or("not a replay log")?;
+        let mut r = WireReader::new(body);
+        let version = u16::decode_canonical(&mut r)?;
+        if version != REPLAY_VERSION {
+            return Err(format!("unsupported replay log version {}", version));
+        }
+        let log = Self {
+            seed: u64::decode_canonical(&mut r)?,
+            recorded_by: String::decode_canonical(&mut r)?,
+            steps: Vec::<ReplayStep>::decode_canonical(&mut r)?,
+        };
+        r.finish()?;
+        Ok(log)
+    }
+}
+
+impl CanonicalEncode for ReplayStep {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.at_millis.encode_canonical(out);
+        self.op.encode_canonical(out);
+        self.digest.encode_canonical(out);
+    }
+}
+
+impl CanonicalDecode for ReplayStep {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Self {
+            at_millis: u64::decode_canonical(r)?,
+            op: TxnOp::decode_canonical(r)?,
+            digest: Hash::decode_canonical(r)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps without their timestamps
+    fn trace(log: &ReplayLog) -> Vec<(TxnOp, Hash)> {
+        log.steps.iter().map(|s| (s.op.clone(), s.digest)).collect()
+    }
+
+    #[test]
+    fn test_replay_finds_first_divergence() {
+        crate::setup::init_test_parameters();
+        let log = ReplayLog::generate(42, 60, 8);
+        assert_eq!(trace(&log), trace(&ReplayLog::generate(42, 60, 8)));
+        assert_ne!(trace(&log), trace(&ReplayLog::generate(43, 60, 8)));
+        assert_eq!(log.first_divergence(), None);
+
+        let loaded = ReplayLog::from_bytes(&log.to_bytes()).unwrap();
+        assert_eq!(loaded, log);
+        assert!(ReplayLog::from_bytes(&log.to_bytes()[..20]).is_err());
+
+        // A digest recorded by a build that hashed differently
+        let mut changed = log.clone();
+        changed.steps[17].digest = [0u8; 32];
+        let divergence = changed.first_divergence().unwrap();
+        assert_eq!(divergence.step, 17);
+        assert_eq!(divergence.actual, log.steps[17].digest);
+    }
+}
@@ -1,18 +1,43 @@
 use crate::Hash;
 use crate::acc_proof::{AccProof, MembershipProof, NonMembershipProof};
+use crate::canonical::{CanonicalEncode, impl_canonical_eq, normalize_g1};
+use crate::epoch::{RootEntry, check_proof_depth};
+use crate::fid_tree::{FidAbsenceProof, FidProof};
 use crate::merkle_proof::Proof as MerkleProof;
+use crate::metrics::{Instrumented, ProofStats};
+use crate::policy::{ProofMode, VerificationLevel};
+use crate::query::Query;
+use crate::txn::TxnOp;
+use crate::validate::{Validate, check_point};
+use accumulator_ads::acc::utils::digest_to_prime_field;
+use accumulator_ads::digest::Digestible;
 use accumulator_ads::{G1Affine, Set};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryResponse {
     /// found fids if present
     pub fids: Option<Set<String>>,
     /// Merkle proof for the found leaf (if any)
     pub merkle_proof: Option<MerkleProof>,
     /// Accumulator info (acc value)
+    #[serde(with = "crate::canonical::serde_opt_g1")]
     pub accumulator: Option<G1Affine>,
     /// Accumulator proof (Membership or NonMembership)
     pub acc_proof: Option<AccProof>,
+    /// Parts the verifier is expected to check
+    pub mode: ProofMode,
+    /// Live key count of each sibling subtree on the Merkle path, bottom up.
+    /// Node hashes do not commit to counts, so these are hints a client can
+    /// only check for consistency (see `check_key_count_hints`).
+    pub sibling_key_counts: Option<Vec<u64>>,
+    /// `Query::query_hash` of the question answered (None in responses
+    /// decoded from older wire versions)
+    pub query_hash: Option<Hash>,
+    /// Size and prover time of this response, set by the tree that built it
+    /// (see `metrics`). Not encoded, so decoded responses have none.
+    #[serde(skip)]
+    pub stats: Option<ProofStats>,
 }
 
 impl QueryResponse {
@@ -27,18 +52,95 @@ impl QueryResponse {
             merkle_proof,
             accumulator,
             acc_proof,
+            mode: ProofMode::Combined,
+            sibling_key_counts: None,
+            query_hash: None,
+            stats: None,
         }
     }
 
+    /// Record the proof mode this response was built for.
+    pub fn with_mode(mut self, mode: ProofMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Bind the response to the query it answers.
+    pub fn with_query(mut self, query: &Query) -> Self {
+        self.query_hash = Some(query.query_hash());
+        self
+    }
+
+    /// Whether the response was produced for `query`. Responses without a
+    /// query hash (older wire versions) are not bound and always match.
+    pub fn answers(&self, query: &Query) -> bool {
+        self.query_hash.is_none_or(|h| h == query.query_hash())
+    }
+
+    /// Build a response from untrusted parts, rejecting malformed points, paths
+    /// and inconsistent combinations before any pairing work is done.
+    pub fn try_from_parts(
+        fids: Option<Set<String>>,
+        merkle_proof: Option<MerkleProof>,
+        accumulator: Option<G1Affine>,
+        acc_proof: Option<AccProof>,
+    ) -> Result<Self, String> {
+        let response = Self::new(fids, merkle_proof, accumulator, acc_proof);
+        response.validate()?;
+        Ok(response)
+    }
+
     /// Get root hash from the proof (if present)
     pub fn root_hash(&self) -> Option<Hash> {
         self.merkle_proof.as_ref().map(|p| p.root_hash)
     }
 
+    /// Return the canonical form of this response: curve points are normalized
+    /// and the outer accumulator is dropped for non-membership answers, whose
+    /// proof already carries the accumulator it was checked against.
+    pub fn canonicalize(&self) -> Self {
+        let acc_proof = self.acc_proof.as_ref().map(AccProof::canonicalize);
+        let accumulator = match acc_proof {
+            Some(AccProof::NonMembership(_)) => None,
+            _ => self.accumulator.map(normalize_g1),
+        };
+        Self {
+            fids: self.fids.clone(),
+            merkle_proof: self.merkle_proof.as_ref().map(|p| p.canonicalize()),
+            accumulator,
+            acc_proof,
+            mode: self.mode,
+            sibling_key_counts: self.sibling_key_counts.clone(),
+            query_hash: self.query_hash,
+            stats: self.stats,
+        }
+    }
+
     /// Verify both the Merkle path (leaf correctness) and the accumulator membership witness.
     /// Returns true only if both checks pass. Requires the original `key` and `fids` used
     /// to build the leaf hash.
+    ///
+    /// ```
+    /// use accumulator_ads::Set;
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// tree.insert("bob".to_string(), "doc2".to_string());
+    /// let response = tree.select_with_proof("alice").unwrap();
+    ///
+    /// let fids = Set::from_vec(vec!["doc1".to_string()]);
+    /// assert!(response.verify_full("alice", &fids));
+    /// // A server cannot hide or add FIDs, or answer for another key
+    /// assert!(!response.verify_full("alice", &Set::from_vec(vec!["doc9".to_string()])));
+    /// assert!(!response.verify_full("bob", &fids));
+    /// ```
     pub fn verify_full(&self, key: &str, fids: &Set<String>) -> bool {
+        if !self.answers(&Query::Point(key.to_string())) {
+            return false;
+        }
+
         // verify Merkle path using provided key/fids (prevents leaf tampering)
         let merkle_ok = match &self.merkle_proof {
             Some(p) => p.verify_with_kv(key, fids),
@@ -60,15 +162,282 @@ impl QueryResponse {
             _ => false,
         }
     }
+
+    /// Verify an answer that `key` is absent, against the global accumulator
+    /// the client trusts (e.g. `EpochCommitment::global_acc` of a pinned epoch).
+    /// Checks:
+    /// 1. The response answers a point query for `key` and returns no FIDs
+    /// 2. The non-membership proof is for `key`, both its label and the
+    ///    element the Bézout relation is checked for
+    /// 3. The proof's accumulator, and the epoch it names if any, is `trusted_acc`
+    /// 4. The Bézout relation holds against that accumulator
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// let trusted = tree.publish_epoch().global_acc;
+    /// let response = tree.select_with_proof("mallory").unwrap();
+    ///
+    /// assert!(response.verify_absent("mallory", &trusted));
+    /// // Neither another key's absence nor a live key passes
+    /// assert!(!response.verify_absent("eve", &trusted));
+    /// assert!(!tree.select_with_proof("alice").unwrap().verify_absent("alice", &trusted));
+    /// ```
+    pub fn verify_absent(&self, key: &str, trusted_acc: &G1Affine) -> bool {
+        if !self.answers(&Query::Point(key.to_string())) || self.fids.is_some() {
+            return false;
+        }
+        let Some(AccProof::NonMembership(nm)) = &self.acc_proof else {
+            return false;
+        };
+        if nm.acc_proof.element != digest_to_prime_field(&key.to_digest()) {
+            return false;
+        }
+        let trusted = normalize_g1(*trusted_acc);
+        if normalize_g1(nm.accumulator) != trusted
+            || nm
+                .commitment
+                .as_ref()
+                .is_some_and(|c| normalize_g1(c.global_acc) != trusted)
+        {
+            return false;
+        }
+        nm.verify(key)
+    }
+
+    /// Verify the parts required by `mode`: the Merkle path against the
+    /// returned FIDs, the accumulator witness, or both.
+    pub fn verify_by_mode(&self, key: &str) -> bool {
+        match self.verify_by_mode_deferred(key) {
+            Ok(None) => true,
+            Ok(Some((acc, witness))) => MembershipProof { witness }.verify(&acc, key),
+            Err(()) => false,
+        }
+    }
+}
+
+impl QueryResponse {
+    /// `verify_by_mode` with the membership witness, if the mode checks one,
+    /// returned as (accumulator, witness) instead of paired. `Err` if any
+    /// other check fails.
+    fn verify_by_mode_deferred(&self, key: &str) -> Result<Option<(G1Affine, G1Affine)>, ()> {
+        if !self.answers(&Query::Point(key.to_string())) {
+            return Err(());
+        }
+        let merkle_ok = |fids: &Set<String>| {
+            self.merkle_proof
+                .as_ref()
+                .is_some_and(|p| p.verify_with_kv(key, fids))
+        };
+        match (self.mode, &self.fids, &self.acc_proof) {
+            (ProofMode::MerkleOnly, Some(fids), _) if merkle_ok(fids) => Ok(None),
+            (_, None, Some(AccProof::NonMembership(nm))) if nm.verify(key) => Ok(None),
+            (ProofMode::AccumulatorOnly, _, Some(AccProof::Membership(mp))) => self
+                .accumulator
+                .map(|acc| Some((acc, mp.witness)))
+                .ok_or(()),
+            (ProofMode::Combined, Some(fids), acc_proof) if merkle_ok(fids) => {
+                match (&self.accumulator, acc_proof) {
+                    (Some(_), Some(AccProof::NonMembership(nm))) if nm.verify(key) => Ok(None),
+                    (Some(acc), Some(AccProof::Membership(mp))) => Ok(Some((*acc, mp.witness))),
+                    _ => Err(()),
+                }
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+/// Whether every `(key, response)` passes `verify_by_mode`. Merkle paths and
+/// non-membership proofs are checked in parallel; the membership witnesses
+/// are checked together with one product of pairings
+/// (`verify_membership_batch`), so a large batch costs two pairings instead
+/// of two per response. A false result does not say which response failed.
+///
+/// ```
+/// use accumulator_tree::prelude::*;
+/// use accumulator_tree::response::verify_batch;
+/// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+///
+/// let mut tree = AccumulatorTree::new();
+/// for key in ["alice", "bob", "carol"] {
+///     tree.insert(key.to_string(), format!("{}.doc", key));
+/// }
+/// let mut batch: Vec<(&str, QueryResponse)> = ["alice", "bob", "carol", "dave"]
+///     .into_iter()
+///     .map(|key| (key, tree.select_with_proof(key).unwrap()))
+///     .collect();
+/// assert!(verify_batch(&batch));
+///
+/// // Alice's answer does not pass for bob
+/// batch[0].0 = "bob";
+/// assert!(!verify_batch(&batch));
+/// ```
+pub fn verify_batch<K: AsRef<str> + Sync>(responses: &[(K, QueryResponse)]) -> bool {
+    #[cfg(feature = "parallel")]
+    use rayon::prelude::*;
+
+    let deferred: Result<Vec<_>, ()> = ark_std::cfg_iter!(responses)
+        .map(|(key, response)| response.verify_by_mode_deferred(key.as_ref()))
+        .collect();
+    let Ok(deferred) = deferred else {
+        return false;
+    };
+    let witnesses: Vec<(G1Affine, G1Affine, &str)> = deferred
+        .into_iter()
+        .zip(responses)
+        .filter_map(|(membership, (key, _))| membership.map(|(acc, w)| (acc, w, key.as_ref())))
+        .collect();
+    crate::acc_proof::verify_membership_batch(&witnesses)
+}
+
+impl QueryResponse {
+    /// Whether the response carries every part `level` checks. Absence is
+    /// proven by the accumulator alone, so a non-membership answer carries
+    /// `Full` and `AccumulatorOnly` but not `HashOnly`.
+    pub fn carries(&self, level: VerificationLevel) -> bool {
+        if let Some(AccProof::NonMembership(_)) = &self.acc_proof {
+            return level.checks_accumulator();
+        }
+        let merkle = self.fids.is_some() && self.merkle_proof.is_some();
+        let accumulator =
+            self.accumulator.is_some() && matches!(self.acc_proof, Some(AccProof::Membership(_)));
+        (merkle || !level.checks_merkle()) && (accumulator || !level.checks_accumulator())
+    }
+
+    /// Check the parts of the response `level` asks for, whatever `mode` the
+    /// server chose. Fails if the response does not carry them (see
+    /// `carries`), so a verifier never silently accepts a weaker proof than
+    /// it asked for.
+    pub fn verify_at(&self, key: &str, level: VerificationLevel) -> bool {
+        if !self.answers(&Query::Point(key.to_string())) || !self.carries(level) {
+            return false;
+        }
+        if let Some(AccProof::NonMembership(nm)) = &self.acc_proof {
+            return self.fids.is_none() && nm.verify(key);
+        }
+        let merkle_ok = !level.checks_merkle()
+            || match (&self.fids, &self.merkle_proof) {
+                (Some(fids), Some(p)) => p.verify_with_kv(key, fids),
+                _ => false,
+            };
+        let accumulator_ok = !level.checks_accumulator()
+            || match (&self.accumulator, &self.acc_proof) {
+                (Some(acc), Some(AccProof::Membership(mp))) => mp.verify(acc, key),
+                _ => false,
+            };
+        merkle_ok && accumulator_ok
+    }
+
+    /// Check the sibling key-count hints against the Merkle path and the
+    /// committed total key count:
+    /// 1. There is one hint per path step
+    /// 2. The sibling at step `i` spans 2^i leaves, so holds at most 2^i keys
+    /// 3. The siblings and the proven key together fit in `total_keys`
+    pub fn check_key_count_hints(&self, total_keys: u64) -> Result<(), String> {
+        let (Some(counts), Some(proof)) = (&self.sibling_key_counts, &self.merkle_proof) else {
+            return Err("response carries no key-count hints".to_string());
+        };
+        if counts.len() != proof.path.len() {
+            return Err("expected one key-count hint per path step".to_string());
+        }
+        let mut sum = 1u64;
+        for (i, count) in counts.iter().enumerate() {
+            if i >= 64 || *count > 1u64 << i {
+                return Err(format!("sibling {} cannot hold {} keys", i, count));
+            }
+            sum = sum.saturating_add(*count);
+        }
+        if sum > total_keys {
+            return Err(format!(
+                "path accounts for {} keys, more than the {} committed",
+                sum, total_keys
+            ));
+        }
+        Ok(())
+    }
 }
 
+/// Whether a FID is in a chunked FID set, with the evidence.
 #[derive(Debug, Clone)]
+pub enum FidAnswer {
+    Present(FidProof),
+    Absent(FidAbsenceProof),
+}
+
+impl FidAnswer {
+    /// FID root the evidence leads to.
+    pub fn fid_root(&self) -> Hash {
+        match self {
+            FidAnswer::Present(p) => p.fid_root(),
+            FidAnswer::Absent(p) => p.fid_root(),
+        }
+    }
+}
+
+/// Proof that a single FID does or does not belong to a key whose FID set is
+/// chunked. The full set is never transferred: the leaf commits to its FID
+/// root, and `answer` relates the FID to that root.
+#[derive(Debug, Clone)]
+pub struct FidQueryResponse {
+    /// Chunk holding the FID, or the chunks around where it would be, with
+    /// their paths to the leaf's FID root
+    pub answer: FidAnswer,
+    /// Merkle proof for the chunked leaf
+    pub merkle_proof: MerkleProof,
+    /// Accumulator of the root containing the key
+    pub accumulator: G1Affine,
+    /// Membership witness of the key in `accumulator`
+    pub acc_proof: MembershipProof,
+}
+
+impl FidQueryResponse {
+    /// Whether the response claims that the FID is present.
+    pub fn is_present(&self) -> bool {
+        matches!(self.answer, FidAnswer::Present(_))
+    }
+
+    /// Verify that `fid` is in the FID set of `key`, and that the key's leaf
+    /// and accumulator membership check out.
+    pub fn verify(&self, key: &str, fid: &str) -> bool {
+        match &self.answer {
+            FidAnswer::Present(p) => p.verify(fid) && self.verify_leaf(key),
+            FidAnswer::Absent(_) => false,
+        }
+    }
+
+    /// Verify that `fid` is not in the FID set of `key`, and that the key's
+    /// leaf and accumulator membership check out.
+    pub fn verify_absent(&self, key: &str, fid: &str) -> bool {
+        match &self.answer {
+            FidAnswer::Present(_) => false,
+            FidAnswer::Absent(p) => p.verify(fid) && self.verify_leaf(key),
+        }
+    }
+
+    fn verify_leaf(&self, key: &str) -> bool {
+        let leaf = crate::utils::leaf_hash_chunked(key, &self.answer.fid_root(), 0, false);
+        leaf == self.merkle_proof.leaf_hash
+            && self.merkle_proof.verify()
+            && self.acc_proof.verify(&self.accumulator, key)
+    }
+
+    pub fn root_hash(&self) -> Hash {
+        self.merkle_proof.root_hash
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InsertResponse {
     /// key inserted
     pub key: String,
     /// fids inserted
     pub fids: Set<String>,
     /// accumulator value after insertion for the root containing the key
+    #[serde(with = "crate::canonical::serde_opt_g1")]
     pub post_accumulator: Option<G1Affine>,
     /// Merkle proof for the inserted leaf after insertion
     pub post_merkle_proof: Option<MerkleProof>,
@@ -76,32 +445,31 @@ pub struct InsertResponse {
     pub post_acc_proof: Option<MembershipProof>,
     /// optional non-membership proof captured before insertion
     pub pre_acc_proof: Option<NonMembershipProof>,
+    /// Size and prover time of this response, set by the tree that built it
+    /// (see `metrics`). Not encoded, so decoded responses have none.
+    #[serde(skip)]
+    pub stats: Option<ProofStats>,
 }
 
 impl InsertResponse {
-    pub fn new(
-        key: String,
-        fids: Set<String>,
-        post_accumulator: Option<G1Affine>,
-        post_merkle_proof: Option<MerkleProof>,
-        post_acc_proof: Option<MembershipProof>,
-        pre_acc_proof: Option<NonMembershipProof>,
-    ) -> Self {
-        Self {
-            key,
-            fids,
-            post_accumulator,
-            post_merkle_proof,
-            post_acc_proof,
-            pre_acc_proof,
-        }
-    }
-
     /// Get post-insertion root hash from the proof (if present)
     pub fn post_root_hash(&self) -> Option<Hash> {
         self.post_merkle_proof.as_ref().map(|p| p.root_hash)
     }
 
+    /// Return the response with all curve points in normalized form.
+    pub fn canonicalize(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            fids: self.fids.clone(),
+            post_accumulator: self.post_accumulator.map(normalize_g1),
+            post_merkle_proof: self.post_merkle_proof.as_ref().map(|p| p.canonicalize()),
+            post_acc_proof: self.post_acc_proof.as_ref().map(|p| p.canonicalize()),
+            pre_acc_proof: self.pre_acc_proof.as_ref().map(|p| p.canonicalize()),
+            stats: self.stats,
+        }
+    }
+
     /// Verify that the insertion was well-formed.
     /// Checks:
     /// 1. Pre-insertion non-membership proof validates (if present)
@@ -110,10 +478,10 @@ impl InsertResponse {
     /// 4. Post-proof matches the inserted key and FID set
     pub fn verify_insert(&self) -> bool {
         // 1. Verify pre-insertion non-membership proof (if present)
-        if let Some(nm_proof) = &self.pre_acc_proof {
-            if !nm_proof.verify(&self.key) {
-                return false; // Key was already in tree before insertion
-            }
+        if let Some(nm_proof) = &self.pre_acc_proof
+            && !nm_proof.verify(&self.key)
+        {
+            return false; // Key was already in tree before insertion
         }
 
         // 2. Verify post-insertion Merkle proof
@@ -142,7 +510,7 @@ impl InsertResponse {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateResponse {
     /// key where the FID was updated
     pub key: String,
@@ -157,46 +525,24 @@ pub struct UpdateResponse {
     /// membership proof for the leaf before update
     pub pre_merkle_proof: Option<MerkleProof>,
     /// accumulator value before update (for the root containing the key)
+    #[serde(with = "crate::canonical::serde_opt_g1")]
     pub pre_accumulator: Option<G1Affine>,
     /// membership proof for the old element
     pub pre_acc_proof: Option<MembershipProof>,
     /// membership proof for the leaf after update
     pub post_merkle_proof: MerkleProof,
     /// accumulator value after update (for the root containing the key)
+    #[serde(with = "accumulator_ads::acc::serde_impl")]
     pub post_accumulator: G1Affine,
     /// membership proof for the new element
     pub post_acc_proof: MembershipProof,
+    /// Size and prover time of this response, set by the tree that built it
+    /// (see `metrics`). Not encoded, so decoded responses have none.
+    #[serde(skip)]
+    pub stats: Option<ProofStats>,
 }
 
 impl UpdateResponse {
-    pub fn new(
-        key: String,
-        old_fid: String,
-        new_fid: String,
-        old_fids: Option<Set<String>>,
-        new_fids: Set<String>,
-        pre_merkle_proof: Option<MerkleProof>,
-        pre_acc: Option<G1Affine>,
-        pre_acc_proof: Option<MembershipProof>,
-        post_merkle_proof: MerkleProof,
-        post_acc: G1Affine,
-        post_acc_proof: MembershipProof,
-    ) -> Self {
-        Self {
-            key,
-            old_fid,
-            new_fid,
-            old_fids,
-            new_fids,
-            pre_merkle_proof,
-            pre_accumulator: pre_acc,
-            pre_acc_proof,
-            post_merkle_proof,
-            post_accumulator: post_acc,
-            post_acc_proof,
-        }
-    }
-
     /// Get pre-update root hash from the proof (if present)
     pub fn pre_root_hash(&self) -> Option<Hash> {
         self.pre_merkle_proof.as_ref().map(|p| p.root_hash)
@@ -207,6 +553,24 @@ impl UpdateResponse {
         self.post_merkle_proof.root_hash
     }
 
+    /// Return the response with all curve points in normalized form.
+    pub fn canonicalize(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            old_fid: self.old_fid.clone(),
+            new_fid: self.new_fid.clone(),
+            old_fids: self.old_fids.clone(),
+            new_fids: self.new_fids.clone(),
+            pre_merkle_proof: self.pre_merkle_proof.as_ref().map(|p| p.canonicalize()),
+            pre_accumulator: self.pre_accumulator.map(normalize_g1),
+            pre_acc_proof: self.pre_acc_proof.as_ref().map(|p| p.canonicalize()),
+            post_merkle_proof: self.post_merkle_proof.canonicalize(),
+            post_accumulator: normalize_g1(self.post_accumulator),
+            post_acc_proof: self.post_acc_proof.canonicalize(),
+            stats: self.stats,
+        }
+    }
+
     /// Verify that the update was well-formed: validates that a specific FID was replaced.
     /// Checks:
     /// 1. The old_fid existed in the old FID set
@@ -238,10 +602,10 @@ impl UpdateResponse {
                 return false;
             }
             // Also verify the pre-proof matches the old FID set
-            if let Some(old) = &self.old_fids {
-                if !pre_p.verify_with_kv(&self.key, old) {
-                    return false;
-                }
+            if let Some(old) = &self.old_fids
+                && !pre_p.verify_with_kv(&self.key, old)
+            {
+                return false;
             }
         }
 
@@ -271,10 +635,10 @@ impl UpdateResponse {
         }
 
         // 6. Verify accumulator membership for both pre and post states
-        if let (Some(acc), Some(mp)) = (&self.pre_accumulator, &self.pre_acc_proof) {
-            if !mp.verify(acc, &self.key) {
-                return false;
-            }
+        if let (Some(acc), Some(mp)) = (&self.pre_accumulator, &self.pre_acc_proof)
+            && !mp.verify(acc, &self.key)
+        {
+            return false;
         }
 
         if !self
@@ -288,7 +652,7 @@ impl UpdateResponse {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeleteResponse {
     /// key from which the FID was deleted
     pub key: String,
@@ -301,40 +665,22 @@ pub struct DeleteResponse {
     /// membership proof for the leaf before deletion
     pub pre_merkle_proof: Option<MerkleProof>,
     /// accumulator value before deletion (for the root containing the key)
+    #[serde(with = "crate::canonical::serde_opt_g1")]
     pub pre_accumulator: Option<G1Affine>,
     /// membership proof for the old element
     pub pre_acc_proof: Option<MembershipProof>,
     /// merkle/path proof for the leaf after deletion
     pub post_merkle_proof: MerkleProof,
     /// accumulator value after deletion for the root containing the key
+    #[serde(with = "accumulator_ads::acc::serde_impl")]
     pub post_accumulator: G1Affine,
+    /// Size and prover time of this response, set by the tree that built it
+    /// (see `metrics`). Not encoded, so decoded responses have none.
+    #[serde(skip)]
+    pub stats: Option<ProofStats>,
 }
 
 impl DeleteResponse {
-    pub fn new(
-        key: String,
-        deleted_fid: String,
-        old_fids: Option<Set<String>>,
-        new_fids: Set<String>,
-        pre_merkle_proof: Option<MerkleProof>,
-        pre_acc: Option<G1Affine>,
-        pre_acc_proof: Option<MembershipProof>,
-        post_merkle_proof: MerkleProof,
-        post_acc: G1Affine,
-    ) -> Self {
-        Self {
-            key,
-            deleted_fid,
-            old_fids,
-            new_fids,
-            pre_merkle_proof,
-            pre_accumulator: pre_acc,
-            pre_acc_proof,
-            post_merkle_proof,
-            post_accumulator: post_acc,
-        }
-    }
-
     /// Get pre-deletion root hash from the proof (if present)
     pub fn pre_root_hash(&self) -> Option<Hash> {
         self.pre_merkle_proof.as_ref().map(|p| p.root_hash)
@@ -345,12 +691,27 @@ impl DeleteResponse {
         self.post_merkle_proof.root_hash
     }
 
+    /// Return the response with all curve points in normalized form.
+    pub fn canonicalize(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            deleted_fid: self.deleted_fid.clone(),
+            old_fids: self.old_fids.clone(),
+            new_fids: self.new_fids.clone(),
+            pre_merkle_proof: self.pre_merkle_proof.as_ref().map(|p| p.canonicalize()),
+            pre_accumulator: self.pre_accumulator.map(normalize_g1),
+            pre_acc_proof: self.pre_acc_proof.as_ref().map(|p| p.canonicalize()),
+            post_merkle_proof: self.post_merkle_proof.canonicalize(),
+            post_accumulator: normalize_g1(self.post_accumulator),
+            stats: self.stats,
+        }
+    }
+
     /// Verify deletion: validates that a specific FID was removed from the key's FID set.
     /// Checks:
     /// 1. The deleted FID existed in the old FID set
     /// 2. The new FID set = old FID set - deleted FID
     /// 3. Merkle proofs validate (pre and post)
-
     /// 4. Sibling paths match (only leaf content changed, not structure)
     /// 5. Accumulator membership holds for the key in pre-state
     /// 6. Post-state hash matches the new FID set (or empty_hash if tombstoned)
@@ -376,10 +737,10 @@ impl DeleteResponse {
                 return false;
             }
             // Also verify the pre-proof matches the old FID set
-            if let Some(old) = &self.old_fids {
-                if !pre_p.verify_with_kv(&self.key, old) {
-                    return false;
-                }
+            if let Some(old) = &self.old_fids
+                && !pre_p.verify_with_kv(&self.key, old)
+            {
+                return false;
             }
         }
 
@@ -418,16 +779,559 @@ impl DeleteResponse {
         }
 
         // 6. Verify accumulator membership for pre-state (key was in tree)
-        if let (Some(acc), Some(mp)) = (&self.pre_accumulator, &self.pre_acc_proof) {
-            if !mp.verify(acc, &self.key) {
+        if let (Some(acc), Some(mp)) = (&self.pre_accumulator, &self.pre_acc_proof)
+            && !mp.verify(acc, &self.key)
+        {
+            return false;
+        }
+
+        true
+    }
+}
+
+/// Proof that a tombstoned key was explicitly brought back with a new FID set.
+#[derive(Debug, Clone)]
+pub struct ReviveResponse {
+    pub key: String,
+    /// FID set the key was revived with
+    pub fids: Set<String>,
+    /// Epoch in which the key had been tombstoned, if the tree recorded it
+    pub deleted_epoch: Option<u64>,
+    /// Tombstoned leaf before the revive
+    pub pre: LeafWitness,
+    /// Live leaf after the revive
+    pub post: LeafWitness,
+    /// accumulator value after the revive for the root containing the key
+    pub post_accumulator: Option<G1Affine>,
+    /// membership proof for the key in post_accumulator
+    pub post_acc_proof: Option<MembershipProof>,
+    /// Size and prover time of this response, set by the tree that built it
+    /// (see `metrics`). Not encoded, so decoded responses have none.
+    pub stats: Option<ProofStats>,
+}
+
+impl ReviveResponse {
+    /// Return the response with all curve points in normalized form.
+    pub fn canonicalize(&self) -> Self {
+        Self {
+            pre: LeafWitness {
+                merkle_proof: self.pre.merkle_proof.canonicalize(),
+                ..self.pre.clone()
+            },
+            post: LeafWitness {
+                merkle_proof: self.post.merkle_proof.canonicalize(),
+                ..self.post.clone()
+            },
+            post_accumulator: self.post_accumulator.map(normalize_g1),
+            post_acc_proof: self.post_acc_proof.as_ref().map(|p| p.canonicalize()),
+            ..self.clone()
+        }
+    }
+
+    /// Verify the tombstoned -> live transition.
+    /// Checks:
+    /// 1. The pre leaf is a tombstone for the key and its path verifies
+    /// 2. The post leaf is live, holds exactly `fids` (non-empty) and its path verifies
+    /// 3. Accumulator membership holds for the key in the post state
+    pub fn verify_revive(&self) -> bool {
+        // 1. Pre-state tombstone
+        if !self.pre.deleted
+            || !self
+                .pre
+                .merkle_proof
+                .verify_leaf_state(&self.key, &self.pre.fids, 0, true)
+        {
+            return false;
+        }
+
+        // 2. Post-state live leaf with the revived FIDs
+        if self.post.deleted || self.fids.is_empty() || self.post.fids != self.fids {
+            return false;
+        }
+        if !self.post.merkle_proof.verify_with_kv(&self.key, &self.fids) {
+            return false;
+        }
+
+        // 3. Accumulator membership for the post state
+        match (&self.post_accumulator, &self.post_acc_proof) {
+            (Some(acc), Some(mp)) => mp.verify(acc, &self.key),
+            _ => false,
+        }
+    }
+}
+
+/// State of one leaf together with its Merkle path to a root of the forest.
+#[derive(Debug, Clone)]
+pub struct LeafWitness {
+    pub fids: Set<String>,
+    /// Whether the leaf is tombstoned
+    pub deleted: bool,
+    pub merkle_proof: MerkleProof,
+}
+
+impl LeafWitness {
+    /// Check the path and that it ends at one of `roots` with matching depth.
+    pub(crate) fn verify(&self, key: &str, roots: &[RootEntry]) -> bool {
+        check_proof_depth(roots, &self.merkle_proof).is_ok()
+            && self
+                .merkle_proof
+                .verify_leaf_state(key, &self.fids, 0, self.deleted)
+    }
+
+    fn is_live(&self) -> bool {
+        !self.deleted
+    }
+}
+
+/// Before/after state of a key touched by a transaction.
+#[derive(Debug, Clone)]
+pub struct KeyTransition {
+    pub key: String,
+    /// Leaf before the batch (None if the key had never been inserted)
+    pub pre: Option<LeafWitness>,
+    /// Leaf after the batch
+    pub post: LeafWitness,
+}
+
+/// Proof that a committed transaction moved the forest from the pre to the post
+/// state by exactly its staged operations.
+#[derive(Debug, Clone)]
+pub struct TransactionResponse {
+    /// Applied operations, in order
+    pub ops: Vec<TxnOp>,
+    /// Roots before the batch
+    pub pre_roots: Vec<RootEntry>,
+    /// Roots after the batch
+    pub post_roots: Vec<RootEntry>,
+    /// Accumulator over all live keys before the batch
+    pub pre_global_acc: G1Affine,
+    /// Accumulator over all live keys after the batch
+    pub post_global_acc: G1Affine,
+    /// Keys that became live
+    pub added_keys: Set<String>,
+    /// Keys that were tombstoned
+    pub removed_keys: Set<String>,
+    /// One transition per touched key, sorted by key
+    pub transitions: Vec<KeyTransition>,
+}
+
+impl TransactionResponse {
+    /// Forest hash of the pre-state, comparable with `EpochCommitment::forest_hash`.
+    pub fn pre_forest_hash(&self) -> Hash {
+        crate::epoch::forest_hash(&self.pre_roots)
+    }
+
+    /// Forest hash of the post-state, comparable with `EpochCommitment::forest_hash`.
+    pub fn post_forest_hash(&self) -> Hash {
+        crate::epoch::forest_hash(&self.post_roots)
+    }
+
+    /// Verify the transition. Clients should additionally compare the pre/post
+    /// forest hashes and global accumulators with values they trust.
+    /// Checks:
+    /// 1. Exactly the keys named by `ops` have a transition
+    /// 2. Every pre/post leaf proof verifies against a pre/post root
+    /// 3. Replaying `ops` over the pre leaves yields the post leaves
+    /// 4. `added_keys`/`removed_keys` match the liveness changes
+    /// 5. post_acc = pre_acc ^ (P_added(s) / P_removed(s)) (pairing check)
+    pub fn verify(&self) -> bool {
+        // 1. Transitions cover exactly the touched keys
+        let mut touched: Vec<&str> = self.ops.iter().map(TxnOp::key).collect();
+        touched.sort();
+        touched.dedup();
+        let keys: Vec<&str> = self.transitions.iter().map(|t| t.key.as_str()).collect();
+        if keys != touched {
+            return false;
+        }
+
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        for t in &self.transitions {
+            // 2. Leaf proofs
+            if let Some(pre) = &t.pre
+                && !pre.verify(&t.key, &self.pre_roots)
+            {
+                return false;
+            }
+            if !t.post.verify(&t.key, &self.post_roots) {
+                return false;
+            }
+
+            // 3. Replay
+            let mut state = t.pre.as_ref().map(|w| (w.fids.clone(), w.deleted));
+            for op in self.ops.iter().filter(|op| op.key() == t.key) {
+                match op.replay(state) {
+                    Some(next) => state = Some(next),
+                    None => return false,
+                }
+            }
+            if state != Some((t.post.fids.clone(), t.post.deleted)) {
                 return false;
             }
+
+            // 4. Liveness changes
+            let was_live = t.pre.as_ref().is_some_and(LeafWitness::is_live);
+            match (was_live, t.post.is_live()) {
+                (false, true) => added.push(t.key.clone()),
+                (true, false) => removed.push(t.key.clone()),
+                _ => {}
+            }
+        }
+        if Set::from_vec(added) != self.added_keys || Set::from_vec(removed) != self.removed_keys {
+            return false;
         }
 
-        true
+        // 5. Aggregate accumulator delta
+        verify_acc_delta(
+            &self.pre_global_acc,
+            &self.post_global_acc,
+            &self.added_keys,
+            &self.removed_keys,
+        )
+    }
+}
+
+/// Check e(post, g2^P_removed(s)) == e(pre, g2^P_added(s)), i.e. that `post`
+/// accumulates the set of `pre` plus `added` minus `removed`.
+fn verify_acc_delta(
+    pre: &G1Affine,
+    post: &G1Affine,
+    added: &Set<String>,
+    removed: &Set<String>,
+) -> bool {
+    use accumulator_ads::acc::utils::poly_to_g2;
+    use accumulator_ads::{Curve, Fr, digest_set_from_set, expand_to_poly};
+    use ark_ec::PairingEngine;
+
+    let g2_of = |keys: &Set<String>| {
+        let elems: Vec<Fr> = digest_set_from_set(keys);
+        poly_to_g2(expand_to_poly(&elems))
+    };
+    match (g2_of(removed), g2_of(added)) {
+        (Ok(removed), Ok(added)) => Curve::pairing(*post, removed) == Curve::pairing(*pre, added),
+        _ => false,
+    }
+}
+
+/// Optional accumulator value, validated only when present.
+fn check_acc(acc: &Option<G1Affine>, what: &str) -> Result<(), String> {
+    match acc {
+        Some(a) => check_point(a, what),
+        None => Ok(()),
+    }
+}
+
+impl Validate for QueryResponse {
+    fn validate(&self) -> Result<(), String> {
+        check_acc(&self.accumulator, "accumulator")?;
+        self.merkle_proof.validate()?;
+        self.acc_proof.validate()?;
+        if self.fids.is_some() != self.merkle_proof.is_some() {
+            return Err("fids and merkle proof must be present together".to_string());
+        }
+        if let Some(AccProof::Membership(_)) = self.acc_proof
+            && self.accumulator.is_none()
+        {
+            return Err("membership proof without accumulator".to_string());
+        }
+        if !self.mode.includes_merkle() && self.merkle_proof.is_some() {
+            return Err("merkle proof in an accumulator-only response".to_string());
+        }
+        if !self.mode.includes_accumulator()
+            && (self.acc_proof.is_some() || self.accumulator.is_some())
+        {
+            return Err("accumulator in a merkle-only response".to_string());
+        }
+        if let Some(counts) = &self.sibling_key_counts
+            && self.merkle_proof.as_ref().map(|p| p.path.len()) != Some(counts.len())
+        {
+            return Err("key-count hints do not match the merkle path".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Validate for FidQueryResponse {
+    fn validate(&self) -> Result<(), String> {
+        check_point(&self.accumulator, "accumulator")?;
+        match &self.answer {
+            FidAnswer::Present(p) => p.proof.validate()?,
+            FidAnswer::Absent(
+                FidAbsenceProof::Within(p) | FidAbsenceProof::Before(p) | FidAbsenceProof::After(p),
+            ) => p.proof.validate()?,
+            FidAnswer::Absent(FidAbsenceProof::Between(lower, upper)) => {
+                lower.proof.validate()?;
+                upper.proof.validate()?;
+            }
+        }
+        self.merkle_proof.validate()?;
+        self.acc_proof.validate()
+    }
+}
+
+impl Validate for InsertResponse {
+    fn validate(&self) -> Result<(), String> {
+        check_acc(&self.post_accumulator, "post accumulator")?;
+        self.post_merkle_proof.validate()?;
+        self.post_acc_proof.validate()?;
+        self.pre_acc_proof.validate()
+    }
+}
+
+impl Validate for UpdateResponse {
+    fn validate(&self) -> Result<(), String> {
+        check_acc(&self.pre_accumulator, "pre accumulator")?;
+        check_point(&self.post_accumulator, "post accumulator")?;
+        self.pre_merkle_proof.validate()?;
+        self.post_merkle_proof.validate()?;
+        self.pre_acc_proof.validate()?;
+        self.post_acc_proof.validate()
+    }
+}
+
+impl Validate for DeleteResponse {
+    fn validate(&self) -> Result<(), String> {
+        check_acc(&self.pre_accumulator, "pre accumulator")?;
+        check_point(&self.post_accumulator, "post accumulator")?;
+        self.pre_merkle_proof.validate()?;
+        self.post_merkle_proof.validate()?;
+        self.pre_acc_proof.validate()
+    }
+}
+
+impl Validate for ReviveResponse {
+    fn validate(&self) -> Result<(), String> {
+        check_acc(&self.post_accumulator, "post accumulator")?;
+        self.pre.validate()?;
+        self.post.validate()?;
+        self.post_acc_proof.validate()
+    }
+}
+
+impl Validate for LeafWitness {
+    fn validate(&self) -> Result<(), String> {
+        self.merkle_proof.validate()
+    }
+}
+
+impl Validate for TransactionResponse {
+    fn validate(&self) -> Result<(), String> {
+        check_point(&self.pre_global_acc, "pre global accumulator")?;
+        check_point(&self.post_global_acc, "post global accumulator")?;
+        for t in &self.transitions {
+            t.pre.validate()?;
+            t.post.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl CanonicalEncode for QueryResponse {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        let c = self.canonicalize();
+        c.fids.encode_canonical(out);
+        c.merkle_proof.encode_canonical(out);
+        c.accumulator.encode_canonical(out);
+        c.acc_proof.encode_canonical(out);
+        out.push(c.mode.tag());
+        c.sibling_key_counts.encode_canonical(out);
+        c.query_hash.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for FidAnswer {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            FidAnswer::Present(p) => {
+                out.push(0);
+                p.encode_canonical(out);
+            }
+            FidAnswer::Absent(p) => {
+                out.push(1);
+                p.encode_canonical(out);
+            }
+        }
+    }
+}
+
+impl CanonicalEncode for FidQueryResponse {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.answer.encode_canonical(out);
+        self.merkle_proof.encode_canonical(out);
+        self.accumulator.encode_canonical(out);
+        self.acc_proof.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for InsertResponse {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.fids.encode_canonical(out);
+        self.post_accumulator.encode_canonical(out);
+        self.post_merkle_proof.encode_canonical(out);
+        self.post_acc_proof.encode_canonical(out);
+        self.pre_acc_proof.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for UpdateResponse {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.old_fid.encode_canonical(out);
+        self.new_fid.encode_canonical(out);
+        self.old_fids.encode_canonical(out);
+        self.new_fids.encode_canonical(out);
+        self.pre_merkle_proof.encode_canonical(out);
+        self.pre_accumulator.encode_canonical(out);
+        self.pre_acc_proof.encode_canonical(out);
+        self.post_merkle_proof.encode_canonical(out);
+        self.post_accumulator.encode_canonical(out);
+        self.post_acc_proof.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for DeleteResponse {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.deleted_fid.encode_canonical(out);
+        self.old_fids.encode_canonical(out);
+        self.new_fids.encode_canonical(out);
+        self.pre_merkle_proof.encode_canonical(out);
+        self.pre_accumulator.encode_canonical(out);
+        self.pre_acc_proof.encode_canonical(out);
+        self.post_merkle_proof.encode_canonical(out);
+        self.post_accumulator.encode_canonical(out);
     }
 }
 
+impl CanonicalEncode for ReviveResponse {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        let c = self.canonicalize();
+        c.key.encode_canonical(out);
+        c.fids.encode_canonical(out);
+        c.deleted_epoch.encode_canonical(out);
+        c.pre.encode_canonical(out);
+        c.post.encode_canonical(out);
+        c.post_accumulator.encode_canonical(out);
+        c.post_acc_proof.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for LeafWitness {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.fids.encode_canonical(out);
+        self.deleted.encode_canonical(out);
+        self.merkle_proof.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for KeyTransition {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.key.encode_canonical(out);
+        self.pre.encode_canonical(out);
+        self.post.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for TransactionResponse {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.ops.encode_canonical(out);
+        self.pre_roots.encode_canonical(out);
+        self.post_roots.encode_canonical(out);
+        self.pre_global_acc.encode_canonical(out);
+        self.post_global_acc.encode_canonical(out);
+        self.added_keys.encode_canonical(out);
+        self.removed_keys.encode_canonical(out);
+        self.transitions.encode_canonical(out);
+    }
+}
+
+fn encoded_len(part: &impl CanonicalEncode) -> usize {
+    part.canonical_bytes().len()
+}
+
+impl Instrumented for QueryResponse {
+    fn proof_sizes(&self) -> (usize, usize) {
+        (
+            encoded_len(&self.merkle_proof),
+            encoded_len(&self.accumulator) + encoded_len(&self.acc_proof),
+        )
+    }
+
+    fn set_stats(&mut self, stats: ProofStats) {
+        self.stats = Some(stats);
+    }
+}
+
+impl Instrumented for InsertResponse {
+    fn proof_sizes(&self) -> (usize, usize) {
+        (
+            encoded_len(&self.post_merkle_proof),
+            encoded_len(&self.post_accumulator)
+                + encoded_len(&self.post_acc_proof)
+                + encoded_len(&self.pre_acc_proof),
+        )
+    }
+
+    fn set_stats(&mut self, stats: ProofStats) {
+        self.stats = Some(stats);
+    }
+}
+
+impl Instrumented for UpdateResponse {
+    fn proof_sizes(&self) -> (usize, usize) {
+        (
+            encoded_len(&self.pre_merkle_proof) + encoded_len(&self.post_merkle_proof),
+            encoded_len(&self.pre_accumulator)
+                + encoded_len(&self.pre_acc_proof)
+                + encoded_len(&self.post_accumulator)
+                + encoded_len(&self.post_acc_proof),
+        )
+    }
+
+    fn set_stats(&mut self, stats: ProofStats) {
+        self.stats = Some(stats);
+    }
+}
+
+impl Instrumented for DeleteResponse {
+    fn proof_sizes(&self) -> (usize, usize) {
+        (
+            encoded_len(&self.pre_merkle_proof) + encoded_len(&self.post_merkle_proof),
+            encoded_len(&self.pre_accumulator)
+                + encoded_len(&self.pre_acc_proof)
+                + encoded_len(&self.post_accumulator),
+        )
+    }
+
+    fn set_stats(&mut self, stats: ProofStats) {
+        self.stats = Some(stats);
+    }
+}
+
+impl Instrumented for ReviveResponse {
+    fn proof_sizes(&self) -> (usize, usize) {
+        (
+            encoded_len(&self.pre.merkle_proof) + encoded_len(&self.post.merkle_proof),
+            encoded_len(&self.post_accumulator) + encoded_len(&self.post_acc_proof),
+        )
+    }
+
+    fn set_stats(&mut self, stats: ProofStats) {
+        self.stats = Some(stats);
+    }
+}
+
+impl_canonical_eq!(
+    QueryResponse,
+    FidQueryResponse,
+    InsertResponse,
+    UpdateResponse,
+    DeleteResponse,
+    ReviveResponse,
+    TransactionResponse
+);
+
 /// Unit tests for response structures
 ///
 /// These tests verify the basic construction and validation logic of response types.
@@ -436,23 +1340,10 @@ mod tests {
     use super::*;
     use crate::utils::leaf_hash;
     use accumulator_ads::digest_set_from_set;
-    use std::sync::Once;
-
-    static INIT: Once = Once::new();
-
-    fn init_test_params() {
-        INIT.call_once(|| {
-            use accumulator_ads::acc::setup::{PublicParameters, init_public_parameters_direct};
-            use ark_bls12_381::Fr;
-            let secret_s = Fr::from(123456789u128);
-            let params = PublicParameters::generate_for_testing(secret_s, 50);
-            init_public_parameters_direct(params).expect("Failed to initialize");
-        });
-    }
 
     #[test]
     fn test_query_response_construction() {
-        init_test_params();
+        crate::setup::init_test_parameters();
         let fids = Set::from_vec(vec!["fid1".to_string()]);
         let qr = QueryResponse::new(Some(fids.clone()), None, None, None);
 
@@ -463,7 +1354,7 @@ mod tests {
 
     #[test]
     fn test_query_response_verify_full_fails_without_proof() {
-        init_test_params();
+        crate::setup::init_test_parameters();
         let fids = Set::from_vec(vec!["fid1".to_string()]);
         let qr = QueryResponse::new(Some(fids.clone()), None, None, None);
 
@@ -472,9 +1363,13 @@ mod tests {
 
     #[test]
     fn test_insert_response_construction() {
-        init_test_params();
+        crate::setup::init_test_parameters();
         let fids = Set::from_vec(vec!["fid1".to_string()]);
-        let resp = InsertResponse::new("key1".to_string(), fids.clone(), None, None, None, None);
+        let resp = InsertResponse::builder()
+            .key("key1".to_string())
+            .fids(fids.clone())
+            .build()
+            .unwrap();
 
         assert_eq!(resp.key, "key1");
         assert_eq!(resp.fids, fids);
@@ -482,7 +1377,7 @@ mod tests {
 
     #[test]
     fn test_update_response_verify_fails_with_mismatched_paths() {
-        init_test_params();
+        crate::setup::init_test_parameters();
         use crate::utils::empty_hash;
 
         let old_fids = Set::from_vec(vec!["old".to_string()]);
@@ -501,23 +1396,24 @@ mod tests {
             vec![(leaf_hash("other", &other_fids, 0, false), true)], // Different sibling
         );
 
-        let resp = UpdateResponse::new(
-            "key".to_string(),
-            "old".to_string(),
-            "new".to_string(),
-            Some(old_fids),
-            new_fids,
-            Some(pre_proof),
-            Some(crate::utils::empty_acc()), // pre_acc
-            Some(MembershipProof {
+        let resp = UpdateResponse::builder()
+            .key("key".to_string())
+            .old_fid("old".to_string())
+            .new_fid("new".to_string())
+            .old_fids(old_fids)
+            .new_fids(new_fids)
+            .pre_merkle_proof(pre_proof)
+            .pre_accumulator(crate::utils::empty_acc())
+            .pre_acc_proof(MembershipProof {
                 witness: crate::utils::empty_acc(),
-            }), // pre_acc_proof
-            post_proof,
-            crate::utils::empty_acc(),
-            MembershipProof {
+            })
+            .post_merkle_proof(post_proof)
+            .post_accumulator(crate::utils::empty_acc())
+            .post_acc_proof(MembershipProof {
                 witness: crate::utils::empty_acc(),
-            },
-        );
+            })
+            .build()
+            .unwrap();
 
         // Should fail because sibling hashes don't match
         assert!(!resp.verify_update());
@@ -525,23 +1421,21 @@ mod tests {
 
     #[test]
     fn test_delete_response_construction() {
-        init_test_params();
+        crate::setup::init_test_parameters();
         use crate::utils::empty_hash;
 
         let post_proof = MerkleProof::new(empty_hash(), empty_hash(), vec![]);
         let old_fids = Set::from_vec(vec!["fid1".to_string()]);
 
-        let resp = DeleteResponse::new(
-            "key1".to_string(),
-            "fid1".to_string(),
-            Some(old_fids.clone()),
-            Set::new(),
-            None,
-            None,
-            None,
-            post_proof,
-            crate::utils::empty_acc(),
-        );
+        let resp = DeleteResponse::builder()
+            .key("key1".to_string())
+            .deleted_fid("fid1".to_string())
+            .old_fids(old_fids.clone())
+            .new_fids(Set::new())
+            .post_merkle_proof(post_proof)
+            .post_accumulator(crate::utils::empty_acc())
+            .build()
+            .unwrap();
 
         assert_eq!(resp.key, "key1");
         assert_eq!(resp.old_fids, Some(old_fids));
@@ -549,7 +1443,7 @@ mod tests {
 
     #[test]
     fn test_delete_response_verify_post_proof() {
-        init_test_params();
+        crate::setup::init_test_parameters();
         use crate::utils::empty_hash;
         use accumulator_ads::{DynamicAccumulator, Set, digest_set_from_set};
 
@@ -580,17 +1474,18 @@ mod tests {
             witness: crate::utils::empty_acc(),
         };
 
-        let resp = DeleteResponse::new(
-            "key1".to_string(),
-            "fid1".to_string(),
-            Some(old_fids),
-            new_fids,
-            Some(pre_proof),
-            Some(pre_acc),
-            Some(pre_witness),
-            post_proof,
-            crate::utils::empty_acc(),
-        );
+        let resp = DeleteResponse::builder()
+            .key("key1".to_string())
+            .deleted_fid("fid1".to_string())
+            .old_fids(old_fids)
+            .new_fids(new_fids)
+            .pre_merkle_proof(pre_proof)
+            .pre_accumulator(pre_acc)
+            .pre_acc_proof(pre_witness)
+            .post_merkle_proof(post_proof)
+            .post_accumulator(crate::utils::empty_acc())
+            .build()
+            .unwrap();
 
         // Should pass basic verification
         assert!(resp.verify_delete());
@@ -598,7 +1493,7 @@ mod tests {
 
     #[test]
     fn test_nonmembership_proof_verify_key_mismatch() {
-        init_test_params();
+        crate::setup::init_test_parameters();
         use accumulator_ads::Set;
 
         let all_keys = Set::from_vec(vec!["a".to_string(), "b".to_string()]);
@@ -618,7 +1513,7 @@ mod tests {
 
     #[test]
     fn test_nonmembership_proof_fails_for_existing_key() {
-        init_test_params();
+        crate::setup::init_test_parameters();
         use accumulator_ads::Set;
 
         let all_keys = Set::from_vec(vec!["a".to_string(), "b".to_string()]);
@@ -632,4 +1527,286 @@ mod tests {
         let result = NonMembershipProof::new("a".to_string(), acc, &all_keys);
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_try_from_parts_rejects_malformed() {
+        use ark_bls12_381::Fq;
+        use ark_ec::AffineCurve;
+
+        let g1 = G1Affine::prime_subgroup_generator();
+        let fids = Set::from_vec(vec!["fid1".to_string()]);
+        let leaf = leaf_hash("key", &fids, 0, false);
+        let proof = MerkleProof::new(leaf, leaf, vec![]);
+        let membership = AccProof::Membership(MembershipProof { witness: g1 });
+
+        assert!(
+            QueryResponse::try_from_parts(
+                Some(fids.clone()),
+                Some(proof.clone()),
+                Some(g1),
+                Some(membership.clone()),
+            )
+            .is_ok()
+        );
+
+        let off_curve = G1Affine::new(g1.x, g1.y + Fq::from(1u64), false);
+        let err = QueryResponse::try_from_parts(
+            Some(fids.clone()),
+            Some(proof.clone()),
+            Some(off_curve),
+            Some(membership.clone()),
+        )
+        .unwrap_err();
+        assert!(err.contains("accumulator"));
+
+        assert!(QueryResponse::try_from_parts(Some(fids), None, Some(g1), None).is_err());
+        assert!(QueryResponse::try_from_parts(None, None, None, Some(membership)).is_err());
+    }
+
+    #[test]
+    fn test_fid_query_on_chunked_set() {
+        crate::setup::init_test_parameters();
+        let mut tree = crate::AccumulatorTree::new();
+        tree.insert("small".to_string(), "s1".to_string());
+        for i in 0..=crate::fid_tree::FID_CHUNK_THRESHOLD {
+            tree.insert("big".to_string(), format!("doc{:05}", i));
+        }
+
+        let response = tree.select_fid_with_proof("big", "doc00042").unwrap();
+        assert!(response.validate().is_ok());
+        assert!(tree.root_hashes().contains(&response.root_hash()));
+        // The full-set proof commits to the same chunked leaf
+        let full = tree.select_with_proof("big").unwrap();
+        let fids = full.fids.as_ref().unwrap();
+        assert!(
+            full.merkle_proof
+                .as_ref()
+                .unwrap()
+                .verify_with_kv("big", fids)
+        );
+        assert!(full.verify_by_mode("big"));
+        assert_eq!(full.merkle_proof.unwrap(), response.merkle_proof);
+        assert_eq!(full.accumulator, Some(response.accumulator));
+
+        assert!(response.verify("big", "doc00042"));
+        assert!(!response.verify("big", "doc99999"));
+        assert!(!response.verify("other", "doc00042"));
+
+        assert!(!response.verify_absent("big", "doc00042"));
+
+        assert!(tree.select_fid_with_proof("small", "s1").is_err());
+        assert!(tree.select_fid_with_proof("nobody", "doc00042").is_err());
+        let absent = tree.select_fid_with_proof("big", "doc00042x").unwrap();
+        assert!(absent.validate().is_ok() && !absent.is_present());
+        assert!(absent.verify_absent("big", "doc00042x"));
+        assert!(!absent.verify("big", "doc00042x"));
+        assert!(!absent.verify_absent("big", "doc00043"));
+    }
+
+    #[test]
+    fn test_sibling_key_count_hints() {
+        crate::setup::init_test_parameters();
+        let mut tree = crate::AccumulatorTree::new();
+        for k in ["a", "b", "c", "d", "e"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        tree.delete("d", "fd");
+        let total = tree.publish_epoch().key_count;
+
+        let response = tree.select_with_count_hints("a").unwrap();
+        assert_eq!(response.sibling_key_counts, Some(vec![1, 1]));
+        assert!(response.validate().is_ok());
+        assert!(response.check_key_count_hints(total).is_ok());
+        assert!(response.check_key_count_hints(2).is_err());
+
+        // A sibling cannot hold more keys than leaves
+        let mut inflated = response.clone();
+        inflated.sibling_key_counts = Some(vec![2, 1]);
+        assert!(inflated.check_key_count_hints(total).is_err());
+        inflated.sibling_key_counts = Some(vec![1]);
+        assert!(inflated.validate().is_err());
+        assert!(
+            tree.select_with_proof("a")
+                .unwrap()
+                .check_key_count_hints(total)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_revive_policies() {
+        use crate::policy::RevivePolicy;
+        crate::setup::init_test_parameters();
+        let mut tree = crate::AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+        tree.delete("a", "fa");
+        tree.publish_epoch();
+
+        tree.set_revive_policy(RevivePolicy::RequireExplicitRevive);
+        assert!(!tree.insert("a".to_string(), "fa2".to_string()));
+        let mut txn = tree.begin_transaction();
+        txn.insert("a".to_string(), "fa2".to_string());
+        assert!(txn.commit_with_proof().ops.is_empty());
+        assert!(tree.select("a").is_none());
+        assert!(tree.insert("b".to_string(), "fb2".to_string()));
+
+        let fids = Set::from_vec(vec!["x".to_string(), "y".to_string()]);
+        assert!(tree.revive_with_proof("b", fids.clone()).is_err());
+        let response = tree.revive_with_proof("a", fids.clone()).unwrap();
+        assert_eq!(response.deleted_epoch, Some(1));
+        assert_eq!(tree.select("a"), Some(fids));
+        assert!(response.validate().is_ok());
+
+        assert!(response.verify_revive());
+        let mut forged = response.clone();
+        forged.fids = Set::from_vec(vec!["x".to_string()]);
+        assert!(!forged.verify_revive());
+        let mut forged = response.clone();
+        forged.pre = forged.post.clone();
+        assert!(!forged.verify_revive());
+
+        // Single-use keys: no duplicate inserts, no revives
+        tree.set_revive_policy(RevivePolicy::ErrorOnDuplicate);
+        assert!(tree.try_insert("b".to_string(), "fb3".to_string()).is_err());
+        tree.delete("b", "fb");
+        tree.delete("b", "fb2");
+        assert!(
+            tree.revive_with_proof("b", Set::from_vec(vec!["z".to_string()]))
+                .is_err()
+        );
+        assert!(tree.try_insert("c".to_string(), "fc".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_response_bound_to_query() {
+        crate::setup::init_test_parameters();
+        let mut tree = crate::AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        let response = tree.select_with_proof("a").unwrap();
+        assert!(response.verify_by_mode("a"));
+        assert!(response.answers(&Query::Point("a".to_string())));
+        assert!(!response.answers(&Query::Prefix("a".to_string())));
+        assert!(
+            tree.select_with_proof("zzz")
+                .unwrap()
+                .answers(&Query::Point("zzz".to_string()))
+        );
+
+        // The hash is part of the canonical encoding
+        let mut rebound = response.clone();
+        rebound.query_hash = Some(Query::Point("b".to_string()).query_hash());
+        assert_ne!(rebound, response);
+        assert!(!rebound.verify_by_mode("a"));
+    }
+
+    #[test]
+    fn test_verify_absent() {
+        crate::setup::init_test_parameters();
+        let mut tree = crate::AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+        let trusted = tree.publish_epoch().global_acc;
+        let response = tree.select_with_proof("zz").unwrap();
+        assert!(response.verify_absent("zz", &trusted));
+        assert!(
+            !tree
+                .select_with_proof("a")
+                .unwrap()
+                .verify_absent("a", &trusted)
+        );
+
+        // Relabelling a proof for another key fails on its element
+        let mut relabelled = response.clone().with_query(&Query::Point("zy".to_string()));
+        let Some(AccProof::NonMembership(nm)) = &mut relabelled.acc_proof else {
+            panic!("expected a non-membership proof");
+        };
+        nm.key = "zy".to_string();
+        assert!(nm.verify("zy"));
+        assert!(!relabelled.verify_absent("zy", &trusted));
+
+        // Proofs only pass against the accumulator they were built for
+        tree.insert("c".to_string(), "fc".to_string());
+        let later = tree.publish_epoch().global_acc;
+        let fresh = tree.select_with_proof("zz").unwrap();
+        assert!(fresh.verify_absent("zz", &later));
+        assert!(!fresh.verify_absent("zz", &trusted));
+        assert!(!response.verify_absent("zz", &later));
+    }
+
+    #[test]
+    fn test_verify_batch_matches_individual_checks() {
+        crate::setup::init_test_parameters();
+        let keys = ["a", "b", "c", "d"];
+        let mut tree = crate::AccumulatorTree::new();
+        for key in keys {
+            tree.insert(key.to_string(), format!("f{}", key));
+        }
+        let mut batch: Vec<(String, QueryResponse)> = keys
+            .iter()
+            .map(|key| (key.to_string(), tree.select_with_proof(key).unwrap()))
+            .collect();
+        batch.push(("zz".to_string(), tree.select_with_proof("zz").unwrap()));
+        let mut merkle_only = tree
+            .select_with_proof("a")
+            .unwrap()
+            .with_mode(ProofMode::MerkleOnly);
+        merkle_only.acc_proof = None;
+        batch.push(("a".to_string(), merkle_only));
+
+        assert!(batch.iter().all(|(key, r)| r.verify_by_mode(key)));
+        assert!(verify_batch(&batch));
+        assert!(verify_batch::<&str>(&[]));
+
+        // One bad witness fails the whole batch
+        let mut forged = batch.clone();
+        forged[2].1.acc_proof = batch[1].1.acc_proof.clone();
+        assert!(!forged[2].1.verify_by_mode("c"));
+        assert!(!verify_batch(&forged));
+        let mut forged = batch.clone();
+        forged[0].0 = "zz".to_string();
+        assert!(!verify_batch(&forged));
+    }
+
+    #[test]
+    fn test_serde_roundtrip_compressed_points() {
+        crate::setup::init_test_parameters();
+        let mut tree = crate::AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+        tree.publish_epoch();
+
+        fn roundtrip<T: Serialize + serde::de::DeserializeOwned + CanonicalEncode>(value: &T) {
+            let bytes = bincode::serialize(value).unwrap();
+            let decoded: T = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(decoded.canonical_bytes(), value.canonical_bytes());
+        }
+        let present = tree.select_with_proof("a").unwrap();
+        assert!(present.verify_by_mode("a"));
+        roundtrip(&present);
+        roundtrip(present.merkle_proof.as_ref().unwrap());
+        let absent = tree.select_with_proof("zz").unwrap();
+        let Some(AccProof::NonMembership(nm)) = &absent.acc_proof else {
+            panic!("expected a non-membership proof");
+        };
+        assert!(nm.commitment.is_some());
+        roundtrip(&absent);
+        roundtrip(nm.as_ref());
+        roundtrip(
+            &tree
+                .insert_with_proof("c".to_string(), "fc".to_string())
+                .unwrap(),
+        );
+        roundtrip(&tree.update_with_proof("a", "fa", "fd".to_string()).unwrap());
+        roundtrip(&tree.delete_with_proof("b", "fb").unwrap());
+
+        // Points are compressed: a length prefix and 48 bytes
+        let witness = MembershipProof {
+            witness: ark_ec::AffineCurve::prime_subgroup_generator(),
+        };
+        let mut bytes = bincode::serialize(&witness).unwrap();
+        assert_eq!(bytes.len(), 8 + 48);
+        bytes[8] ^= 1;
+        assert!(bincode::deserialize::<MembershipProof>(&bytes).is_err());
+    }
 }
@@ -0,0 +1,244 @@
+//! Builders for the mutation responses.
+//!
+//! `InsertResponse`, `UpdateResponse` and `DeleteResponse` carry up to eleven
+//! fields, several of the same type (`key`/`old_fid`/`new_fid`, pre and post
+//! proofs), so they are assembled by name rather than position:
+//!
+//! ```
+//! use accumulator_tree::prelude::*;
+//! # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+//!
+//! let mut tree = AccumulatorTree::new();
+//! tree.insert("alice".to_string(), "doc1".to_string());
+//! let query = tree.select_with_proof("alice").unwrap();
+//! let witness = match query.acc_proof {
+//!     Some(AccProof::Membership(mp)) => mp,
+//!     _ => unreachable!(),
+//! };
+//!
+//! let response = InsertResponse::builder()
+//!     .key("alice".to_string())
+//!     .fids(query.fids.unwrap())
+//!     .post_merkle_proof(query.merkle_proof)
+//!     .post_accumulator(query.accumulator)
+//!     .post_acc_proof(witness)
+//!     .build()
+//!     .unwrap();
+//! assert!(response.verify_insert());
+//! ```
+//!
+//! Required fields are tracked in the builder's type: each one starts as `()`
+//! and `build` only exists once all of them are set, so a forgotten field is
+//! a compile error rather than a verification failure:
+//!
+//! ```compile_fail
+//! use accumulator_tree::InsertResponse;
+//!
+//! let response = InsertResponse::builder().key("alice".to_string()).build();
+//! ```
+//!
+//! Optional fields accept either a value or an `Option`. `build` runs the
+//! `Validate` checks, like the `try_from_parts` constructors.
+
+use crate::acc_proof::{MembershipProof, NonMembershipProof};
+use crate::merkle_proof::Proof as MerkleProof;
+use crate::metrics::ProofStats;
+use crate::response::{DeleteResponse, InsertResponse, UpdateResponse};
+use crate::validate::Validate;
+use accumulator_ads::{G1Affine, Set};
+
+/// Define `$builder` for `$target`, with one type parameter per required
+/// field and an `Option` slot per optional one.
+macro_rules! response_builder {
+    (
+        $(#[$meta:meta])*
+        $target:ident => $builder:ident {
+            required { $($req:ident: $param:ident = $req_ty:ty),* $(,)? }
+            optional { $($opt:ident: $opt_ty:ty),* $(,)? }
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        #[must_use]
+        pub struct $builder<$($param = ()),*> {
+            $($req: $param,)*
+            $($opt: Option<$opt_ty>,)*
+        }
+
+        impl $target {
+            pub fn builder() -> $builder {
+                $builder {
+                    $($req: (),)*
+                    $($opt: None,)*
+                }
+            }
+        }
+
+        impl<$($param),*> $builder<$($param),*> {
+            $(
+                pub fn $opt(mut self, $opt: impl Into<Option<$opt_ty>>) -> Self {
+                    self.$opt = $opt.into();
+                    self
+                }
+            )*
+        }
+
+        impl $builder<$($req_ty),*> {
+            /// Assemble the response, rejecting malformed points and paths.
+            pub fn build(self) -> Result<$target, String> {
+                let response = $target {
+                    $($req: self.$req,)*
+                    $($opt: self.$opt,)*
+                };
+                response.validate()?;
+                Ok(response)
+            }
+        }
+
+        response_builder!(@setters $builder [] [$($req: $param = $req_ty),*] [$($opt),*]);
+    };
+    (@setters $builder:ident [$($done:ident: $done_param:ident),*] [] [$($opt:ident),*]) => {};
+    (
+        @setters $builder:ident
+        [$($done:ident: $done_param:ident),*]
+        [$cur:ident: $cur_param:ident = $cur_ty:ty $(, $rest:ident: $rest_param:ident = $rest_ty:ty)*]
+        [$($opt:ident),*]
+    ) => {
+        impl<$($done_param,)* $($rest_param),*> $builder<$($done_param,)* (), $($rest_param),*> {
+            pub fn $cur(self, $cur: $cur_ty) -> $builder<$($done_param,)* $cur_ty, $($rest_param),*> {
+                $builder {
+                    $($done: self.$done,)*
+                    $cur,
+                    $($rest: self.$rest,)*
+                    $($opt: self.$opt,)*
+                }
+            }
+        }
+
+        response_builder!(
+            @setters $builder
+            [$($done: $done_param,)* $cur: $cur_param]
+            [$($rest: $rest_param = $rest_ty),*]
+            [$($opt),*]
+        );
+    };
+}
+
+response_builder! {
+    /// Builder for `InsertResponse`, from `InsertResponse::builder()`.
+    InsertResponse => InsertResponseBuilder {
+        required {
+            key: Key = String,
+            fids: Fids = Set<String>,
+        }
+        optional {
+            post_accumulator: G1Affine,
+            post_merkle_proof: MerkleProof,
+            post_acc_proof: MembershipProof,
+            pre_acc_proof: NonMembershipProof,
+            stats: ProofStats,
+        }
+    }
+}
+
+response_builder! {
+    /// Builder for `UpdateResponse`, from `UpdateResponse::builder()`.
+    UpdateResponse => UpdateResponseBuilder {
+        required {
+            key: Key = String,
+            old_fid: OldFid = String,
+            new_fid: NewFid = String,
+            new_fids: NewFids = Set<String>,
+            post_merkle_proof: PostProof = MerkleProof,
+            post_accumulator: PostAcc = G1Affine,
+            post_acc_proof: PostAccProof = MembershipProof,
+        }
+        optional {
+            old_fids: Set<String>,
+            pre_merkle_proof: MerkleProof,
+            pre_accumulator: G1Affine,
+            pre_acc_proof: MembershipProof,
+            stats: ProofStats,
+        }
+    }
+}
+
+response_builder! {
+    /// Builder for `DeleteResponse`, from `DeleteResponse::builder()`.
+    DeleteResponse => DeleteResponseBuilder {
+        required {
+            key: Key = String,
+            deleted_fid: DeletedFid = String,
+            new_fids: NewFids = Set<String>,
+            post_merkle_proof: PostProof = MerkleProof,
+            post_accumulator: PostAcc = G1Affine,
+        }
+        optional {
+            old_fids: Set<String>,
+            pre_merkle_proof: MerkleProof,
+            pre_accumulator: G1Affine,
+            pre_acc_proof: MembershipProof,
+            stats: ProofStats,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::response::{DeleteResponse, UpdateResponse};
+    use crate::utils::leaf_hash;
+    use crate::validate::Validate;
+    use accumulator_ads::{G1Affine, Set};
+    use ark_bls12_381::Fq;
+    use ark_ec::AffineCurve;
+
+    #[test]
+    fn test_builders_set_fields_by_name() {
+        let g1 = G1Affine::prime_subgroup_generator();
+        let old_fids = Set::from_vec(vec!["f1".to_string()]);
+        let new_fids = Set::from_vec(vec!["f2".to_string()]);
+        let pre = crate::merkle_proof::Proof::new(
+            leaf_hash("k", &old_fids, 0, false),
+            leaf_hash("k", &old_fids, 0, false),
+            vec![],
+        );
+        let post = crate::merkle_proof::Proof::new(
+            leaf_hash("k", &new_fids, 0, false),
+            leaf_hash("k", &new_fids, 0, false),
+            vec![],
+        );
+
+        // Setters may come in any order
+        let update = UpdateResponse::builder()
+            .new_fid("f2".to_string())
+            .old_fid("f1".to_string())
+            .key("k".to_string())
+            .pre_merkle_proof(pre.clone())
+            .old_fids(Some(old_fids.clone()))
+            .post_acc_proof(crate::acc_proof::MembershipProof { witness: g1 })
+            .post_accumulator(g1)
+            .post_merkle_proof(post.clone())
+            .new_fids(new_fids.clone())
+            .build()
+            .unwrap();
+        assert_eq!(
+            (update.old_fid.as_str(), update.new_fid.as_str()),
+            ("f1", "f2")
+        );
+        assert_eq!(update.old_fids, Some(old_fids));
+        assert_eq!(update.pre_merkle_proof, Some(pre));
+        assert_eq!(update.pre_accumulator, None);
+        assert!(update.validate().is_ok());
+
+        let off_curve = G1Affine::new(g1.x, g1.y + Fq::from(1u64), false);
+        let err = DeleteResponse::builder()
+            .key("k".to_string())
+            .deleted_fid("f2".to_string())
+            .new_fids(Set::new())
+            .post_merkle_proof(post)
+            .post_accumulator(off_curve)
+            .build()
+            .unwrap_err();
+        assert!(err.contains("post accumulator"));
+    }
+}
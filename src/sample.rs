@@ -0,0 +1,297 @@
+//! Verifiable random sampling of leaves.
+//!
+//! Each root of the forest is a perfect binary tree, so the leaves of the
+//! forest can be numbered left to right, and a Merkle path encodes the position
+//! of its leaf in the direction bits. Sample `i` is the leaf at position
+//! `H(commitment, seed, i) mod leaf_count`. Anyone holding the commitment
+//! digest can recompute the positions and check the returned paths, so the
+//! prover cannot pick which leaves are audited. Tombstoned leaves are sampled
+//! like live ones, which makes the sample usable for estimating the tombstone
+//! ratio.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::epoch::{EpochCommitment, RootEntry, forest_hash};
+use crate::error::AccTreeError;
+use crate::merkle_proof::Proof as MerkleProof;
+use crate::node::Node;
+use accumulator_ads::Set;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+const SAMPLE_DOMAIN: &[u8] = b"acc-tree/sample";
+
+/// Position of sample `i` among `leaf_count` leaves.
+pub fn sample_index(commitment: &EpochCommitment, seed: &[u8], i: u64, leaf_count: u64) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(SAMPLE_DOMAIN);
+    hasher.update(commitment.digest());
+    hasher.update((seed.len() as u64).to_be_bytes());
+    hasher.update(seed);
+    hasher.update(i.to_be_bytes());
+    let digest = hasher.finalize();
+    let wide = u128::from_be_bytes(digest[..16].try_into().unwrap());
+    (wide % leaf_count as u128) as u64
+}
+
+/// A leaf and its Merkle path.
+#[derive(Debug, Clone)]
+pub struct SampledLeaf {
+    /// Position of the leaf in the forest, counting from the leftmost leaf
+    pub index: u64,
+    pub key: String,
+    pub fids: Set<String>,
+    pub deleted: bool,
+    pub proof: MerkleProof,
+}
+
+impl SampledLeaf {
+    /// Position of the leaf inside its root, read from the path directions.
+    fn position_in_root(&self) -> u64 {
+        self.proof
+            .path
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, sibling_is_left))| *sibling_is_left)
+            .map(|(depth, _)| 1u64 << depth)
+            .sum()
+    }
+
+    fn verify(&self) -> bool {
+        self.proof
+            .verify_leaf_state(&self.key, &self.fids, 0, self.deleted)
+    }
+}
+
+/// Sampled leaves of a published epoch, with everything needed to check that
+/// they were selected correctly.
+#[derive(Debug, Clone)]
+pub struct KeySample {
+    pub seed: Vec<u8>,
+    pub commitment: EpochCommitment,
+    /// (level, hash) of the roots of the committed forest
+    pub roots: Vec<RootEntry>,
+    pub leaves: Vec<SampledLeaf>,
+}
+
+impl KeySample {
+    /// Sampled keys, in sample order (with repeats).
+    pub fn keys(&self) -> Vec<&str> {
+        self.leaves.iter().map(|l| l.key.as_str()).collect()
+    }
+
+    /// Fraction of sampled leaves that are tombstones.
+    pub fn tombstone_ratio(&self) -> f64 {
+        if self.leaves.is_empty() {
+            return 0.0;
+        }
+        self.leaves.iter().filter(|l| l.deleted).count() as f64 / self.leaves.len() as f64
+    }
+
+    /// Check the sample against the trusted commitment digest.
+    pub fn verify(&self, trusted_digest: &Hash) -> bool {
+        self.check(trusted_digest).is_ok()
+    }
+
+    /// Checks:
+    /// 1. The commitment is the trusted one and commits to `roots`, which fixes
+    ///    the size of every root
+    /// 2. Sample `i` is the leaf at the position derived from the seed
+    pub fn check(&self, trusted_digest: &Hash) -> Result<(), String> {
+        if self.commitment.digest() != *trusted_digest {
+            return Err("commitment does not match the trusted digest".to_string());
+        }
+        if forest_hash(&self.roots) != self.commitment.forest_hash {
+            return Err("roots do not match the commitment".to_string());
+        }
+
+        // (first leaf index, level) of every root
+        let mut offsets = Vec::with_capacity(self.roots.len());
+        let mut leaf_count = 0u64;
+        for (j, (level, _)) in self.roots.iter().enumerate() {
+            if *level >= 64 {
+                return Err(format!("invalid level for root {}", j));
+            }
+            offsets.push((leaf_count, *level as usize));
+            leaf_count = leaf_count
+                .checked_add(1 << level)
+                .ok_or_else(|| "forest too large".to_string())?;
+        }
+
+        for (i, leaf) in self.leaves.iter().enumerate() {
+            if leaf_count == 0 {
+                return Err("cannot sample an empty forest".to_string());
+            }
+            let expected = sample_index(&self.commitment, &self.seed, i as u64, leaf_count);
+            if leaf.index != expected {
+                return Err(format!("sample {} is not the selected leaf", i));
+            }
+            let j = offsets.partition_point(|(start, _)| *start <= expected) - 1;
+            let (start, level) = offsets[j];
+            if leaf.proof.path.len() != level
+                || leaf.proof.root_hash != self.roots[j].1
+                || start + leaf.position_in_root() != expected
+                || !leaf.verify()
+            {
+                return Err(format!("invalid proof for sample {}", i));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Leaf at position `pos` under `node`, with its bottom-up Merkle path.
+fn leaf_at(node: &Node, pos: u64, path: &mut Vec<(Hash, bool)>) -> SampledLeaf {
+    match node {
+        Node::Leaf {
+            key, fids, deleted, ..
+        } => {
+            path.reverse();
+            let leaf = crate::utils::leaf_hash(key, fids, 0, *deleted);
+            SampledLeaf {
+                index: 0,
+                key: key.clone(),
+                fids: fids.clone(),
+                deleted: *deleted,
+                proof: MerkleProof::new(leaf, leaf, std::mem::take(path)),
+            }
+        }
+        Node::NonLeaf {
+            left, right, level, ..
+        } => {
+            let half = 1u64 << (level - 1);
+            if pos < half {
+                path.push((right.hash(), false));
+                leaf_at(left, pos, path)
+            } else {
+                path.push((left.hash(), true));
+                leaf_at(right, pos - half, path)
+            }
+        }
+    }
+}
+
+/// Select `n` leaves of `roots` for `seed`. `commitment` must commit to `roots`.
+pub(crate) fn sample_leaves(
+    roots: &[Arc<Node>],
+    commitment: &EpochCommitment,
+    seed: &[u8],
+    n: usize,
+) -> Result<KeySample, AccTreeError> {
+    let entries: Vec<RootEntry> = roots.iter().map(|r| (r.level() as u64, r.hash())).collect();
+    if forest_hash(&entries) != commitment.forest_hash {
+        return Err(AccTreeError::ForestChanged);
+    }
+
+    let locate = |root: &Node, root_hash: Hash, start: u64, pos: u64| {
+        let mut leaf = leaf_at(root, pos, &mut Vec::new());
+        if leaf.proof.path.len() != root.level() {
+            return Err(AccTreeError::ProofGenFailed(
+                "root is not a perfect binary tree".to_string(),
+            ));
+        }
+        leaf.index = start + pos;
+        leaf.proof.root_hash = root_hash;
+        Ok::<_, AccTreeError>(leaf)
+    };
+
+    let mut offsets = Vec::with_capacity(roots.len());
+    let mut leaf_count = 0u64;
+    for root in roots {
+        offsets.push(leaf_count);
+        leaf_count += 1 << root.level();
+    }
+    if leaf_count == 0 && n > 0 {
+        return Err(AccTreeError::InvalidInput(
+            "cannot sample an empty forest".to_string(),
+        ));
+    }
+
+    let mut leaves = Vec::with_capacity(n);
+    for i in 0..n {
+        let index = sample_index(commitment, seed, i as u64, leaf_count);
+        let j = offsets.partition_point(|start| *start <= index) - 1;
+        leaves.push(locate(
+            &roots[j],
+            entries[j].1,
+            offsets[j],
+            index - offsets[j],
+        )?);
+    }
+
+    Ok(KeySample {
+        seed: seed.to_vec(),
+        commitment: commitment.clone(),
+        roots: entries,
+        leaves,
+    })
+}
+
+impl CanonicalEncode for SampledLeaf {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.index.encode_canonical(out);
+        self.key.encode_canonical(out);
+        self.fids.encode_canonical(out);
+        self.deleted.encode_canonical(out);
+        self.proof.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for KeySample {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        (self.seed.len() as u32).encode_canonical(out);
+        out.extend_from_slice(&self.seed);
+        self.commitment.encode_canonical(out);
+        self.roots.encode_canonical(out);
+        self.leaves.encode_canonical(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_sample_verifies_and_is_deterministic() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for k in ["a", "b", "c", "d", "e", "f", "g"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+        tree.delete("c", "fc");
+        let digest = tree.publish_epoch().digest();
+
+        let sample = tree.sample_keys(b"audit-1", 16).unwrap();
+        assert!(sample.verify(&digest));
+        assert_eq!(sample.roots, tree.root_entries());
+        assert_eq!(
+            sample.keys(),
+            tree.sample_keys(b"audit-1", 16).unwrap().keys()
+        );
+        assert!((0.0..=1.0).contains(&sample.tombstone_ratio()));
+
+        // The prover cannot swap in a leaf of its choice
+        let other = sample
+            .leaves
+            .iter()
+            .find(|l| l.index != sample.leaves[0].index)
+            .unwrap();
+        let mut cherry_picked = sample.clone();
+        cherry_picked.leaves[0] = other.clone();
+        assert!(!cherry_picked.verify(&digest));
+        let mut other_seed = sample.clone();
+        other_seed.seed = b"audit-2".to_vec();
+        assert!(!other_seed.verify(&digest));
+    }
+
+    #[test]
+    fn test_sample_requires_published_state() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        assert!(tree.sample_keys(b"s", 1).is_err());
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.publish_epoch();
+        tree.insert("b".to_string(), "fb".to_string());
+        assert!(tree.sample_keys(b"s", 1).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+//! Sizing helpers for the trusted setup.
+//!
+//! Every accumulator in the forest is a polynomial commitment over a key set, so
+//! the public parameters must hold at least as many powers of `s` as the largest
+//! set that will ever be accumulated. These helpers let deployers pick
+//! `max_degree` for the ceremony and let the tree refuse configurations that
+//! would otherwise panic mid-merge.
+//!
+//! `init`, `init_from_ptau` and (with the `insecure-test-params` feature)
+//! `init_for_testing` load the parameters through this crate, so callers
+//! need not depend on `accumulator_ads` for it, and `ready` tells whether any
+//! of them has run. `init_from_ptau` reuses an audited powers-of-tau
+//! transcript instead of a ceremony of one's own.
+
+use crate::error::AccTreeError;
+use accumulator_ads::public_parameters_max_degree;
+
+/// Fan-out of the forest built by `AccumulatorTree` (binary merges).
+pub const TREE_ARITY: usize = 2;
+
+/// Optional proof features that change the largest set the accumulator sees.
+///
+/// Non-membership answers and `publish_epoch` accumulate every live key of
+/// the forest at once, so they are not optional here: every setup is sized
+/// for the whole key set. No feature currently raises the degree further.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SetupFeatures {}
+
+/// Minimum `max_degree` the public parameters need for a forest expected to
+/// hold `estimated_keys` live keys.
+///
+/// Roots are perfect subtrees, so the largest root holds the biggest power of
+/// `arity` not exceeding `estimated_keys`. The global accumulator behind
+/// non-membership proofs and `publish_epoch` covers every live key, which is
+/// never fewer, so the answer is the live key count. Returns `InvalidInput`
+/// if `arity < 2`.
+pub fn required_setup_degree(
+    estimated_keys: usize,
+    arity: usize,
+    _features: SetupFeatures,
+) -> Result<usize, AccTreeError> {
+    if arity < 2 {
+        return Err(AccTreeError::InvalidInput(format!(
+            "tree arity must be at least 2, got {}",
+            arity
+        )));
+    }
+    Ok(estimated_keys)
+}
+
+/// Check that the initialized public parameters can serve `estimated_keys` keys.
+/// Returns `ParamsNotInitialized` or `DegreeExceeded` otherwise.
+pub fn check_setup_degree(
+    estimated_keys: usize,
+    features: SetupFeatures,
+) -> Result<(), AccTreeError> {
+    check_available_degree(required_setup_degree(estimated_keys, TREE_ARITY, features)?)
+}
+
+/// Load the public parameters from the ceremony output at `params_path`.
+/// Call once at startup, before the first merge or proof. A tampered or
+/// truncated file, or one whose powers are inconsistent, is refused.
+pub fn init<P: AsRef<std::path::Path>>(params_path: P) -> Result<(), String> {
+    accumulator_ads::acc::setup::init_public_parameters(params_path).map_err(|e| format!("{:#}", e))
+}
+
+/// `init`, refusing a file unless it holds the parameters with digest
+/// `expected` (`PublicParameters::digest`, also in every `VerifierBundle`).
+pub fn init_pinned<P: AsRef<std::path::Path>>(
+    params_path: P,
+    expected: &crate::Hash,
+) -> Result<(), String> {
+    use accumulator_ads::acc::setup::{PublicParameters, init_public_parameters_direct};
+    let params = PublicParameters::load_from_file(params_path).map_err(|e| format!("{:#}", e))?;
+    if params.digest() != *expected {
+        return Err(format!(
+            "public parameters have digest {}, expected {}",
+            hex::encode(params.digest()),
+            hex::encode(expected)
+        ));
+    }
+    init_public_parameters_direct(params).map_err(|e| e.to_string())
+}
+
+/// Load degree `max_degree` parameters from a BLS12-381 powers-of-tau
+/// transcript (`.ptau`, as published by the perpetual powers-of-tau
+/// ceremonies), checking every point and the ratios between powers.
+pub fn init_from_ptau<P: AsRef<std::path::Path>>(
+    ptau_path: P,
+    max_degree: usize,
+) -> Result<(), String> {
+    use accumulator_ads::acc::setup::{PublicParameters, init_public_parameters_direct};
+    let params =
+        PublicParameters::load_from_ptau(ptau_path, max_degree).map_err(|e| format!("{:#}", e))?;
+    init_public_parameters_direct(params).map_err(|e| e.to_string())
+}
+
+/// Degree of the parameters loaded by `init_for_testing`.
+#[cfg(feature = "insecure-test-params")]
+pub const TESTING_DEGREE: usize = 64;
+
+/// `init_insecure_parameters(TESTING_DEGREE)`.
+#[cfg(feature = "insecure-test-params")]
+pub fn init_for_testing() -> Result<(), String> {
+    init_insecure_parameters(TESTING_DEGREE)
+}
+
+/// Load the `init_for_testing` parameters once per test binary, so every unit
+/// test proves and verifies against the same setup.
+#[cfg(test)]
+pub(crate) fn init_test_parameters() {
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+    INIT.call_once(|| init_for_testing().expect("Failed to initialize test parameters"));
+}
+
+/// Whether public parameters have been loaded.
+///
+/// ```
+/// use accumulator_ads::acc::setup::init_public_parameters_direct;
+/// use accumulator_ads::{AccumulatorManager, Fr};
+///
+/// let manager = AccumulatorManager::new(Fr::from(7u64)).unwrap();
+/// init_public_parameters_direct(manager.generate_parameters(4)).unwrap();
+/// assert!(accumulator_tree::ready());
+/// ```
+pub fn ready() -> bool {
+    public_parameters_max_degree().is_some()
+}
+
+/// `Err(ParamsNotInitialized)` unless `ready()`.
+pub(crate) fn ensure_ready() -> Result<(), AccTreeError> {
+    if ready() {
+        Ok(())
+    } else {
+        Err(AccTreeError::ParamsNotInitialized)
+    }
+}
+
+/// Initialize public parameters of degree `max_degree` from the trapdoor of
+/// `insecure_manager`, so a tree given that manager updates in O(1). For
+/// examples and tests, with the `insecure-test-params` feature; deployments
+/// load ceremony output with `init`.
+#[cfg(feature = "insecure-test-params")]
+pub fn init_insecure_parameters(max_degree: usize) -> Result<(), String> {
+    use accumulator_ads::acc::setup::init_public_parameters_direct;
+    init_public_parameters_direct(insecure_manager().generate_parameters(max_degree))
+        .map_err(|e| e.to_string())
+}
+
+/// Manager for the publicly known trapdoor behind `init_insecure_parameters`
+/// (`accumulator_ads::acc::setup::PRI_S`, shared with that crate's tests).
+/// With the `insecure-test-params` feature only: anyone can forge proofs
+/// against those parameters.
+#[cfg(feature = "insecure-test-params")]
+pub fn insecure_manager() -> accumulator_ads::AccumulatorManager {
+    accumulator_ads::AccumulatorManager::new(*accumulator_ads::acc::setup::PRI_S)
+        .expect("trapdoor is non-zero")
+}
+
+/// Check that public parameters are initialized and reach degree `required`.
+pub fn check_available_degree(required: usize) -> Result<(), AccTreeError> {
+    let available = public_parameters_max_degree().ok_or(AccTreeError::ParamsNotInitialized)?;
+    if available < required {
+        return Err(AccTreeError::DegreeExceeded {
+            needed: required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_required_degree_covers_all_keys() {
+        let features = SetupFeatures::default();
+        assert_eq!(required_setup_degree(0, 2, features), Ok(0));
+        assert_eq!(required_setup_degree(7, 2, features), Ok(7));
+        assert_eq!(required_setup_degree(1000, 2, features), Ok(1000));
+        assert_eq!(required_setup_degree(100, 4, features), Ok(100));
+        assert!(matches!(
+            required_setup_degree(100, 1, features),
+            Err(AccTreeError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_with_expected_keys_fails_fast() {
+        init_test_parameters();
+        let features = SetupFeatures::default();
+        assert!(AccumulatorTree::with_expected_keys(TESTING_DEGREE, features).is_ok());
+
+        // A forest of 65 keys has roots of 64 and 1, but publishing an epoch
+        // accumulates all 65
+        assert_eq!(
+            AccumulatorTree::with_expected_keys(TESTING_DEGREE + 1, features).err(),
+            Some(AccTreeError::DegreeExceeded {
+                needed: TESTING_DEGREE + 1,
+                available: TESTING_DEGREE
+            })
+        );
+    }
+
+    #[test]
+    fn test_init_pinned_refuses_other_parameters() {
+        use accumulator_ads::acc::setup::get_public_parameters;
+
+        init_test_parameters();
+        let params = get_public_parameters().unwrap();
+        let path = std::env::temp_dir().join(format!("acc-pinned-{}.bin", std::process::id()));
+        params.save_to_file(&path).unwrap();
+        let err = init_pinned(&path, &[0u8; 32]).unwrap_err();
+        assert!(err.contains(&hex::encode(params.digest())), "{}", err);
+        // Reinstalls the same parameters, so concurrent tests are unaffected
+        assert!(init_pinned(&path, &params.digest()).is_ok());
+        std::fs::write(&path, b"ACPP").unwrap();
+        assert!(init_pinned(&path, &params.digest()).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_select_reports_small_parameters() {
+        init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for i in 0..=TESTING_DEGREE {
+            tree.insert(format!("k{:03}", i), "f".to_string());
+        }
+        // k000 is in the root of 64; non-membership covers all 65 keys
+        assert!(tree.select_with_proof("k000").is_ok());
+        assert_eq!(
+            tree.select_with_proof("missing").unwrap_err(),
+            AccTreeError::DegreeExceeded {
+                needed: TESTING_DEGREE + 1,
+                available: TESTING_DEGREE
+            }
+        );
+    }
+
+    #[test]
+    fn test_inserts_refuse_oversized_roots() {
+        init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for i in 0..127 {
+            tree.try_insert(format!("k{:03}", i), "f".to_string())
+                .unwrap();
+        }
+        let roots = tree.root_hashes();
+
+        // The 128th key would merge everything into one root of 128
+        let too_many = AccTreeError::DegreeExceeded {
+            needed: 128,
+            available: TESTING_DEGREE,
+        };
+        assert_eq!(
+            tree.try_insert("k127".to_string(), "f".to_string()),
+            Err(too_many.clone())
+        );
+        assert_eq!(
+            tree.try_insert_batch([("k127".to_string(), "f".to_string())]),
+            Err(too_many.clone())
+        );
+        let mut txn = tree.begin_transaction();
+        assert_eq!(
+            txn.try_insert("k127".to_string(), "f".to_string()),
+            Err(too_many)
+        );
+        assert!(txn.commit().is_empty());
+        assert_eq!(tree.root_hashes(), roots);
+
+        // Tombstones leave room: the merged root holds 64 live keys
+        for i in 0..64 {
+            tree.delete(&format!("k{:03}", i), "f");
+        }
+        assert!(tree.try_insert("k127".to_string(), "f".to_string()).is_ok());
+        assert_eq!(tree.root_hashes().len(), 1);
+        // More keys on existing leaves never merge
+        assert_eq!(
+            tree.insert_batch([("k127".to_string(), "g".to_string())]),
+            1
+        );
+    }
+}
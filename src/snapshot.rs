@@ -0,0 +1,361 @@
+//! Whole-forest snapshots.
+//!
+//! `AccumulatorTree::to_bytes` writes every root together with the cached key
+//! sets and accumulators of its nodes, the last published epoch and the
+//! deletion and staging bookkeeping, so `AccumulatorTree::from_bytes` restores
+//! a populated tree without recomputing a single accumulator. Node hashes are
+//! recomputed while loading and must match the stored ones, which catches
+//! truncated or corrupted files. Registered anchors are not part of a
+//! snapshot; add them again after loading.
+//!
+//! `TreeSnapshot` is the in-memory counterpart: an immutable version of the
+//! tree that shares its nodes with the live forest, for serving proofs against
+//! a published root while writes continue.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::epoch::{PublishedEpoch, RootStamp};
+use crate::key_filter::KeyFilter;
+use crate::node::{INLINE_KEYS, Node, NodeKeys};
+use crate::tree::AccumulatorTree;
+use crate::txn::TxnOp;
+use crate::utils::nonleaf_hash;
+use crate::wire::{CanonicalDecode, WireReader};
+use std::sync::Arc;
+
+/// Leading bytes of a tree snapshot.
+pub const SNAPSHOT_MAGIC: &[u8; 4] = b"ACTS";
+
+/// Version written by `AccumulatorTree::to_bytes`.
+pub const SNAPSHOT_VERSION: u16 = 3;
+
+/// Immutable version of a tree taken by `AccumulatorTree::snapshot`.
+///
+/// Dereferences to a read-only `AccumulatorTree`, so every query and proof
+/// method (`select_with_proof`, `range_with_proof`, ...) answers against the
+/// forest and published epoch as they were when the snapshot was taken.
+/// Cloning is cheap and clones can be handed to reader threads.
+#[derive(Clone)]
+pub struct TreeSnapshot {
+    tree: Arc<AccumulatorTree>,
+}
+
+impl TreeSnapshot {
+    pub(crate) fn new(tree: AccumulatorTree) -> Self {
+        Self {
+            tree: Arc::new(tree),
+        }
+    }
+}
+
+impl std::ops::Deref for TreeSnapshot {
+    type Target = AccumulatorTree;
+
+    fn deref(&self) -> &AccumulatorTree {
+        &self.tree
+    }
+}
+
+/// Deepest node accepted when loading; far above any real forest.
+const MAX_SNAPSHOT_LEVEL: u64 = 64;
+
+impl CanonicalEncode for NodeKeys {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            NodeKeys::Inline(keys) => {
+                out.push(0);
+                keys.to_vec().encode_canonical(out);
+            }
+            NodeKeys::Filtered { filter, len } => {
+                out.push(1);
+                (*len as u64).encode_canonical(out);
+                filter.words().to_vec().encode_canonical(out);
+            }
+        }
+    }
+}
+
+impl CanonicalDecode for NodeKeys {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        match u8::decode_canonical(r)? {
+            0 => {
+                let mut keys = Vec::<String>::decode_canonical(r)?;
+                if keys.len() > INLINE_KEYS {
+                    return Err(format!("{} inline keys exceed {}", keys.len(), INLINE_KEYS));
+                }
+                keys.sort_unstable();
+                Ok(NodeKeys::Inline(keys.into_iter().collect()))
+            }
+            1 => {
+                let len = u64::decode_canonical(r)? as usize;
+                let filter = KeyFilter::from_words(Vec::<u64>::decode_canonical(r)?)?;
+                Ok(NodeKeys::Filtered { filter, len })
+            }
+            tag => Err(format!("unknown node key tag {}", tag)),
+        }
+    }
+}
+
+impl CanonicalEncode for Node {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            Node::Leaf {
+                key,
+                fids,
+                level,
+                deleted,
+                ..
+            } => {
+                out.push(0);
+                key.encode_canonical(out);
+                fids.encode_canonical(out);
+                (*level as u64).encode_canonical(out);
+                deleted.encode_canonical(out);
+            }
+            Node::NonLeaf {
+                hash,
+                keys,
+                acc,
+                level,
+                left,
+                right,
+                stale,
+            } => {
+                out.push(1);
+                hash.encode_canonical(out);
+                keys.encode_canonical(out);
+                acc.encode_canonical(out);
+                (*level as u64).encode_canonical(out);
+                stale.encode_canonical(out);
+                left.encode_canonical(out);
+                right.encode_canonical(out);
+            }
+        }
+    }
+}
+
+impl CanonicalDecode for Node {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        decode_node(r, MAX_SNAPSHOT_LEVEL)
+    }
+}
+
+/// Decode a node whose level is at most `max_level`, checking that levels
+/// step down by one per child and that stored hashes match the children.
+fn decode_node(r: &mut WireReader, max_level: u64) -> Result<Node, String> {
+    match u8::decode_canonical(r)? {
+        0 => {
+            let key = String::decode_canonical(r)?;
+            let fids = CanonicalDecode::decode_canonical(r)?;
+            let level = u64::decode_canonical(r)?;
+            let deleted = bool::decode_canonical(r)?;
+            if level != 0 {
+                return Err(format!("leaf '{}' at level {}", key, level));
+            }
+            Ok(Node::leaf(key, fids, deleted))
+        }
+        1 => {
+            let hash = Hash::decode_canonical(r)?;
+            let keys = NodeKeys::decode_canonical(r)?;
+            let acc = CanonicalDecode::decode_canonical(r)?;
+            let level = u64::decode_canonical(r)?;
+            let stale = bool::decode_canonical(r)?;
+            if level == 0 || level > max_level {
+                return Err(format!("internal node at level {}", level));
+            }
+            let left = decode_node(r, level - 1)?;
+            let right = decode_node(r, level - 1)?;
+            if left.level() as u64 != level - 1 || right.level() as u64 != level - 1 {
+                return Err(format!("children of a level {} node are unbalanced", level));
+            }
+            if nonleaf_hash(left.hash(), right.hash()) != hash {
+                return Err(format!("hash mismatch at level {}", level));
+            }
+            Ok(Node::NonLeaf {
+                hash,
+                keys,
+                acc,
+                level: level as usize,
+                left: Arc::new(left),
+                right: Arc::new(right),
+                stale,
+            })
+        }
+        tag => Err(format!("unknown node tag {}", tag)),
+    }
+}
+
+impl CanonicalDecode for TxnOp {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let tag = u8::decode_canonical(r)?;
+        let key = String::decode_canonical(r)?;
+        match tag {
+            0 => Ok(TxnOp::Insert {
+                key,
+                fid: String::decode_canonical(r)?,
+            }),
+            1 => Ok(TxnOp::Update {
+                key,
+                old_fid: String::decode_canonical(r)?,
+                new_fid: String::decode_canonical(r)?,
+            }),
+            2 => Ok(TxnOp::Delete {
+                key,
+                fid: String::decode_canonical(r)?,
+            }),
+            _ => Err(format!("unknown operation tag {}", tag)),
+        }
+    }
+}
+
+impl CanonicalEncode for PublishedEpoch {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.commitment.encode_canonical(out);
+        self.keys.encode_canonical(out);
+        let roots: Vec<((u64, Hash), u64)> =
+            self.roots.iter().map(|s| (s.root, s.changed_in)).collect();
+        roots.encode_canonical(out);
+        crate::clock::unix_millis(self.published_at).encode_canonical(out);
+    }
+}
+
+impl CanonicalDecode for PublishedEpoch {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let commitment = CanonicalDecode::decode_canonical(r)?;
+        let keys = CanonicalDecode::decode_canonical(r)?;
+        let roots = Vec::<((u64, Hash), u64)>::decode_canonical(r)?
+            .into_iter()
+            .map(|(root, changed_in)| RootStamp { root, changed_in })
+            .collect();
+        let published_at = crate::clock::from_unix_millis(u64::decode_canonical(r)?);
+        Ok(Self {
+            commitment,
+            keys,
+            roots,
+            published_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AccumulatorTree;
+    use crate::node::Node;
+    use crate::policy::RevivePolicy;
+    use crate::storage::{MemoryStore, get_tree, put_tree};
+    use crate::txn::TxnOp;
+    use accumulator_ads::Set;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for key in ["a", "b", "c", "d", "e", "f"] {
+            tree.insert(key.to_string(), format!("fid-{}", key));
+        }
+        tree.delete("c", "fid-c");
+        let clock = crate::clock::MockClock::new(crate::clock::from_unix_millis(1_700_000_000_123));
+        tree.set_clock(std::sync::Arc::new(clock));
+        tree.publish_epoch();
+        tree.insert("g".to_string(), "fid-g".to_string());
+        tree.stage(TxnOp::Insert {
+            key: "h".to_string(),
+            fid: "fid-h".to_string(),
+        });
+        tree.set_revive_policy(RevivePolicy::ErrorOnDuplicate);
+
+        let store = MemoryStore::new();
+        put_tree(&store, "trees/main", &tree).unwrap();
+        let mut loaded = get_tree(&store, "trees/main").unwrap().unwrap();
+        assert!(get_tree(&store, "trees/missing").unwrap().is_none());
+
+        assert_eq!(loaded.root_hashes(), tree.root_hashes());
+        assert_eq!(loaded.epoch(), tree.epoch());
+        assert_eq!(loaded.published_at(), tree.published_at());
+        assert_eq!(loaded.staged(), tree.staged());
+        assert_eq!(loaded.revive_policy(), RevivePolicy::ErrorOnDuplicate);
+        assert_eq!(loaded.has_stale_nodes(), tree.has_stale_nodes());
+        for key in ["a", "c", "g", "h", "z"] {
+            assert_eq!(loaded.select(key), tree.select(key), "{}", key);
+        }
+        assert_eq!(
+            loaded.root_manifest().unwrap().roots,
+            tree.root_manifest().unwrap().roots
+        );
+        assert_eq!(loaded.to_bytes(), tree.to_bytes());
+        assert_eq!(loaded.publish().commitment, tree.publish().commitment);
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_rejected() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for key in ["a", "b", "c", "d"] {
+            tree.insert(key.to_string(), format!("fid-{}", key));
+        }
+        let bytes = tree.to_bytes();
+        assert!(AccumulatorTree::from_bytes(&bytes).is_ok());
+        assert!(AccumulatorTree::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(AccumulatorTree::from_bytes(&bytes[1..]).is_err());
+
+        // A changed FID no longer hashes to the stored parent hash
+        let at = bytes
+            .windows(5)
+            .position(|w| w == b"fid-b")
+            .expect("fid in snapshot");
+        let mut corrupt = bytes.clone();
+        corrupt[at + 4] = b'x';
+        let err = AccumulatorTree::from_bytes(&corrupt).err().unwrap();
+        assert!(
+            matches!(&err, crate::AccTreeError::Malformed(m) if m.contains("hash mismatch")),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_tree_snapshot_shares_nodes() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for i in 0..8 {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        let trusted = tree.publish_epoch().digest();
+        let snapshot = tree.snapshot();
+        let reader = {
+            let snapshot = snapshot.clone();
+            std::thread::spawn(move || snapshot.range_with_proof("k2", "k6").unwrap())
+        };
+
+        // Writes after the snapshot copy only the path to "k0"
+        tree.insert("k0".to_string(), "g".to_string());
+        tree.delete("k7", "f7");
+        tree.insert("k8".to_string(), "f8".to_string());
+        let next = tree.publish_epoch().digest();
+        let root = tree.roots.iter().find(|r| r.level() == 3).unwrap();
+        let (Node::NonLeaf { left: old, .. }, Node::NonLeaf { left: new, .. }) =
+            (&*snapshot.roots[0], &**root)
+        else {
+            panic!("expected a level-3 root");
+        };
+        let (Node::NonLeaf { right: old, .. }, Node::NonLeaf { right: new, .. }) = (&**old, &**new)
+        else {
+            panic!("expected level-2 children");
+        };
+        assert!(Arc::ptr_eq(old, new));
+        assert!(!Arc::ptr_eq(&snapshot.roots[0], root));
+
+        // The snapshot still answers for the epoch it captured
+        assert!(reader.join().unwrap().verify(&trusted));
+        assert_eq!(
+            snapshot.select("k0"),
+            Some(Set::from_vec(vec!["f0".to_string()]))
+        );
+        assert!(snapshot.select("k8").is_none());
+        let response = snapshot.range_with_proof("k0", "k9").unwrap();
+        assert!(response.verify(&trusted) && !response.verify(&next));
+        assert_eq!(response.entries.len(), 8);
+        assert!(tree.range_with_proof("k0", "k9").unwrap().verify(&next));
+    }
+}
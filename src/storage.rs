@@ -0,0 +1,376 @@
+//! Object storage for parameter files, tree snapshots and archived proofs.
+//!
+//! `ObjectStore` is a flat key/blob interface in the shape of S3-style object
+//! stores: keys are `/`-separated paths and objects are written and read whole.
+//! `FsStore` maps keys onto a directory and `MemoryStore` keeps objects in
+//! memory; a remote backend only has to implement the four trait methods.
+//! With the `sled-store` feature, `SledStore` keeps objects in an embedded
+//! on-disk database; with the `s3` feature, `S3Store` keeps them in an S3
+//! bucket.
+
+use crate::archive::ProofArchive;
+use crate::canonical::CanonicalEncode;
+use accumulator_ads::acc::setup::PublicParameters;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "s3")]
+pub use s3::{S3Config, S3Store};
+
+/// Flat key/blob store.
+pub trait ObjectStore {
+    /// Write `bytes` under `key`, replacing any existing object.
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String>;
+    /// Read the object under `key`, or `None` if there is none.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String>;
+    /// Keys starting with `prefix`, in lexicographic order.
+    fn list(&self, prefix: &str) -> Result<Vec<String>, String>;
+    /// Remove the object under `key` (no-op if absent).
+    fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// Reject keys that are empty, absolute or escape the store root.
+pub fn check_object_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.starts_with('/') || key.ends_with('/') {
+        return Err(format!("invalid object key '{}'", key));
+    }
+    if key
+        .split('/')
+        .any(|part| part.is_empty() || part == "." || part == "..")
+    {
+        return Err(format!("invalid object key '{}'", key));
+    }
+    Ok(())
+}
+
+/// Objects stored as files under a root directory.
+#[derive(Debug, Clone)]
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, String> {
+        check_object_key(key)?;
+        Ok(self.root.join(key))
+    }
+
+    fn collect_keys(&self, dir: &std::path::Path, out: &mut Vec<String>) -> Result<(), String> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("read {:?}: {}", dir, e)),
+        };
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.is_dir() {
+                self.collect_keys(&path, out)?;
+            } else if let Ok(rel) = path.strip_prefix(&self.root) {
+                let parts: Vec<String> = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                out.push(parts.join("/"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ObjectStore for FsStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("create {:?}: {}", parent, e))?;
+        }
+        // Write to a temporary file first so readers never see a partial object
+        let tmp = path.with_extension("partial");
+        std::fs::write(&tmp, bytes).map_err(|e| format!("write {:?}: {}", tmp, e))?;
+        std::fs::rename(&tmp, &path).map_err(|e| format!("rename {:?}: {}", path, e))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let path = self.path(key)?;
+        match std::fs::read(&path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("read {:?}: {}", path, e)),
+        }
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let mut keys = Vec::new();
+        self.collect_keys(&self.root, &mut keys)?;
+        keys.retain(|k| k.starts_with(prefix));
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        let path = self.path(key)?;
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("delete {:?}: {}", path, e)),
+        }
+    }
+}
+
+/// Objects kept in memory; useful for tests and as a write-through cache.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    objects: RwLock<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ObjectStore for MemoryStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        check_object_key(key)?;
+        self.objects
+            .write()
+            .unwrap()
+            .insert(key.to_string(), bytes.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        check_object_key(key)?;
+        Ok(self.objects.read().unwrap().get(key).cloned())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        Ok(self
+            .objects
+            .read()
+            .unwrap()
+            .range(prefix.to_string()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, _)| k.clone())
+            .collect())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        check_object_key(key)?;
+        self.objects.write().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// Objects kept in an embedded sled database on disk.
+#[cfg(feature = "sled-store")]
+#[derive(Debug, Clone)]
+pub struct SledStore {
+    db: sled::Db,
+}
+
+#[cfg(feature = "sled-store")]
+impl SledStore {
+    /// Open (or create) the database at `path`.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let db = sled::open(path).map_err(|e| format!("open {:?}: {}", path, e))?;
+        Ok(Self { db })
+    }
+
+    /// Write buffered updates to disk.
+    pub fn flush(&self) -> Result<(), String> {
+        self.db.flush().map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(feature = "sled-store")]
+impl ObjectStore for SledStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        check_object_key(key)?;
+        self.db
+            .insert(key, bytes)
+            .map(|_| ())
+            .map_err(|e| format!("put '{}': {}", key, e))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        check_object_key(key)?;
+        self.db
+            .get(key)
+            .map(|v| v.map(|v| v.to_vec()))
+            .map_err(|e| format!("get '{}': {}", key, e))
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        self.db
+            .scan_prefix(prefix)
+            .keys()
+            .map(|k| {
+                let k = k.map_err(|e| e.to_string())?;
+                String::from_utf8(k.to_vec()).map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        check_object_key(key)?;
+        self.db
+            .remove(key)
+            .map(|_| ())
+            .map_err(|e| format!("delete '{}': {}", key, e))
+    }
+}
+
+/// Store public parameters under `key`.
+pub fn put_parameters(
+    store: &dyn ObjectStore,
+    key: &str,
+    params: &PublicParameters,
+) -> Result<(), String> {
+    let mut bytes = Vec::new();
+    params
+        .serialize_unchecked(&mut bytes)
+        .map_err(|e| format!("serialize parameters: {}", e))?;
+    store.put(key, &bytes)
+}
+
+/// Load public parameters stored under `key`, validating every point.
+pub fn get_parameters(
+    store: &dyn ObjectStore,
+    key: &str,
+) -> Result<Option<PublicParameters>, String> {
+    let Some(bytes) = store.get(key)? else {
+        return Ok(None);
+    };
+    let params = PublicParameters::deserialize_unchecked(&bytes[..])
+        .map_err(|e| format!("deserialize parameters '{}': {}", key, e))?;
+    params
+        .validate()
+        .map_err(|e| format!("parameters '{}': {}", key, e))?;
+    Ok(Some(params))
+}
+
+/// Store a snapshot of `tree` under `key` (see `AccumulatorTree::to_bytes`).
+pub fn put_tree(
+    store: &dyn ObjectStore,
+    key: &str,
+    tree: &crate::AccumulatorTree,
+) -> Result<(), String> {
+    store.put(key, &tree.to_bytes())
+}
+
+/// Load the tree snapshot stored under `key`.
+pub fn get_tree(
+    store: &dyn ObjectStore,
+    key: &str,
+) -> Result<Option<crate::AccumulatorTree>, String> {
+    let Some(bytes) = store.get(key)? else {
+        return Ok(None);
+    };
+    crate::AccumulatorTree::from_bytes(&bytes)
+        .map(Some)
+        .map_err(|e| format!("tree '{}': {}", key, e))
+}
+
+impl<V: CanonicalEncode + Clone> ProofArchive<V> {
+    /// Write every unexpired response of `epoch` to `store` as
+    /// `<prefix>/<epoch>/<query hash hex>`, holding its canonical encoding.
+    /// Returns the number of objects written.
+    pub fn export_epoch(
+        &self,
+        epoch: u64,
+        store: &dyn ObjectStore,
+        prefix: &str,
+    ) -> Result<usize, String> {
+        let entries = self.epoch_entries(epoch);
+        for (key, value) in &entries {
+            let object = format!("{}/{}/{}", prefix, epoch, hex::encode(key.query_hash));
+            store.put(&object, &value.canonical_bytes())?;
+        }
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{ArchiveConfig, ArchiveKey, point_query_hash};
+    use crate::merkle_proof::Proof;
+
+    #[test]
+    fn test_object_keys_and_stores() {
+        assert!(check_object_key("audit/1/abc").is_ok());
+        for bad in ["", "/abs", "dir/", "a//b", "a/../b"] {
+            assert!(check_object_key(bad).is_err(), "{}", bad);
+        }
+
+        let dir = std::env::temp_dir().join(format!("acc-tree-store-{}", std::process::id()));
+        let fs = FsStore::new(&dir);
+        let mem = MemoryStore::new();
+        for store in [&fs as &dyn ObjectStore, &mem] {
+            store.put("p/b", b"2").unwrap();
+            store.put("p/a", b"1").unwrap();
+            store.put("q", b"3").unwrap();
+            assert_eq!(store.get("p/a").unwrap(), Some(b"1".to_vec()));
+            assert_eq!(store.list("p/").unwrap(), vec!["p/a", "p/b"]);
+            store.delete("p/a").unwrap();
+            assert_eq!(store.get("p/a").unwrap(), None);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "sled-store")]
+    #[test]
+    fn test_sled_store() {
+        let dir = std::env::temp_dir().join(format!("acc-tree-sled-{}", std::process::id()));
+        let store = SledStore::open(&dir).unwrap();
+        store.put("p/b", b"2").unwrap();
+        store.put("p/a", b"1").unwrap();
+        store.put("q", b"3").unwrap();
+        assert_eq!(store.list("p/").unwrap(), vec!["p/a", "p/b"]);
+        store.delete("p/a").unwrap();
+        assert_eq!(store.get("p/a").unwrap(), None);
+        assert!(store.put("../x", b"").is_err());
+        store.flush().unwrap();
+        drop(store);
+        // sled's background flusher holds the file lock for a moment after
+        // the last handle is dropped
+        let reopened = (0..50)
+            .find_map(|_| {
+                SledStore::open(&dir)
+                    .inspect_err(|_| std::thread::sleep(std::time::Duration::from_millis(20)))
+                    .ok()
+            })
+            .unwrap();
+        assert_eq!(reopened.get("q").unwrap(), Some(b"3".to_vec()));
+        drop(reopened);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parameters_and_archive_roundtrip() {
+        use ark_bls12_381::Fr;
+
+        let store = MemoryStore::new();
+        let params = PublicParameters::generate_for_testing(Fr::from(7u64), 3);
+        put_parameters(&store, "params/test.bin", &params).unwrap();
+        let loaded = get_parameters(&store, "params/test.bin").unwrap().unwrap();
+        assert_eq!(loaded.g1_s_vec, params.g1_s_vec);
+        assert!(get_parameters(&store, "params/missing").unwrap().is_none());
+
+        let mut archive = ProofArchive::new(ArchiveConfig::default());
+        let proof = Proof::new([1u8; 32], [1u8; 32], vec![]);
+        archive.insert(ArchiveKey::new(2, point_query_hash("a")), proof.clone());
+        assert_eq!(archive.export_epoch(2, &store, "audit").unwrap(), 1);
+        let object = format!("audit/2/{}", hex::encode(point_query_hash("a")));
+        assert_eq!(store.get(&object).unwrap(), Some(proof.canonical_bytes()));
+    }
+}
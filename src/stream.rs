@@ -0,0 +1,186 @@
+//! Streaming proof output.
+//!
+//! Large exports (audits, range dumps) should not hold every proof in memory
+//! before writing. `ProofWriter` writes one record per proof as soon as it is
+//! generated, either as NDJSON or CSV. Writes go through a bounded buffer
+//! straight into the sink, so a slow consumer (pipe, socket, disk) blocks the
+//! producer instead of letting generated proofs pile up.
+
+use crate::AccumulatorTree;
+use crate::canonical::CanonicalEncode;
+use std::io::{self, BufWriter, Write};
+
+/// Default size of the write buffer in front of the sink.
+pub const DEFAULT_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Record format of a proof stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// One JSON object per line: `{"key":..,"epoch":..,"digest":..,"proof":..}`
+    Ndjson,
+    /// `key,epoch,digest,proof` with a header row
+    Csv,
+}
+
+/// Writes proofs to a sink one record at a time.
+///
+/// Each record carries the key, the epoch the proof was generated in, the
+/// SHA-256 digest of the proof's canonical encoding and the encoding itself,
+/// all hex-encoded except the key and epoch.
+pub struct ProofWriter<W: Write> {
+    out: BufWriter<W>,
+    format: StreamFormat,
+    records: u64,
+}
+
+impl<W: Write> ProofWriter<W> {
+    pub fn new(sink: W, format: StreamFormat) -> io::Result<Self> {
+        Self::with_capacity(DEFAULT_BUFFER_BYTES, sink, format)
+    }
+
+    /// Like `new`, buffering at most `capacity` bytes before writing to `sink`.
+    pub fn with_capacity(capacity: usize, sink: W, format: StreamFormat) -> io::Result<Self> {
+        let mut out = BufWriter::with_capacity(capacity, sink);
+        if format == StreamFormat::Csv {
+            out.write_all(b"key,epoch,digest,proof\n")?;
+        }
+        Ok(Self {
+            out,
+            format,
+            records: 0,
+        })
+    }
+
+    /// Number of records written so far.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Write the record for one proof.
+    pub fn write_proof<V: CanonicalEncode>(
+        &mut self,
+        key: &str,
+        epoch: u64,
+        proof: &V,
+    ) -> io::Result<()> {
+        let bytes = proof.canonical_bytes();
+        let digest = hex::encode(proof.canonical_digest());
+        let proof = hex::encode(bytes);
+        match self.format {
+            StreamFormat::Ndjson => writeln!(
+                self.out,
+                "{{\"key\":{},\"epoch\":{},\"digest\":\"{}\",\"proof\":\"{}\"}}",
+                json_string(key),
+                epoch,
+                digest,
+                proof
+            )?,
+            StreamFormat::Csv => writeln!(
+                self.out,
+                "{},{},{},{}",
+                csv_field(key),
+                epoch,
+                digest,
+                proof
+            )?,
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Flush buffered records and return the sink.
+    pub fn finish(self) -> io::Result<W> {
+        self.out.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+/// Generate a point proof for each key and stream it to `writer` as it is
+/// produced. Returns the number of records written; a proof that cannot be
+/// generated stops the stream with an `io::Error` wrapping the `AccTreeError`.
+pub fn stream_point_proofs<W, I, K>(
+    tree: &AccumulatorTree,
+    keys: I,
+    writer: &mut ProofWriter<W>,
+) -> io::Result<u64>
+where
+    W: Write,
+    I: IntoIterator<Item = K>,
+    K: AsRef<str>,
+{
+    let epoch = tree.epoch();
+    let start = writer.records();
+    for key in keys {
+        let key = key.as_ref();
+        let response = tree.select_with_proof(key).map_err(io::Error::other)?;
+        writer.write_proof(key, epoch, &response)?;
+    }
+    Ok(writer.records() - start)
+}
+
+/// Quote and escape `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_proof::Proof;
+    use crate::utils::leaf_hash;
+    use accumulator_ads::Set;
+
+    fn proof_for(key: &str) -> Proof {
+        let leaf = leaf_hash(key, &Set::from_vec(vec!["f".to_string()]), 0, false);
+        Proof::new(leaf, leaf, vec![])
+    }
+
+    #[test]
+    fn test_ndjson_records() {
+        let mut writer = ProofWriter::new(Vec::new(), StreamFormat::Ndjson).unwrap();
+        writer.write_proof("a", 3, &proof_for("a")).unwrap();
+        writer.write_proof("q\"uote", 3, &proof_for("b")).unwrap();
+        assert_eq!(writer.records(), 2);
+
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"key\":\"a\",\"epoch\":3,\"digest\":\""));
+        assert!(lines[0].contains(&hex::encode(proof_for("a").canonical_digest())));
+        assert!(lines[1].starts_with("{\"key\":\"q\\\"uote\","));
+    }
+
+    #[test]
+    fn test_csv_flushes_through_small_buffer() {
+        // A buffer smaller than one record forces every write through to the sink
+        let mut writer = ProofWriter::with_capacity(8, Vec::new(), StreamFormat::Csv).unwrap();
+        writer.write_proof("a,b", 1, &proof_for("a")).unwrap();
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some("key,epoch,digest,proof"));
+        assert!(lines.next().unwrap().starts_with("\"a,b\",1,"));
+        assert_eq!(lines.next(), None);
+    }
+}
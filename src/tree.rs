@@ -1,9 +1,125 @@
-use crate::utils::Hash;
+use crate::epoch::{EpochCommitment, PublishedEpoch};
+use crate::error::AccTreeError;
 use crate::node::Node;
+use crate::utils::Hash;
 use accumulator_ads::Set;
+use std::collections::HashMap;
+use std::sync::Arc;
 
+/// Forest of perfect Merkle trees over `key -> FID set` leaves, each internal
+/// node also accumulating the keys below it.
+///
+/// The server mutates the tree and answers queries with proofs; a client that
+/// trusts an epoch digest checks them without holding the tree. Proofs and
+/// the root manifest travel as framed wire messages (`to_wire`/`from_wire`).
+///
+/// ```
+/// use accumulator_tree::prelude::*;
+/// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+///
+/// // Server
+/// let mut tree = AccumulatorTree::new();
+/// tree.insert("alice".to_string(), "doc1".to_string());
+/// tree.insert("bob".to_string(), "doc2".to_string());
+/// let trusted = tree.publish_epoch().digest();
+/// let manifest = to_wire(&tree.root_manifest().unwrap());
+/// let answer = to_wire(&tree.select_with_proof("alice").unwrap());
+///
+/// // Client, pinned to `trusted`
+/// let manifest: RootManifest = from_wire(&manifest).unwrap();
+/// assert!(manifest.verify(&trusted));
+/// let response: QueryResponse = from_wire(&answer).unwrap();
+/// assert!(response.verify_by_mode("alice"));
+/// assert!(manifest.has_root(&response.root_hash().unwrap()));
+/// assert!(response.fids.unwrap().contains(&"doc1".to_string()));
+/// ```
 pub struct AccumulatorTree {
-    pub roots: Vec<Box<Node>>,
+    pub(crate) roots: Vec<Arc<Node>>,
+    /// Number of epochs published so far
+    epoch: u64,
+    /// Commitment and key set of the last published epoch
+    published: Option<Arc<PublishedEpoch>>,
+    /// Epoch in which each currently tombstoned key was deleted
+    deleted_at: Arc<HashMap<String, u64>>,
+    /// Mutations staged for the next `publish`, not yet visible to readers
+    staged: Vec<crate::txn::TxnOp>,
+    /// Hooks handed every commitment produced by `publish`
+    anchors: Vec<Box<dyn crate::anchor::Anchor>>,
+    /// Feeds handed an `EpochDelta` by every `publish`
+    watchers: Vec<std::sync::mpsc::Sender<crate::watch::EpochDelta>>,
+    /// How inserts treat keys that already have a leaf
+    revive_policy: crate::policy::RevivePolicy,
+    /// Key order of range queries, fixed at construction
+    order: crate::policy::OrderPolicy,
+    /// Whether roots keep their leaves sorted by `order`, fixed at construction
+    leaf_order: crate::policy::LeafOrder,
+    /// Time source for publication times
+    clock: std::sync::Arc<dyn crate::clock::Clock>,
+    /// Hook handed the `ProofStats` of every proof-carrying response
+    metrics: Option<Arc<dyn crate::metrics::ProofMetrics>>,
+    /// Last published epochs kept for `prove_key_transition`, oldest first
+    history: std::collections::VecDeque<crate::history::EpochRecord>,
+    /// Number of published epochs to keep in `history`
+    history_depth: usize,
+    /// Tombstone ratio at which `delete` compacts the forest, if any
+    compaction_threshold: Option<f64>,
+    /// Root of every key with a leaf (see `key_index`)
+    key_index: Arc<crate::key_index::KeyIndex>,
+    /// Accumulator over every live key once computed, kept current by
+    /// inserts and deletes (see `global_acc`)
+    global_acc: std::sync::OnceLock<accumulator_ads::G1Affine>,
+    /// Holder of the trapdoor, if any, for O(1) updates of `global_acc`
+    manager: Option<Arc<accumulator_ads::AccumulatorManager>>,
+}
+
+/// Summary of one root of the forest, from `AccumulatorTree::roots_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootInfo {
+    /// Position in forest order
+    pub index: usize,
+    /// Height of the root; it has `2^level` leaves
+    pub level: usize,
+    pub hash: Hash,
+    /// Accumulator over the root's key set
+    pub acc: accumulator_ads::G1Affine,
+    pub live_keys: u64,
+    /// Tombstoned leaves still in the root
+    pub tombstones: u64,
+}
+
+/// Live `(key, fids)` entries of a tree, from `AccumulatorTree::iter`.
+pub struct Iter<'a> {
+    /// Remaining leaves of each root, last root first so that exhausted
+    /// roots pop off the end
+    roots: Vec<std::iter::Peekable<crate::node::Leaves<'a>>>,
+    /// Set when every root is sorted, to merge them in key order
+    order: Option<crate::policy::OrderPolicy>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a Set<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some(order) = self.order else {
+            while let Some(current) = self.roots.last_mut() {
+                if let Some(entry) = current.next() {
+                    return Some(entry);
+                }
+                self.roots.pop();
+            }
+            return None;
+        };
+        // Keys are unique across roots, so the smallest head comes next
+        let mut next: Option<(usize, &str)> = None;
+        for (i, root) in self.roots.iter_mut().enumerate() {
+            if let Some(&(key, _)) = root.peek()
+                && next.is_none_or(|(_, best)| order.compare(key, best) == std::cmp::Ordering::Less)
+            {
+                next = Some((i, key));
+            }
+        }
+        self.roots[next?.0].next()
+    }
 }
 
 impl Default for AccumulatorTree {
@@ -14,141 +130,1598 @@ impl Default for AccumulatorTree {
 
 impl AccumulatorTree {
     pub fn new() -> Self {
-        Self { roots: Vec::new() }
+        Self {
+            roots: Vec::new(),
+            epoch: 0,
+            published: None,
+            deleted_at: Arc::default(),
+            staged: Vec::new(),
+            anchors: Vec::new(),
+            watchers: Vec::new(),
+            revive_policy: crate::policy::RevivePolicy::default(),
+            order: crate::policy::OrderPolicy::default(),
+            leaf_order: crate::policy::LeafOrder::default(),
+            clock: crate::clock::system_clock(),
+            metrics: None,
+            history: std::collections::VecDeque::new(),
+            history_depth: 0,
+            compaction_threshold: None,
+            key_index: Arc::default(),
+            global_acc: std::sync::OnceLock::new(),
+            manager: None,
+        }
+    }
+
+    /// Create an empty tree whose range queries follow `order`. The order is
+    /// recorded in every published commitment and cannot be changed later.
+    pub fn with_order(order: crate::policy::OrderPolicy) -> Self {
+        Self {
+            order,
+            ..Self::new()
+        }
+    }
+
+    pub fn order(&self) -> crate::policy::OrderPolicy {
+        self.order
+    }
+
+    /// Create an empty tree whose roots keep their leaves sorted by `order`,
+    /// so `prove_gap` can show a key is absent from neighbouring leaves.
+    /// Inserts that merge overlapping roots rebuild the merged root.
+    pub fn with_sorted_leaves(order: crate::policy::OrderPolicy) -> Self {
+        Self {
+            order,
+            leaf_order: crate::policy::LeafOrder::Sorted,
+            ..Self::new()
+        }
+    }
+
+    pub fn leaf_order(&self) -> crate::policy::LeafOrder {
+        self.leaf_order
+    }
+
+    /// The order merges keep leaves in, if the tree sorts them.
+    pub(crate) fn sorted_by(&self) -> Option<crate::policy::OrderPolicy> {
+        (self.leaf_order == crate::policy::LeafOrder::Sorted).then_some(self.order)
+    }
+
+    /// Whether every root's leaves, tombstones included, are strictly sorted
+    /// by the tree's order. Holds for every tree built with
+    /// `with_sorted_leaves`; an auditor holding a snapshot can check it.
+    pub fn leaves_sorted(&self) -> bool {
+        self.roots.iter().all(|root| {
+            let mut leaves = Vec::new();
+            crate::node::push_leaves(root, &mut leaves);
+            leaves.windows(2).all(|w| {
+                self.order
+                    .compare(w[0].edge_key(false), w[1].edge_key(false))
+                    == std::cmp::Ordering::Less
+            })
+        })
+    }
+
+    /// Create an empty tree after checking that the initialized public parameters
+    /// can accumulate `estimated_keys` keys with the given features enabled.
+    /// See `setup::required_setup_degree` for how the degree is derived.
+    pub fn with_expected_keys(
+        estimated_keys: usize,
+        features: crate::setup::SetupFeatures,
+    ) -> Result<Self, AccTreeError> {
+        crate::setup::check_setup_degree(estimated_keys, features)?;
+        Ok(Self::new())
+    }
+
+    /// Build a tree from a key dump on `threads` worker threads (0 uses one per
+    /// core). The result is the forest that inserting every fid of every entry
+    /// in order would produce: repeated keys keep their first position and get
+    /// the union of their FIDs. Each root is split into partitions that are
+    /// built, hashed and accumulated concurrently, then joined upwards.
+    /// Like `insert`, accumulating a root of n keys needs parameters of
+    /// degree n. Without the `parallel` feature the build runs on the calling
+    /// thread and `threads` is ignored.
+    pub fn build_parallel(
+        entries: Vec<(String, Set<String>)>,
+        threads: usize,
+    ) -> Result<Self, AccTreeError> {
+        let mut position: HashMap<String, usize> = HashMap::new();
+        let mut leaves: Vec<(String, Set<String>)> = Vec::with_capacity(entries.len());
+        for (key, fids) in entries {
+            if fids.is_empty() {
+                return Err(AccTreeError::InvalidInput(format!(
+                    "key {} has no FIDs",
+                    key
+                )));
+            }
+            match position.get(&key) {
+                Some(&i) => leaves[i].1 = leaves[i].1.union(&fids),
+                None => {
+                    position.insert(key.clone(), leaves.len());
+                    leaves.push((key, fids));
+                }
+            }
+        }
+        drop(position);
+
+        #[cfg(feature = "parallel")]
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| AccTreeError::InvalidInput(format!("thread pool: {}", e)))?;
+        #[cfg(feature = "parallel")]
+        let workers = pool.current_num_threads().max(1);
+        #[cfg(not(feature = "parallel"))]
+        let workers = {
+            let _ = threads;
+            1
+        };
+        // A few partitions per worker keeps threads busy when roots are uneven
+        let grain = (leaves.len() / (workers * 4)).max(1);
+
+        // Leaves do not end up in insertion order, so ask `normalize` where
+        // each one goes
+        let mut slots: Vec<Option<(String, Set<String>)>> = leaves.into_iter().map(Some).collect();
+        let chunks: Vec<Vec<(String, Set<String>)>> = replay_normalize(&vec![0; slots.len()], 0)
+            .into_iter()
+            .map(|shape| {
+                shape
+                    .units()
+                    .into_iter()
+                    .map(|i| slots[i].take().unwrap())
+                    .collect()
+            })
+            .collect();
+
+        let build = || -> Vec<crate::node::Subtree> {
+            #[cfg(feature = "parallel")]
+            use rayon::prelude::*;
+            ark_std::cfg_iter!(chunks)
+                .map(|chunk| crate::node::Subtree::build(chunk, grain))
+                .collect()
+        };
+        #[cfg(feature = "parallel")]
+        let subtrees = pool.install(build);
+        #[cfg(not(feature = "parallel"))]
+        let subtrees = build();
+
+        let mut tree = Self::new();
+        tree.roots = subtrees.into_iter().map(|s| s.into_node()).collect();
+        tree.key_index = Arc::new(crate::key_index::KeyIndex::build(&tree.roots));
+        Ok(tree)
+    }
+
+    /// Build a tree with sorted leaves (see `with_sorted_leaves`) from
+    /// `(key, fid)` pairs already in `order`, in one pass. Neighbouring
+    /// pairs with the same key share a leaf, and each node's hash and
+    /// accumulator are computed once, bottom up, instead of on every merge
+    /// as n inserts would. The forest is the one inserting the pairs and
+    /// compacting would leave. Fails if a key sorts before the one ahead of
+    /// it, or if the largest root needs a higher degree than the parameters
+    /// have.
+    ///
+    /// ```
+    /// use accumulator_tree::AccumulatorTree;
+    /// use accumulator_tree::policy::OrderPolicy;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let pairs = [("a", "doc1"), ("a", "doc2"), ("b", "doc3"), ("c", "doc4")];
+    /// let tree = AccumulatorTree::bulk_load(
+    ///     OrderPolicy::Natural,
+    ///     pairs.iter().map(|(k, f)| (k.to_string(), f.to_string())),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(tree.select("a").unwrap().len(), 2);
+    /// assert!(tree.leaves_sorted());
+    /// ```
+    pub fn bulk_load(
+        order: crate::policy::OrderPolicy,
+        sorted: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, AccTreeError> {
+        let mut leaves: Vec<(String, Set<String>)> = Vec::new();
+        for (key, fid) in sorted {
+            match leaves.last_mut() {
+                Some((last, fids)) if *last == key => {
+                    fids.insert(fid);
+                    continue;
+                }
+                Some((last, _)) if order.compare(last, &key) != std::cmp::Ordering::Less => {
+                    return Err(AccTreeError::InvalidInput(format!(
+                        "key {} sorts before {}",
+                        key, last
+                    )));
+                }
+                _ => leaves.push((key, Set::from_vec(vec![fid]))),
+            }
+        }
+        if let Some(largest) = leaves.len().checked_ilog2() {
+            crate::setup::check_available_degree(1 << largest)?;
+        }
+
+        let mut tree = Self::with_sorted_leaves(order);
+        tree.roots = build_roots(leaves, true);
+        tree.key_index = Arc::new(crate::key_index::KeyIndex::build(&tree.roots));
+        Ok(tree)
     }
 
     // ==========================================
     // Public API - Forest Management
     // ==========================================
 
+    /// Union of the live keys across all roots.
+    /// Read from the leaves: cached NonLeaf key sets are not updated on tombstoning.
+    fn all_keys(&self) -> Set<String> {
+        Set::from_vec(self.iter().map(|(key, _)| key.to_string()).collect())
+    }
+
+    /// Live entries, borrowed from the leaves. A tree with sorted leaves
+    /// yields them in its key order; others go root by root, in forest order.
+    ///
+    /// ```
+    /// use accumulator_tree::AccumulatorTree;
+    /// use accumulator_tree::policy::OrderPolicy;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::with_sorted_leaves(OrderPolicy::Natural);
+    /// for key in ["c", "a", "b"] {
+    ///     tree.insert(key.to_string(), format!("doc-{}", key));
+    /// }
+    /// let keys: Vec<&str> = tree.iter().map(|(key, _)| key).collect();
+    /// assert_eq!(keys, ["a", "b", "c"]);
+    /// ```
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            roots: self
+                .roots
+                .iter()
+                .rev()
+                .map(|root| root.iter_leaves().peekable())
+                .collect(),
+            order: self.sorted_by(),
+        }
+    }
+
+    /// Accumulator over every live key in the forest. Computed from the key
+    /// set on first use, then updated in O(1) per key an insert, revive or
+    /// delete adds or tombstones, as long as the tree has an
+    /// `AccumulatorManager` (see `set_manager`); otherwise, and after batch
+    /// operations, it is recomputed on next use.
+    ///
+    /// ```
+    /// use accumulator_ads::{DynamicAccumulator, Set, digest_set_from_set};
+    /// use accumulator_tree::AccumulatorTree;
+    /// use std::sync::Arc;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.set_manager(Arc::new(accumulator_tree::setup::insecure_manager()))
+    ///     .unwrap();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// tree.global_acc();
+    /// tree.insert("bob".to_string(), "doc2".to_string());
+    /// tree.insert("carol".to_string(), "doc3".to_string());
+    /// tree.delete("alice", "doc1");
+    ///
+    /// let keys = Set::from_vec(vec!["bob".to_string(), "carol".to_string()]);
+    /// let expected = DynamicAccumulator::calculate_commitment(&digest_set_from_set(&keys));
+    /// assert_eq!(tree.global_acc(), expected);
+    /// ```
+    pub fn global_acc(&self) -> accumulator_ads::G1Affine {
+        *self
+            .global_acc
+            .get_or_init(|| Self::global_accumulator(&self.all_keys()))
+    }
+
+    /// Fold keys that became live (`added`) or were tombstoned (`removed`)
+    /// into the cached global accumulator, or drop it if that takes a
+    /// recomputation anyway.
+    fn update_global_acc(&mut self, added: &[&str], removed: &[&str]) {
+        use crate::witness_cache::key_element;
+
+        let Some(mut acc) = self.global_acc.take() else {
+            return;
+        };
+        let Some(manager) = &self.manager else {
+            return;
+        };
+        for key in added {
+            acc = manager.add(acc, key_element(key));
+        }
+        for key in removed {
+            match manager.delete(acc, key_element(key)) {
+                Ok(next) => acc = next,
+                Err(_) => return,
+            }
+        }
+        let _ = self.global_acc.set(acc);
+    }
+
+    /// Drop the cached global accumulator after a batch change.
+    pub(crate) fn forget_global_acc(&mut self) {
+        self.global_acc.take();
+    }
+
+    /// Hashes of the roots, in forest order.
+    pub fn root_hashes(&self) -> Vec<Hash> {
+        self.roots.iter().map(|r| r.hash()).collect()
+    }
+
+    /// (level, hash) of the roots, in forest order; the pre-image of the forest hash.
+    pub fn root_entries(&self) -> Vec<crate::epoch::RootEntry> {
+        self.roots
+            .iter()
+            .map(|r| (r.level() as u64, r.hash()))
+            .collect()
+    }
+
+    /// Level, hash, accumulator and key counts of each root, in forest order.
+    ///
+    /// ```
+    /// use accumulator_tree::AccumulatorTree;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// for key in ["a", "b", "c"] {
+    ///     tree.insert(key.to_string(), format!("doc-{}", key));
+    /// }
+    /// tree.delete("b", "doc-b");
+    ///
+    /// let roots = tree.roots_info();
+    /// let levels: Vec<usize> = roots.iter().map(|r| r.level).collect();
+    /// assert_eq!(levels, vec![0, 1]);
+    /// assert_eq!((roots[1].live_keys, roots[1].tombstones), (1, 1));
+    /// assert_eq!(roots[0].hash, tree.root_hashes()[0]);
+    /// ```
+    pub fn roots_info(&self) -> Vec<RootInfo> {
+        self.roots
+            .iter()
+            .enumerate()
+            .map(|(index, r)| {
+                let live_keys = r.live_key_count();
+                RootInfo {
+                    index,
+                    level: r.level(),
+                    hash: r.hash(),
+                    acc: r.acc(),
+                    live_keys,
+                    tombstones: (1u64 << r.level()) - live_keys,
+                }
+            })
+            .collect()
+    }
+
+    /// The roots as `Peak`s, in forest order.
+    pub fn peaks(&self) -> Vec<crate::peaks::Peak> {
+        self.roots
+            .iter()
+            .map(|r| crate::peaks::Peak {
+                level: r.level() as u64,
+                hash: r.hash(),
+                acc: r.acc(),
+            })
+            .collect()
+    }
+
+    /// Single commitment to every root hash, level and accumulator (see
+    /// `peaks`). Accumulators are taken as they are: `refresh` first if
+    /// deletions are pending.
+    ///
+    /// ```
+    /// use accumulator_tree::AccumulatorTree;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// for key in ["alice", "bob", "carol"] {
+    ///     tree.insert(key.to_string(), "doc1".to_string());
+    /// }
+    /// let digest = tree.root_digest();
+    ///
+    /// let proof = tree.select_with_proof("carol").unwrap().merkle_proof.unwrap();
+    /// let peak = tree.peak_proof(&proof.root_hash).unwrap();
+    /// assert!(proof.verify_in_digest(&peak, &digest));
+    /// ```
+    pub fn root_digest(&self) -> Hash {
+        crate::peaks::bag_peaks(&self.peaks())
+    }
+
+    /// Proof that the root with hash `root_hash` is part of `root_digest`.
+    pub fn peak_proof(&self, root_hash: &Hash) -> Option<crate::peaks::PeakProof> {
+        let index = self.roots.iter().position(|r| r.hash() == *root_hash)?;
+        crate::peaks::PeakProof::new(&self.peaks(), index)
+    }
+
+    /// Merkle proof for all of `keys` at once, sharing the sibling hashes
+    /// their paths have in common. Keys without a live leaf are listed in
+    /// `missing`.
+    ///
+    /// ```
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    /// use accumulator_tree::prelude::*;
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// for key in ["alice", "bob", "carol", "dave"] {
+    ///     tree.insert(key.to_string(), format!("{}.doc", key));
+    /// }
+    /// let proof = tree.get_many_with_proof(&["alice", "carol", "erin"]);
+    ///
+    /// let proof: accumulator_tree::MultiProof = from_wire(&to_wire(&proof)).unwrap();
+    /// assert!(proof.verify());
+    /// assert_eq!(proof.fids("carol"), tree.select("carol").as_ref());
+    /// assert_eq!(proof.missing, vec!["erin".to_string()]);
+    /// ```
+    pub fn get_many_with_proof(&self, keys: &[&str]) -> crate::multiproof::MultiProof {
+        let mut groups: std::collections::BTreeMap<usize, Vec<&str>> = Default::default();
+        let mut missing = Vec::new();
+        for key in keys {
+            match self.root_index(key) {
+                Some(i) if self.roots[i].select(key).is_some() => {
+                    groups.entry(i).or_default().push(key)
+                }
+                _ => missing.push(key.to_string()),
+            }
+        }
+        missing.sort();
+        missing.dedup();
+        crate::multiproof::MultiProof {
+            roots: groups
+                .into_iter()
+                .map(|(i, keys)| crate::multiproof::RootMultiProof::new(&self.roots[i], &keys))
+                .collect(),
+            missing,
+        }
+    }
+
+    /// Show `key` has no live leaf by the leaves around it in every root.
+    /// Only trees built with `with_sorted_leaves` can; see `gap` for what the
+    /// proof assumes.
+    ///
+    /// ```
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    /// use accumulator_tree::OrderPolicy;
+    /// use accumulator_tree::prelude::*;
+    ///
+    /// let mut tree = AccumulatorTree::with_sorted_leaves(OrderPolicy::Lexicographic);
+    /// for key in ["dave", "alice", "erin", "bob", "carol"] {
+    ///     tree.insert(key.to_string(), format!("{}.doc", key));
+    /// }
+    /// let proof = tree.prove_gap("bert").unwrap();
+    ///
+    /// let proof: accumulator_tree::GapProof = from_wire(&to_wire(&proof)).unwrap();
+    /// assert!(proof.verify(&tree.root_entries(), &tree.order()));
+    /// assert!(tree.prove_gap("bob").is_err());
+    /// ```
+    pub fn prove_gap(&self, key: &str) -> Result<crate::gap::GapProof, AccTreeError> {
+        let order = self.sorted_by().ok_or_else(|| {
+            AccTreeError::InvalidInput("gap proofs need a tree with sorted leaves".to_string())
+        })?;
+        let roots = self
+            .roots
+            .iter()
+            .map(|root| crate::gap::RootGap::new(root, key, &order))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| AccTreeError::KeyLive(key.to_string()))?;
+        Ok(crate::gap::GapProof {
+            key: key.to_string(),
+            roots,
+        })
+    }
+
+    /// Accumulator over an arbitrary key set (empty accumulator for the empty set).
+    fn global_accumulator(keys: &Set<String>) -> accumulator_ads::G1Affine {
+        if keys.is_empty() {
+            crate::utils::empty_acc()
+        } else {
+            let digest_set = accumulator_ads::digest_set_from_set(keys);
+            accumulator_ads::DynamicAccumulator::calculate_commitment(&digest_set)
+        }
+    }
+
+    /// Recompute the key sets and accumulators that deletions marked stale.
+    /// Tombstoning only updates hashes on the way up, so a run of deletes pays
+    /// for each affected ancestor once, here. Returns the number of nodes
+    /// recomputed.
+    pub fn refresh(&mut self) -> usize {
+        #[cfg(feature = "parallel")]
+        use rayon::prelude::*;
+        ark_std::cfg_iter_mut!(self.roots)
+            .map(crate::node::refresh_shared)
+            .sum()
+    }
+
+    /// Rebuild the forest from its live leaves, dropping every tombstone, and
+    /// return the number dropped. The result is the forest inserting the live
+    /// keys in their current forest order (in key order, if leaves are
+    /// sorted) would produce, so roots shrink and every root accumulator and
+    /// witness changes: take a `snapshot` first and hand out `witness_delta`
+    /// afterwards. Compacted keys are forgotten, so `select_status` reports
+    /// them absent rather than tombstoned. A forest without tombstones is left
+    /// as is.
+    ///
+    /// ```
+    /// use accumulator_tree::AccumulatorTree;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// for i in 0..4 {
+    ///     tree.insert(format!("k{}", i), format!("f{}", i));
+    /// }
+    /// tree.delete("k1", "f1");
+    /// tree.delete("k2", "f2");
+    /// assert_eq!(tree.compact(), 2);
+    /// assert_eq!(tree.tombstone_count(), 0);
+    /// assert_eq!(tree.roots_info().iter().map(|r| r.level).collect::<Vec<_>>(), vec![1]);
+    /// assert!(tree.select("k3").is_some());
+    /// assert_eq!(tree.compact(), 0);
+    /// ```
+    pub fn compact(&mut self) -> usize {
+        let dropped = self.tombstone_count() as usize;
+        if dropped == 0 {
+            return 0;
+        }
+        let mut leaves: Vec<(String, Set<String>)> = self
+            .roots
+            .iter()
+            .flat_map(|r| r.collect_leaves(None))
+            .collect();
+        if let Some(order) = self.sorted_by() {
+            leaves.sort_by(|a, b| order.compare(&a.0, &b.0));
+        }
+        self.roots = build_roots(leaves, self.sorted_by().is_some());
+        self.key_index = Arc::new(crate::key_index::KeyIndex::build(&self.roots));
+        Arc::make_mut(&mut self.deleted_at).clear();
+        dropped
+    }
+
+    /// Number of tombstoned leaves across all roots.
+    pub fn tombstone_count(&self) -> u64 {
+        self.roots_info().iter().map(|r| r.tombstones).sum()
+    }
+
+    pub fn compaction_threshold(&self) -> Option<f64> {
+        self.compaction_threshold
+    }
+
+    /// Compact (see `compact`) whenever a `delete` leaves at least `ratio` of
+    /// the leaves tombstoned; `None` turns auto-compaction off. Deletions
+    /// made through `delete_with_proof` or a transaction never compact, since
+    /// their proofs refer to the tombstoned leaf.
+    pub fn set_compaction_threshold(&mut self, ratio: Option<f64>) {
+        self.compaction_threshold = ratio;
+    }
+
+    /// Compact if the tombstone ratio reached the configured threshold.
+    fn maybe_compact(&mut self) {
+        let Some(threshold) = self.compaction_threshold else {
+            return;
+        };
+        let leaves: u64 = self.roots.iter().map(|r| 1u64 << r.level()).sum();
+        if leaves > 0 && self.tombstone_count() as f64 >= threshold * leaves as f64 {
+            self.compact();
+        }
+    }
+
+    /// Whether some root's key set or accumulator awaits `refresh`.
+    pub fn has_stale_nodes(&self) -> bool {
+        self.roots.iter().any(|r| r.is_stale())
+    }
+
+    /// Publish a new epoch: snapshot the forest hash and the global accumulator
+    /// over all live keys. Non-membership proofs are answered against the last
+    /// published epoch and carry its commitment. Stale nodes are refreshed first.
+    pub fn publish_epoch(&mut self) -> EpochCommitment {
+        self.refresh();
+        let keys = self.all_keys();
+        let roots = self.root_entries();
+        self.epoch += 1;
+        let stamps = roots
+            .iter()
+            .map(|root| {
+                let unchanged = self
+                    .published
+                    .iter()
+                    .flat_map(|p| &p.roots)
+                    .find(|s| s.root == *root);
+                crate::epoch::RootStamp {
+                    root: *root,
+                    changed_in: unchanged.map_or(self.epoch, |s| s.changed_in),
+                }
+            })
+            .collect();
+        let commitment = EpochCommitment::new(
+            self.epoch,
+            crate::epoch::forest_hash(&roots),
+            self.global_acc(),
+            keys.len() as u64,
+        )
+        .with_key_order(self.order.id());
+        self.published = Some(Arc::new(PublishedEpoch {
+            commitment: commitment.clone(),
+            keys,
+            roots: stamps,
+            published_at: self.clock.now(),
+        }));
+        if self.history_depth > 0 {
+            self.history.push_back(crate::history::EpochRecord {
+                published: self.published.clone().unwrap(),
+                roots: self.roots.clone(),
+                ops: Vec::new(),
+            });
+            while self.history.len() > self.history_depth {
+                self.history.pop_front();
+            }
+        }
+        commitment
+    }
+
+    /// Queue a mutation for the next `publish`. Reads and proofs keep answering
+    /// against the current (published) roots until then.
+    pub fn stage(&mut self, op: crate::txn::TxnOp) {
+        self.staged.push(op);
+    }
+
+    /// Mutations queued for the next `publish`.
+    pub fn staged(&self) -> &[crate::txn::TxnOp] {
+        &self.staged
+    }
+
+    /// Drop every staged mutation.
+    pub fn discard_staged(&mut self) {
+        self.staged.clear();
+    }
+
+    /// Register a hook that anchors every epoch published through `publish`.
+    pub fn add_anchor(&mut self, anchor: Box<dyn crate::anchor::Anchor>) {
+        self.anchors.push(anchor);
+    }
+
+    /// Subscribe to published epochs: every later `publish` sends the
+    /// receiver an `EpochDelta` proving what changed (see `watch`). Dropping
+    /// the receiver unsubscribes it.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// use accumulator_tree::epoch::genesis_commitment;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// let feed = tree.watch();
+    /// tree.stage(TxnOp::Insert { key: "alice".to_string(), fid: "doc1".to_string() });
+    /// tree.publish();
+    ///
+    /// let delta = feed.try_recv().unwrap();
+    /// assert!(delta.verify(&genesis_commitment().digest()));
+    /// assert_eq!(delta.changed_keys(), vec!["alice"]);
+    /// ```
+    pub fn watch(&mut self) -> std::sync::mpsc::Receiver<crate::watch::EpochDelta> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.watchers.push(tx);
+        rx
+    }
+
+    /// Apply every staged mutation atomically and publish the resulting epoch.
+    /// Staged operations that no longer apply (e.g. deleting an absent FID) are
+    /// skipped and left out of the transition proof. Registered anchors are
+    /// invoked with the new commitment; an anchor failure does not undo the
+    /// publication and is reported in `receipts`. Receivers from `watch` get
+    /// the epoch's `EpochDelta`.
+    pub fn publish(&mut self) -> crate::epoch::EpochTransition {
+        let previous = self
+            .published_commitment()
+            .cloned()
+            .unwrap_or_else(crate::epoch::genesis_commitment);
+        let staged = std::mem::take(&mut self.staged);
+        let mut txn = self.begin_transaction();
+        for op in &staged {
+            txn.apply_op(op);
+        }
+        let proof = txn.commit_with_proof();
+        let commitment = self.publish_epoch();
+        if let Some(record) = self.history.back_mut() {
+            record.ops = proof.ops.clone();
+        }
+        let receipts = self.anchors.iter().map(|a| a.anchor(&commitment)).collect();
+        if !self.watchers.is_empty() {
+            let delta = crate::watch::EpochDelta {
+                epoch: commitment.epoch,
+                changed_keys_proof: proof.clone(),
+                new_commitment: commitment.clone(),
+                consistency_proof: crate::watch::ConsistencyProof {
+                    previous,
+                    roots: self
+                        .published
+                        .as_ref()
+                        .map_or_else(Vec::new, |p| p.roots.clone()),
+                },
+            };
+            self.watchers.retain(|tx| tx.send(delta.clone()).is_ok());
+        }
+        crate::epoch::EpochTransition {
+            commitment,
+            proof,
+            receipts,
+        }
+    }
+
+    /// Whether the roots changed (through direct mutations) since the last
+    /// published epoch. Always true before the first publication.
+    pub fn has_unpublished_changes(&self) -> bool {
+        match &self.published {
+            Some(p) => crate::epoch::forest_hash(&self.root_entries()) != p.commitment.forest_hash,
+            None => true,
+        }
+    }
+
+    /// Number of the last published epoch (0 before the first publication).
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Commitment of the last published epoch, if any.
+    pub fn published_commitment(&self) -> Option<&EpochCommitment> {
+        self.published.as_ref().map(|p| &p.commitment)
+    }
+
+    /// When the last epoch was published, by the tree's clock.
+    pub fn published_at(&self) -> Option<std::time::SystemTime> {
+        self.published.as_ref().map(|p| p.published_at)
+    }
+
+    /// Current time by the tree's clock.
+    pub(crate) fn now(&self) -> std::time::SystemTime {
+        self.clock.now()
+    }
+
+    /// Use `clock` for publication times instead of the system clock.
+    pub fn set_clock(&mut self, clock: std::sync::Arc<dyn crate::clock::Clock>) {
+        self.clock = clock;
+    }
+
+    /// Update the global accumulator with `manager`'s trapdoor instead of
+    /// recomputing it after every insert or delete. Refused unless the
+    /// loaded public parameters are the powers of that trapdoor.
+    ///
+    /// ```
+    /// use accumulator_ads::{AccumulatorManager, Fr};
+    /// use accumulator_tree::{AccTreeError, AccumulatorTree};
+    /// use std::sync::Arc;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// let stranger = Arc::new(AccumulatorManager::new(Fr::from(7u64)).unwrap());
+    /// assert!(matches!(tree.set_manager(stranger), Err(AccTreeError::InvalidInput(_))));
+    /// assert!(tree.set_manager(Arc::new(accumulator_tree::setup::insecure_manager())).is_ok());
+    /// ```
+    pub fn set_manager(
+        &mut self,
+        manager: Arc<accumulator_ads::AccumulatorManager>,
+    ) -> Result<(), AccTreeError> {
+        let params = accumulator_ads::get_public_parameters()
+            .map_err(|_| AccTreeError::ParamsNotInitialized)?;
+        if !manager.matches_parameters(&params) {
+            return Err(AccTreeError::InvalidInput(
+                "trapdoor does not match the public parameters".to_string(),
+            ));
+        }
+        self.manager = Some(manager);
+        Ok(())
+    }
+
+    /// Hand the `ProofStats` of every proof-carrying response to `metrics`.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn crate::metrics::ProofMetrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Attach `ProofStats` to a response whose construction began at
+    /// `started`, and report them to the metrics hook.
+    fn instrument<R: crate::metrics::Instrumented>(
+        &self,
+        op: crate::metrics::ProofOp,
+        started: std::time::Instant,
+        mut response: R,
+    ) -> R {
+        let prover_time = started.elapsed();
+        let (merkle_bytes, witness_bytes) = response.proof_sizes();
+        let stats = crate::metrics::ProofStats {
+            merkle_bytes,
+            witness_bytes,
+            prover_time,
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.record(op, &stats);
+        }
+        response.set_stats(stats);
+        response
+    }
+
+    /// Roots of the last published epoch with the epoch each last changed in.
+    pub fn root_manifest(&self) -> Option<crate::epoch::RootManifest> {
+        self.published.as_ref().map(|p| crate::epoch::RootManifest {
+            commitment: p.commitment.clone(),
+            roots: p.roots.clone(),
+        })
+    }
+
+    /// Serialize the whole forest, including cached accumulators, the last
+    /// published epoch and staged operations. Anchors, the clock and
+    /// retained history are not written.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        use crate::canonical::CanonicalEncode;
+        let mut out = crate::snapshot::SNAPSHOT_MAGIC.to_vec();
+        crate::snapshot::SNAPSHOT_VERSION.encode_canonical(&mut out);
+        self.roots.encode_canonical(&mut out);
+        self.epoch.encode_canonical(&mut out);
+        self.published.encode_canonical(&mut out);
+        let mut deleted_at: Vec<(String, u64)> = self
+            .deleted_at
+            .iter()
+            .map(|(k, e)| (k.clone(), *e))
+            .collect();
+        deleted_at.sort_unstable();
+        deleted_at.encode_canonical(&mut out);
+        self.staged.encode_canonical(&mut out);
+        out.push(self.revive_policy.tag());
+        self.order.id().encode_canonical(&mut out);
+        out.push(self.leaf_order.tag());
+        out
+    }
+
+    /// Load a forest written by `to_bytes`. Node hashes are checked against
+    /// their children; anchors, a custom clock and history retention must be
+    /// set again. A tree with a custom key order is loaded with
+    /// `from_bytes_with_order`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AccTreeError> {
+        Self::decode_snapshot(bytes, None).map_err(AccTreeError::Malformed)
+    }
+
+    /// Load a forest written by `to_bytes` from a tree ordered by `order`.
+    pub fn from_bytes_with_order(
+        bytes: &[u8],
+        order: crate::policy::OrderPolicy,
+    ) -> Result<Self, AccTreeError> {
+        Self::decode_snapshot(bytes, Some(order)).map_err(AccTreeError::Malformed)
+    }
+
+    fn decode_snapshot(
+        bytes: &[u8],
+        order: Option<crate::policy::OrderPolicy>,
+    ) -> Result<Self, String> {
+        use crate::wire::{CanonicalDecode, WireReader};
+        let body = bytes
+            .strip_prefix(crate::snapshot::SNAPSHOT_MAGIC)
+            .ok_or("not a tree snapshot")?;
+        let mut r = WireReader::new(body);
+        let version = u16::decode_canonical(&mut r)?;
+        if version != crate::snapshot::SNAPSHOT_VERSION {
+            return Err(format!("unsupported snapshot version {}", version));
+        }
+        let roots = Vec::<Arc<Node>>::decode_canonical(&mut r)?;
+        let epoch = u64::decode_canonical(&mut r)?;
+        let published = Option::<Arc<PublishedEpoch>>::decode_canonical(&mut r)?;
+        let deleted_at = Arc::new(
+            Vec::<(String, u64)>::decode_canonical(&mut r)?
+                .into_iter()
+                .collect(),
+        );
+        let staged = Vec::<crate::txn::TxnOp>::decode_canonical(&mut r)?;
+        let tag = u8::decode_canonical(&mut r)?;
+        let revive_policy = crate::policy::RevivePolicy::from_tag(tag)
+            .ok_or_else(|| format!("unknown revive policy tag {}", tag))?;
+        let order_id = u16::decode_canonical(&mut r)?;
+        let order = match order {
+            Some(order) if order.id() == order_id => order,
+            Some(order) => {
+                return Err(format!(
+                    "snapshot uses key order {}, not {}",
+                    order_id,
+                    order.id()
+                ));
+            }
+            None => crate::policy::OrderPolicy::from_id(order_id)
+                .ok_or_else(|| format!("snapshot uses custom key order {}", order_id))?,
+        };
+        let tag = u8::decode_canonical(&mut r)?;
+        let leaf_order = crate::policy::LeafOrder::from_tag(tag)
+            .ok_or_else(|| format!("unknown leaf order tag {}", tag))?;
+        r.finish()?;
+        Ok(Self {
+            key_index: Arc::new(crate::key_index::KeyIndex::build(&roots)),
+            roots,
+            epoch,
+            published,
+            deleted_at,
+            staged,
+            anchors: Vec::new(),
+            watchers: Vec::new(),
+            revive_policy,
+            order,
+            leaf_order,
+            clock: crate::clock::system_clock(),
+            metrics: None,
+            history: std::collections::VecDeque::new(),
+            history_depth: 0,
+            compaction_threshold: None,
+            global_acc: std::sync::OnceLock::new(),
+            manager: None,
+        })
+    }
+
+    /// Write the forest to `store`, skipping subtrees it already holds, and
+    /// make its roots the stored root list. Returns the number of nodes
+    /// written. Only the forest is stored; use `to_bytes` for epochs and
+    /// staged operations.
+    pub fn save_nodes(
+        &self,
+        store: &dyn crate::node_store::NodeStore,
+    ) -> Result<usize, AccTreeError> {
+        let save = || {
+            let mut written = 0;
+            for root in &self.roots {
+                written += crate::node_store::save_subtree(store, root)?;
+            }
+            store.put_roots(&self.root_hashes())?;
+            Ok(written)
+        };
+        save().map_err(AccTreeError::Storage)
+    }
+
+    /// Load the forest last written by `save_nodes`, checking every node
+    /// against its hash. The tree starts with no published epoch.
+    pub fn load_nodes(store: &dyn crate::node_store::NodeStore) -> Result<Self, AccTreeError> {
+        let hashes = store
+            .get_roots()
+            .map_err(AccTreeError::Storage)?
+            .ok_or_else(|| AccTreeError::Storage("store holds no forest".to_string()))?;
+        let roots = hashes
+            .iter()
+            .map(|h| crate::node_store::load_subtree(store, h))
+            .collect::<Result<Vec<_>, String>>()
+            .map_err(AccTreeError::Storage)?;
+        Ok(Self {
+            key_index: Arc::new(crate::key_index::KeyIndex::build(&roots)),
+            roots,
+            ..Self::new()
+        })
+    }
+
+    /// Capture the current forest, last published epoch and tombstone
+    /// bookkeeping as an immutable `TreeSnapshot`. Nodes are shared rather
+    /// than copied: later mutations copy only the paths they change, so the
+    /// snapshot stays valid while writes continue. Staged operations, anchors
+    /// and retained history are not captured.
+    pub fn snapshot(&self) -> crate::snapshot::TreeSnapshot {
+        crate::snapshot::TreeSnapshot::new(Self {
+            roots: self.roots.clone(),
+            epoch: self.epoch,
+            published: self.published.clone(),
+            deleted_at: self.deleted_at.clone(),
+            staged: Vec::new(),
+            anchors: Vec::new(),
+            watchers: Vec::new(),
+            revive_policy: self.revive_policy,
+            order: self.order,
+            leaf_order: self.leaf_order,
+            clock: self.clock.clone(),
+            metrics: self.metrics.clone(),
+            history: std::collections::VecDeque::new(),
+            history_depth: 0,
+            compaction_threshold: self.compaction_threshold,
+            key_index: self.key_index.clone(),
+            global_acc: self.global_acc.clone(),
+            manager: self.manager.clone(),
+        })
+    }
+
+    /// Witnesses invalidated since `before` (typically a `snapshot` taken
+    /// before a `refresh`, a `compact` or a batch of inserts), regenerated against the
+    /// root accumulators their keys now belong to. See `witness_delta`.
+    /// Fails if the public parameters cannot commit to a regenerated witness.
+    pub fn witness_delta(
+        &self,
+        before: &AccumulatorTree,
+    ) -> Result<crate::witness_delta::CompactionDelta, AccTreeError> {
+        crate::witness_delta::CompactionDelta::between(&before.roots, &self.roots)
+    }
+
+    /// Turn the tree into a read-only `FrozenTree` for serving proofs:
+    /// stale nodes are refreshed and the membership witness of every live key
+    /// is computed once, so point proofs skip the accumulator arithmetic.
+    /// Fails, consuming the tree, if the public parameters are missing or too
+    /// small for a root.
+    pub fn freeze(self) -> Result<crate::frozen::FrozenTree, AccTreeError> {
+        crate::frozen::FrozenTree::new(self)
+    }
+
+    /// Keep the forests of the last `depth` published epochs (0 keeps none)
+    /// so `prove_key_transition` can answer for them. Retained forests share
+    /// the nodes later epochs left unchanged with the live tree.
+    pub fn retain_history(&mut self, depth: usize) {
+        self.history_depth = depth;
+        while self.history.len() > depth {
+            self.history.pop_front();
+        }
+    }
+
+    /// Prove how `key` changed from published epoch `from` to epoch `to`
+    /// (see `history`). Both epochs must still be retained.
+    pub fn prove_key_transition(
+        &self,
+        key: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<crate::history::KeyTransitionProof, AccTreeError> {
+        if from > to {
+            return Err(AccTreeError::InvalidInput(format!(
+                "epoch {} is after epoch {}",
+                from, to
+            )));
+        }
+        let record = |epoch: u64| {
+            self.history
+                .iter()
+                .find(|r| r.epoch() == epoch)
+                .ok_or(AccTreeError::EpochNotRetained(epoch))
+        };
+        let (before, after) = (record(from)?, record(to)?);
+        let ops = self
+            .history
+            .iter()
+            .filter(|r| r.epoch() > from && r.epoch() <= to)
+            .flat_map(|r| {
+                r.ops
+                    .iter()
+                    .filter(|op| op.key() == key)
+                    .map(|op| (r.epoch(), op.clone()))
+            })
+            .collect();
+        Ok(crate::history::KeyTransitionProof {
+            key: key.to_string(),
+            from: before.published.commitment.clone(),
+            from_roots: before.published.roots.iter().map(|s| s.root).collect(),
+            before: before.key_state(key)?,
+            to: after.published.commitment.clone(),
+            to_roots: after.published.roots.iter().map(|s| s.root).collect(),
+            after: after.key_state(key)?,
+            ops,
+        })
+    }
+
+    /// Start staging a batch of mutations that is applied all at once on
+    /// `TreeTxn::commit` or discarded on abort.
+    pub fn begin_transaction(&mut self) -> crate::txn::TreeTxn<'_> {
+        crate::txn::TreeTxn::new(self)
+    }
+
+    /// Leaf state of `key` in any root (FID set, tombstoned).
+    pub(crate) fn leaf_state(&self, key: &str) -> Option<(Set<String>, bool)> {
+        self.root_of(key).iter().find_map(|r| r.leaf_state(key))
+    }
+
+    /// Current leaf of `key` (live or tombstoned) with its Merkle path.
+    pub(crate) fn leaf_witness(&self, key: &str) -> Option<crate::response::LeafWitness> {
+        leaf_witness_in(&self.roots, key)
+    }
+
+    /// Remember that `key` was tombstoned; it becomes visible with the next epoch.
+    pub(crate) fn record_deletion(&mut self, key: &str) {
+        Arc::make_mut(&mut self.deleted_at).insert(key.to_string(), self.epoch + 1);
+    }
+
+    pub(crate) fn clear_deletion(&mut self, key: &str) {
+        Arc::make_mut(&mut self.deleted_at).remove(key);
+    }
+
+    /// Check that the public parameters reach the degree of every accumulator
+    /// that adding `added` new leaves and reviving the tombstoned `revived`
+    /// keys computes. The first leaf of an empty forest needs none.
+    pub(crate) fn check_insert_degree(
+        &self,
+        added: usize,
+        revived: &[&str],
+    ) -> Result<(), AccTreeError> {
+        if self.roots.is_empty() && added <= 1 {
+            return Ok(());
+        }
+        let mut units: Vec<(usize, u64)> = self
+            .roots
+            .iter()
+            .map(|r| (r.level(), r.live_key_count()))
+            .collect();
+        let revived: Vec<usize> = revived.iter().filter_map(|k| self.root_index(k)).collect();
+        for &i in &revived {
+            units[i].1 += 1;
+        }
+        let needed = revived.iter().map(|&i| units[i].1).max().unwrap_or(0);
+        units.extend(std::iter::repeat_n((0, 1), added));
+        let needed = needed.max(merge_degree(&units, self.roots.len()));
+        crate::setup::check_available_degree(needed as usize)
+    }
+
     fn normalize(&mut self) {
         self.roots.sort_by_key(|n| n.level());
+        let sorted_by = self.sorted_by();
 
-        let mut stack: Vec<Box<Node>> = Vec::new();
+        // (root, whether its keys moved into it)
+        let mut stack: Vec<(Arc<Node>, bool)> = Vec::new();
 
         for node in self.roots.drain(..) {
             let mut cur = node;
-            while let Some(top) = stack.last() {
+            // A level-0 root may be a new leaf; it is cheap to index again
+            let mut moved = cur.level() == 0;
+            while let Some((top, _)) = stack.last() {
                 if top.level() == cur.level() {
-                    let left = stack.pop().unwrap();
-                    cur = Node::merge(left, cur, None);
+                    let (left, _) = stack.pop().unwrap();
+                    cur = match &sorted_by {
+                        Some(order) => Node::merge_sorted(left, cur, order),
+                        None => Node::merge(left, cur, None),
+                    };
+                    moved = true;
                 } else {
                     break;
                 }
             }
-            stack.push(cur);
+            stack.push((cur, moved));
+        }
+
+        for (root, moved) in &stack {
+            if *moved {
+                Arc::make_mut(&mut self.key_index).assign(root);
+            }
+        }
+        self.roots = stack.into_iter().map(|(root, _)| root).collect();
+    }
+
+    /// Point the keys of the roots at `positions` at them in the key index.
+    pub(crate) fn index_roots(&mut self, positions: impl IntoIterator<Item = usize>) {
+        let index = Arc::make_mut(&mut self.key_index);
+        for i in positions {
+            index.assign(&self.roots[i]);
+        }
+    }
+
+    /// Level of the root holding a leaf of `key`, live or tombstoned. Looked
+    /// up in the key index rather than by asking every root (see `key_index`).
+    ///
+    /// ```
+    /// use accumulator_tree::AccumulatorTree;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// for key in ["alice", "bob", "carol"] {
+    ///     tree.insert(key.to_string(), "doc1".to_string());
+    /// }
+    /// assert_eq!(tree.locate("alice"), Some(1));
+    /// assert_eq!(tree.locate("carol"), Some(0));
+    /// assert_eq!(tree.locate("mallory"), None);
+    /// ```
+    pub fn locate(&self, key: &str) -> Option<crate::key_index::RootId> {
+        self.root_index(key).map(|i| self.roots[i].level())
+    }
+
+    /// Position of the root holding a leaf of `key`.
+    fn root_index(&self, key: &str) -> Option<usize> {
+        let level = self.key_index.get(key)?;
+        if let Some(i) = self.roots.iter().position(|r| r.level() == level)
+            && self.roots[i].leaf_state(key).is_some()
+        {
+            return Some(i);
         }
+        // The fingerprint belongs to another key
+        self.roots.iter().position(|r| r.leaf_state(key).is_some())
+    }
+
+    /// The root holding a leaf of `key` as a slice of zero or one roots.
+    fn root_of(&self, key: &str) -> &[Arc<Node>] {
+        match self.root_index(key) {
+            Some(i) => std::slice::from_ref(&self.roots[i]),
+            None => &[],
+        }
+    }
 
-        self.roots = stack;
+    pub fn revive_policy(&self) -> crate::policy::RevivePolicy {
+        self.revive_policy
     }
 
-    pub fn insert(&mut self, key: String, fid: String) {
+    /// Set how inserts treat live and tombstoned keys (see `RevivePolicy`).
+    pub fn set_revive_policy(&mut self, policy: crate::policy::RevivePolicy) {
+        self.revive_policy = policy;
+    }
+
+    /// Insert `fid` under `key`. Returns false (and changes nothing) if the
+    /// revive policy refuses the insert; `try_insert` reports why.
+    pub fn insert(&mut self, key: String, fid: String) -> bool {
+        self.try_insert(key, fid).is_ok()
+    }
+
+    /// Insert many `(key, fid)` pairs, normalizing once at the end. The result
+    /// is the forest that calling `insert` on each pair in order produces, but
+    /// new leaves are combined bottom-up into their final places, so each new
+    /// internal node is hashed and accumulated exactly once. Pairs the revive
+    /// policy rejects are skipped as `insert` would; returns how many applied.
+    /// A tree with sorted leaves inserts the pairs one by one. If the public
+    /// parameters cannot serve the batch nothing is applied and the result is
+    /// 0; `try_insert_batch` reports why.
+    pub fn insert_batch(&mut self, items: impl IntoIterator<Item = (String, String)>) -> usize {
+        self.try_insert_batch(items).unwrap_or(0)
+    }
+
+    /// `insert_batch`, failing before any change with `DegreeExceeded` if an
+    /// accumulator the batch builds is larger than the public parameters
+    /// allow, or with `ParamsNotInitialized` if there are none.
+    pub fn try_insert_batch(
+        &mut self,
+        items: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<usize, AccTreeError> {
+        let items: Vec<(String, String)> = items.into_iter().collect();
+        let mut added = std::collections::HashSet::new();
+        let mut revived = std::collections::HashSet::new();
+        for (key, _) in &items {
+            match self.leaf_state(key) {
+                None => added.insert(key.as_str()),
+                Some((_, true)) => revived.insert(key.as_str()),
+                Some((_, false)) => false,
+            };
+        }
+        let revived: Vec<&str> = revived.into_iter().collect();
+        self.check_insert_degree(added.len(), &revived)?;
+
+        if self.sorted_by().is_some() {
+            // Merges depend on the keys, so there is no shape to precompute
+            return Ok(items
+                .into_iter()
+                .map(|(key, fid)| usize::from(self.insert(key, fid)))
+                .sum());
+        }
+        let mut position: HashMap<String, usize> = HashMap::new();
+        let mut leaves: Vec<(String, Set<String>)> = Vec::new();
+        let mut applied = 0;
+        for (key, fid) in items {
+            if let Some(&i) = position.get(&key) {
+                // Sequentially this key would already have a live leaf
+                if self.revive_policy.check_insert(&key, Some(false)).is_ok() {
+                    leaves[i].1.insert(fid);
+                    applied += 1;
+                }
+            } else if self.leaf_state(&key).is_some() {
+                // Existing leaves change in place; the forest shape is unaffected
+                applied += usize::from(self.insert(key, fid));
+            } else {
+                position.insert(key.clone(), leaves.len());
+                leaves.push((key, Set::from_vec(vec![fid])));
+                applied += 1;
+            }
+        }
+        if leaves.is_empty() {
+            return Ok(applied);
+        }
+
+        let levels: Vec<usize> = self
+            .roots
+            .iter()
+            .map(|r| r.level())
+            .chain(std::iter::repeat_n(0, leaves.len()))
+            .collect();
+        let added: Vec<&str> = leaves.iter().map(|(key, _)| key.as_str()).collect();
+        self.update_global_acc(&added, &[]);
+        let shapes = replay_normalize(&levels, self.roots.len());
+        let mut roots: Vec<Option<Arc<Node>>> = self.roots.drain(..).map(Some).collect();
+        let mut leaves: Vec<Option<(String, Set<String>)>> = leaves.into_iter().map(Some).collect();
+        let moved: Vec<usize> = shapes
+            .iter()
+            .enumerate()
+            .filter(|(_, shape)| !matches!(shape, Shape::Unit(u) if *u < roots.len()))
+            .map(|(i, _)| i)
+            .collect();
+        self.roots = build_shapes(shapes, &mut roots, &mut leaves);
+        self.index_roots(moved);
+        Ok(applied)
+    }
+
+    /// Insert `fid` under `key`, reviving a tombstoned key if the revive
+    /// policy allows it.
+    pub fn try_insert(&mut self, key: String, fid: String) -> Result<(), AccTreeError> {
+        let root = self.root_index(&key);
+        let leaf = root
+            .and_then(|i| self.roots[i].leaf_state(&key))
+            .map(|(_, deleted)| deleted);
+        self.revive_policy
+            .check_insert(&key, leaf)
+            .map_err(AccTreeError::PolicyRejected)?;
+
         // If there's an existing active leaf for `key`, add fid to it
-        if let Some(root) = self.roots.iter_mut().find(|r| r.has_key(&key)) {
-            root.insert_fid(&key, fid);
-            return;
+        if let (Some(idx), Some(false)) = (root, leaf) {
+            Arc::make_mut(&mut self.roots[idx]).insert_fid(&key, fid);
+            return Ok(());
         }
 
+        // Anything but the first leaf joins a key set, which needs parameters
+        let revived = if leaf == Some(true) {
+            vec![key.as_str()]
+        } else {
+            vec![]
+        };
+        self.check_insert_degree(usize::from(leaf.is_none()), &revived)?;
+
         // If there's a deleted/tombstoned leaf for `key`, revive it
-        if let Some(idx) = self.roots.iter().position(|r| {
-            // Check if any leaf with this key exists (even if deleted)
-            matches!(
-                r.recurse_select_proof_including_deleted(&key, &mut Vec::new()),
-                Some(_)
-            )
-        }) {
+        if let Some(idx) = root {
             let root = self.roots.remove(idx);
-            let revived = root.revive(&key, &fid);
+            self.clear_deletion(&key);
+            self.update_global_acc(&[&key], &[]);
+            let revived = Arc::unwrap_or_clone(root).revive(&key, &fid);
             self.roots.push(revived);
             self.normalize();
-            return;
+            return Ok(());
         }
 
         // Create new leaf
-        self.roots.push(Box::new(Node::Leaf {
-            key,
-            fids: Set::from_vec(vec![fid]),
-            level: 0,
-            deleted: false,
-        }));
+        self.update_global_acc(&[&key], &[]);
+        self.roots
+            .push(Arc::new(Node::leaf(key, Set::from_vec(vec![fid]), false)));
+        self.normalize();
+        Ok(())
+    }
+
+    /// Bring a tombstoned key back with `fids`, proving the leaf went from
+    /// tombstoned to live. Allowed under every revive policy except
+    /// `ErrorOnDuplicate`; fails if the key is live or has never existed.
+    ///
+    /// ```
+    /// use accumulator_ads::Set;
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// tree.delete("alice", "doc1");
+    /// let fids = Set::from_vec(vec!["doc2".to_string()]);
+    /// let bytes = to_wire(&tree.revive_with_proof("alice", fids).unwrap());
+    ///
+    /// let response: ReviveResponse = from_wire(&bytes).unwrap();
+    /// assert!(response.verify_revive());
+    /// assert!(tree.revive_with_proof("bob", Set::new()).is_err());
+    /// ```
+    pub fn revive_with_proof(
+        &mut self,
+        key: &str,
+        fids: Set<String>,
+    ) -> Result<crate::response::ReviveResponse, AccTreeError> {
+        let started = std::time::Instant::now();
+        self.revive_policy
+            .check_revive(key)
+            .map_err(AccTreeError::PolicyRejected)?;
+        if fids.is_empty() {
+            return Err(AccTreeError::InvalidInput(
+                "cannot revive a key with an empty FID set".to_string(),
+            ));
+        }
+        let pre = self
+            .leaf_witness(key)
+            .ok_or_else(|| AccTreeError::KeyNotFound(key.to_string()))?;
+        if !pre.deleted {
+            return Err(AccTreeError::KeyLive(key.to_string()));
+        }
+        let deleted_epoch = self.deleted_at.get(key).copied();
+
+        let idx = self
+            .root_index(key)
+            .ok_or_else(|| AccTreeError::KeyNotFound(key.to_string()))?;
+        let root = self.roots.remove(idx);
+        self.clear_deletion(key);
+        self.update_global_acc(&[key], &[]);
+        self.roots
+            .push(Arc::unwrap_or_clone(root).revive_with(key, &fids));
         self.normalize();
+
+        let post = self.leaf_witness(key).ok_or_else(|| {
+            AccTreeError::ProofGenFailed("post-revive: key not found".to_string())
+        })?;
+        let qr = self.prove_point(key)?;
+        let post_acc_proof = match qr.acc_proof {
+            Some(crate::acc_proof::AccProof::Membership(mp)) => Some(mp),
+            _ => None,
+        };
+        let response = crate::response::ReviveResponse {
+            key: key.to_string(),
+            fids,
+            deleted_epoch,
+            pre,
+            post,
+            post_accumulator: qr.accumulator,
+            post_acc_proof,
+            stats: None,
+        };
+        Ok(self.instrument(crate::metrics::ProofOp::Revive, started, response))
+    }
+
+    /// Apply mixed operations as one batch. The result matches applying them
+    /// in order, except that operations which would not apply are skipped and
+    /// reported instead of failing silently. Each touched root is rewritten in
+    /// a single pass (see `batch`); only keys without a leaf go through the
+    /// regular insert-and-merge path.
+    pub fn apply_ops(&mut self, ops: Vec<crate::txn::TxnOp>) -> crate::batch::BatchReport {
+        use crate::txn::TxnOp;
+        use std::collections::HashSet;
+
+        let mut report = crate::batch::BatchReport::default();
+        let wanted: HashSet<&str> = ops.iter().map(|op| op.key()).collect();
+        let mut located: HashMap<String, (usize, crate::node::LeafChange)> = HashMap::new();
+        for (i, root) in self.roots.iter().enumerate() {
+            let mut found = Vec::new();
+            root.locate_leaves(&wanted, &mut Vec::new(), &mut found);
+            located.extend(found.into_iter().map(|(key, leaf)| (key, (i, leaf))));
+        }
+
+        // Replay every operation on its key's leaf state
+        let mut states: HashMap<String, Option<(Set<String>, bool)>> = HashMap::new();
+        let mut order: Vec<String> = Vec::new();
+        for op in ops {
+            let key = op.key().to_string();
+            let state = states.entry(key.clone()).or_insert_with(|| {
+                order.push(key.clone());
+                located
+                    .get(&key)
+                    .map(|(_, (_, fids, deleted))| (fids.clone(), *deleted))
+            });
+            if let TxnOp::Insert { .. } = op
+                && self
+                    .revive_policy
+                    .check_insert(&key, state.as_ref().map(|(_, deleted)| *deleted))
+                    .is_err()
+            {
+                report.rejected.push(op);
+                continue;
+            }
+            match op.replay(state.clone()) {
+                Some(next) => {
+                    *state = Some(next);
+                    report.applied += 1;
+                }
+                None => report.rejected.push(op),
+            }
+        }
+
+        let mut changes: Vec<Vec<crate::node::LeafChange>> = vec![Vec::new(); self.roots.len()];
+        let mut new_leaves = Vec::new();
+        for key in order {
+            let Some((fids, deleted)) = states.remove(&key).flatten() else {
+                continue;
+            };
+            let before = located.get(&key);
+            if before.is_some_and(|(_, (_, f, d))| *f == fids && *d == deleted) {
+                continue;
+            }
+            let was_deleted = before.is_some_and(|(_, (_, _, d))| *d);
+            if deleted && !was_deleted {
+                self.record_deletion(&key);
+            } else if !deleted && was_deleted {
+                self.clear_deletion(&key);
+            }
+            match before {
+                Some((i, (route, _, _))) => changes[*i].push((route.clone(), fids, deleted)),
+                None => new_leaves.push(Node::leaf(key.clone(), fids, deleted)),
+            }
+            report.changed_keys.push(key);
+        }
+
+        for (i, mut root_changes) in changes.into_iter().enumerate() {
+            if root_changes.is_empty() {
+                continue;
+            }
+            let root = &mut self.roots[i];
+            let before = (root.level() as u64, root.hash());
+            root_changes.sort_by(|a, b| a.0.cmp(&b.0));
+            Arc::make_mut(root).apply_leaf_states(&root_changes, 0);
+            report.transitions.push(crate::batch::RootTransition {
+                index: i,
+                before,
+                after: (root.level() as u64, root.hash()),
+            });
+        }
+
+        // One normalize per leaf, as sequential inserts would, so the shape matches
+        report.new_leaves = new_leaves.len();
+        for leaf in new_leaves {
+            self.roots.push(Arc::new(leaf));
+            self.normalize();
+        }
+        self.forget_global_acc();
+        report.roots_after = self.root_entries();
+        report
     }
 
     /// Insert with proof: returns pre-insert snapshot and post-insert proofs.
     /// Note: strong non-membership proofs are not implemented; we provide a pre-insert
     /// snapshot (`pre_roots`) that a verifier can use with application-level checks.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// let response = tree
+    ///     .insert_with_proof("bob".to_string(), "doc2".to_string())
+    ///     .unwrap();
+    ///
+    /// let response: InsertResponse = from_wire(&to_wire(&response)).unwrap();
+    /// assert!(response.verify_insert());
+    /// assert_eq!(response.key, "bob");
+    /// ```
     pub fn insert_with_proof(
         &mut self,
         key: String,
         fid: String,
-    ) -> crate::response::InsertResponse {
+    ) -> Result<crate::response::InsertResponse, AccTreeError> {
+        let started = std::time::Instant::now();
+        // Even a first leaf needs the parameters for its witness; refuse
+        // before anything changes
+        crate::setup::ensure_ready()?;
         // capture pre-insert non-membership proof (if any)
         let pre_nonmembership = self.select_nonmembership_proof(&key);
 
         // perform insertion (this will revive if exists)
-        self.insert(key.clone(), fid.clone());
+        self.try_insert(key.clone(), fid.clone())?;
 
         // build post-insert proof for the inserted key
-        let qr = self.select_with_proof(&key);
+        let qr = self.prove_point(&key)?;
         let post_acc = qr.accumulator;
         let post_proof = qr.merkle_proof;
         let post_acc_witness = match qr.acc_proof {
             Some(crate::acc_proof::AccProof::Membership(mp)) => Some(mp.witness),
             _ => None,
         };
-        let post_fids = qr.fids.unwrap_or_else(|| Set::new());
+        let post_fids = qr.fids.unwrap_or_default();
 
         let post_acc_proof =
             post_acc_witness.map(|w| crate::acc_proof::MembershipProof { witness: w });
 
-        crate::response::InsertResponse::new(
-            key,
-            post_fids,
-            post_acc,
-            post_proof,
-            post_acc_proof,
-            pre_nonmembership,
-        )
+        let response = crate::response::InsertResponse::builder()
+            .key(key)
+            .fids(post_fids)
+            .post_accumulator(post_acc)
+            .post_merkle_proof(post_proof)
+            .post_acc_proof(post_acc_proof)
+            .pre_acc_proof(pre_nonmembership)
+            .build()
+            .map_err(AccTreeError::ProofGenFailed)?;
+        Ok(self.instrument(crate::metrics::ProofOp::Insert, started, response))
     }
 
-    /// Produce a non-membership proof for `key` by returning the predecessor and successor
-    /// leaves (if any) together with their Merkle proofs. Returns `None` if the key exists.
     /// Generate a cryptographically sound non-membership proof
     /// This uses the accumulator's Bézout coefficient approach to prove
-    /// that a key is NOT in the accumulated set
+    /// that a key is NOT in the accumulated set. Returns `None` if the key exists.
+    ///
+    /// When an epoch has been published the proof is built against that epoch's
+    /// cached global accumulator and carries its commitment, so clients can check
+    /// it with `verify_in_epoch`. Keys that were live at publication time (and
+    /// deleted since) fall back to an unbound proof over the current key set.
     pub fn select_nonmembership_proof(
         &self,
         key: &str,
     ) -> Option<crate::acc_proof::NonMembershipProof> {
-        // First check if key exists anywhere
-        for root in &self.roots {
-            if root.has_key(key) {
-                return None; // Key exists, cannot create non-membership proof
-            }
+        // First check if key exists anywhere (at the leaf: stale key sets may
+        // still list tombstoned keys)
+        if self.select(key).is_some() {
+            return None; // Key exists, cannot create non-membership proof
         }
 
-        // Collect all keys from all roots to build the complete set
-        let mut all_keys = accumulator_ads::Set::<String>::new();
-        for root in &self.roots {
-            all_keys = all_keys.union(&root.keys());
+        if let Some(published) = &self.published
+            && let Some(proof) = crate::acc_proof::NonMembershipProof::new(
+                key.to_string(),
+                published.commitment.global_acc,
+                &published.keys,
+            )
+        {
+            return Some(proof.with_commitment(published.commitment.clone()));
         }
 
-        // Calculate the global accumulator for all keys
-        let global_acc = if all_keys.is_empty() {
-            // Empty tree: use empty accumulator
-            crate::utils::empty_acc()
-        } else {
-            // Calculate accumulator commitment for all keys
-            let digest_set = accumulator_ads::digest_set_from_set(&all_keys);
-            accumulator_ads::DynamicAccumulator::calculate_commitment(&digest_set)
-        };
+        // The Bézout coefficients still need every key
+        let all_keys = self.all_keys();
+        let global_acc = self.global_acc();
 
         // Generate non-membership proof using accumulator's Bézout approach
         crate::acc_proof::NonMembershipProof::new(key.to_string(), global_acc, &all_keys)
     }
 
     pub fn select(&self, key: &str) -> Option<Set<String>> {
-        for r in &self.roots {
+        for r in self.root_of(key) {
             if let Some(v) = r.select(key) {
                 return Some(v);
             }
@@ -156,76 +1729,498 @@ impl AccumulatorTree {
         None
     }
 
+    /// Like `select`, but distinguishes a tombstoned key from one that never existed.
+    pub fn select_status(&self, key: &str) -> crate::lookup::LookupResult {
+        use crate::lookup::LookupResult;
+
+        for r in self.root_of(key) {
+            match r.leaf_state(key) {
+                Some((fids, false)) => return LookupResult::Live(fids),
+                Some((_, true)) => {
+                    return LookupResult::Tombstoned {
+                        deleted_epoch: self.deleted_at.get(key).copied(),
+                    };
+                }
+                None => {}
+            }
+        }
+        LookupResult::Absent
+    }
+
     /// Return the query result together with a proof that the leaf belongs
-    /// to the subtree rooted at the returned root hash.
-    pub fn select_with_proof(&self, key: &str) -> crate::response::QueryResponse {
-        for r in &self.roots {
+    /// to the subtree rooted at the returned root hash. A key without a live
+    /// leaf is answered with a non-membership proof once an epoch has been
+    /// published. Fails with `ParamsNotInitialized` or `DegreeExceeded` when
+    /// the public parameters are missing or too small for the accumulator the
+    /// proof is computed against.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// tree.publish_epoch();
+    ///
+    /// for key in ["alice", "mallory"] {
+    ///     let bytes = to_wire(&tree.select_with_proof(key).unwrap());
+    ///     let response: QueryResponse = from_wire(&bytes).unwrap();
+    ///     assert!(response.verify_by_mode(key));
+    ///     assert_eq!(response.fids.is_some(), key == "alice");
+    /// }
+    /// ```
+    pub fn select_with_proof(
+        &self,
+        key: &str,
+    ) -> Result<crate::response::QueryResponse, AccTreeError> {
+        let started = std::time::Instant::now();
+        let response = self.prove_point(key)?;
+        Ok(self.instrument(crate::metrics::ProofOp::Select, started, response))
+    }
+
+    /// `select_with_proof` without stats, for the mutations that embed it.
+    fn prove_point(&self, key: &str) -> Result<crate::response::QueryResponse, AccTreeError> {
+        self.select_with_proof_using(key, |root, key| {
+            crate::witness_cache::compute_witness(&root.keys(), key)
+        })
+    }
+
+    /// Degree the witness for `key` needs: the size of its root if it is live,
+    /// otherwise the key set the non-membership proof is taken over.
+    pub(crate) fn proof_degree(&self, key: &str) -> usize {
+        if let Some(root) = self.root_of(key).iter().find(|r| r.select(key).is_some()) {
+            return root.live_key_count() as usize;
+        }
+        match &self.published {
+            Some(published) => published.keys.len(),
+            None => self.all_keys().len(),
+        }
+    }
+
+    /// Like `select_with_proof`, and attach the live key count of every
+    /// sibling on the Merkle path (see `QueryResponse::check_key_count_hints`).
+    pub fn select_with_count_hints(
+        &self,
+        key: &str,
+    ) -> Result<crate::response::QueryResponse, AccTreeError> {
+        let mut response = self.select_with_proof(key)?;
+        let Some(proof) = &response.merkle_proof else {
+            return Ok(response);
+        };
+        let Some(root) = self.roots.iter().find(|r| r.hash() == proof.root_hash) else {
+            return Ok(response);
+        };
+
+        // Walk down from the root, following the path top first
+        let mut counts = Vec::with_capacity(proof.path.len());
+        let mut node: &Node = root;
+        for (_, sibling_is_left) in proof.path.iter().rev() {
+            let Node::NonLeaf { left, right, .. } = node else {
+                return Ok(response);
+            };
+            let (sibling, next) = if *sibling_is_left {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            counts.push(sibling.live_key_count());
+            node = next;
+        }
+        counts.reverse();
+        response.sibling_key_counts = Some(counts);
+        Ok(response)
+    }
+
+    /// Like `select_with_proof`, but reuse membership witnesses from `cache`
+    /// and store newly computed ones in it. Fails as `select_with_proof` does.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// use accumulator_tree::witness_cache::WitnessCache;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// let mut cache = WitnessCache::new();
+    /// tree.select_with_proof_cached("alice", &mut cache).unwrap();
+    /// let response = tree.select_with_proof_cached("alice", &mut cache).unwrap();
+    /// assert_eq!(cache.stats(), (1, 1));
+    ///
+    /// let response: QueryResponse = from_wire(&to_wire(&response)).unwrap();
+    /// assert!(response.verify_by_mode("alice"));
+    /// ```
+    pub fn select_with_proof_cached(
+        &self,
+        key: &str,
+        cache: &mut crate::witness_cache::WitnessCache,
+    ) -> Result<crate::response::QueryResponse, AccTreeError> {
+        self.select_with_proof_using(key, |root, key| cache.get_or_compute(root, key))
+    }
+
+    /// Answer a point query in the mode chosen by `policy`, feeding the time
+    /// spent on each proof part back into it. Absent keys can only be proven
+    /// with the accumulator, so merkle-only policies fail for them.
+    pub fn select_with_policy(
+        &self,
+        key: &str,
+        policy: &mut crate::policy::ProofPolicy,
+    ) -> Result<crate::response::QueryResponse, AccTreeError> {
+        use crate::policy::ProofMode;
+        use std::time::Instant;
+
+        let mode = policy.choose().map_err(AccTreeError::PolicyRejected)?;
+        for r in self.root_of(key) {
+            let start = Instant::now();
+            let mut path: Vec<(Hash, bool)> = Vec::new();
+            let Some(fids) = r.recurse_select_with_proof(key, &mut path) else {
+                continue;
+            };
+            let (mut merkle_cost, mut acc_cost) = (None, None);
+            let mut response = crate::response::QueryResponse::new(None, None, None, None);
+            if mode.includes_merkle() {
+                let leaf_h = crate::utils::leaf_hash(key, &fids, 0, false);
+                response.merkle_proof =
+                    Some(crate::merkle_proof::Proof::new(r.hash(), leaf_h, path));
+                response.fids = Some(fids);
+                merkle_cost = Some(start.elapsed());
+            }
+            if mode.includes_accumulator() {
+                let start = Instant::now();
+                let witness = crate::witness_cache::compute_witness(&r.keys(), key)?;
+                response.accumulator = Some(r.acc());
+                response.acc_proof = Some(crate::acc_proof::AccProof::Membership(
+                    crate::acc_proof::MembershipProof { witness },
+                ));
+                acc_cost = Some(start.elapsed());
+            }
+            policy.record(merkle_cost, acc_cost);
+            return Ok(response
+                .with_mode(mode)
+                .with_query(&crate::query::Query::Point(key.to_string())));
+        }
+
+        if mode == ProofMode::MerkleOnly {
+            return Err(AccTreeError::PolicyRejected(format!(
+                "key '{}' not found; absence needs an accumulator proof",
+                key
+            )));
+        }
+        let start = Instant::now();
+        let nm = self.select_nonmembership_proof(key).ok_or_else(|| {
+            AccTreeError::ProofGenFailed(format!("cannot prove absence of key '{}'", key))
+        })?;
+        policy.record(None, Some(start.elapsed()));
+        let nm_proof = crate::acc_proof::AccProof::NonMembership(Box::new(nm));
+        Ok(
+            crate::response::QueryResponse::new(None, None, None, Some(nm_proof))
+                .with_mode(ProofMode::AccumulatorOnly)
+                .with_query(&crate::query::Query::Point(key.to_string())),
+        )
+    }
+
+    pub(crate) fn select_with_proof_using(
+        &self,
+        key: &str,
+        witness: impl FnMut(&Node, &str) -> Result<accumulator_ads::G1Affine, AccTreeError>,
+    ) -> Result<crate::response::QueryResponse, AccTreeError> {
+        Ok(self
+            .point_response(key, witness)?
+            .with_query(&crate::query::Query::Point(key.to_string())))
+    }
+
+    fn point_response(
+        &self,
+        key: &str,
+        mut witness: impl FnMut(&Node, &str) -> Result<accumulator_ads::G1Affine, AccTreeError>,
+    ) -> Result<crate::response::QueryResponse, AccTreeError> {
+        // Leaf accumulators and non-membership proofs need the parameters too
+        crate::setup::check_available_degree(self.proof_degree(key))?;
+        for r in self.root_of(key) {
             let mut path: Vec<(Hash, bool)> = Vec::new();
             if let Some(fids) = r.recurse_select_with_proof(key, &mut path) {
                 let leaf_h = crate::utils::leaf_hash(key, &fids, 0, false);
                 let root_h = r.hash();
                 let proof = crate::merkle_proof::Proof::new(root_h, leaf_h, path);
                 // create accumulator membership witness for the key
-                let acc_val = r.acc();
-                let key_set = accumulator_ads::Set::from_vec(vec![key.to_string()]);
-                let key_digest_set = accumulator_ads::digest_set_from_set(&key_set);
-                let key_elem = *key_digest_set.iter().next().unwrap();
-                let acc_inst = accumulator_ads::DynamicAccumulator::from_value(acc_val);
-                let acc_witness = acc_inst
-                    .compute_membership_witness(key_elem)
-                    .unwrap_or(acc_val);
                 let acc_proof =
                     crate::acc_proof::AccProof::Membership(crate::acc_proof::MembershipProof {
-                        witness: acc_witness,
+                        witness: witness(r, key)?,
                     });
-                return crate::response::QueryResponse::new(
+                return Ok(crate::response::QueryResponse::new(
                     Some(fids),
                     Some(proof),
                     Some(r.acc()),
                     Some(acc_proof),
-                );
+                ));
             }
         }
         // not found: try to construct non-membership proof
         if let Some(nm) = self.select_nonmembership_proof(key) {
-            let nm_proof = crate::acc_proof::AccProof::NonMembership(nm);
-            crate::response::QueryResponse::new(None, None, None, Some(nm_proof))
+            let nm_proof = crate::acc_proof::AccProof::NonMembership(Box::new(nm));
+            Ok(crate::response::QueryResponse::new(
+                None,
+                None,
+                None,
+                Some(nm_proof),
+            ))
         } else {
-            crate::response::QueryResponse::new(None, None, None, None)
+            Ok(crate::response::QueryResponse::new(None, None, None, None))
+        }
+    }
+
+    /// Select `n` leaves of the last published epoch, determined by the
+    /// commitment and `seed`, with proofs that they are the selected ones.
+    /// Fails if the forest has changed since publication.
+    pub fn sample_keys(
+        &self,
+        seed: &[u8],
+        n: usize,
+    ) -> Result<crate::sample::KeySample, AccTreeError> {
+        let commitment = self
+            .published_commitment()
+            .ok_or(AccTreeError::NotPublished)?;
+        crate::sample::sample_leaves(&self.roots, commitment, seed, n)
+    }
+
+    /// Every live key in `[start, end)` of the last published epoch, in the
+    /// tree's key order, with its FIDs and a proof that the list is complete
+    /// (see `range`). Fails if the forest has changed since publication.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// use accumulator_tree::range::RangeResponse;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// for key in ["user/a", "user/b", "admin"] {
+    ///     tree.insert(key.to_string(), format!("doc-{}", key));
+    /// }
+    /// let trusted = tree.publish_epoch().digest();
+    /// let bytes = to_wire(&tree.range_with_proof("user/", "user0").unwrap());
+    ///
+    /// let response: RangeResponse = from_wire(&bytes).unwrap();
+    /// assert!(response.verify(&trusted));
+    /// assert_eq!(response.entries.len(), 2);
+    /// ```
+    pub fn range_with_proof(
+        &self,
+        start: &str,
+        end: &str,
+    ) -> Result<crate::range::RangeResponse, AccTreeError> {
+        let published = self.published.as_ref().ok_or(AccTreeError::NotPublished)?;
+        let roots = self.root_entries();
+        if crate::epoch::forest_hash(&roots) != published.commitment.forest_hash {
+            return Err(AccTreeError::ForestChanged);
+        }
+
+        let mut keys: Vec<&String> = published.keys.iter().collect();
+        keys.sort_by(|a, b| self.order.compare(a, b));
+        let mut entries = Vec::new();
+        let mut other_keys = Vec::new();
+        for key in keys {
+            if self.order.in_range(key, start, end) {
+                let leaf = self.leaf_witness(key).ok_or_else(|| {
+                    AccTreeError::ProofGenFailed(format!("published key '{}' has no leaf", key))
+                })?;
+                entries.push(crate::range::RangeEntry {
+                    key: key.clone(),
+                    leaf,
+                });
+            } else {
+                other_keys.push(key.clone());
+            }
+        }
+        Ok(crate::range::RangeResponse {
+            start: start.to_string(),
+            end: end.to_string(),
+            commitment: published.commitment.clone(),
+            roots,
+            entries,
+            other_keys,
+        })
+    }
+
+    /// `range_with_proof` as a stream: a header, then one item per key in
+    /// the range, proven only when the consumer pulls it (see
+    /// `range_stream`). The stream borrows the tree, so the forest cannot
+    /// change while it is read.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// use accumulator_tree::range_stream::{RangeHeader, RangeItem, RangeVerifier};
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// for key in ["user/a", "user/b", "admin"] {
+    ///     tree.insert(key.to_string(), format!("doc-{}", key));
+    /// }
+    /// let trusted = tree.publish_epoch().digest();
+    /// let mut stream = tree.select_range_stream("user/", "user0").unwrap();
+    /// let header = to_wire(stream.header());
+    ///
+    /// let header: RangeHeader = from_wire(&header).unwrap();
+    /// let mut verifier = RangeVerifier::new(header, &trusted).unwrap();
+    /// for item in stream.by_ref() {
+    ///     let item: RangeItem = from_wire(&to_wire(&item.unwrap())).unwrap();
+    ///     verifier.push(&item).unwrap();
+    /// }
+    /// assert!(verifier.finish().is_ok());
+    /// ```
+    pub fn select_range_stream(
+        &self,
+        start: &str,
+        end: &str,
+    ) -> Result<crate::range_stream::RangeStream<'_>, AccTreeError> {
+        let published = self.published.as_ref().ok_or(AccTreeError::NotPublished)?;
+        let roots = self.root_entries();
+        if crate::epoch::forest_hash(&roots) != published.commitment.forest_hash {
+            return Err(AccTreeError::ForestChanged);
         }
+
+        let mut keys: Vec<&String> = published.keys.iter().collect();
+        keys.sort_by(|a, b| self.order.compare(a, b));
+        let (in_range, others): (Vec<&String>, Vec<&String>) = keys
+            .into_iter()
+            .partition(|k| self.order.in_range(k, start, end));
+        let header = crate::range_stream::RangeHeader {
+            start: start.to_string(),
+            end: end.to_string(),
+            commitment: published.commitment.clone(),
+            roots,
+            other_keys: others.into_iter().cloned().collect(),
+        };
+        Ok(crate::range_stream::RangeStream::new(
+            header,
+            &self.roots,
+            in_range,
+        ))
+    }
+
+    /// Prove that `fid` does or does not belong to `key` without returning the
+    /// whole FID set. Only keys whose FID set is chunked (see `fid_tree`) are
+    /// answered this way; smaller sets are returned in full by
+    /// `select_with_proof`.
+    ///
+    /// ```
+    /// use accumulator_tree::fid_tree::FID_CHUNK_THRESHOLD;
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// for i in 0..=FID_CHUNK_THRESHOLD {
+    ///     tree.insert("popular".to_string(), format!("doc{:05}", i));
+    /// }
+    /// let response = tree.select_fid_with_proof("popular", "doc00042").unwrap();
+    ///
+    /// let response: FidQueryResponse = from_wire(&to_wire(&response)).unwrap();
+    /// assert!(response.verify("popular", "doc00042"));
+    /// assert!(!response.verify("popular", "doc99999"));
+    ///
+    /// let response = tree.select_fid_with_proof("popular", "doc99999").unwrap();
+    /// assert!(!response.is_present());
+    /// assert!(response.verify_absent("popular", "doc99999"));
+    /// assert!(!response.verify_absent("popular", "doc00042"));
+    /// ```
+    pub fn select_fid_with_proof(
+        &self,
+        key: &str,
+        fid: &str,
+    ) -> Result<crate::response::FidQueryResponse, AccTreeError> {
+        for r in self.root_of(key) {
+            let mut path: Vec<(Hash, bool)> = Vec::new();
+            let Some(fids) = r.recurse_select_with_proof(key, &mut path) else {
+                continue;
+            };
+            if !crate::fid_tree::is_chunked(&fids) {
+                return Err(AccTreeError::InvalidInput(format!(
+                    "FID set of key '{}' is not chunked; use select_with_proof",
+                    key
+                )));
+            }
+            let answer = match crate::fid_tree::FidProof::new(&fids, fid) {
+                Some(proof) => crate::response::FidAnswer::Present(proof),
+                None => crate::response::FidAnswer::Absent(
+                    crate::fid_tree::FidAbsenceProof::new(&fids, fid)
+                        .expect("a chunked FID set is not empty"),
+                ),
+            };
+            let leaf_h = crate::utils::leaf_hash_chunked(key, &answer.fid_root(), 0, false);
+            return Ok(crate::response::FidQueryResponse {
+                answer,
+                merkle_proof: crate::merkle_proof::Proof::new(r.hash(), leaf_h, path),
+                accumulator: r.acc(),
+                acc_proof: crate::acc_proof::MembershipProof {
+                    witness: crate::witness_cache::compute_witness(&r.keys(), key)?,
+                },
+            });
+        }
+        Err(AccTreeError::KeyNotFound(key.to_string()))
     }
 
     /// Update a specific FID: replace old_fid with new_fid in the key's FID set.
+    /// Returns false (and changes nothing) if the key or FID is missing;
+    /// `try_update` reports which.
     pub fn update(&mut self, key: &str, old_fid: &str, new_fid: String) -> bool {
-        if let Some(root) = self.roots.iter_mut().find(|r| r.has_key(key)) {
-            root.update_fid(key, old_fid, new_fid)
-        } else {
-            false
+        self.try_update(key, old_fid, new_fid).is_ok()
+    }
+
+    /// Replace `old_fid` with `new_fid` in the FID set of a live `key`.
+    pub fn try_update(
+        &mut self,
+        key: &str,
+        old_fid: &str,
+        new_fid: String,
+    ) -> Result<(), AccTreeError> {
+        self.check_fid(key, old_fid)?;
+        let idx = self
+            .root_index(key)
+            .ok_or_else(|| AccTreeError::KeyNotFound(key.to_string()))?;
+        Arc::make_mut(&mut self.roots[idx]).update_fid(key, old_fid, new_fid);
+        Ok(())
+    }
+
+    /// Check that `key` is live and `fid` is one of its FIDs.
+    fn check_fid(&self, key: &str, fid: &str) -> Result<(), AccTreeError> {
+        let fids = self
+            .select(key)
+            .ok_or_else(|| AccTreeError::KeyNotFound(key.to_string()))?;
+        if !fids.contains(&fid.to_string()) {
+            return Err(AccTreeError::FidNotFound {
+                key: key.to_string(),
+                fid: fid.to_string(),
+            });
         }
+        Ok(())
     }
 
     /// Update with proof: returns an `UpdateResponse` capturing pre/post proofs
     /// and accumulator witnesses so a verifier can confirm that only one FID
     /// was replaced in the `key`'s FID set and the rest of the tree is unchanged.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// tree.insert("bob".to_string(), "doc2".to_string());
+    /// let response = tree.update_with_proof("alice", "doc1", "doc3".to_string()).unwrap();
+    ///
+    /// let response: UpdateResponse = from_wire(&to_wire(&response)).unwrap();
+    /// assert!(response.verify_update());
+    /// assert!(tree.update_with_proof("alice", "doc1", "doc4".to_string()).is_err());
+    /// ```
     pub fn update_with_proof(
         &mut self,
         key: &str,
         old_fid: &str,
         new_fid: String,
-    ) -> Result<crate::response::UpdateResponse, String> {
-        // obtain pre-update proof (must exist)
-        let pre_qr = self.select_with_proof(key);
+    ) -> Result<crate::response::UpdateResponse, AccTreeError> {
+        let started = std::time::Instant::now();
+        // the key and old_fid must exist before proofs are built
+        self.check_fid(key, old_fid)?;
+        let pre_qr = self.prove_point(key)?;
         let old_fids = pre_qr.fids.clone();
-        if old_fids.is_none() {
-            return Err(format!("key '{}' not found for update", key));
-        }
-        // check if the old_fid exists in the set
-        if !old_fids.as_ref().unwrap().contains(&old_fid.to_string()) {
-            return Err(format!(
-                "old_fid '{}' not found in key '{}' for update",
-                old_fid, key
-            ));
-        }
         // capture pre acc/root
         let pre_acc = pre_qr.accumulator;
         let pre_acc_witness = match pre_qr.acc_proof {
@@ -235,69 +2230,170 @@ impl AccumulatorTree {
         let pre_proof = pre_qr.merkle_proof;
 
         // perform the update
-        if !self.update(key, old_fid, new_fid.clone()) {
-            return Err("update failed".to_string());
-        }
+        self.try_update(key, old_fid, new_fid.clone())?;
 
         // obtain post-update proof
-        let post_qs = self.select_with_proof(key);
-        if post_qs.fids.is_none() {
-            return Err("post-update: key missing after update".to_string());
-        }
-        let new_fids = post_qs.fids.clone().unwrap();
-        let post_proof = post_qs.merkle_proof.expect("post proof present");
-        let post_acc = post_qs.accumulator.expect("post acc present");
-        let post_acc_proof = match post_qs.acc_proof {
-            Some(crate::acc_proof::AccProof::Membership(mp)) => mp,
-            _ => panic!("post acc witness present"),
+        let post_qs = self.prove_point(key)?;
+        let (Some(new_fids), Some(post_proof), Some(post_acc)) =
+            (post_qs.fids, post_qs.merkle_proof, post_qs.accumulator)
+        else {
+            return Err(AccTreeError::ProofGenFailed(
+                "post-update: key missing after update".to_string(),
+            ));
+        };
+        let Some(crate::acc_proof::AccProof::Membership(post_acc_proof)) = post_qs.acc_proof else {
+            return Err(AccTreeError::ProofGenFailed(
+                "post-update: no membership witness".to_string(),
+            ));
         };
         let pre_acc_proof =
             pre_acc_witness.map(|w| crate::acc_proof::MembershipProof { witness: w });
 
-        Ok(crate::response::UpdateResponse::new(
-            key.to_string(),
-            old_fid.to_string(),
-            new_fid,
-            old_fids,
-            new_fids,
-            pre_proof,
-            pre_acc,
-            pre_acc_proof,
-            post_proof,
-            post_acc,
-            post_acc_proof,
-        ))
+        crate::response::UpdateResponse::builder()
+            .key(key.to_string())
+            .old_fid(old_fid.to_string())
+            .new_fid(new_fid)
+            .old_fids(old_fids)
+            .new_fids(new_fids)
+            .pre_merkle_proof(pre_proof)
+            .pre_accumulator(pre_acc)
+            .pre_acc_proof(pre_acc_proof)
+            .post_merkle_proof(post_proof)
+            .post_accumulator(post_acc)
+            .post_acc_proof(post_acc_proof)
+            .build()
+            .map(|response| self.instrument(crate::metrics::ProofOp::Update, started, response))
+            .map_err(AccTreeError::ProofGenFailed)
+    }
+
+    /// `insert` of a typed key and value, stored as `key.tree_key()` and
+    /// `value.to_fid()` (see `key`).
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// let id = [7u8; 32];
+    /// tree.insert_typed(&id, &b"\xff\x00blob".to_vec());
+    /// let values: Vec<Vec<u8>> = tree.select_typed(&id).unwrap();
+    /// assert_eq!(values, vec![b"\xff\x00blob".to_vec()]);
+    ///
+    /// let response = tree.select_with_proof(&id.tree_key()).unwrap();
+    /// assert!(response.verify_by_mode(&id.tree_key()));
+    /// ```
+    pub fn insert_typed<K: crate::key::AccKey + ?Sized, V: crate::key::AccValue>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> bool {
+        self.insert(key.tree_key(), value.to_fid())
+    }
+
+    /// Values stored under a typed key, sorted by FID. FIDs that do not
+    /// decode as `V` are skipped.
+    pub fn select_typed<K: crate::key::AccKey + ?Sized, V: crate::key::AccValue>(
+        &self,
+        key: &K,
+    ) -> Option<Vec<V>> {
+        let fids = self.select(&key.tree_key())?;
+        let mut fids: Vec<&String> = fids.iter().collect();
+        fids.sort();
+        Some(fids.iter().filter_map(|fid| V::from_fid(fid)).collect())
+    }
+
+    /// `delete` of a typed key and value.
+    pub fn delete_typed<K: crate::key::AccKey + ?Sized, V: crate::key::AccValue>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> bool {
+        self.delete(&key.tree_key(), &value.to_fid())
     }
 
     /// Delete a specific FID from the FID set of a key.
     /// If the FID set becomes empty, the leaf is tombstoned (marked as deleted).
-    pub fn delete(&mut self, key: &str, fid: &str) {
-        if let Some(root) = self.roots.iter_mut().find(|r| r.has_key(key)) {
-            root.delete_fid(key, fid);
+    /// Returns false (and changes nothing) if the key or FID is missing;
+    /// `try_delete` reports which.
+    pub fn delete(&mut self, key: &str, fid: &str) -> bool {
+        self.try_delete(key, fid).is_ok()
+    }
+
+    /// Delete `fid` from the FID set of a live `key`, tombstoning the key if
+    /// it was the last one. The forest is then compacted if tombstones reached
+    /// the compaction threshold.
+    ///
+    /// ```
+    /// use accumulator_tree::{AccTreeError, AccumulatorTree};
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// assert_eq!(
+    ///     tree.try_delete("alice", "doc2"),
+    ///     Err(AccTreeError::FidNotFound {
+    ///         key: "alice".to_string(),
+    ///         fid: "doc2".to_string(),
+    ///     })
+    /// );
+    /// assert!(tree.try_delete("alice", "doc1").is_ok());
+    /// assert_eq!(
+    ///     tree.try_delete("alice", "doc1"),
+    ///     Err(AccTreeError::KeyNotFound("alice".to_string()))
+    /// );
+    /// ```
+    pub fn try_delete(&mut self, key: &str, fid: &str) -> Result<(), AccTreeError> {
+        self.delete_in_place(key, fid)?;
+        self.maybe_compact();
+        Ok(())
+    }
+
+    /// `try_delete` without auto-compaction, keeping the tombstone in place.
+    fn delete_in_place(&mut self, key: &str, fid: &str) -> Result<(), AccTreeError> {
+        self.check_fid(key, fid)?;
+        let idx = self
+            .root_index(key)
+            .ok_or_else(|| AccTreeError::KeyNotFound(key.to_string()))?;
+        // Deleting the last FID drops the key from a merged root's key set
+        let last = self.select(key).is_some_and(|fids| fids.len() == 1);
+        if last && self.roots[idx].level() > 0 {
+            crate::setup::ensure_ready()?;
+        }
+        let root = &mut self.roots[idx];
+        Arc::make_mut(root).delete_fid(key, fid);
+        if let Some((_, true)) = root.leaf_state(key) {
+            self.record_deletion(key);
+            self.update_global_acc(&[], &[key]);
         }
+        Ok(())
     }
 
     /// Delete with proof: returns a `DeleteResponse` capturing pre/post proofs.
     /// Deletes a specific FID from the key's FID set. If the set becomes empty,
     /// the leaf is tombstoned and the tree integrity (path siblings) is preserved.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// tree.insert("alice".to_string(), "doc2".to_string());
+    /// let response = tree.delete_with_proof("alice", "doc1").unwrap();
+    ///
+    /// let response: DeleteResponse = from_wire(&to_wire(&response)).unwrap();
+    /// assert!(response.verify_delete());
+    /// assert_eq!(response.new_fids.len(), 1);
+    /// ```
     pub fn delete_with_proof(
         &mut self,
         key: &str,
         fid: &str,
-    ) -> Result<crate::response::DeleteResponse, String> {
-        // capture pre-state proof (must exist)
-        let pre_qr = self.select_with_proof(key);
+    ) -> Result<crate::response::DeleteResponse, AccTreeError> {
+        let started = std::time::Instant::now();
+        // the key and fid must exist before proofs are built
+        self.check_fid(key, fid)?;
+        let pre_qr = self.prove_point(key)?;
         let old_fids = pre_qr.fids.clone();
-        if old_fids.is_none() {
-            return Err(format!("key '{}' not found for delete", key));
-        }
-        // check if the fid exists in the set
-        if !old_fids.as_ref().unwrap().contains(&fid.to_string()) {
-            return Err(format!(
-                "fid '{}' not found in key '{}' for delete",
-                fid, key
-            ));
-        }
         let pre_proof = pre_qr.merkle_proof;
         let pre_acc = pre_qr.accumulator;
         let pre_acc_proof = match pre_qr.acc_proof {
@@ -305,11 +2401,12 @@ impl AccumulatorTree {
             _ => None,
         };
 
-        // perform deletion
-        self.delete(key, fid);
+        // perform deletion; the post-state accumulator must be current
+        self.delete_in_place(key, fid)?;
+        self.refresh();
 
         // find post-state proof (may still be active if other FIDs remain, or tombstoned if empty)
-        for r in self.roots.iter() {
+        for r in self.root_of(key) {
             let mut path: Vec<(Hash, bool)> = Vec::new();
             if let Some(post_fids) = r.recurse_select_proof_including_deleted(key, &mut path) {
                 let root_h = r.hash();
@@ -325,22 +2422,28 @@ impl AccumulatorTree {
                 };
                 let post_proof = crate::merkle_proof::Proof::new(root_h, leaf_h, path);
                 let post_acc = r.acc();
-                return Ok(crate::response::DeleteResponse::new(
-                    key.to_string(),
-                    fid.to_string(), // deleted_fid
-                    old_fids,        // old_fids
-                    post_fids,       // new_fids
-                    pre_proof,
-                    pre_acc,
-                    pre_acc_proof,
-                    post_proof,
-                    post_acc,
-                ));
+                return crate::response::DeleteResponse::builder()
+                    .key(key.to_string())
+                    .deleted_fid(fid.to_string())
+                    .old_fids(old_fids)
+                    .new_fids(post_fids)
+                    .pre_merkle_proof(pre_proof)
+                    .pre_accumulator(pre_acc)
+                    .pre_acc_proof(pre_acc_proof)
+                    .post_merkle_proof(post_proof)
+                    .post_accumulator(post_acc)
+                    .build()
+                    .map(|response| {
+                        self.instrument(crate::metrics::ProofOp::Delete, started, response)
+                    })
+                    .map_err(AccTreeError::ProofGenFailed);
             }
         }
 
         // If we reach here, the leaf was not found (unexpected)
-        Err("post-delete: key not found".to_string())
+        Err(AccTreeError::ProofGenFailed(
+            "post-delete: key not found".to_string(),
+        ))
     }
 
     // ==========================================
@@ -348,7 +2451,7 @@ impl AccumulatorTree {
     // ==========================================
 
     #[cfg(test)]
-    pub fn test_merge_nodes(left: Box<Node>, right: Box<Node>) -> Box<Node> {
+    pub fn test_merge_nodes(left: Arc<Node>, right: Arc<Node>) -> Arc<Node> {
         Node::merge(left, right, None)
     }
 
@@ -363,7 +2466,173 @@ impl AccumulatorTree {
     }
 
     #[cfg(test)]
-    pub fn test_revive_recursive(node: Box<Node>, key: &str, fid: &str) -> Box<Node> {
+    pub fn test_revive_recursive(node: Node, key: &str, fid: &str) -> Arc<Node> {
         node.revive(key, fid)
     }
 }
+
+/// Leaf of `key` (live or tombstoned) under one of `roots`, with its path.
+pub(crate) fn leaf_witness_in(
+    roots: &[Arc<Node>],
+    key: &str,
+) -> Option<crate::response::LeafWitness> {
+    for r in roots {
+        let mut path: Vec<(Hash, bool)> = Vec::new();
+        if r.recurse_select_proof_including_deleted(key, &mut path)
+            .is_some()
+        {
+            let (fids, deleted) = r.leaf_state(key)?;
+            let leaf_h = crate::utils::leaf_hash(key, &fids, 0, deleted);
+            return Some(crate::response::LeafWitness {
+                fids,
+                deleted,
+                merkle_proof: crate::merkle_proof::Proof::new(r.hash(), leaf_h, path),
+            });
+        }
+    }
+    None
+}
+
+/// Where a unit (an existing root or a new leaf) ends up after `normalize`.
+enum Shape {
+    Unit(usize),
+    Pair(Box<Shape>, Box<Shape>),
+}
+
+impl Shape {
+    /// Units under this shape, left to right.
+    fn units(&self) -> Vec<usize> {
+        match self {
+            Shape::Unit(i) => vec![*i],
+            Shape::Pair(left, right) => {
+                let mut units = left.units();
+                units.extend(right.units());
+                units
+            }
+        }
+    }
+
+    /// Move the units of this shape out of `roots` and `leaves`. Unit `i` is
+    /// `roots[i]` if there is one and otherwise leaf `i - roots.len()`;
+    /// all-leaf subtrees are kept together to be built in one go.
+    fn take(
+        self,
+        roots: &mut [Option<Arc<Node>>],
+        leaves: &mut [Option<(String, Set<String>)>],
+    ) -> Part {
+        let units = self.units();
+        if units.iter().all(|&i| i >= roots.len()) {
+            return Part::Leaves(
+                units
+                    .iter()
+                    .map(|&i| leaves[i - roots.len()].take().unwrap())
+                    .collect(),
+            );
+        }
+        match self {
+            Shape::Unit(i) => Part::Root(roots[i].take().unwrap()),
+            Shape::Pair(left, right) => Part::Pair(
+                Box::new(left.take(roots, leaves)),
+                Box::new(right.take(roots, leaves)),
+            ),
+        }
+    }
+}
+
+/// A `Shape` holding its units, so its sides can be built on other threads.
+enum Part {
+    Root(Arc<Node>),
+    Leaves(Vec<(String, Set<String>)>),
+    Pair(Box<Part>, Box<Part>),
+}
+
+impl Part {
+    /// Build the node. The two sides of a pair, and the halves of a subtree
+    /// of new leaves, are built concurrently.
+    fn build(self) -> Arc<Node> {
+        match self {
+            Part::Root(root) => root,
+            Part::Leaves(chunk) => {
+                let grain = (chunk.len() / (crate::utils::current_num_threads() * 4)).max(1);
+                crate::node::Subtree::build(&chunk, grain).into_node()
+            }
+            Part::Pair(left, right) => {
+                let (left, right) = crate::utils::join(|| left.build(), || right.build());
+                Node::merge(left, right, None)
+            }
+        }
+    }
+}
+
+/// Build the roots for `shapes` (see `Shape::take`), independent roots
+/// concurrently.
+fn build_shapes(
+    shapes: Vec<Shape>,
+    roots: &mut [Option<Arc<Node>>],
+    leaves: &mut [Option<(String, Set<String>)>],
+) -> Vec<Arc<Node>> {
+    #[cfg(feature = "parallel")]
+    use rayon::prelude::*;
+    let parts: Vec<Part> = shapes
+        .into_iter()
+        .map(|shape| shape.take(roots, leaves))
+        .collect();
+    ark_std::cfg_into_iter!(parts).map(Part::build).collect()
+}
+
+/// Roots of a forest over `leaves` built bottom up, each node once. In
+/// insertion order the layout is the one `normalize` reaches; `sorted`
+/// leaves (already in key order) go where sorted inserts leave them, the
+/// smallest keys in the largest (last) root and each root in order.
+fn build_roots(leaves: Vec<(String, Set<String>)>, sorted: bool) -> Vec<Arc<Node>> {
+    let shapes = replay_normalize(&vec![0; leaves.len()], 0);
+    let mut slots: Vec<Option<(String, Set<String>)>> = if sorted {
+        let mut slots = vec![None; leaves.len()];
+        for (unit, leaf) in shapes.iter().rev().flat_map(Shape::units).zip(leaves) {
+            slots[unit] = Some(leaf);
+        }
+        slots
+    } else {
+        leaves.into_iter().map(Some).collect()
+    };
+    build_shapes(shapes, &mut [], &mut slots)
+}
+
+/// Largest live key count among the roots `normalize` merges when units of
+/// the given (level, live key count) are pushed as in `replay_normalize`;
+/// 0 if nothing merges. Every accumulator built along the way is over a
+/// subset of one of these roots, so this is the degree the merges need.
+pub(crate) fn merge_degree(units: &[(usize, u64)], first_new: usize) -> u64 {
+    let levels: Vec<usize> = units.iter().map(|&(level, _)| level).collect();
+    replay_normalize(&levels, first_new)
+        .iter()
+        .filter(|shape| matches!(shape, Shape::Pair(..)))
+        .map(|shape| shape.units().iter().map(|&i| units[i].1).sum())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Replay `normalize` on units of the given levels: units before `first_new`
+/// are the current roots, and each later unit is pushed followed by a
+/// normalize, as `insert` does. `normalize` merges the newest pair of equal
+/// roots first, so units do not end up in push order. Returns the resulting
+/// roots in forest order.
+fn replay_normalize(levels: &[usize], first_new: usize) -> Vec<Shape> {
+    let mut roots: Vec<(usize, Shape)> = (0..first_new)
+        .map(|i| (levels[i], Shape::Unit(i)))
+        .collect();
+    for (i, &level) in levels.iter().enumerate().skip(first_new) {
+        roots.push((level, Shape::Unit(i)));
+        roots.sort_by_key(|(level, _)| *level);
+        let mut stack: Vec<(usize, Shape)> = Vec::new();
+        for mut cur in roots.drain(..) {
+            while stack.last().is_some_and(|top| top.0 == cur.0) {
+                let (level, left) = stack.pop().unwrap();
+                cur = (level + 1, Shape::Pair(Box::new(left), Box::new(cur.1)));
+            }
+            stack.push(cur);
+        }
+        roots = stack;
+    }
+    roots.into_iter().map(|(_, shape)| shape).collect()
+}
@@ -0,0 +1,647 @@
+//! All-or-nothing batches of tree mutations.
+//!
+//! A `TreeTxn` stages inserts, updates and deletes against the live forest
+//! without touching it. Roots are copied on write: a root is cloned the first
+//! time a staged operation modifies it, so untouched roots cost nothing and the
+//! forest stays readable (through the transaction) in its staged state. Commit
+//! swaps the staged roots in at once; abort (or drop) discards them.
+
+use crate::canonical::CanonicalEncode;
+use crate::error::AccTreeError;
+use crate::node::Node;
+use crate::response::{KeyTransition, TransactionResponse};
+use crate::tree::{AccumulatorTree, merge_degree};
+use accumulator_ads::Set;
+use std::sync::Arc;
+
+/// A staged mutation, recorded only if it changed the staged state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxnOp {
+    Insert {
+        key: String,
+        fid: String,
+    },
+    Update {
+        key: String,
+        old_fid: String,
+        new_fid: String,
+    },
+    Delete {
+        key: String,
+        fid: String,
+    },
+}
+
+impl TxnOp {
+    /// Key the operation applies to.
+    pub fn key(&self) -> &str {
+        match self {
+            TxnOp::Insert { key, .. } | TxnOp::Update { key, .. } | TxnOp::Delete { key, .. } => {
+                key
+            }
+        }
+    }
+
+    /// Apply the operation to a leaf state `(fids, tombstoned)` (None: no leaf)
+    /// with the same semantics as the tree. Returns None if it does not apply.
+    pub fn replay(&self, state: Option<(Set<String>, bool)>) -> Option<(Set<String>, bool)> {
+        let live = state.filter(|(_, deleted)| !*deleted).map(|(fids, _)| fids);
+        match (self, live) {
+            (TxnOp::Insert { fid, .. }, Some(fids)) => {
+                Some((fids.union(&Set::from_vec(vec![fid.clone()])), false))
+            }
+            (TxnOp::Insert { fid, .. }, None) => Some((Set::from_vec(vec![fid.clone()]), false)),
+            (
+                TxnOp::Update {
+                    old_fid, new_fid, ..
+                },
+                Some(fids),
+            ) if fids.contains(old_fid) => {
+                let fids = fids
+                    .difference(&Set::from_vec(vec![old_fid.clone()]))
+                    .union(&Set::from_vec(vec![new_fid.clone()]));
+                Some((fids, false))
+            }
+            (TxnOp::Delete { fid, .. }, Some(fids)) if fids.contains(fid) => {
+                let fids = fids.difference(&Set::from_vec(vec![fid.clone()]));
+                let deleted = fids.is_empty();
+                Some((fids, deleted))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl CanonicalEncode for TxnOp {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            TxnOp::Insert { key, fid } => {
+                out.push(0);
+                key.encode_canonical(out);
+                fid.encode_canonical(out);
+            }
+            TxnOp::Update {
+                key,
+                old_fid,
+                new_fid,
+            } => {
+                out.push(1);
+                key.encode_canonical(out);
+                old_fid.encode_canonical(out);
+                new_fid.encode_canonical(out);
+            }
+            TxnOp::Delete { key, fid } => {
+                out.push(2);
+                key.encode_canonical(out);
+                fid.encode_canonical(out);
+            }
+        }
+    }
+}
+
+/// A root of the staged forest: still shared with the tree, or a private copy.
+enum Slot {
+    /// Unmodified root, by index into the tree's roots
+    Base(usize),
+    /// Root that has been copied by this transaction
+    Owned(Arc<Node>),
+    /// Root that has been created (merged or a new leaf) by this transaction;
+    /// its keys are reindexed on commit
+    Created(Arc<Node>),
+}
+
+/// Staged batch of mutations over an `AccumulatorTree`.
+pub struct TreeTxn<'a> {
+    tree: &'a mut AccumulatorTree,
+    slots: Vec<Slot>,
+    ops: Vec<TxnOp>,
+}
+
+impl<'a> TreeTxn<'a> {
+    pub(crate) fn new(tree: &'a mut AccumulatorTree) -> Self {
+        let slots = (0..tree.roots.len()).map(Slot::Base).collect();
+        Self {
+            tree,
+            slots,
+            ops: Vec::new(),
+        }
+    }
+
+    fn node(&self, i: usize) -> &Node {
+        match &self.slots[i] {
+            Slot::Base(idx) => &self.tree.roots[*idx],
+            Slot::Owned(node) | Slot::Created(node) => node,
+        }
+    }
+
+    /// Copy-on-write access to the root in slot `i`.
+    fn node_mut(&mut self, i: usize) -> &mut Node {
+        if let Slot::Base(idx) = self.slots[i] {
+            self.slots[i] = Slot::Owned(self.tree.roots[idx].clone());
+        }
+        match &mut self.slots[i] {
+            Slot::Owned(node) | Slot::Created(node) => Arc::make_mut(node),
+            Slot::Base(_) => unreachable!("slot was just copied"),
+        }
+    }
+
+    /// Take ownership of the root in slot `i`, copying it if still shared.
+    fn take_owned(&mut self, i: usize) -> Arc<Node> {
+        match self.slots.remove(i) {
+            Slot::Base(idx) => self.tree.roots[idx].clone(),
+            Slot::Owned(node) | Slot::Created(node) => node,
+        }
+    }
+
+    fn find_live(&self, key: &str) -> Option<usize> {
+        // Stale key sets may still list tombstoned keys, so check the leaf
+        (0..self.slots.len()).find(|&i| self.node(i).select(key).is_some())
+    }
+
+    fn level(&self, slot: &Slot) -> usize {
+        match slot {
+            Slot::Base(idx) => self.tree.roots[*idx].level(),
+            Slot::Owned(node) | Slot::Created(node) => node.level(),
+        }
+    }
+
+    /// Same merge order as `AccumulatorTree::normalize`, so a committed batch
+    /// yields exactly the forest the operations would have built one by one.
+    fn normalize(&mut self) {
+        let mut pending = std::mem::take(&mut self.slots);
+        pending.sort_by_key(|s| self.level(s));
+        let sorted_by = self.tree.sorted_by();
+
+        for slot in pending {
+            self.slots.push(slot);
+            while let [.., left, right] = self.slots.as_slice() {
+                if self.level(left) != self.level(right) {
+                    break;
+                }
+                let n = self.slots.len();
+                let right = self.take_owned(n - 1);
+                let left = self.take_owned(n - 2);
+                let merged = match &sorted_by {
+                    Some(order) => Node::merge_sorted(left, right, order),
+                    None => Node::merge(left, right, None),
+                };
+                self.slots.push(Slot::Created(merged));
+            }
+        }
+    }
+
+    /// Stage an insert of `fid` under `key` (reviving a tombstoned key).
+    /// Stages nothing if the tree's revive policy refuses the insert or the
+    /// public parameters cannot serve it; `try_insert` reports why.
+    pub fn insert(&mut self, key: String, fid: String) {
+        let _ = self.try_insert(key, fid);
+    }
+
+    /// Stage an insert as `AccumulatorTree::try_insert` would apply it.
+    pub fn try_insert(&mut self, key: String, fid: String) -> Result<(), AccTreeError> {
+        let leaf = (0..self.slots.len())
+            .find_map(|i| self.node(i).leaf_state(&key))
+            .map(|(_, deleted)| deleted);
+        self.tree
+            .revive_policy()
+            .check_insert(&key, leaf)
+            .map_err(AccTreeError::PolicyRejected)?;
+
+        if let Some(i) = self.find_live(&key) {
+            if self.node_mut(i).insert_fid(&key, fid.clone()) {
+                self.ops.push(TxnOp::Insert { key, fid });
+            }
+            return Ok(());
+        }
+
+        let tombstone = (0..self.slots.len()).find(|&i| self.node(i).leaf_state(&key).is_some());
+        if !self.slots.is_empty() {
+            let mut units: Vec<(usize, u64)> = (0..self.slots.len())
+                .map(|i| (self.node(i).level(), self.node(i).live_key_count()))
+                .collect();
+            let needed = match tombstone {
+                // Revived in place; levels are unique, so nothing merges
+                Some(i) => units[i].1 + 1,
+                None => {
+                    units.push((0, 1));
+                    merge_degree(&units, self.slots.len())
+                }
+            };
+            crate::setup::check_available_degree(needed as usize)?;
+        }
+
+        if let Some(i) = tombstone {
+            let created = matches!(self.slots[i], Slot::Created(_));
+            let revived = Arc::unwrap_or_clone(self.take_owned(i)).revive(&key, &fid);
+            self.slots.push(if created {
+                Slot::Created(revived)
+            } else {
+                Slot::Owned(revived)
+            });
+        } else {
+            self.slots.push(Slot::Created(Arc::new(Node::leaf(
+                key.clone(),
+                Set::from_vec(vec![fid.clone()]),
+                false,
+            ))));
+        }
+        self.normalize();
+        self.ops.push(TxnOp::Insert { key, fid });
+        Ok(())
+    }
+
+    /// Stage replacing `old_fid` with `new_fid` under `key`.
+    /// Returns false (and stages nothing) if `key` does not hold `old_fid`.
+    pub fn update(&mut self, key: &str, old_fid: &str, new_fid: String) -> bool {
+        let Some(i) = self.find_live(key) else {
+            return false;
+        };
+        if !self
+            .node(i)
+            .select(key)
+            .is_some_and(|f| f.contains(&old_fid.to_string()))
+        {
+            return false;
+        }
+        let changed = self.node_mut(i).update_fid(key, old_fid, new_fid.clone());
+        if changed {
+            self.ops.push(TxnOp::Update {
+                key: key.to_string(),
+                old_fid: old_fid.to_string(),
+                new_fid,
+            });
+        }
+        changed
+    }
+
+    /// Stage deleting `fid` from `key`, tombstoning the leaf if its set empties.
+    /// Returns false (and stages nothing) if `key` does not hold `fid`.
+    pub fn delete(&mut self, key: &str, fid: &str) -> bool {
+        let Some(i) = self.find_live(key) else {
+            return false;
+        };
+        if !self
+            .node(i)
+            .select(key)
+            .is_some_and(|f| f.contains(&fid.to_string()))
+        {
+            return false;
+        }
+        self.node_mut(i).delete_fid(key, fid);
+        self.ops.push(TxnOp::Delete {
+            key: key.to_string(),
+            fid: fid.to_string(),
+        });
+        true
+    }
+
+    /// Stage a recorded operation. Returns whether it applied.
+    pub fn apply_op(&mut self, op: &TxnOp) -> bool {
+        let before = self.ops.len();
+        match op {
+            TxnOp::Insert { key, fid } => self.insert(key.clone(), fid.clone()),
+            TxnOp::Update {
+                key,
+                old_fid,
+                new_fid,
+            } => {
+                self.update(key, old_fid, new_fid.clone());
+            }
+            TxnOp::Delete { key, fid } => {
+                self.delete(key, fid);
+            }
+        }
+        self.ops.len() > before
+    }
+
+    /// FID set of `key` in the staged state.
+    pub fn select(&self, key: &str) -> Option<Set<String>> {
+        (0..self.slots.len()).find_map(|i| self.node(i).select(key))
+    }
+
+    /// Operations staged so far, in order.
+    pub fn ops(&self) -> &[TxnOp] {
+        &self.ops
+    }
+
+    /// Number of roots copied or created so far.
+    pub fn dirty_roots(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|s| !matches!(s, Slot::Base(_)))
+            .count()
+    }
+
+    /// Apply every staged operation to the tree at once and return them.
+    pub fn commit(self) -> Vec<TxnOp> {
+        self.apply().1
+    }
+
+    /// Commit and return a single proof covering the whole batch.
+    ///
+    /// ```
+    /// use accumulator_tree::prelude::*;
+    /// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+    ///
+    /// let mut tree = AccumulatorTree::new();
+    /// tree.insert("alice".to_string(), "doc1".to_string());
+    /// let mut txn = tree.begin_transaction();
+    /// txn.insert("bob".to_string(), "doc2".to_string());
+    /// txn.update("alice", "doc1", "doc3".to_string());
+    /// let response = txn.commit_with_proof();
+    ///
+    /// let response: TransactionResponse = from_wire(&to_wire(&response)).unwrap();
+    /// assert!(response.verify());
+    /// assert_eq!(response.ops.len(), 2);
+    /// ```
+    pub fn commit_with_proof(self) -> TransactionResponse {
+        let mut keys: Vec<String> = self.ops.iter().map(|op| op.key().to_string()).collect();
+        keys.sort();
+        keys.dedup();
+
+        let pre_roots = self.tree.root_entries();
+        let pre_global_acc = self.tree.global_acc();
+        let pre: Vec<_> = keys.iter().map(|k| self.tree.leaf_witness(k)).collect();
+
+        let (tree, ops) = self.apply();
+
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut transitions = Vec::with_capacity(keys.len());
+        for (key, pre) in keys.into_iter().zip(pre) {
+            let post = tree
+                .leaf_witness(&key)
+                .expect("every touched key has a leaf after commit");
+            let was_live = pre.as_ref().is_some_and(|w| !w.deleted);
+            match (was_live, !post.deleted) {
+                (false, true) => added.push(key.clone()),
+                (true, false) => removed.push(key.clone()),
+                _ => {}
+            }
+            transitions.push(KeyTransition { key, pre, post });
+        }
+
+        TransactionResponse {
+            ops,
+            pre_roots,
+            post_roots: tree.root_entries(),
+            pre_global_acc,
+            post_global_acc: tree.global_acc(),
+            added_keys: Set::from_vec(added),
+            removed_keys: Set::from_vec(removed),
+            transitions,
+        }
+    }
+
+    /// Swap the staged roots into the tree and update its tombstone bookkeeping.
+    fn apply(self) -> (&'a mut AccumulatorTree, Vec<TxnOp>) {
+        let mut base: Vec<Option<Arc<Node>>> = std::mem::take(&mut self.tree.roots)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut created = Vec::new();
+        let roots = self
+            .slots
+            .into_iter()
+            .enumerate()
+            .map(|(i, slot)| match slot {
+                Slot::Base(idx) => base[idx].take().expect("each base root is used once"),
+                Slot::Owned(node) => node,
+                Slot::Created(node) => {
+                    created.push(i);
+                    node
+                }
+            })
+            .collect();
+        self.tree.roots = roots;
+        self.tree.index_roots(created);
+        self.tree.forget_global_acc();
+
+        for op in &self.ops {
+            match op {
+                TxnOp::Insert { key, .. } => self.tree.clear_deletion(key),
+                TxnOp::Delete { key, .. } => {
+                    if let Some((_, true)) = self.tree.leaf_state(key) {
+                        self.tree.record_deletion(key);
+                    }
+                }
+                TxnOp::Update { .. } => {}
+            }
+        }
+        (self.tree, self.ops)
+    }
+
+    /// Discard every staged operation, leaving the tree untouched.
+    pub fn abort(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::LookupResult;
+
+    fn root_hashes(tree: &AccumulatorTree) -> Vec<crate::Hash> {
+        tree.roots.iter().map(|r| r.hash()).collect()
+    }
+
+    fn sample_tree() -> AccumulatorTree {
+        let mut tree = AccumulatorTree::new();
+        for (k, f) in [
+            ("a", "fa"),
+            ("b", "fb"),
+            ("c", "fc"),
+            ("d", "fd"),
+            ("e", "fe"),
+        ] {
+            tree.insert(k.to_string(), f.to_string());
+        }
+        tree
+    }
+
+    #[test]
+    fn test_commit_matches_sequential_operations() {
+        crate::setup::init_test_parameters();
+        let mut expected = sample_tree();
+        expected.insert("f".to_string(), "ff".to_string());
+        expected.update("a", "fa", "fa2".to_string());
+        expected.delete("c", "fc");
+        expected.insert("a".to_string(), "fa3".to_string());
+
+        let mut tree = sample_tree();
+        let mut txn = tree.begin_transaction();
+        txn.insert("f".to_string(), "ff".to_string());
+        assert!(txn.update("a", "fa", "fa2".to_string()));
+        assert!(txn.delete("c", "fc"));
+        txn.insert("a".to_string(), "fa3".to_string());
+        assert!(!txn.delete("zzz", "f"));
+        assert_eq!(
+            txn.select("a"),
+            Some(Set::from_vec(vec!["fa2".to_string(), "fa3".to_string()]))
+        );
+        assert_eq!(txn.ops().len(), 4);
+        txn.commit();
+
+        assert_eq!(root_hashes(&tree), root_hashes(&expected));
+        assert!(tree.select_status("c").is_tombstoned());
+    }
+
+    #[test]
+    fn test_apply_ops_matches_sequential_operations() {
+        crate::setup::init_test_parameters();
+        let op = |kind: &str, key: &str, a: &str, b: &str| match kind {
+            "ins" => TxnOp::Insert {
+                key: key.to_string(),
+                fid: a.to_string(),
+            },
+            "upd" => TxnOp::Update {
+                key: key.to_string(),
+                old_fid: a.to_string(),
+                new_fid: b.to_string(),
+            },
+            _ => TxnOp::Delete {
+                key: key.to_string(),
+                fid: a.to_string(),
+            },
+        };
+        let ops = vec![
+            op("ins", "f", "ff", ""),
+            op("upd", "a", "fa", "fa2"),
+            op("ins", "a", "fa3", ""),
+            op("del", "c", "fc", ""),
+            op("del", "b", "fb", ""),
+            op("ins", "g", "fg", ""),
+            op("del", "a", "fa3", ""),
+            op("ins", "b", "fb2", ""),
+            op("del", "zzz", "f", ""),
+            op("upd", "d", "missing", "x"),
+            op("ins", "e", "fe", ""),
+        ];
+
+        let mut expected = sample_tree();
+        for op in &ops {
+            match op {
+                TxnOp::Insert { key, fid } => {
+                    expected.insert(key.clone(), fid.clone());
+                }
+                TxnOp::Update {
+                    key,
+                    old_fid,
+                    new_fid,
+                } => {
+                    expected.update(key, old_fid, new_fid.clone());
+                }
+                TxnOp::Delete { key, fid } => {
+                    expected.delete(key, fid);
+                }
+            }
+        }
+
+        let mut tree = sample_tree();
+        let before = root_hashes(&tree);
+        let report = tree.apply_ops(ops);
+        assert_eq!(root_hashes(&tree), root_hashes(&expected));
+        assert_eq!(report.applied, 9);
+        assert_eq!(report.rejected.len(), 2);
+        assert_eq!(report.changed_keys, ["f", "a", "c", "b", "g"]);
+        assert_eq!(report.new_leaves, 2);
+        // a, b and c share the root of four; e is untouched
+        assert_eq!(report.transitions.len(), 1);
+        assert_eq!(
+            report.transitions[0].before.1,
+            before[report.transitions[0].index]
+        );
+        assert_eq!(report.roots_after, tree.root_entries());
+        assert!(tree.select_status("c").is_tombstoned());
+        assert!(!tree.has_stale_nodes());
+        assert_eq!(tree.publish_epoch(), expected.publish_epoch());
+    }
+
+    #[test]
+    fn test_abort_leaves_tree_untouched() {
+        crate::setup::init_test_parameters();
+        let mut tree = sample_tree();
+        let before = root_hashes(&tree);
+
+        let mut txn = tree.begin_transaction();
+        txn.insert("x".to_string(), "fx".to_string());
+        txn.delete("a", "fa");
+        txn.abort();
+
+        assert_eq!(root_hashes(&tree), before);
+        assert_eq!(tree.select_status("x"), LookupResult::Absent);
+        assert!(tree.select_status("a").is_live());
+    }
+
+    #[test]
+    fn test_untouched_roots_are_not_copied() {
+        crate::setup::init_test_parameters();
+        // 5 keys: roots of 4 and 1 leaves
+        let mut tree = sample_tree();
+        assert_eq!(tree.roots.len(), 2);
+
+        let mut txn = tree.begin_transaction();
+        assert!(txn.update("e", "fe", "fe2".to_string()));
+        assert_eq!(txn.dirty_roots(), 1);
+        txn.commit();
+        assert_eq!(
+            tree.select("e"),
+            Some(Set::from_vec(vec!["fe2".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_commit_with_proof_verifies() {
+        crate::setup::init_test_parameters();
+        let mut tree = sample_tree();
+        tree.delete("e", "fe");
+        let pre_commitment = tree.publish_epoch();
+
+        let mut txn = tree.begin_transaction();
+        txn.insert("f".to_string(), "ff".to_string());
+        txn.update("a", "fa", "fa2".to_string());
+        txn.delete("b", "fb");
+        txn.insert("e".to_string(), "fe2".to_string());
+        let resp = txn.commit_with_proof();
+        let post_commitment = tree.publish_epoch();
+
+        assert!(resp.verify());
+        assert_eq!(resp.pre_forest_hash(), pre_commitment.forest_hash);
+        assert_eq!(resp.post_forest_hash(), post_commitment.forest_hash);
+        assert_eq!(resp.pre_global_acc, pre_commitment.global_acc);
+        assert_eq!(resp.post_global_acc, post_commitment.global_acc);
+        assert_eq!(
+            resp.added_keys,
+            Set::from_vec(vec!["e".to_string(), "f".to_string()])
+        );
+        assert_eq!(resp.removed_keys, Set::from_vec(vec!["b".to_string()]));
+    }
+
+    #[test]
+    fn test_transaction_proof_rejects_tampering() {
+        crate::setup::init_test_parameters();
+        let mut tree = sample_tree();
+        let mut txn = tree.begin_transaction();
+        txn.insert("f".to_string(), "ff".to_string());
+        txn.delete("b", "fb");
+        let resp = txn.commit_with_proof();
+        assert!(resp.verify());
+
+        // Hiding an operation
+        let mut hidden = resp.clone();
+        hidden.ops.pop();
+        assert!(!hidden.verify());
+
+        // Claiming a different operation
+        let mut swapped = resp.clone();
+        swapped.ops[0] = TxnOp::Insert {
+            key: "f".to_string(),
+            fid: "other".to_string(),
+        };
+        assert!(!swapped.verify());
+
+        // Accumulator that does not match the key delta
+        let mut bad_acc = resp.clone();
+        bad_acc.post_global_acc = bad_acc.pre_global_acc;
+        assert!(!bad_acc.verify());
+    }
+}
@@ -5,6 +5,21 @@ use sha2::{Digest, Sha256};
 
 pub type Hash = [u8; 32];
 
+/// `rayon::join` with the `parallel` feature; without it, runs `a` then `b`.
+pub(crate) use accumulator_ads::acc::utils::join;
+
+/// Threads parallel work is spread over: the size of rayon's pool.
+#[cfg(feature = "parallel")]
+pub(crate) fn current_num_threads() -> usize {
+    rayon::current_num_threads()
+}
+
+/// Threads parallel work is spread over: 1 without the `parallel` feature.
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn current_num_threads() -> usize {
+    1
+}
+
 lazy_static! {
     // Empty hash now represents a default empty leaf node
     pub static ref EMPTY_HASH: Hash = leaf_hash("", &Set::new(), 0, false);
@@ -24,7 +39,13 @@ pub fn empty_acc() -> G1Affine {
 /// fids: Set of document IDs (sorted for determinism)
 /// level: Tree level (usually 0 for leaves)
 /// deleted: Tombstone status
+///
+/// Sets larger than `fid_tree::FID_CHUNK_THRESHOLD` are committed to through
+/// their chunked FID root (see `leaf_hash_chunked`).
 pub fn leaf_hash(key: &str, fids: &Set<String>, level: usize, deleted: bool) -> Hash {
+    if crate::fid_tree::is_chunked(fids) {
+        return leaf_hash_chunked(key, &crate::fid_tree::fid_root(fids), level, deleted);
+    }
     let mut hasher = Sha256::new();
     hasher.update((key.len() as u32).to_be_bytes());
     hasher.update(key.as_bytes());
@@ -41,11 +62,25 @@ pub fn leaf_hash(key: &str, fids: &Set<String>, level: usize, deleted: bool) ->
 
     // Include metadata
     hasher.update((level as u64).to_le_bytes());
-    hasher.update(&[(if deleted { 1 } else { 0 }) as u8]);
+    hasher.update([(if deleted { 1 } else { 0 }) as u8]);
 
     hasher.finalize().into()
 }
 
+/// Hash a leaf whose FID set is committed to by its chunked FID root.
+/// The count field is set to `u32::MAX` so it cannot collide with a leaf
+/// listing its FIDs.
+pub fn leaf_hash_chunked(key: &str, fid_root: &Hash, level: usize, deleted: bool) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update((key.len() as u32).to_be_bytes());
+    hasher.update(key.as_bytes());
+    hasher.update(u32::MAX.to_be_bytes());
+    hasher.update(fid_root);
+    hasher.update((level as u64).to_le_bytes());
+    hasher.update([(if deleted { 1 } else { 0 }) as u8]);
+    hasher.finalize().into()
+}
+
 pub fn nonleaf_hash(left: Hash, right: Hash) -> Hash {
     let mut hasher = Sha256::new();
     hasher.update(left);
@@ -150,17 +185,7 @@ mod tests {
 
     #[test]
     fn test_empty_acc_is_cached() {
-        // Need to initialize params for this test
-        use accumulator_ads::acc::setup::{PublicParameters, init_public_parameters_direct};
-        use ark_bls12_381::Fr;
-        use std::sync::Once;
-
-        static INIT: Once = Once::new();
-        INIT.call_once(|| {
-            let secret_s = Fr::from(123456789u128);
-            let params = PublicParameters::generate_for_testing(secret_s, 10);
-            init_public_parameters_direct(params).expect("Failed to initialize");
-        });
+        crate::setup::init_test_parameters();
 
         let acc1 = empty_acc();
         let acc2 = empty_acc();
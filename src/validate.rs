@@ -0,0 +1,151 @@
+//! Structural validation of proof parts received from untrusted sources.
+//!
+//! Verification assumes well-formed inputs: 32-byte hashes, Merkle paths no
+//! deeper than any forest can grow, and curve points in the prime-order
+//! subgroup. These checks are cheap compared to the pairings they guard, so the
+//! `try_*` constructors run them before a proof or response is handed out.
+
+use crate::Hash;
+use crate::acc_proof::{AccProof, MembershipProof, NonMembershipProof};
+use crate::epoch::EpochCommitment;
+use crate::merkle_proof::Proof;
+use accumulator_ads::acc::utils::validate_point;
+use ark_ec::models::SWModelParameters;
+use ark_ec::short_weierstrass_jacobian::GroupAffine;
+
+/// Deepest Merkle path a forest can produce (one level per bit of the key count).
+pub const MAX_PATH_LEN: usize = 64;
+
+/// Convert an untrusted byte slice into a `Hash`, rejecting other lengths.
+pub(crate) fn hash_from_slice(bytes: &[u8], what: &str) -> Result<Hash, String> {
+    bytes.try_into().map_err(|_| {
+        format!(
+            "{}: expected {} bytes, got {}",
+            what,
+            std::mem::size_of::<Hash>(),
+            bytes.len()
+        )
+    })
+}
+
+/// Check that a G1/G2 point is on the curve and in the prime-order subgroup.
+pub(crate) fn check_point<P: SWModelParameters>(p: &GroupAffine<P>, what: &str) -> Result<(), String> {
+    validate_point(p).map_err(|e| format!("{}: {}", what, e))
+}
+
+/// Check that a Merkle path is no longer than `MAX_PATH_LEN`.
+pub(crate) fn check_path_len(len: usize, what: &str) -> Result<(), String> {
+    if len > MAX_PATH_LEN {
+        return Err(format!(
+            "{}: path length {} exceeds maximum {}",
+            what, len, MAX_PATH_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Types whose structure can be checked without any pairing work.
+pub trait Validate {
+    fn validate(&self) -> Result<(), String>;
+}
+
+/// Validate a batch of received items in parallel, reporting the first failure
+/// by index. Useful when a client ingests many proofs at once.
+pub fn validate_batch<T: Validate + Sync>(items: &[T]) -> Result<(), String> {
+    let check = |(i, item): (usize, &T)| item.validate().err().map(|e| (i, e));
+    #[cfg(feature = "parallel")]
+    let failure = {
+        use rayon::prelude::*;
+        items.par_iter().enumerate().find_map_first(check)
+    };
+    #[cfg(not(feature = "parallel"))]
+    let failure = items.iter().enumerate().find_map(check);
+    match failure {
+        Some((i, e)) => Err(format!("item {}: {}", i, e)),
+        None => Ok(()),
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Some(v) => v.validate(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Validate for Proof {
+    fn validate(&self) -> Result<(), String> {
+        check_path_len(self.path.len(), "merkle proof")
+    }
+}
+
+impl Validate for MembershipProof {
+    fn validate(&self) -> Result<(), String> {
+        check_point(&self.witness, "membership witness")
+    }
+}
+
+impl Validate for accumulator_ads::NonMembershipProof {
+    fn validate(&self) -> Result<(), String> {
+        check_point(&self.witness, "non-membership witness")?;
+        check_point(&self.g2_a, "non-membership g2_a")
+    }
+}
+
+impl Validate for EpochCommitment {
+    fn validate(&self) -> Result<(), String> {
+        check_point(&self.global_acc, "epoch global accumulator")
+    }
+}
+
+impl Validate for NonMembershipProof {
+    fn validate(&self) -> Result<(), String> {
+        check_point(&self.accumulator, "non-membership accumulator")?;
+        self.acc_proof.validate()?;
+        self.commitment.validate()
+    }
+}
+
+impl Validate for AccProof {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            AccProof::Membership(mp) => mp.validate(),
+            AccProof::NonMembership(nm) => nm.validate(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accumulator_ads::G1Affine;
+    use ark_bls12_381::Fq;
+    use ark_ec::AffineCurve;
+
+    #[test]
+    fn test_hash_from_slice_length() {
+        assert_eq!(hash_from_slice(&[7u8; 32], "h").unwrap(), [7u8; 32]);
+        let err = hash_from_slice(&[7u8; 31], "root hash").unwrap_err();
+        assert!(err.contains("root hash: expected 32 bytes, got 31"));
+    }
+
+    #[test]
+    fn test_bad_witness_rejected() {
+        let g1 = G1Affine::prime_subgroup_generator();
+        assert!(MembershipProof { witness: g1 }.validate().is_ok());
+
+        let off_curve = G1Affine::new(g1.x, g1.y + Fq::from(1u64), false);
+        let err = AccProof::Membership(MembershipProof { witness: off_curve })
+            .validate()
+            .unwrap_err();
+        assert!(err.contains("membership witness"));
+
+        let batch = vec![
+            MembershipProof { witness: g1 },
+            MembershipProof { witness: off_curve },
+        ];
+        assert!(validate_batch(&batch).unwrap_err().starts_with("item 1:"));
+    }
+}
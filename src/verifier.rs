@@ -0,0 +1,307 @@
+//! Verification against roots the client trusts.
+//!
+//! The `verify_*` methods of the responses only check that a proof is
+//! consistent with itself: the Merkle path leads to the root hash it names and
+//! the witness holds for the accumulator it carries. Both come from the
+//! server, which could make them up. A `Verifier` holds the roots the client
+//! trusts, each with its accumulator if that is known, and the global
+//! accumulator absence proofs are checked against. It rejects responses that
+//! end at any other root or carry any other accumulator.
+//!
+//! Roots with their accumulators come from `AccumulatorTree::roots_info` or a
+//! signed announcement; a `RootManifest` checked against a trusted epoch
+//! digest authenticates root hashes only, since root accumulators are not part
+//! of the commitment. Answers ending at such a root are checked by their
+//! Merkle path alone.
+
+use crate::Hash;
+use crate::canonical::normalize_g1;
+use crate::epoch::RootManifest;
+use crate::merkle_proof::Proof as MerkleProof;
+use crate::policy::VerificationLevel;
+use crate::response::{
+    DeleteResponse, FidQueryResponse, InsertResponse, QueryResponse, ReviveResponse, UpdateResponse,
+};
+use accumulator_ads::{G1Affine, Set};
+use std::collections::HashMap;
+
+/// Trusted roots and global accumulator, through which responses are verified.
+///
+/// ```
+/// use accumulator_ads::Set;
+/// use accumulator_tree::prelude::*;
+/// # accumulator_tree::setup::init_insecure_parameters(16).unwrap();
+///
+/// let mut tree = AccumulatorTree::new();
+/// tree.insert("alice".to_string(), "doc1".to_string());
+/// tree.insert("bob".to_string(), "doc2".to_string());
+/// let global_acc = tree.publish_epoch().global_acc;
+/// let roots = tree.roots_info().into_iter().map(|r| (r.hash, r.acc));
+/// let mut verifier = Verifier::new(roots, global_acc);
+///
+/// let fids = verifier.verify_query("alice", &tree.select_with_proof("alice").unwrap());
+/// assert_eq!(fids, Ok(Some(Set::from_vec(vec!["doc1".to_string()]))));
+/// let absent = tree.select_with_proof("mallory").unwrap();
+/// assert_eq!(verifier.verify_query("mallory", &absent), Ok(None));
+///
+/// // A mutation ends at roots the verifier has not been given yet
+/// let response = tree.insert_with_proof("carol".to_string(), "doc3".to_string()).unwrap();
+/// assert!(verifier.verify_insert(&response).is_err());
+/// for root in tree.roots_info() {
+///     verifier.register(root.hash, root.acc);
+/// }
+/// assert!(verifier.verify_insert(&response).is_ok());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Verifier {
+    /// Trusted roots by hash, with their accumulator if it is known
+    roots: HashMap<Hash, Option<G1Affine>>,
+    /// Global accumulator absence proofs must be built against
+    global_acc: Option<G1Affine>,
+}
+
+impl Verifier {
+    /// Trust `roots`, given as (root hash, root accumulator) pairs, and check
+    /// absence against `global_acc`.
+    pub fn new(roots: impl IntoIterator<Item = (Hash, G1Affine)>, global_acc: G1Affine) -> Self {
+        Self {
+            roots: roots
+                .into_iter()
+                .map(|(hash, acc)| (hash, Some(normalize_g1(acc))))
+                .collect(),
+            global_acc: Some(normalize_g1(global_acc)),
+        }
+    }
+
+    /// Trust the roots of `manifest` once it checks out against the epoch
+    /// digest the client trusts. Their accumulators are unknown until
+    /// registered.
+    pub fn from_manifest(manifest: &RootManifest, trusted: &Hash) -> Result<Self, String> {
+        if !manifest.verify(trusted) {
+            return Err("root manifest does not match the trusted epoch".to_string());
+        }
+        Ok(Self {
+            roots: manifest.roots.iter().map(|s| (s.root.1, None)).collect(),
+            global_acc: Some(normalize_g1(manifest.commitment.global_acc)),
+        })
+    }
+
+    /// Trust `root`, with its accumulator if known. Registering a root again
+    /// replaces its accumulator.
+    pub fn register(&mut self, root: Hash, acc: impl Into<Option<G1Affine>>) {
+        self.roots.insert(root, acc.into().map(normalize_g1));
+    }
+
+    /// Check absence against `global_acc` from now on.
+    pub fn set_global_acc(&mut self, global_acc: G1Affine) {
+        self.global_acc = Some(normalize_g1(global_acc));
+    }
+
+    pub fn is_registered(&self, root: &Hash) -> bool {
+        self.roots.contains_key(root)
+    }
+
+    /// Check `proof` ends at a trusted root and `acc`, if given, is that
+    /// root's accumulator. Returns whether the accumulator was checked.
+    fn check_root(
+        &self,
+        proof: &MerkleProof,
+        acc: Option<&G1Affine>,
+        what: &str,
+    ) -> Result<bool, String> {
+        let trusted = self
+            .roots
+            .get(&proof.root_hash)
+            .ok_or_else(|| format!("{} root is not a trusted root", what))?;
+        match (trusted, acc) {
+            (Some(trusted), Some(acc)) if *trusted != normalize_g1(*acc) => Err(format!(
+                "{} accumulator is not the trusted accumulator of its root",
+                what
+            )),
+            (Some(_), Some(_)) => Ok(true),
+            _ => Ok(false),
+        }
+    }
+
+    /// Verify a point query. Returns the FIDs of a present key, or None for a
+    /// key proven absent against the trusted global accumulator. Membership
+    /// answers must carry a Merkle path to a trusted root; their witness is
+    /// checked too when the root's accumulator is known.
+    pub fn verify_query(
+        &self,
+        key: &str,
+        response: &QueryResponse,
+    ) -> Result<Option<Set<String>>, String> {
+        if response.fids.is_none() {
+            let global_acc = self
+                .global_acc
+                .as_ref()
+                .ok_or_else(|| "no trusted global accumulator".to_string())?;
+            if !response.verify_absent(key, global_acc) {
+                return Err(format!("invalid absence proof for '{}'", key));
+            }
+            return Ok(None);
+        }
+
+        let (Some(fids), Some(proof)) = (&response.fids, &response.merkle_proof) else {
+            return Err("membership answer without a Merkle path".to_string());
+        };
+        let level = match self.check_root(proof, response.accumulator.as_ref(), "proof")? {
+            true => VerificationLevel::Full,
+            false => VerificationLevel::HashOnly,
+        };
+        if !response.verify_at(key, level) {
+            return Err(format!("invalid proof for '{}'", key));
+        }
+        Ok(Some(fids.clone()))
+    }
+
+    /// Verify whether `fid` is in the FID set of `key`, against a trusted
+    /// root. Returns whether it is.
+    pub fn verify_fid_query(
+        &self,
+        key: &str,
+        fid: &str,
+        response: &FidQueryResponse,
+    ) -> Result<bool, String> {
+        self.check_root(&response.merkle_proof, Some(&response.accumulator), "proof")?;
+        let present = response.is_present();
+        let valid = match present {
+            true => response.verify(key, fid),
+            false => response.verify_absent(key, fid),
+        };
+        if !valid {
+            return Err(format!("invalid FID proof for '{}'", key));
+        }
+        Ok(present)
+    }
+
+    /// Verify an insert whose post-state root is trusted.
+    pub fn verify_insert(&self, response: &InsertResponse) -> Result<(), String> {
+        let proof = response
+            .post_merkle_proof
+            .as_ref()
+            .ok_or_else(|| "insert response without a post-insert path".to_string())?;
+        self.check_root(proof, response.post_accumulator.as_ref(), "post-insert")?;
+        match response.verify_insert() {
+            true => Ok(()),
+            false => Err(format!("invalid insert proof for '{}'", response.key)),
+        }
+    }
+
+    /// Verify an update whose pre- and post-state roots are both trusted.
+    pub fn verify_update(&self, response: &UpdateResponse) -> Result<(), String> {
+        if let Some(proof) = &response.pre_merkle_proof {
+            self.check_root(proof, response.pre_accumulator.as_ref(), "pre-update")?;
+        }
+        self.check_root(
+            &response.post_merkle_proof,
+            Some(&response.post_accumulator),
+            "post-update",
+        )?;
+        match response.verify_update() {
+            true => Ok(()),
+            false => Err(format!("invalid update proof for '{}'", response.key)),
+        }
+    }
+
+    /// Verify a delete whose pre- and post-state roots are both trusted.
+    pub fn verify_delete(&self, response: &DeleteResponse) -> Result<(), String> {
+        if let Some(proof) = &response.pre_merkle_proof {
+            self.check_root(proof, response.pre_accumulator.as_ref(), "pre-delete")?;
+        }
+        self.check_root(
+            &response.post_merkle_proof,
+            Some(&response.post_accumulator),
+            "post-delete",
+        )?;
+        match response.verify_delete() {
+            true => Ok(()),
+            false => Err(format!("invalid delete proof for '{}'", response.key)),
+        }
+    }
+
+    /// Verify a revive whose pre- and post-state roots are both trusted.
+    pub fn verify_revive(&self, response: &ReviveResponse) -> Result<(), String> {
+        self.check_root(&response.pre.merkle_proof, None, "pre-revive")?;
+        self.check_root(
+            &response.post.merkle_proof,
+            response.post_accumulator.as_ref(),
+            "post-revive",
+        )?;
+        match response.verify_revive() {
+            true => Ok(()),
+            false => Err(format!("invalid revive proof for '{}'", response.key)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use crate::acc_proof::{AccProof, MembershipProof};
+
+    fn commit(keys: &[&str]) -> G1Affine {
+        let set = Set::from_vec(keys.iter().map(|k| k.to_string()).collect());
+        accumulator_ads::DynamicAccumulator::calculate_commitment(
+            &accumulator_ads::digest_set_from_set(&set),
+        )
+    }
+
+    #[test]
+    fn test_rejects_unregistered_roots_and_accumulators() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+        let trusted = tree.publish_epoch().digest();
+        let manifest = tree.root_manifest().unwrap();
+        let mut verifier = Verifier::from_manifest(&manifest, &trusted).unwrap();
+        assert!(Verifier::from_manifest(&manifest, &[0u8; 32]).is_err());
+
+        // Root accumulators are unknown: the Merkle path alone decides
+        let fa = Set::from_vec(vec!["fa".to_string()]);
+        let response = tree.select_with_proof("a").unwrap();
+        assert_eq!(verifier.verify_query("a", &response), Ok(Some(fa.clone())));
+        assert_eq!(
+            verifier.verify_query("zz", &tree.select_with_proof("zz").unwrap()),
+            Ok(None)
+        );
+
+        // A server answering from a forest of its own making
+        let mut forged = AccumulatorTree::new();
+        forged.insert("a".to_string(), "evil".to_string());
+        assert!(
+            verifier
+                .verify_query("a", &forged.select_with_proof("a").unwrap())
+                .is_err()
+        );
+        forged.publish_epoch();
+        assert!(
+            verifier
+                .verify_query("zz", &forged.select_with_proof("zz").unwrap())
+                .is_err()
+        );
+
+        // Once the root's accumulator is known, only that one passes
+        let root = response.merkle_proof.as_ref().unwrap().root_hash;
+        assert_eq!(response.accumulator, Some(commit(&["a", "b"])));
+        verifier.register(root, commit(&["a", "b"]));
+        assert_eq!(verifier.verify_query("a", &response), Ok(Some(fa)));
+        let mut other = response.clone();
+        other.accumulator = Some(commit(&["a"]));
+        other.acc_proof = Some(AccProof::Membership(MembershipProof {
+            witness: commit(&[]),
+        }));
+        assert!(other.verify_by_mode("a"));
+        assert!(verifier.verify_query("a", &other).is_err());
+
+        // Mutations are checked against the roots they end at
+        let inserted = tree
+            .insert_with_proof("c".to_string(), "fc".to_string())
+            .unwrap();
+        let err = verifier.verify_insert(&inserted).unwrap_err();
+        assert!(err.contains("not a trusted root"), "{}", err);
+    }
+}
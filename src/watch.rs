@@ -0,0 +1,187 @@
+//! Verifiable epoch feed for mirrors and light clients.
+//!
+//! `AccumulatorTree::watch` returns a receiver that gets one `EpochDelta` per
+//! `publish`. A follower pinned to the digest of some epoch (the genesis
+//! commitment for a fresh follower) checks each delta against it and moves its
+//! pin to `new_commitment`. It never needs to poll or fetch the full state.
+//!
+//! A delta proves the step from the previous published epoch to the new one:
+//! - `changed_keys_proof` is the transition proof `publish` returns, covering
+//!   every changed key.
+//! - `consistency_proof` ties the transition's pre-state to the previous
+//!   commitment and its post-state to the new one.
+//!
+//! A tree that is also mutated directly, or published with `publish_epoch`,
+//! breaks the chain. The next delta then fails `check`, and the follower has
+//! to resync from a `RootManifest`.
+
+use crate::Hash;
+use crate::canonical::{CanonicalEncode, impl_canonical_eq};
+use crate::epoch::{EpochCommitment, RootManifest, RootStamp};
+use crate::response::TransactionResponse;
+use crate::validate::Validate;
+
+/// Links the transition of an `EpochDelta` to the commitments on each side.
+#[derive(Debug, Clone)]
+pub struct ConsistencyProof {
+    /// Commitment of the previous published epoch, genesis before the first
+    pub previous: EpochCommitment,
+    /// Roots of the new epoch with the epoch each last changed in
+    pub roots: Vec<RootStamp>,
+}
+
+/// What changed in one published epoch, from `AccumulatorTree::watch`.
+#[derive(Debug, Clone)]
+pub struct EpochDelta {
+    pub epoch: u64,
+    /// Proof that the staged operations, and nothing else, changed the forest
+    pub changed_keys_proof: TransactionResponse,
+    pub new_commitment: EpochCommitment,
+    pub consistency_proof: ConsistencyProof,
+}
+
+impl EpochDelta {
+    /// Keys whose leaves the epoch changed, sorted.
+    pub fn changed_keys(&self) -> Vec<&str> {
+        self.changed_keys_proof
+            .transitions
+            .iter()
+            .map(|t| t.key.as_str())
+            .collect()
+    }
+
+    /// Roots (with their position) that are new in this epoch.
+    pub fn changed_roots(&self) -> Vec<(usize, crate::epoch::RootEntry)> {
+        self.manifest().changed_since(self.epoch - 1)
+    }
+
+    fn manifest(&self) -> RootManifest {
+        RootManifest {
+            commitment: self.new_commitment.clone(),
+            roots: self.consistency_proof.roots.clone(),
+        }
+    }
+
+    pub fn verify(&self, trusted_previous: &Hash) -> bool {
+        self.check(trusted_previous).is_ok()
+    }
+
+    /// Checks:
+    /// 1. The previous commitment is the trusted one and the new one follows it
+    /// 2. The transition starts from the previous forest and global accumulator
+    /// 3. The transition ends at the new forest and global accumulator, and the
+    ///    listed roots hash to the new forest
+    /// 4. The transition proof verifies
+    ///
+    /// On success the follower's new trusted digest is `new_commitment.digest()`.
+    pub fn check(&self, trusted_previous: &Hash) -> Result<(), String> {
+        let previous = &self.consistency_proof.previous;
+        let next = &self.new_commitment;
+        let transition = &self.changed_keys_proof;
+        if previous.digest() != *trusted_previous {
+            return Err("previous commitment does not match the trusted digest".to_string());
+        }
+        if next.epoch != self.epoch || next.epoch != previous.epoch + 1 {
+            return Err(format!(
+                "epoch {} does not follow epoch {}",
+                next.epoch, previous.epoch
+            ));
+        }
+        if transition.pre_forest_hash() != previous.forest_hash
+            || transition.pre_global_acc != previous.global_acc
+        {
+            return Err("transition does not start from the previous epoch".to_string());
+        }
+        if transition.post_forest_hash() != next.forest_hash
+            || transition.post_global_acc != next.global_acc
+        {
+            return Err("transition does not end at the new epoch".to_string());
+        }
+        if !self.manifest().verify(&next.digest()) {
+            return Err("roots do not match the new commitment".to_string());
+        }
+        if !transition.verify() {
+            return Err("transition proof does not verify".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl CanonicalEncode for ConsistencyProof {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.previous.encode_canonical(out);
+        self.roots.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for EpochDelta {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.epoch.encode_canonical(out);
+        self.changed_keys_proof.encode_canonical(out);
+        self.new_commitment.encode_canonical(out);
+        self.consistency_proof.encode_canonical(out);
+    }
+}
+
+impl_canonical_eq!(ConsistencyProof, EpochDelta);
+
+impl Validate for EpochDelta {
+    fn validate(&self) -> Result<(), String> {
+        self.changed_keys_proof.validate()?;
+        self.new_commitment.validate()?;
+        self.consistency_proof.previous.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AccumulatorTree;
+    use crate::epoch::genesis_commitment;
+    use crate::txn::TxnOp;
+    use crate::wire::{from_wire, to_wire};
+
+    fn insert(key: &str, fid: &str) -> TxnOp {
+        TxnOp::Insert {
+            key: key.to_string(),
+            fid: fid.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_follower_chains_deltas_from_genesis() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        let feed = tree.watch();
+        let dropped = tree.watch();
+        drop(dropped);
+
+        tree.stage(insert("a", "fa"));
+        tree.stage(insert("b", "fb"));
+        tree.publish();
+        tree.stage(insert("c", "fc"));
+        tree.stage(TxnOp::Delete {
+            key: "a".to_string(),
+            fid: "fa".to_string(),
+        });
+        let published = tree.publish().commitment;
+
+        let mut trusted = genesis_commitment().digest();
+        for delta in feed.try_iter() {
+            let delta: super::EpochDelta = from_wire(&to_wire(&delta)).unwrap();
+            delta.check(&trusted).unwrap();
+            trusted = delta.new_commitment.digest();
+        }
+        assert_eq!(trusted, published.digest());
+
+        // A direct mutation is not in the next transition
+        tree.insert("d".to_string(), "fd".to_string());
+        tree.stage(insert("e", "fe"));
+        tree.publish();
+        let delta = feed.try_recv().unwrap();
+        assert_eq!(delta.changed_keys(), vec!["e"]);
+        assert!(!delta.changed_roots().is_empty());
+        let err = delta.check(&trusted).unwrap_err();
+        assert!(err.contains("previous epoch"), "{}", err);
+        assert!(!delta.verify(&delta.new_commitment.digest()));
+    }
+}
@@ -0,0 +1,951 @@
+//! Versioned wire format for proofs and responses.
+//!
+//! Every message is framed as `magic || version || type tag || payload`, where
+//! the payload is the canonical encoding of the value. Decoding dispatches on
+//! the version, so a later change to hashing or commitment layout can add a new
+//! version and keep accepting the old one instead of breaking deployed
+//! verifiers. Decoded curve points are checked to be valid subgroup elements.
+//!
+//! Merkle proofs, query and mutation responses and non-membership proofs also
+//! implement serde, for transports that already carry serde types. Their
+//! points use the same compressed encoding and the same subgroup checks, but
+//! the encoding is unversioned and depends on the serde format.
+
+use crate::Hash;
+use crate::acc_proof::{AccProof, MembershipProof, NonMembershipProof};
+use crate::canonical::CanonicalEncode;
+use crate::epoch::{EpochCommitment, RootManifest, RootStamp};
+use crate::fid_tree::{FidAbsenceProof, FidProof};
+use crate::gap::{GapLeaf, GapProof, RootGap};
+use crate::merkle_proof::Proof as MerkleProof;
+use crate::multiproof::{MultiLeaf, MultiProof, RootMultiProof};
+use crate::peaks::{Peak, PeakProof};
+use crate::policy::ProofMode;
+use crate::query::{BoolOp, Query};
+use crate::range::{RangeEntry, RangeResponse};
+use crate::range_stream::{RangeHeader, RangeItem};
+use crate::response::{
+    DeleteResponse, FidAnswer, FidQueryResponse, InsertResponse, KeyTransition, LeafWitness,
+    QueryResponse, ReviveResponse, TransactionResponse, UpdateResponse,
+};
+use crate::watch::{ConsistencyProof, EpochDelta};
+use crate::witness_delta::{AccChange, CompactionDelta};
+use accumulator_ads::{Fr, G1Affine, Set};
+use ark_ec::models::SWModelParameters;
+use ark_ec::short_weierstrass_jacobian::GroupAffine;
+use ark_serialize::CanonicalDeserialize;
+
+/// Leading bytes of every wire message.
+pub const WIRE_MAGIC: &[u8; 4] = b"ACTW";
+
+/// Version written by `to_wire`.
+pub const WIRE_VERSION: u16 = 5;
+
+const HEADER_LEN: usize = WIRE_MAGIC.len() + 2 + 1;
+
+/// Cursor over untrusted input.
+pub struct WireReader<'a> {
+    buf: &'a [u8],
+    /// Wire version the input was written with
+    version: u16,
+}
+
+impl<'a> WireReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self::with_version(buf, WIRE_VERSION)
+    }
+
+    /// Reader over input written by an older `version`, for decoders whose
+    /// layout changed between versions.
+    pub fn with_version(buf: &'a [u8], version: u16) -> Self {
+        Self { buf, version }
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.buf.len() < n {
+            return Err(format!(
+                "truncated input: need {} bytes, have {}",
+                n,
+                self.buf.len()
+            ));
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    /// Fail if any input is left over.
+    pub fn finish(self) -> Result<(), String> {
+        if !self.buf.is_empty() {
+            return Err(format!("{} trailing bytes", self.buf.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Inverse of `CanonicalEncode`.
+pub trait CanonicalDecode: Sized {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String>;
+}
+
+macro_rules! impl_decode_for_int {
+    ($($t:ty),*) => {$(
+        impl CanonicalDecode for $t {
+            fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+                let bytes = r.take(std::mem::size_of::<$t>())?;
+                Ok(<$t>::from_be_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+impl_decode_for_int!(u8, u16, u32, u64);
+
+impl CanonicalDecode for bool {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        match u8::decode_canonical(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(format!("invalid bool byte {}", b)),
+        }
+    }
+}
+
+impl CanonicalDecode for Hash {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(r.take(32)?.try_into().unwrap())
+    }
+}
+
+impl CanonicalDecode for String {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let len = u32::decode_canonical(r)? as usize;
+        String::from_utf8(r.take(len)?.to_vec()).map_err(|_| "string is not UTF-8".to_string())
+    }
+}
+
+impl CanonicalDecode for Set<String> {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Set::from_vec(Vec::<String>::decode_canonical(r)?))
+    }
+}
+
+impl CanonicalDecode for Fr {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Fr::deserialize(&mut r.buf).map_err(|e| format!("field element: {}", e))
+    }
+}
+
+impl<P: SWModelParameters> CanonicalDecode for GroupAffine<P> {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let p = Self::deserialize(&mut r.buf).map_err(|e| format!("curve point: {}", e))?;
+        crate::validate::check_point(&p, "curve point")?;
+        Ok(p)
+    }
+}
+
+impl<T: CanonicalDecode> CanonicalDecode for Option<T> {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        match u8::decode_canonical(r)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode_canonical(r)?)),
+            b => Err(format!("invalid option tag {}", b)),
+        }
+    }
+}
+
+impl<T: CanonicalDecode> CanonicalDecode for Box<T> {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Box::new(T::decode_canonical(r)?))
+    }
+}
+
+impl<T: CanonicalDecode> CanonicalDecode for std::sync::Arc<T> {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(std::sync::Arc::new(T::decode_canonical(r)?))
+    }
+}
+
+impl<T: CanonicalDecode> CanonicalDecode for Vec<T> {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let len = u32::decode_canonical(r)? as usize;
+        // Every element takes at least one byte; don't trust the length for allocation
+        let mut items = Vec::with_capacity(len.min(r.buf.len()));
+        for _ in 0..len {
+            items.push(T::decode_canonical(r)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<A: CanonicalDecode, B: CanonicalDecode> CanonicalDecode for (A, B) {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok((A::decode_canonical(r)?, B::decode_canonical(r)?))
+    }
+}
+
+impl CanonicalDecode for MerkleProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let root_hash = Hash::decode_canonical(r)?;
+        let leaf_hash = Hash::decode_canonical(r)?;
+        let path: Vec<(Hash, bool)> = Vec::decode_canonical(r)?;
+        crate::validate::check_path_len(path.len(), "merkle proof")?;
+        Ok(MerkleProof::new(root_hash, leaf_hash, path))
+    }
+}
+
+impl CanonicalDecode for MembershipProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(MembershipProof {
+            witness: CanonicalDecode::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for accumulator_ads::NonMembershipProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Self {
+            element: CanonicalDecode::decode_canonical(r)?,
+            witness: CanonicalDecode::decode_canonical(r)?,
+            g2_a: CanonicalDecode::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for EpochCommitment {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let commitment = EpochCommitment::new(
+            u64::decode_canonical(r)?,
+            Hash::decode_canonical(r)?,
+            CanonicalDecode::decode_canonical(r)?,
+            u64::decode_canonical(r)?,
+        );
+        // Key orders were added in version 4; older commitments are lexicographic
+        if r.version() < 4 {
+            return Ok(commitment);
+        }
+        Ok(commitment.with_key_order(u16::decode_canonical(r)?))
+    }
+}
+
+impl CanonicalDecode for NonMembershipProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(NonMembershipProof {
+            key: String::decode_canonical(r)?,
+            accumulator: CanonicalDecode::decode_canonical(r)?,
+            acc_proof: CanonicalDecode::decode_canonical(r)?,
+            commitment: Option::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for AccProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        match u8::decode_canonical(r)? {
+            0 => Ok(AccProof::Membership(MembershipProof::decode_canonical(r)?)),
+            1 => Ok(AccProof::NonMembership(Box::decode_canonical(r)?)),
+            t => Err(format!("invalid accumulator proof tag {}", t)),
+        }
+    }
+}
+
+impl CanonicalDecode for ProofMode {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let tag = u8::decode_canonical(r)?;
+        ProofMode::from_tag(tag).ok_or_else(|| format!("invalid proof mode tag {}", tag))
+    }
+}
+
+/// Deepest `Query::Boolean` nesting accepted from untrusted input.
+pub const MAX_QUERY_DEPTH: usize = 32;
+
+impl Query {
+    fn decode_nested(r: &mut WireReader, depth: usize) -> Result<Self, String> {
+        if depth > MAX_QUERY_DEPTH {
+            return Err(format!("query nested deeper than {}", MAX_QUERY_DEPTH));
+        }
+        match u8::decode_canonical(r)? {
+            0 => Ok(Query::Point(String::decode_canonical(r)?)),
+            1 => Ok(Query::Range {
+                start: String::decode_canonical(r)?,
+                end: String::decode_canonical(r)?,
+            }),
+            2 => Ok(Query::Prefix(String::decode_canonical(r)?)),
+            3 => {
+                let tag = u8::decode_canonical(r)?;
+                let op =
+                    BoolOp::from_tag(tag).ok_or_else(|| format!("invalid boolean op {}", tag))?;
+                let len = u32::decode_canonical(r)? as usize;
+                let mut operands = Vec::with_capacity(len.min(r.buf.len()));
+                for _ in 0..len {
+                    operands.push(Self::decode_nested(r, depth + 1)?);
+                }
+                Ok(Query::Boolean { op, operands })
+            }
+            4 => Ok(Query::Sample {
+                seed: Vec::decode_canonical(r)?,
+                n: u64::decode_canonical(r)?,
+            }),
+            t => Err(format!("invalid query tag {}", t)),
+        }
+    }
+}
+
+impl CanonicalDecode for Query {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Self::decode_nested(r, 0)
+    }
+}
+
+impl QueryResponse {
+    /// Version 1 payload: no key-count hints.
+    fn decode_v1(r: &mut WireReader) -> Result<Self, String> {
+        let response = QueryResponse::new(
+            Option::decode_canonical(r)?,
+            Option::decode_canonical(r)?,
+            Option::decode_canonical(r)?,
+            Option::decode_canonical(r)?,
+        );
+        Ok(response.with_mode(ProofMode::decode_canonical(r)?))
+    }
+
+    /// Version 2 payload: no query hash.
+    fn decode_v2(r: &mut WireReader) -> Result<Self, String> {
+        let mut response = Self::decode_v1(r)?;
+        response.sibling_key_counts = Option::decode_canonical(r)?;
+        Ok(response)
+    }
+}
+
+impl CanonicalDecode for QueryResponse {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        let mut response = Self::decode_v2(r)?;
+        response.query_hash = Option::decode_canonical(r)?;
+        Ok(response)
+    }
+}
+
+impl CanonicalDecode for RootStamp {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(RootStamp {
+            root: CanonicalDecode::decode_canonical(r)?,
+            changed_in: u64::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for RootManifest {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(RootManifest {
+            commitment: EpochCommitment::decode_canonical(r)?,
+            roots: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for FidProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(FidProof {
+            chunk: Vec::decode_canonical(r)?,
+            proof: MerkleProof::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for FidAbsenceProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        match u8::decode_canonical(r)? {
+            0 => Ok(FidAbsenceProof::Within(FidProof::decode_canonical(r)?)),
+            1 => Ok(FidAbsenceProof::Between(
+                FidProof::decode_canonical(r)?,
+                FidProof::decode_canonical(r)?,
+            )),
+            2 => Ok(FidAbsenceProof::Before(FidProof::decode_canonical(r)?)),
+            3 => Ok(FidAbsenceProof::After(FidProof::decode_canonical(r)?)),
+            tag => Err(format!("unknown FID absence tag {}", tag)),
+        }
+    }
+}
+
+impl CanonicalDecode for FidAnswer {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        // Absence answers were added in version 5; older ones are all present
+        if r.version() < 5 {
+            return Ok(FidAnswer::Present(FidProof::decode_canonical(r)?));
+        }
+        match u8::decode_canonical(r)? {
+            0 => Ok(FidAnswer::Present(FidProof::decode_canonical(r)?)),
+            1 => Ok(FidAnswer::Absent(FidAbsenceProof::decode_canonical(r)?)),
+            tag => Err(format!("unknown FID answer tag {}", tag)),
+        }
+    }
+}
+
+impl CanonicalDecode for FidQueryResponse {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(FidQueryResponse {
+            answer: FidAnswer::decode_canonical(r)?,
+            merkle_proof: MerkleProof::decode_canonical(r)?,
+            accumulator: CanonicalDecode::decode_canonical(r)?,
+            acc_proof: MembershipProof::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for InsertResponse {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(InsertResponse {
+            key: String::decode_canonical(r)?,
+            fids: Set::decode_canonical(r)?,
+            post_accumulator: Option::decode_canonical(r)?,
+            post_merkle_proof: Option::decode_canonical(r)?,
+            post_acc_proof: Option::decode_canonical(r)?,
+            pre_acc_proof: Option::decode_canonical(r)?,
+            stats: None,
+        })
+    }
+}
+
+impl CanonicalDecode for UpdateResponse {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(UpdateResponse {
+            key: String::decode_canonical(r)?,
+            old_fid: String::decode_canonical(r)?,
+            new_fid: String::decode_canonical(r)?,
+            old_fids: Option::decode_canonical(r)?,
+            new_fids: Set::decode_canonical(r)?,
+            pre_merkle_proof: Option::decode_canonical(r)?,
+            pre_accumulator: Option::decode_canonical(r)?,
+            pre_acc_proof: Option::decode_canonical(r)?,
+            post_merkle_proof: MerkleProof::decode_canonical(r)?,
+            post_accumulator: CanonicalDecode::decode_canonical(r)?,
+            post_acc_proof: MembershipProof::decode_canonical(r)?,
+            stats: None,
+        })
+    }
+}
+
+impl CanonicalDecode for DeleteResponse {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(DeleteResponse {
+            key: String::decode_canonical(r)?,
+            deleted_fid: String::decode_canonical(r)?,
+            old_fids: Option::decode_canonical(r)?,
+            new_fids: Set::decode_canonical(r)?,
+            pre_merkle_proof: Option::decode_canonical(r)?,
+            pre_accumulator: Option::decode_canonical(r)?,
+            pre_acc_proof: Option::decode_canonical(r)?,
+            post_merkle_proof: MerkleProof::decode_canonical(r)?,
+            post_accumulator: CanonicalDecode::decode_canonical(r)?,
+            stats: None,
+        })
+    }
+}
+
+impl CanonicalDecode for LeafWitness {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(LeafWitness {
+            fids: Set::decode_canonical(r)?,
+            deleted: bool::decode_canonical(r)?,
+            merkle_proof: MerkleProof::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for ReviveResponse {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(ReviveResponse {
+            key: String::decode_canonical(r)?,
+            fids: Set::decode_canonical(r)?,
+            deleted_epoch: Option::decode_canonical(r)?,
+            pre: LeafWitness::decode_canonical(r)?,
+            post: LeafWitness::decode_canonical(r)?,
+            post_accumulator: Option::decode_canonical(r)?,
+            post_acc_proof: Option::decode_canonical(r)?,
+            stats: None,
+        })
+    }
+}
+
+impl CanonicalDecode for KeyTransition {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(KeyTransition {
+            key: String::decode_canonical(r)?,
+            pre: Option::decode_canonical(r)?,
+            post: LeafWitness::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for TransactionResponse {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(TransactionResponse {
+            ops: Vec::decode_canonical(r)?,
+            pre_roots: Vec::decode_canonical(r)?,
+            post_roots: Vec::decode_canonical(r)?,
+            pre_global_acc: CanonicalDecode::decode_canonical(r)?,
+            post_global_acc: CanonicalDecode::decode_canonical(r)?,
+            added_keys: Set::decode_canonical(r)?,
+            removed_keys: Set::decode_canonical(r)?,
+            transitions: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for AccChange {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Self {
+            old_acc: CanonicalDecode::decode_canonical(r)?,
+            new_acc: CanonicalDecode::decode_canonical(r)?,
+            witnesses: CanonicalDecode::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for CompactionDelta {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Self {
+            changes: Vec::<AccChange>::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for RangeEntry {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(RangeEntry {
+            key: String::decode_canonical(r)?,
+            leaf: LeafWitness::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for RangeResponse {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(RangeResponse {
+            start: String::decode_canonical(r)?,
+            end: String::decode_canonical(r)?,
+            commitment: EpochCommitment::decode_canonical(r)?,
+            roots: Vec::decode_canonical(r)?,
+            entries: Vec::decode_canonical(r)?,
+            other_keys: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for RangeHeader {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(RangeHeader {
+            start: String::decode_canonical(r)?,
+            end: String::decode_canonical(r)?,
+            commitment: EpochCommitment::decode_canonical(r)?,
+            roots: Vec::decode_canonical(r)?,
+            other_keys: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for RangeItem {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(RangeItem {
+            key: String::decode_canonical(r)?,
+            fids: Set::decode_canonical(r)?,
+            kept: u64::decode_canonical(r)?,
+            fresh: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for Peak {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(Peak {
+            level: u64::decode_canonical(r)?,
+            hash: Hash::decode_canonical(r)?,
+            acc: G1Affine::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for PeakProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(PeakProof {
+            peak: Peak::decode_canonical(r)?,
+            left: Vec::decode_canonical(r)?,
+            right: Option::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for MultiLeaf {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(MultiLeaf {
+            index: u64::decode_canonical(r)?,
+            key: String::decode_canonical(r)?,
+            fids: Set::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for RootMultiProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(RootMultiProof {
+            root_hash: Hash::decode_canonical(r)?,
+            level: u64::decode_canonical(r)?,
+            leaves: Vec::decode_canonical(r)?,
+            siblings: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for MultiProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(MultiProof {
+            roots: Vec::decode_canonical(r)?,
+            missing: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for GapLeaf {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(GapLeaf {
+            index: u64::decode_canonical(r)?,
+            key: String::decode_canonical(r)?,
+            fids: Set::decode_canonical(r)?,
+            deleted: bool::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for RootGap {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(RootGap {
+            root_hash: Hash::decode_canonical(r)?,
+            level: u64::decode_canonical(r)?,
+            leaves: Vec::decode_canonical(r)?,
+            siblings: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for GapProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(GapProof {
+            key: String::decode_canonical(r)?,
+            roots: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for ConsistencyProof {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(ConsistencyProof {
+            previous: EpochCommitment::decode_canonical(r)?,
+            roots: Vec::decode_canonical(r)?,
+        })
+    }
+}
+
+impl CanonicalDecode for EpochDelta {
+    fn decode_canonical(r: &mut WireReader) -> Result<Self, String> {
+        Ok(EpochDelta {
+            epoch: u64::decode_canonical(r)?,
+            changed_keys_proof: TransactionResponse::decode_canonical(r)?,
+            new_commitment: EpochCommitment::decode_canonical(r)?,
+            consistency_proof: ConsistencyProof::decode_canonical(r)?,
+        })
+    }
+}
+
+/// Types that can be sent as a framed, versioned message.
+pub trait WireMessage: CanonicalEncode + CanonicalDecode {
+    /// Distinguishes message types sharing the framing
+    const TYPE_TAG: u8;
+
+    /// Decode a payload written by `version`. Types whose encoding changes
+    /// override this to keep decoding older versions.
+    fn decode_version(version: u16, r: &mut WireReader) -> Result<Self, String> {
+        match version {
+            1..=WIRE_VERSION => Self::decode_canonical(r),
+            v => Err(format!("unsupported wire version {}", v)),
+        }
+    }
+}
+
+impl WireMessage for MerkleProof {
+    const TYPE_TAG: u8 = 1;
+}
+
+impl WireMessage for AccProof {
+    const TYPE_TAG: u8 = 2;
+}
+
+impl WireMessage for NonMembershipProof {
+    const TYPE_TAG: u8 = 3;
+}
+
+impl WireMessage for EpochCommitment {
+    const TYPE_TAG: u8 = 4;
+}
+
+impl WireMessage for Query {
+    const TYPE_TAG: u8 = 6;
+}
+
+impl WireMessage for QueryResponse {
+    const TYPE_TAG: u8 = 5;
+
+    fn decode_version(version: u16, r: &mut WireReader) -> Result<Self, String> {
+        match version {
+            1 => Self::decode_v1(r),
+            2 => Self::decode_v2(r),
+            3..=WIRE_VERSION => Self::decode_canonical(r),
+            v => Err(format!("unsupported wire version {}", v)),
+        }
+    }
+}
+
+impl WireMessage for InsertResponse {
+    const TYPE_TAG: u8 = 7;
+}
+
+impl WireMessage for UpdateResponse {
+    const TYPE_TAG: u8 = 8;
+}
+
+impl WireMessage for DeleteResponse {
+    const TYPE_TAG: u8 = 9;
+}
+
+impl WireMessage for ReviveResponse {
+    const TYPE_TAG: u8 = 10;
+}
+
+impl WireMessage for FidQueryResponse {
+    const TYPE_TAG: u8 = 11;
+}
+
+impl WireMessage for RangeResponse {
+    const TYPE_TAG: u8 = 12;
+}
+
+impl WireMessage for TransactionResponse {
+    const TYPE_TAG: u8 = 13;
+}
+
+impl WireMessage for RootManifest {
+    const TYPE_TAG: u8 = 14;
+}
+
+impl WireMessage for CompactionDelta {
+    const TYPE_TAG: u8 = 15;
+}
+
+impl WireMessage for RangeHeader {
+    const TYPE_TAG: u8 = 16;
+}
+
+impl WireMessage for RangeItem {
+    const TYPE_TAG: u8 = 17;
+}
+
+impl WireMessage for PeakProof {
+    const TYPE_TAG: u8 = 18;
+}
+
+impl WireMessage for MultiProof {
+    const TYPE_TAG: u8 = 19;
+}
+
+impl WireMessage for EpochDelta {
+    const TYPE_TAG: u8 = 20;
+}
+
+impl WireMessage for GapProof {
+    const TYPE_TAG: u8 = 21;
+}
+
+/// Frame `value` with the current wire version.
+pub fn to_wire<T: WireMessage>(value: &T) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN);
+    out.extend_from_slice(WIRE_MAGIC);
+    WIRE_VERSION.encode_canonical(&mut out);
+    out.push(T::TYPE_TAG);
+    value.encode_canonical(&mut out);
+    out
+}
+
+/// Read the (version, type tag) header of a wire message.
+pub fn peek_header(bytes: &[u8]) -> Result<(u16, u8), String> {
+    let mut r = WireReader::new(bytes);
+    if r.take(WIRE_MAGIC.len())? != WIRE_MAGIC {
+        return Err("not a wire message (bad magic)".to_string());
+    }
+    Ok((
+        u16::decode_canonical(&mut r)?,
+        u8::decode_canonical(&mut r)?,
+    ))
+}
+
+/// Decode a message of type `T`, dispatching on its version.
+pub fn from_wire<T: WireMessage>(bytes: &[u8]) -> Result<T, String> {
+    let (version, tag) = peek_header(bytes)?;
+    if tag != T::TYPE_TAG {
+        return Err(format!(
+            "unexpected message type {} (expected {})",
+            tag,
+            T::TYPE_TAG
+        ));
+    }
+    let mut r = WireReader::with_version(&bytes[HEADER_LEN..], version);
+    let value = T::decode_version(version, &mut r)?;
+    r.finish()?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+
+    #[test]
+    fn test_responses_roundtrip() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+        let commitment = tree.publish_epoch();
+
+        for key in ["a", "zzz"] {
+            let response = tree.select_with_proof(key).unwrap();
+            let decoded: QueryResponse = from_wire(&to_wire(&response)).unwrap();
+            assert_eq!(decoded, response);
+            assert!(decoded.verify_by_mode(key));
+        }
+        let decoded: EpochCommitment = from_wire(&to_wire(&commitment)).unwrap();
+        assert_eq!(decoded.digest(), commitment.digest());
+    }
+
+    /// Decode `value` from its frame and check it re-encodes identically.
+    fn assert_roundtrips<T: WireMessage>(value: &T) {
+        let bytes = to_wire(value);
+        assert_eq!(peek_header(&bytes).unwrap().1, T::TYPE_TAG);
+        let decoded: T = from_wire(&bytes).unwrap();
+        assert_eq!(decoded.canonical_bytes(), value.canonical_bytes());
+    }
+
+    #[test]
+    fn test_mutation_responses_roundtrip() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("a".to_string(), "fa2".to_string());
+        assert_roundtrips(
+            &tree
+                .insert_with_proof("b".to_string(), "fb".to_string())
+                .unwrap(),
+        );
+        assert_roundtrips(&tree.update_with_proof("a", "fa", "fc".to_string()).unwrap());
+        assert_roundtrips(&tree.delete_with_proof("a", "fc").unwrap());
+        tree.delete("b", "fb");
+        let fids = Set::from_vec(vec!["fd".to_string()]);
+        assert_roundtrips(&tree.revive_with_proof("b", fids).unwrap());
+
+        let mut txn = tree.begin_transaction();
+        txn.insert("c".to_string(), "fe".to_string());
+        txn.delete("a", "fa2");
+        assert_roundtrips(&txn.commit_with_proof());
+
+        for i in 0..=crate::fid_tree::FID_CHUNK_THRESHOLD {
+            tree.insert("big".to_string(), format!("f{:05}", i));
+        }
+        assert_roundtrips(&tree.select_fid_with_proof("big", "f00007").unwrap());
+        let absent = tree.select_fid_with_proof("big", "f00007x").unwrap();
+        assert_roundtrips(&absent);
+
+        // Version 4 FID responses have no answer tag and are all present
+        let present = tree.select_fid_with_proof("big", "f00007").unwrap();
+        let mut v4 = to_wire(&present);
+        v4[4..6].copy_from_slice(&4u16.to_be_bytes());
+        v4.remove(HEADER_LEN);
+        let decoded: FidQueryResponse = from_wire(&v4).unwrap();
+        assert_eq!(decoded.canonical_bytes(), present.canonical_bytes());
+        v4[4..6].copy_from_slice(&5u16.to_be_bytes());
+        assert!(from_wire::<FidQueryResponse>(&v4).is_err());
+        tree.publish_epoch();
+        assert_roundtrips(&tree.range_with_proof("a", "c").unwrap());
+        assert_roundtrips(&tree.root_manifest().unwrap());
+        assert_roundtrips(&tree.peak_proof(&tree.root_hashes()[0]).unwrap());
+        assert_roundtrips(&tree.get_many_with_proof(&["a", "b", "c", "zz"]));
+    }
+
+    #[test]
+    fn test_rejects_bad_frames() {
+        let proof = MerkleProof::new([1u8; 32], [2u8; 32], vec![([3u8; 32], true)]);
+        let bytes = to_wire(&proof);
+        assert_eq!(peek_header(&bytes), Ok((WIRE_VERSION, 1)));
+
+        let mut future = bytes.clone();
+        future[4..6].copy_from_slice(&99u16.to_be_bytes());
+        assert!(
+            from_wire::<MerkleProof>(&future)
+                .unwrap_err()
+                .contains("unsupported wire version 99")
+        );
+        assert!(from_wire::<AccProof>(&bytes).is_err());
+        assert!(from_wire::<MerkleProof>(&bytes[..bytes.len() - 1]).is_err());
+        assert!(from_wire::<MerkleProof>(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_decodes_older_query_responses() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.insert("a".to_string(), "fa".to_string());
+        tree.insert("b".to_string(), "fb".to_string());
+
+        let hinted = tree.select_with_count_hints("a").unwrap();
+        let decoded: QueryResponse = from_wire(&to_wire(&hinted)).unwrap();
+        assert_eq!(decoded.sibling_key_counts, Some(vec![1]));
+
+        // A version 2 frame ends after the hints, a version 1 frame after the
+        // mode byte; neither carries a query hash
+        let plain = tree.select_with_proof("a").unwrap();
+        assert!(plain.query_hash.is_some());
+        let mut v2 = to_wire(&plain);
+        v2.truncate(v2.len() - 33);
+        v2[4..6].copy_from_slice(&2u16.to_be_bytes());
+        let decoded: QueryResponse = from_wire(&v2).unwrap();
+        let unbound = QueryResponse {
+            query_hash: None,
+            ..plain.clone()
+        };
+        assert_eq!(decoded, unbound);
+        let mut v1 = v2.clone();
+        v1.pop();
+        v1[4..6].copy_from_slice(&1u16.to_be_bytes());
+        let decoded: QueryResponse = from_wire(&v1).unwrap();
+        assert_eq!(decoded, unbound);
+    }
+
+    #[test]
+    fn test_decodes_version_3_commitments() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::with_order(crate::OrderPolicy::Natural);
+        tree.insert("a".to_string(), "fa".to_string());
+        let commitment = tree.publish_epoch();
+        let decoded: EpochCommitment = from_wire(&to_wire(&commitment)).unwrap();
+        assert_eq!(decoded.key_order, 1);
+
+        // Version 3 commitments end before the key order
+        let mut v3 = to_wire(&commitment);
+        v3.truncate(v3.len() - 2);
+        v3[4..6].copy_from_slice(&3u16.to_be_bytes());
+        let decoded: EpochCommitment = from_wire(&v3).unwrap();
+        assert_eq!(decoded, commitment.clone().with_key_order(0));
+        assert!(from_wire::<EpochCommitment>(&v3[..v3.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! Cache of membership witnesses, persistable to an `ObjectStore`.
+//!
+//! A witness depends only on the root accumulator and the key, so it stays
+//! valid until that root is rebuilt. The cache is keyed by both and can be
+//! saved next to other prover state and reloaded on startup, so hot keys are
+//! answered without recomputing their witnesses after a restart. Loaded points
+//! are validated like any other untrusted input.
+
+use crate::Hash;
+use crate::canonical::CanonicalEncode;
+use crate::error::AccTreeError;
+use crate::node::Node;
+use crate::storage::ObjectStore;
+use crate::witness_delta::CompactionDelta;
+use accumulator_ads::{G1Affine, Set};
+use ark_serialize::CanonicalDeserialize;
+use std::collections::HashMap;
+
+/// Format version written at the start of a saved cache.
+const CACHE_FORMAT_VERSION: u8 = 1;
+
+/// Membership witness of `key` in the accumulator of `keys`, the key set of
+/// its root. Computed from the public parameters alone, so it verifies under
+/// any setup, ceremony and imported transcripts included. Fails with
+/// `KeyNotFound` if `key` is not in `keys`, and with `ParamsNotInitialized` or
+/// `DegreeExceeded` if the parameters cannot commit to the quotient.
+pub fn compute_witness(keys: &Set<String>, key: &str) -> Result<G1Affine, AccTreeError> {
+    Ok(compute_witnesses(keys, &[key.to_string()])?[0])
+}
+
+/// `compute_witness` for each of `targets` in the accumulator of `keys`, in
+/// one batch that expands the set once. Past log₂ n targets, the witnesses
+/// of the whole set are computed together (`compute_all_witnesses`) and the
+/// targets' picked out.
+pub fn compute_witnesses(
+    keys: &Set<String>,
+    targets: &[String],
+) -> Result<Vec<G1Affine>, AccTreeError> {
+    if let Some(missing) = targets.iter().find(|k| !keys.contains(*k)) {
+        return Err(AccTreeError::KeyNotFound(missing.clone()));
+    }
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+    if targets.len() > keys.len().ilog2() as usize {
+        let all = compute_all_witnesses(keys)?;
+        let position: HashMap<&String, usize> =
+            keys.iter().enumerate().map(|(i, k)| (k, i)).collect();
+        return Ok(targets.iter().map(|k| all[position[k]]).collect());
+    }
+    crate::setup::check_available_degree(keys.len() - 1)?;
+    let elements = accumulator_ads::digest_set_from_set(keys);
+    let targets: Vec<_> = targets.iter().map(|k| key_element(k)).collect();
+    accumulator_ads::DynamicAccumulator::membership_witnesses(&elements, &targets)
+        .map_err(|e| AccTreeError::ProofGenFailed(format!("membership witness: {:#}", e)))
+}
+
+/// Witness of every key of `keys`, in the set's iteration order, in
+/// O(n log² n) group operations from the public parameters
+/// (`DynamicAccumulator::all_membership_witnesses`).
+pub fn compute_all_witnesses(keys: &Set<String>) -> Result<Vec<G1Affine>, AccTreeError> {
+    crate::setup::check_available_degree(keys.len().saturating_sub(1))?;
+    let elements = accumulator_ads::digest_set_from_set(keys);
+    accumulator_ads::DynamicAccumulator::all_membership_witnesses(&elements)
+        .map_err(|e| AccTreeError::ProofGenFailed(format!("membership witnesses: {:#}", e)))
+}
+
+pub(crate) fn key_element(key: &str) -> accumulator_ads::Fr {
+    let key_set = accumulator_ads::Set::from_vec(vec![key.to_string()]);
+    *accumulator_ads::digest_set_from_set(&key_set)
+        .first()
+        .unwrap()
+}
+
+#[derive(Debug, Clone)]
+struct CachedWitness {
+    acc: G1Affine,
+    witness: G1Affine,
+}
+
+/// Membership witnesses keyed by (root accumulator, key).
+#[derive(Debug, Clone, Default)]
+pub struct WitnessCache {
+    entries: HashMap<(Hash, String), CachedWitness>,
+    hits: u64,
+    misses: u64,
+}
+
+impl WitnessCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// (hits, misses) since the cache was created or loaded.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+
+    /// Return the cached witness of `key` in the accumulator of `root`,
+    /// computing it on a miss. A failed computation is not cached.
+    pub fn get_or_compute(&mut self, root: &Node, key: &str) -> Result<G1Affine, AccTreeError> {
+        let acc = root.acc();
+        let id = (acc.canonical_digest(), key.to_string());
+        if let Some(entry) = self.entries.get(&id) {
+            self.hits += 1;
+            return Ok(entry.witness);
+        }
+        self.misses += 1;
+        let witness = compute_witness(&root.keys(), key)?;
+        self.entries.insert(id, CachedWitness { acc, witness });
+        Ok(witness)
+    }
+
+    /// Drop witnesses for accumulators not in `live` (e.g. the current roots'
+    /// accumulators). Returns the number of entries removed.
+    pub fn retain_accumulators(&mut self, live: &[G1Affine]) -> usize {
+        let live: Vec<Hash> = live.iter().map(|a| a.canonical_digest()).collect();
+        let before = self.entries.len();
+        self.entries.retain(|(acc, _), _| live.contains(acc));
+        before - self.entries.len()
+    }
+
+    /// Move cached witnesses invalidated by a rebuild of their accumulator
+    /// to the regenerated ones in `delta`, and drop those of keys that have
+    /// no successor. Returns (moved, dropped).
+    pub fn apply_delta(&mut self, delta: &CompactionDelta) -> (usize, usize) {
+        let (mut moved, mut dropped) = (0, 0);
+        for change in &delta.changes {
+            let old = change.old_acc.canonical_digest();
+            let stale: Vec<String> = self
+                .entries
+                .keys()
+                .filter(|(acc, _)| *acc == old)
+                .map(|(_, key)| key.clone())
+                .collect();
+            for key in stale {
+                self.entries.remove(&(old, key.clone()));
+                match delta.refreshed(&key, &change.old_acc) {
+                    Some((acc, witness)) => {
+                        let id = (acc.canonical_digest(), key);
+                        self.entries.insert(id, CachedWitness { acc, witness });
+                        moved += 1;
+                    }
+                    None => dropped += 1,
+                }
+            }
+        }
+        (moved, dropped)
+    }
+
+    /// Save the cache to `store` under `object`.
+    pub fn save(&self, store: &dyn ObjectStore, object: &str) -> Result<(), String> {
+        let mut entries: Vec<(&String, &CachedWitness)> =
+            self.entries.iter().map(|((_, k), e)| (k, e)).collect();
+        entries.sort_by(|a, b| {
+            (a.0, a.1.acc.canonical_digest()).cmp(&(b.0, b.1.acc.canonical_digest()))
+        });
+
+        let mut out = vec![CACHE_FORMAT_VERSION];
+        (entries.len() as u64).encode_canonical(&mut out);
+        for (key, entry) in entries {
+            entry.acc.encode_canonical(&mut out);
+            key.encode_canonical(&mut out);
+            entry.witness.encode_canonical(&mut out);
+        }
+        store.put(object, &out)
+    }
+
+    /// Load a cache saved with `save`. A missing object yields an empty cache.
+    pub fn load(store: &dyn ObjectStore, object: &str) -> Result<Self, String> {
+        let Some(bytes) = store.get(object)? else {
+            return Ok(Self::new());
+        };
+        let mut r = Reader(&bytes);
+        let version = r.take(1)?[0];
+        if version != CACHE_FORMAT_VERSION {
+            return Err(format!("unsupported witness cache version {}", version));
+        }
+        let count = u64::from_be_bytes(r.take(8)?.try_into().unwrap());
+
+        let mut cache = Self::new();
+        for i in 0..count {
+            let acc = r.point(&format!("entry {} accumulator", i))?;
+            let len = u32::from_be_bytes(r.take(4)?.try_into().unwrap()) as usize;
+            let key = String::from_utf8(r.take(len)?.to_vec())
+                .map_err(|_| format!("entry {}: key is not UTF-8", i))?;
+            let witness = r.point(&format!("entry {} witness", i))?;
+            cache.entries.insert(
+                (acc.canonical_digest(), key),
+                CachedWitness { acc, witness },
+            );
+        }
+        if !r.0.is_empty() {
+            return Err("trailing bytes after witness cache".to_string());
+        }
+        Ok(cache)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.0.len() < n {
+            return Err("truncated witness cache".to_string());
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn point(&mut self, what: &str) -> Result<G1Affine, String> {
+        let p = G1Affine::deserialize(&mut self.0).map_err(|e| format!("{}: {}", what, e))?;
+        crate::validate::check_point(&p, what)?;
+        Ok(p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccumulatorTree;
+    use crate::storage::MemoryStore;
+
+    #[test]
+    fn test_cached_select_matches_and_survives_restart() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for k in ["a", "b", "c"] {
+            tree.insert(k.to_string(), format!("f{}", k));
+        }
+
+        let mut cache = WitnessCache::new();
+        let cached = tree.select_with_proof_cached("a", &mut cache).unwrap();
+        tree.select_with_proof_cached("a", &mut cache).unwrap();
+        assert_eq!(cache.stats(), (1, 1));
+        assert_eq!(cached, tree.select_with_proof("a").unwrap());
+        assert!(cached.verify_by_mode("a"));
+
+        let store = MemoryStore::new();
+        cache.save(&store, "prover/witnesses").unwrap();
+        let mut reloaded = WitnessCache::load(&store, "prover/witnesses").unwrap();
+        assert_eq!(reloaded.len(), 1);
+        tree.select_with_proof_cached("a", &mut reloaded).unwrap();
+        assert_eq!(reloaded.stats(), (1, 0));
+
+        // Rebuilt roots leave stale entries behind until pruned
+        tree.insert("d".to_string(), "fd".to_string());
+        let live: Vec<G1Affine> = tree.roots.iter().map(|r| r.acc()).collect();
+        assert_eq!(reloaded.retain_accumulators(&live), 1);
+        assert!(
+            WitnessCache::load(&store, "prover/missing")
+                .unwrap()
+                .is_empty()
+        );
+
+        let keys = Set::from_vec(vec!["a".to_string()]);
+        assert_eq!(
+            compute_witness(&keys, "b"),
+            Err(AccTreeError::KeyNotFound("b".to_string()))
+        );
+
+        // Batches over most of a root match the single witnesses
+        let keys = tree.roots[0].keys();
+        let targets: Vec<String> = keys.iter().cloned().collect();
+        let batch = compute_witnesses(&keys, &targets).unwrap();
+        for (key, witness) in targets.iter().zip(&batch) {
+            assert_eq!(compute_witness(&keys, key).unwrap(), *witness);
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_corrupt_cache() {
+        crate::setup::init_test_parameters();
+        let store = MemoryStore::new();
+        let mut cache = WitnessCache::new();
+        let leaf = Node::leaf(
+            "a".to_string(),
+            Set::from_vec(vec!["fa".to_string()]),
+            false,
+        );
+        assert!(cache.get_or_compute(&leaf, "a").is_ok());
+        cache.save(&store, "w").unwrap();
+
+        let mut bytes = store.get("w").unwrap().unwrap();
+        bytes.pop();
+        store.put("w", &bytes).unwrap();
+        assert!(WitnessCache::load(&store, "w").is_err());
+    }
+}
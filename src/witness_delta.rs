@@ -0,0 +1,226 @@
+//! Regenerating witnesses after root accumulators are rebuilt.
+//!
+//! A membership witness is issued against the accumulator of the key's root,
+//! so it breaks whenever that accumulator is recomputed: when `refresh` drops
+//! tombstoned keys from stale roots, when `compact` rebuilds the forest, or
+//! when an insert merges roots into a larger one. `AccumulatorTree::witness_delta` compares the forest with an
+//! earlier `TreeSnapshot` and returns a `CompactionDelta` holding, for every
+//! root accumulator that disappeared, the accumulator its surviving keys moved
+//! to and their new witnesses, computed one batch per accumulator.
+//!
+//! Refresh protocol:
+//! 1. Before a refresh, a compaction or a batch of inserts, the server takes
+//!    a snapshot.
+//! 2. Afterwards it computes the delta against it, applies it to its
+//!    `WitnessCache` and publishes it as a wire message next to the epoch.
+//! 3. A client holding `(key, acc, witness)` looks its key up with
+//!    `CompactionDelta::refreshed(key, acc)` and replaces both, then checks
+//!    the new witness like any other. A key that is not listed under its old
+//!    accumulator was deleted, or its accumulator did not change.
+//!
+//! Deltas are not chained: a client that missed one asks the server for a
+//! fresh proof instead.
+
+use crate::canonical::CanonicalEncode;
+use crate::error::AccTreeError;
+use crate::node::Node;
+use crate::witness_cache::compute_witnesses;
+use accumulator_ads::{G1Affine, Set};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Keys that moved from one root accumulator to another, with their new
+/// witnesses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccChange {
+    pub old_acc: G1Affine,
+    pub new_acc: G1Affine,
+    /// (key, witness in `new_acc`)
+    pub witnesses: Vec<(String, G1Affine)>,
+}
+
+/// Witnesses regenerated for every key whose root accumulator changed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CompactionDelta {
+    pub changes: Vec<AccChange>,
+}
+
+impl CompactionDelta {
+    /// Delta from the roots `before` to the roots `after`.
+    pub(crate) fn between(before: &[Arc<Node>], after: &[Arc<Node>]) -> Result<Self, AccTreeError> {
+        let old_accs: Vec<G1Affine> = before.iter().map(|r| r.acc()).collect();
+        let new_accs: Vec<G1Affine> = after.iter().map(|r| r.acc()).collect();
+        // Key sets of the new roots, and the new root of each key that moved
+        let mut new_keys: HashMap<usize, Set<String>> = HashMap::new();
+        let mut moved_to: HashMap<String, usize> = HashMap::new();
+        for (i, (root, acc)) in after.iter().zip(&new_accs).enumerate() {
+            if !old_accs.contains(acc) {
+                let keys = root.keys();
+                moved_to.extend(keys.iter().map(|k| (k.clone(), i)));
+                new_keys.insert(i, keys);
+            }
+        }
+
+        let mut changes: Vec<AccChange> = Vec::new();
+        for (root, old_acc) in before.iter().zip(&old_accs) {
+            if new_accs.contains(old_acc) {
+                continue;
+            }
+            let mut by_root: Vec<(usize, Vec<String>)> = Vec::new();
+            let mut keys: Vec<String> = root.keys().iter().cloned().collect();
+            keys.sort();
+            for key in keys {
+                let Some(&new_root) = moved_to.get(&key) else {
+                    continue;
+                };
+                match by_root.iter_mut().find(|(i, _)| *i == new_root) {
+                    Some((_, keys)) => keys.push(key),
+                    None => by_root.push((new_root, vec![key])),
+                }
+            }
+            for (new_root, keys) in by_root {
+                let witnesses = compute_witnesses(&new_keys[&new_root], &keys)?;
+                changes.push(AccChange {
+                    old_acc: *old_acc,
+                    new_acc: new_accs[new_root],
+                    witnesses: keys.into_iter().zip(witnesses).collect(),
+                });
+            }
+        }
+        Ok(Self { changes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// New (accumulator, witness) of `key` if its witness in `old_acc` was
+    /// invalidated and regenerated.
+    pub fn refreshed(&self, key: &str, old_acc: &G1Affine) -> Option<(G1Affine, G1Affine)> {
+        self.changes
+            .iter()
+            .filter(|c| c.old_acc == *old_acc)
+            .find_map(|c| {
+                let (_, witness) = c.witnesses.iter().find(|(k, _)| k == key)?;
+                Some((c.new_acc, *witness))
+            })
+    }
+
+    /// Number of regenerated witnesses.
+    pub fn witness_count(&self) -> usize {
+        self.changes.iter().map(|c| c.witnesses.len()).sum()
+    }
+}
+
+impl CanonicalEncode for AccChange {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.old_acc.encode_canonical(out);
+        self.new_acc.encode_canonical(out);
+        self.witnesses.encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for CompactionDelta {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.changes.encode_canonical(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompactionDelta;
+    use crate::AccumulatorTree;
+    use crate::acc_proof::AccProof;
+    use crate::wire::{from_wire, to_wire};
+    use crate::witness_cache::WitnessCache;
+    use accumulator_ads::G1Affine;
+
+    /// (accumulator, witness) of a present key.
+    fn issued(tree: &AccumulatorTree, key: &str) -> (G1Affine, G1Affine) {
+        let response = tree.select_with_proof(key).unwrap();
+        assert!(response.verify_by_mode(key));
+        match response.acc_proof {
+            Some(AccProof::Membership(mp)) => (response.accumulator.unwrap(), mp.witness),
+            other => panic!("no membership witness for {}: {:?}", key, other),
+        }
+    }
+
+    #[test]
+    fn test_delta_regenerates_invalidated_witnesses() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        for i in 0..4 {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        tree.insert("k4".to_string(), "f4".to_string());
+        let (old_acc, _) = issued(&tree, "k0");
+        let mut cache = WitnessCache::new();
+        let root_with = |tree: &AccumulatorTree, acc: G1Affine| {
+            tree.roots.iter().find(|r| r.acc() == acc).unwrap().clone()
+        };
+        let old_root = root_with(&tree, old_acc);
+        assert!(cache.get_or_compute(&old_root, "k0").is_ok());
+        assert!(cache.get_or_compute(&old_root, "k1").is_ok());
+
+        // Dropping a tombstone from the accumulator moves the other keys
+        let before = tree.snapshot();
+        tree.delete("k1", "f1");
+        assert!(tree.witness_delta(&before).unwrap().is_empty());
+        tree.refresh();
+        let delta = tree.witness_delta(&before).unwrap();
+        assert_eq!(delta.witness_count(), 3);
+        assert_eq!(delta.refreshed("k0", &old_acc), Some(issued(&tree, "k0")));
+        assert_eq!(delta.refreshed("k1", &old_acc), None);
+        assert_eq!(delta.refreshed("k4", &old_acc), None);
+
+        let decoded: CompactionDelta = from_wire(&to_wire(&delta)).unwrap();
+        assert_eq!(delta, decoded);
+        assert_eq!(cache.apply_delta(&delta), (1, 1));
+        let (new_acc, witness) = issued(&tree, "k0");
+        assert_eq!(
+            cache.get_or_compute(&root_with(&tree, new_acc), "k0"),
+            Ok(witness)
+        );
+        assert_eq!(cache.stats(), (1, 2));
+
+        // Merging roots moves every key of the merged ones
+        let before = tree.snapshot();
+        for i in 5..8 {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        let delta = tree.witness_delta(&before).unwrap();
+        assert_eq!(delta.refreshed("k0", &new_acc), Some(issued(&tree, "k0")));
+        assert_eq!(
+            delta.refreshed("k4", &issued(&before, "k4").0),
+            Some(issued(&tree, "k4"))
+        );
+    }
+
+    #[test]
+    fn test_delta_covers_auto_compaction() {
+        crate::setup::init_test_parameters();
+        let mut tree = AccumulatorTree::new();
+        tree.set_compaction_threshold(Some(0.5));
+        for i in 0..8 {
+            tree.insert(format!("k{}", i), format!("f{}", i));
+        }
+        let (old_acc, _) = issued(&tree, "k0");
+
+        tree.delete("k1", "f1");
+        tree.delete("k2", "f2");
+        tree.delete("k3", "f3");
+        assert_eq!(tree.tombstone_count(), 3);
+        let before = tree.snapshot();
+        tree.delete("k4", "f4");
+        assert_eq!(tree.tombstone_count(), 0);
+        assert_eq!(tree.roots_info().len(), 1);
+        assert_eq!(tree.roots_info()[0].level, 2);
+        assert!(tree.select("k1").is_none());
+        assert!(tree.select("k7").is_some());
+
+        let delta = tree.witness_delta(&before).unwrap();
+        assert_eq!(delta.witness_count(), 4);
+        assert_eq!(delta.refreshed("k0", &old_acc), Some(issued(&tree, "k0")));
+        assert_eq!(delta.refreshed("k4", &old_acc), None);
+    }
+}
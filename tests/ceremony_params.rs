@@ -0,0 +1,46 @@
+//! Runs in its own process so the tree proves against ceremony parameters,
+//! whose secret nobody holds.
+
+use accumulator_ads::Fr;
+use accumulator_ads::acc::setup::ceremony::Ceremony;
+use accumulator_tree::AccumulatorTree;
+
+#[test]
+fn test_proofs_verify_under_ceremony_parameters() {
+    let mut ceremony = Ceremony::new(32).unwrap();
+    for tau in [987654321u64, 31337] {
+        ceremony.contribute(Fr::from(tau)).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("acc-ceremony-params-{}", std::process::id()));
+    ceremony.verify().unwrap();
+    ceremony
+        .into_parameters()
+        .unwrap()
+        .save_to_file(&path)
+        .unwrap();
+    accumulator_tree::init(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut tree = AccumulatorTree::new();
+    for k in ["a", "b", "c", "d"] {
+        tree.insert(k.to_string(), format!("f{}", k));
+    }
+    let inserted = tree
+        .insert_with_proof("e".to_string(), "fe".to_string())
+        .unwrap();
+    assert!(inserted.verify_insert());
+
+    for k in ["a", "b", "c", "d", "e"] {
+        let response = tree.select_with_proof(k).unwrap();
+        let fids = response.fids.clone().unwrap();
+        assert!(response.verify_by_mode(k), "{}", k);
+        assert!(response.verify_full(k, &fids), "{}", k);
+    }
+    assert!(tree.select_with_proof("zz").unwrap().verify_by_mode("zz"));
+
+    tree.insert("b".to_string(), "fb2".to_string());
+    let deleted = tree.delete_with_proof("b", "fb").unwrap();
+    assert!(deleted.verify_delete());
+    let frozen = tree.freeze().unwrap();
+    assert!(frozen.select_with_proof("a").unwrap().verify_by_mode("a"));
+}
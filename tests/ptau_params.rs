@@ -0,0 +1,86 @@
+//! Runs in its own process so the tree proves against parameters imported
+//! from a powers-of-tau transcript, and then against the same parameters
+//! pinned by digest.
+
+use accumulator_ads::Fr;
+use accumulator_ads::acc::setup::ceremony::Ceremony;
+use accumulator_tree::AccumulatorTree;
+use ark_bls12_381::{Fq, FqParameters};
+use ark_ff::FpParameters;
+
+/// Little-endian Montgomery limbs, as `.ptau` stores coordinates.
+fn lem(x: &Fq) -> Vec<u8> {
+    (x.0).0.iter().flat_map(|limb| limb.to_le_bytes()).collect()
+}
+
+fn section(out: &mut Vec<u8>, kind: u32, data: &[u8]) {
+    out.extend_from_slice(&kind.to_le_bytes());
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+/// A snarkjs transcript of `power` for a secret nobody kept.
+fn write_ptau(power: u32) -> Vec<u8> {
+    let g2_count = 1usize << power;
+    let mut ceremony = Ceremony::new(2 * g2_count - 2).unwrap();
+    ceremony.contribute(Fr::from(987654321u64)).unwrap();
+    let params = ceremony.into_parameters().unwrap();
+
+    let mut header = 48u32.to_le_bytes().to_vec();
+    for limb in FqParameters::MODULUS.0 {
+        header.extend_from_slice(&limb.to_le_bytes());
+    }
+    header.extend_from_slice(&power.to_le_bytes());
+    header.extend_from_slice(&power.to_le_bytes());
+    let g1: Vec<u8> = params
+        .g1_s_vec
+        .iter()
+        .flat_map(|p| [lem(&p.x), lem(&p.y)].concat())
+        .collect();
+    let g2: Vec<u8> = params.g2_s_vec[..g2_count]
+        .iter()
+        .flat_map(|p| [lem(&p.x.c0), lem(&p.x.c1), lem(&p.y.c0), lem(&p.y.c1)].concat())
+        .collect();
+
+    let mut out = b"ptau".to_vec();
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&3u32.to_le_bytes());
+    section(&mut out, 1, &header);
+    section(&mut out, 2, &g1);
+    section(&mut out, 3, &g2);
+    out
+}
+
+fn assert_proofs_verify(tree: &mut AccumulatorTree) {
+    for k in ["a", "b", "c", "d", "e"] {
+        let response = tree.select_with_proof(k).unwrap();
+        assert!(response.verify_by_mode(k), "{}", k);
+    }
+    assert!(tree.select_with_proof("zz").unwrap().verify_by_mode("zz"));
+    let inserted = tree
+        .insert_with_proof("f".to_string(), "ff".to_string())
+        .unwrap();
+    assert!(inserted.verify_insert());
+}
+
+#[test]
+fn test_proofs_verify_under_imported_parameters() {
+    let path = std::env::temp_dir().join(format!("acc-ptau-params-{}", std::process::id()));
+    std::fs::write(&path, write_ptau(4)).unwrap();
+    accumulator_tree::init_from_ptau(&path, 15).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut tree = AccumulatorTree::new();
+    for k in ["a", "b", "c", "d", "e"] {
+        tree.insert(k.to_string(), format!("f{}", k));
+    }
+    assert_proofs_verify(&mut tree);
+
+    // The same parameters saved and pinned by their digest
+    let params = accumulator_ads::get_public_parameters().unwrap();
+    params.save_to_file(&path).unwrap();
+    accumulator_tree::init_pinned(&path, &params.digest()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    tree.delete("f", "ff");
+    assert_proofs_verify(&mut tree);
+}
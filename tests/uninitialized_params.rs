@@ -0,0 +1,55 @@
+//! Runs in its own process so the public parameters start uninitialized.
+
+use accumulator_tree::{
+    AccTreeError, AccumulatorTree, ProofPolicy, VerifierCapabilities, WitnessCache,
+};
+
+#[test]
+fn test_proofs_without_parameters_return_errors() {
+    let mut tree = AccumulatorTree::new();
+    // A single leaf needs no accumulator until a proof is asked for
+    assert!(tree.insert("a".to_string(), "fa".to_string()));
+
+    let err = tree.select_with_proof("a").unwrap_err();
+    assert_eq!(err, AccTreeError::ParamsNotInitialized);
+    assert!(tree.select_with_proof("b").is_err());
+    assert!(tree.select_with_count_hints("a").is_err());
+    assert!(tree.delete_with_proof("a", "fa").is_err());
+    assert!(
+        tree.insert_with_proof("a".to_string(), "fa2".to_string())
+            .is_err()
+    );
+    assert_eq!(tree.select("a").unwrap().len(), 1);
+
+    // Witness computation reports the missing parameters
+    let pairing = VerifierCapabilities {
+        merkle: true,
+        pairing: true,
+    };
+    let err = tree.select_with_policy("a", &mut ProofPolicy::new(pairing));
+    assert_eq!(err.unwrap_err(), AccTreeError::ParamsNotInitialized);
+    let mut cache = WitnessCache::new();
+    assert!(tree.select_with_proof_cached("a", &mut cache).is_err());
+    assert!(cache.is_empty());
+    let mut single = AccumulatorTree::new();
+    single.insert("a".to_string(), "fa".to_string());
+    assert_eq!(
+        single.freeze().err(),
+        Some(AccTreeError::ParamsNotInitialized)
+    );
+
+    // Growing a key set is refused rather than panicking mid-merge
+    assert!(!accumulator_tree::ready());
+    let err = tree.try_insert("b".to_string(), "fb".to_string());
+    assert_eq!(err, Err(AccTreeError::ParamsNotInitialized));
+    assert!(tree.select("b").is_none());
+
+    #[cfg(feature = "insecure-test-params")]
+    {
+        accumulator_tree::init_for_testing().unwrap();
+        assert!(accumulator_tree::ready());
+        tree.try_insert("b".to_string(), "fb".to_string()).unwrap();
+        assert!(tree.select_with_proof("b").is_ok());
+        assert!(tree.try_delete("a", "fa").is_ok());
+    }
+}